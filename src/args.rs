@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::{Error, LitStr, Token, bracketed};
+
+/// Parses a duration literal such as `"250ms"`, `"30s"`, `"5m"` or `"1h"`.
+pub fn parse_duration(lit: &LitStr) -> syn::Result<Duration> {
+    let value = lit.value();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| Error::new_spanned(lit, "Expected a duration such as \"500ms\", \"30s\", \"5m\" or \"1h\""))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 3600)),
+        _ => Err(Error::new_spanned(lit, "Unknown duration unit, expected one of `ms`, `s`, `m`, `h`")),
+    }
+}
+
+/// Parses a list of string literals such as `["https://a.example", "https://b.example"]`.
+pub fn parse_str_list(input: ParseStream) -> syn::Result<Vec<LitStr>> {
+    let content;
+    bracketed!(content in input);
+    let items = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+    Ok(items.into_iter().collect())
+}
+
+/// Joins the `///` doc comments of an item into one string, `None` when it has none.
+pub fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+                ..
+            }) => Some(doc.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).trim_end().to_string())
+        .collect();
+    let doc = lines.join("\n").trim().to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+/// The arguments after a handler's input: `ctx` for a `connectors::Context`, the resource for a
+/// `connectors::Resource<T>` and `config` for a `connectors::Config<T>`, told apart by their type's name. Each is
+/// preceded by a comma.
+pub fn handler_args<'a>(
+    params: impl Iterator<Item = &'a syn::FnArg>,
+    ctx: proc_macro2::TokenStream,
+    config: Option<proc_macro2::TokenStream>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    params
+        .map(|arg| {
+            let ty = match arg {
+                syn::FnArg::Typed(pt) => match &*pt.ty {
+                    syn::Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+                    _ => None,
+                },
+                syn::FnArg::Receiver(_) => None,
+            };
+            match ty.as_deref() {
+                Some("Context") => Ok(quote::quote! { , #ctx }),
+                Some("Resource") => Ok(quote::quote! { , crate::connectors::Resource::get() }),
+                Some("Config") => match &config {
+                    Some(config) => Ok(quote::quote! { , #config }),
+                    None => Err(Error::new_spanned(arg, "`connectors::Config<T>` requires `config = T` on the connector")),
+                },
+                _ => Err(Error::new_spanned(arg, "Expected `ctx: connectors::Context` or a `connectors::Resource<T>`")),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,592 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, FnArg, ItemFn, LitBool, LitInt, Token};
+
+use crate::args::{doc_comment, handler_args, parse_duration, parse_str_list};
+use crate::serde_attrs::check_rename_rule;
+
+pub struct ConnectorArgs {
+    name: String,
+    aliases: Vec<syn::LitStr>,
+    /// `None` when the handler takes an operation enum covering the whole connector.
+    operation: Option<String>,
+    version: u32,
+    protobuf: bool,
+    /// The handler returns a `Stream` of items, served to direct callers as they come.
+    stream: bool,
+    max_attempts: u32,
+    backoff: Duration,
+    max_in_flight: Option<usize>,
+    cache: Option<Duration>,
+    /// The most results `cache` keeps, the oldest being evicted first.
+    cache_capacity: usize,
+    /// Calls slower than this are logged, overriding `connector_main!`'s `slow_call`.
+    slow_call: Option<Duration>,
+    deprecated: Option<syn::LitStr>,
+    /// HTTP date of the `Sunset` header.
+    sunset: Option<String>,
+    /// Function returning the tower layer wrapping the connector's `/csp/{name}` route.
+    layer: Option<syn::Path>,
+    /// `GET` serves the operation on `GET /csp/{name}/{operation}` as well, its input taken from the query string.
+    method: Option<syn::LitStr>,
+    /// Serves the operation on `/csp/{name}/{path}` with `method`, such as `"contacts/{id}"`.
+    path: Option<syn::LitStr>,
+    /// The connector's config, loaded from its settings at startup and taken as a `connectors::Config<T>`.
+    config: Option<syn::Type>,
+    /// The key case of the input and output, `"none"` opting out of `connector_main!`'s.
+    rename_all: Option<syn::LitStr>,
+}
+// Abridged Parse impl for brevity
+impl Parse for ConnectorArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut path = None;
+        let mut aliases = Vec::new();
+        let mut operation = None;
+        let mut version = 1;
+        let mut protobuf = false;
+        let mut stream = false;
+        let mut max_attempts = 1;
+        let mut backoff = Duration::from_millis(200);
+        let mut max_in_flight = None;
+        let mut cache = None;
+        let mut cache_capacity = None;
+        let mut slow_call = None;
+        let mut deprecated = None;
+        let mut sunset = None;
+        let mut layer = None;
+        let mut method = None;
+        let mut sub_path = None;
+        let mut config = None;
+        let mut rename_all = None;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "name" {
+                let value: syn::LitStr = input.parse()?;
+                path = Some(value.value());
+            } else if key == "aliases" {
+                aliases = parse_str_list(input)?;
+            } else if key == "operation" {
+                let value: syn::LitStr = input.parse()?;
+                operation = Some(value.value());
+            } else if key == "version" {
+                let value: LitInt = input.parse()?;
+                version = value.base10_parse()?;
+                if version == 0 {
+                    return Err(Error::new_spanned(value, "Versions start at 1"));
+                }
+            } else if key == "protobuf" {
+                protobuf = input.parse::<LitBool>()?.value;
+            } else if key == "stream" {
+                stream = input.parse::<LitBool>()?.value;
+            } else if key == "max_attempts" {
+                max_attempts = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "backoff" {
+                backoff = parse_duration(&input.parse()?)?;
+            } else if key == "max_in_flight" {
+                max_in_flight = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if key == "cache" {
+                cache = Some(parse_duration(&input.parse()?)?);
+            } else if key == "cache_capacity" {
+                let value: LitInt = input.parse()?;
+                cache_capacity = Some(value.base10_parse()?);
+                if cache_capacity == Some(0) {
+                    return Err(Error::new_spanned(value, "`cache_capacity` must be at least 1"));
+                }
+            } else if key == "slow_call" {
+                slow_call = Some(parse_duration(&input.parse()?)?);
+            } else if key == "deprecated" {
+                deprecated = Some(input.parse::<syn::LitStr>()?);
+            } else if key == "sunset" {
+                sunset = Some(http_date(&input.parse()?)?);
+            } else if key == "layer" {
+                layer = Some(input.parse::<syn::Path>()?);
+            } else if key == "method" {
+                let lit = input.parse::<syn::LitStr>()?;
+                if !["GET", "POST", "PUT", "PATCH", "DELETE"].contains(&lit.value().as_str()) {
+                    return Err(Error::new_spanned(lit, "Expected \"GET\", \"POST\", \"PUT\", \"PATCH\" or \"DELETE\""));
+                }
+                method = Some(lit);
+            } else if key == "path" {
+                sub_path = Some(parse_sub_path(input)?);
+            } else if key == "config" {
+                config = Some(input.parse::<syn::Type>()?);
+            } else if key == "rename_all" {
+                let rule = input.parse::<syn::LitStr>()?;
+                if rule.value() != "none" {
+                    check_rename_rule(&rule)?;
+                }
+                rename_all = Some(rule);
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if stream && (protobuf || cache.is_some()) {
+            return Err(Error::new(input.span(), "`stream = true` can't be combined with `protobuf` or `cache`"));
+        }
+        if cache.is_none() && cache_capacity.is_some() {
+            return Err(Error::new(input.span(), "`cache_capacity` requires `cache`"));
+        }
+        if deprecated.is_none() && sunset.is_some() {
+            return Err(Error::new(input.span(), "`sunset` requires `deprecated`"));
+        }
+        Ok(ConnectorArgs {
+            name: path.ok_or_else(|| syn::Error::new(input.span(), "Missing 'path' parameter"))?,
+            aliases,
+            operation,
+            version,
+            protobuf,
+            stream,
+            max_attempts,
+            backoff,
+            max_in_flight,
+            cache,
+            cache_capacity: cache_capacity.unwrap_or(1024),
+            slow_call,
+            deprecated,
+            sunset,
+            layer,
+            method,
+            path: sub_path,
+            config,
+            rename_all,
+        })
+    }
+}
+
+/// A path under `/csp/{name}/`, such as `"contacts/search"`, whose `{field}` segments fill input fields.
+fn parse_sub_path(input: ParseStream) -> syn::Result<syn::LitStr> {
+    let lit = input.parse::<syn::LitStr>()?;
+    let value = lit.value();
+    let valid = !value.is_empty()
+        && value.split('/').all(|segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(field) => !field.is_empty() && field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            None => !segment.is_empty() && !segment.contains(['{', '}', '*', '?', '#']),
+        });
+    if !valid {
+        return Err(Error::new_spanned(lit, "Expected a path such as \"contacts/{id}\", without leading or trailing `/`"));
+    }
+    Ok(lit)
+}
+
+/// Turns a `"2027-01-31"` date into the HTTP date `Sun, 31 Jan 2027 00:00:00 GMT`.
+fn http_date(lit: &syn::LitStr) -> syn::Result<String> {
+    let value = lit.value();
+    let parts: Vec<u32> = value.split('-').filter_map(|part| part.parse().ok()).collect();
+    let &[year, month, day] = parts.as_slice() else {
+        return Err(Error::new_spanned(lit, "Expected a date such as \"2027-01-31\""));
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || day == 0 || day > month_days[month as usize - 1] {
+        return Err(Error::new_spanned(lit, "Expected a date such as \"2027-01-31\""));
+    }
+    // Days since 1970-01-01, a Thursday.
+    let days: u32 = (1970..year).map(|y| if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) { 366 } else { 365 }).sum::<u32>()
+        + month_days[..month as usize - 1].iter().sum::<u32>()
+        + day
+        - 1;
+    let weekday = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"][days as usize % 7];
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"][month as usize - 1];
+    Ok(format!("{}, {:02} {} {} 00:00:00 GMT", weekday, day, month, year))
+}
+
+/// The `T` of a handler returning `Result<T, E>`, as written.
+fn output_type(output: &syn::ReturnType) -> String {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return "()".to_string();
+    };
+    let ok = match &**ty {
+        syn::Type::Path(path) => path.path.segments.last().and_then(|segment| match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) if segment.ident == "Result" => args.args.first(),
+            _ => None,
+        }),
+        _ => None,
+    };
+    match ok {
+        Some(ok) => quote!(#ok).to_string(),
+        None => quote!(#ty).to_string(),
+    }
+    .replace(' ', "")
+}
+
+fn capitalize_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
+
+/// Calls the handler with the arguments produced by the `decode` closure in scope (plus `ctx` when the handler
+/// takes it), yielding `Result<Out, connectors::HandlerError>`.
+///
+/// With `max_attempts > 1`, errors the handler marks as retriable (`connectors::HandlerError::retriable`) are
+/// retried with exponential backoff and jitter; the envelope is decoded again for each attempt, so inputs need
+/// not be `Clone`.
+fn invoke_handler(fn_name: &syn::Ident, ctx_arg: &TokenStream, max_attempts: u32, backoff: Duration) -> TokenStream {
+    let run = quote! {
+        let started = std::time::Instant::now();
+        let decoded = decode();
+        ctx.record_timings(|timings| timings.deserialize += started.elapsed());
+        let (id, input) = decoded?;
+        let started = std::time::Instant::now();
+        let result = #fn_name(id, input #ctx_arg).await;
+        ctx.record_timings(|timings| timings.handler += started.elapsed());
+    };
+    if max_attempts <= 1 {
+        return quote! {{
+            #run
+            result.map_err(crate::connectors::HandlerError::from_error)
+        }};
+    }
+    let backoff_ms = backoff.as_millis() as u64;
+    quote! {{
+        let mut attempt: u32 = 1;
+        loop {
+            #run
+            match result {
+                Ok(out) => break Ok(out),
+                Err(e) => {
+                    let e = crate::connectors::HandlerError::from_error(e);
+                    if !e.retriable || attempt >= #max_attempts {
+                        break Err(e);
+                    }
+                    tokio::time::sleep(crate::connectors::retry_backoff(std::time::Duration::from_millis(#backoff_ms), attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }}
+}
+
+pub fn expand(args: ConnectorArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    let name = args.name;
+    let operation = args.operation;
+    let version = args.version;
+    let aliases = &args.aliases;
+    if let Some(alias) = aliases.iter().find(|alias| alias.value() == name) {
+        return Err(Error::new_spanned(alias, "An alias must differ from the connector's name"));
+    }
+
+    let fn_name = &input_fn.sig.ident;
+    let deprecated = match &args.deprecated {
+        Some(message) => quote! { Some(#message) },
+        None => quote! { None },
+    };
+    let sunset = match &args.sunset {
+        Some(date) => quote! { Some(#date) },
+        None => quote! { None },
+    };
+    let layer = match &args.layer {
+        Some(layer) => quote! {
+            Some({
+                fn layer(route: axum::routing::MethodRouter) -> axum::routing::MethodRouter {
+                    route.layer(#layer())
+                }
+                layer
+            })
+        },
+        None => quote! { None },
+    };
+    let description = match doc_comment(&input_fn.attrs) {
+        Some(doc) => quote! { Some(#doc) },
+        None => quote! { None },
+    };
+
+    if input_fn.sig.inputs.len() < 2 {
+        return Err(Error::new_spanned(
+            &input_fn.sig.inputs,
+            "Expected 2 parameters: (id: u64, params: T), the id also a `String`, `i64` or `uuid::Uuid`, optionally followed by `ctx: connectors::Context`, `connectors::Resource<T>`s and a `connectors::Config<T>`",
+        ));
+    }
+    if input_fn.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Function must be async"));
+    }
+    let id_arg = input_fn.sig.inputs.first().unwrap();
+    let id_ty = if let FnArg::Typed(pt) = id_arg {
+        &pt.ty
+    } else {
+        return Err(Error::new_spanned(id_arg, "Expected typed first param"));
+    };
+    let params_arg = input_fn.sig.inputs.iter().nth(1).unwrap();
+    let input_ty = if let FnArg::Typed(pt) = params_arg {
+        &pt.ty
+    } else {
+        return Err(Error::new_spanned(params_arg, "Expected typed second param"));
+    };
+
+    // The catch-all handler takes every operation without one of its own, and an operation enum (no `operation`)
+    // one operation per variant; in both cases the envelope's whole `params` is the handler's input.
+    let catch_all = operation.as_deref() == Some("*");
+    let whole_params = catch_all || operation.is_none();
+    if whole_params && args.protobuf {
+        return Err(Error::new_spanned(&input_fn.sig.ident, "`protobuf = true` requires a single, named `operation`"));
+    }
+    let method = args.method.as_ref().map_or_else(|| "POST".to_string(), syn::LitStr::value);
+    if method == "GET" && whole_params {
+        return Err(Error::new_spanned(&args.method, "`method = \"GET\"` requires a single, named `operation`"));
+    }
+    if whole_params && args.path.is_some() {
+        return Err(Error::new_spanned(&args.path, "`path` requires a single, named `operation`"));
+    }
+    if !["GET", "POST"].contains(&method.as_str()) && args.path.is_none() {
+        return Err(Error::new_spanned(&args.method, format!("`method = \"{}\"` requires `path`", method)));
+    }
+    // Query and path parameters are strings, reading them into the input takes the input's field types.
+    let from_params = matches!(method.as_str(), "GET" | "DELETE") || args.path.as_ref().is_some_and(|path| path.value().contains('{'));
+    let get_check = from_params.then(|| {
+        quote! {
+            const _: fn() = || {
+                fn query_input<T: crate::connectors::ConnectorInput>() {}
+                query_input::<#input_ty>();
+            };
+        }
+    });
+    let sub_path = match &args.path {
+        Some(path) => quote! { Some(#path) },
+        None => quote! { None },
+    };
+    let op_ident = match operation.as_deref() {
+        Some("*") => "any",
+        Some(operation) => operation,
+        None => "ops",
+    };
+    let operations = match &operation {
+        Some(operation) => quote! { &[#operation] },
+        None => quote! { <#input_ty as crate::connectors::Operations>::OPERATIONS },
+    };
+    let input_type = quote!(#input_ty).to_string().replace(' ', "");
+    let output_type = output_type(&input_fn.sig.output);
+    // How errors name the operation; an enum handler is named after its input type.
+    let operation = operation.clone().unwrap_or_else(|| quote!(#input_ty).to_string().replace(' ', ""));
+
+    // Version 1 keeps the unsuffixed names, later versions of the same operation get their own items.
+    let suffix = if version == 1 { String::new() } else { format!("V{}", version) };
+    let params_struct = format_ident!("Params{}{}{}", capitalize_first(&name), capitalize_first(op_ident), suffix);
+    let request_struct = format_ident!("Request{}{}{}", capitalize_first(&name), capitalize_first(op_ident), suffix);
+    let exec_fn = match version {
+        1 => format_ident!("exec_raw_{}_{}", &name, op_ident),
+        _ => format_ident!("exec_raw_{}_{}_v{}", &name, op_ident, version),
+    };
+    let fields_fn = format_ident!("{}_fields", exec_fn);
+    let rename_all = match args.rename_all.as_ref().map(|rule| (rule, rule.value())) {
+        Some((_, rule)) if rule == "none" => quote! { None },
+        Some((rule, _)) => quote! { Some(#rule) },
+        None => quote! { crate::connectors::RENAME_ALL },
+    };
+
+    let config_arg = args.config.as_ref().map(|config| quote! { crate::connectors::Config::<#config>::get(#name) });
+    let ctx_arg = handler_args(input_fn.sig.inputs.iter().skip(2), quote! { ctx.clone() }, config_arg)?;
+    let config_recipe = args.config.as_ref().map(|config| {
+        let type_name = quote!(#config).to_string().replace(' ', "");
+        quote! {
+            ::inventory::submit! {
+                crate::connectors::ConfigRecipe {
+                    connector: #name,
+                    type_name: #type_name,
+                    load: crate::connectors::load_config::<#config>,
+                }
+            }
+        }
+    });
+    let invoke = invoke_handler(fn_name, &ctx_arg, args.max_attempts, args.backoff);
+    let slow_call = match args.slow_call {
+        Some(threshold) => {
+            let threshold_ms = threshold.as_millis() as u64;
+            quote! { Some(std::time::Duration::from_millis(#threshold_ms)) }
+        }
+        None => quote! { None },
+    };
+
+    // The slot is held across retries, so it bounds concurrent handler runs rather than attempts.
+    let shed_check = match args.max_in_flight {
+        Some(max) => quote! {
+            static IN_FLIGHT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let _permit = crate::connectors::InFlight::acquire(&IN_FLIGHT, #max).ok_or_else(|| {
+                crate::connectors::ExecError::Overloaded(format!("Too many calls in flight for `{}`/`{}`, try again later", #name, #operation))
+            })?;
+        },
+        None => quote! {},
+    };
+
+    // Protobuf connectors short-circuit before the serde path; input/output must implement `prost::Message`.
+    let protobuf_path = if args.protobuf {
+        quote! {
+            if format == crate::connectors::Format::Protobuf {
+                let decode = || -> Result<_, crate::connectors::ExecError> {
+                    let env = <crate::connectors::ProtoEnvelope as prost::Message>::decode(bytes.as_ref())
+                        .map_err(|e| crate::connectors::ExecError::Input(format!("Bad Protobuf envelope for `{}`/`{}`: {}", #name, #operation, e)))?;
+                    if env.operation != #operation {
+                        return Err(crate::connectors::ExecError::Input(format!("Operation mismatch: expected `{}`, got `{}`", #operation, env.operation)));
+                    }
+                    let input = <#input_ty as prost::Message>::decode(env.input.as_slice())
+                        .map_err(|e| crate::connectors::ExecError::Input(format!("Bad Protobuf for `{}`/`{}`: {}", #name, #operation, e)))?;
+                    let id = crate::connectors::envelope_id::<#id_ty>(serde_json::Value::from(env.id))
+                        .map_err(|e| crate::connectors::ExecError::Input(format!("Bad Protobuf id for `{}`/`{}`: {}", #name, #operation, e)))?;
+                    Ok((id, input))
+                };
+                return match #invoke {
+                    Ok(out) => {
+                        let started = std::time::Instant::now();
+                        let encoded = prost::Message::encode_to_vec(&out);
+                        ctx.record_timings(|timings| timings.serialize += started.elapsed());
+                        Ok(crate::connectors::Output::Protobuf(encoded))
+                    }
+                    Err(e) => Err(crate::connectors::ExecError::from(e)),
+                };
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // A streaming handler returns its stream, which needs `streaming = true` in `connector_main!` to be served.
+    let call = if args.stream {
+        quote! {
+            match #invoke {
+                Ok(items) => Ok(crate::connectors::Output::Stream(crate::connectors::ItemStream::new(items))),
+                Err(e) => Err(crate::connectors::ExecError::from(e)),
+            }
+        }
+    } else {
+        quote! {
+            match #invoke {
+                Ok(out) => {
+                    let started = std::time::Instant::now();
+                    let value = serde_json::to_value(out);
+                    ctx.record_timings(|timings| timings.serialize += started.elapsed());
+                    value
+                        .map(crate::connectors::Output::Value)
+                        .map_err(|e| crate::connectors::ExecError::Handler(e.to_string()))
+                }
+                Err(e) => Err(crate::connectors::ExecError::from(e)),
+            }
+        }
+    };
+
+    let (params_ty, params_items, take_input) = if whole_params {
+        (quote! { #input_ty }, quote! {}, quote! { Ok((req.id, req.params)) })
+    } else {
+        let items = quote! {
+            #[derive(Debug, serde::Deserialize)]
+            pub struct #params_struct {
+                pub operation: String,
+                pub input: #input_ty,
+            }
+        };
+        let take = quote! {
+            // (Optional) sanity check — not strictly needed since dispatcher already matched
+            if req.params.operation != #operation {
+                ctx.classify(crate::connectors::ErrorClass::Validation);
+                return Err(crate::connectors::ExecError::Input(format!("Operation mismatch: expected `{}`, got `{}`", #operation, req.params.operation)));
+            }
+            Ok((req.id, req.params.input))
+        };
+        (quote! { #params_struct }, items, take)
+    };
+
+    let body = quote! {
+        #shed_check
+        #protobuf_path
+
+        let bytes = crate::connectors::transform_envelope(#name, format, bytes)?;
+        let bytes = match #fields_fn() {
+            Some(fields) => crate::connectors::apply_defaults(fields, format, bytes)?,
+            None => bytes,
+        };
+        if let Some(field) = #fields_fn().and_then(|fields| crate::connectors::missing_field(fields, format, &bytes)) {
+            let field = crate::connectors::wire_name(#rename_all, field.name);
+            ctx.classify(crate::connectors::ErrorClass::Validation);
+            return Err(crate::connectors::ExecError::Input(format!("Missing required field `{}` for `{}`/`{}`", field, #name, #operation)));
+        }
+
+        let decode = || -> Result<_, crate::connectors::ExecError> {
+            // Full, typed deserialization for THIS connector/op
+            let req: #request_struct = format.decode(&bytes)
+                .map_err(|e| crate::connectors::ExecError::Input(format!("Bad {} for `{}`/`{}`: {}", format.name(), #name, #operation, e)))?;
+            #take_input
+        };
+
+        // Call user's handler
+        #call
+    };
+
+    // Cached connectors record successful outputs per input, a hit skips the handler and the in-flight cap.
+    let body = match args.cache {
+        Some(ttl) => {
+            let ttl_ms = ttl.as_millis() as u64;
+            let capacity = args.cache_capacity;
+            quote! {
+                static CACHE: std::sync::OnceLock<crate::connectors::ResponseCache> = std::sync::OnceLock::new();
+                let cache = CACHE.get_or_init(|| crate::connectors::ResponseCache::new(std::time::Duration::from_millis(#ttl_ms), #capacity));
+                let key = cache.key(format, &bytes);
+                if let Some(out) = key.and_then(|key| cache.get(key)) {
+                    return Ok(out);
+                }
+                let result: Result<crate::connectors::Output, crate::connectors::ExecError> = async move { #body }.await;
+                if let (Some(key), Ok(out)) = (key, &result) {
+                    cache.insert(key, out.clone());
+                }
+                result
+            }
+        }
+        None => body,
+    };
+
+    let out = quote! {
+        #params_items
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct #request_struct {
+            #[serde(default, deserialize_with = "crate::connectors::deserialize_id")]
+            pub id: #id_ty,
+            pub params: #params_ty,
+        }
+
+        #input_fn
+        #get_check
+
+        fn #fields_fn() -> Option<&'static [crate::connectors::FieldMeta]> {
+            #[allow(unused_imports)]
+            use crate::connectors::{InputFields as _, NoInputFields as _};
+            (&crate::connectors::InputProbe::<#input_ty>(std::marker::PhantomData)).input_fields()
+        }
+
+        fn #exec_fn(bytes: axum::body::Bytes, format: crate::connectors::Format, ctx: crate::connectors::Context) -> crate::connectors::DispatcherFuture {
+            Box::pin(async move {
+                #body
+            })
+        }
+
+        ::inventory::submit! {
+            crate::connectors::ConnectorRecipe {
+                name: #name,
+                operations: #operations,
+                version: #version,
+                aliases: &[#(#aliases),*],
+                description: #description,
+                deprecated: #deprecated,
+                sunset: #sunset,
+                layer: #layer,
+                method: #method,
+                path: #sub_path,
+                input_type: #input_type,
+                output_type: #output_type,
+                input_fields: #fields_fn,
+                rename_all: #rename_all,
+                slow_call: #slow_call,
+                exec_raw: &#exec_fn,
+            }
+        }
+
+        #config_recipe
+    };
+    Ok(out)
+}
@@ -0,0 +1,173 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, LitStr, Type};
+
+use crate::args::doc_comment;
+use crate::serde_attrs::{SerdeAttrs, apply_rename_all};
+
+/// Options of one field, from `#[connector(label = "...", description = "...", group = "...", optional, feel, secret,
+/// default = "...")]`, with `#[secret]` standing for `#[connector(secret)]` and `#[default = "..."]` for
+/// `#[connector(default = "...")]`.
+#[derive(Default)]
+struct FieldAttrs {
+    label: Option<LitStr>,
+    description: Option<LitStr>,
+    group: Option<LitStr>,
+    optional: bool,
+    feel: Option<LitStr>,
+    secret: bool,
+    default: Option<LitStr>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut out = FieldAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("secret")) {
+            attr.meta.require_path_only()?;
+            out.secret = true;
+        }
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("default")) {
+            let syn::Meta::NameValue(meta) = &attr.meta else {
+                return Err(Error::new_spanned(attr, "Expected `#[default = \"...\"]`"));
+            };
+            let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) = &meta.value else {
+                return Err(Error::new_spanned(&meta.value, "`default` takes the value as a string, e.g. `#[default = \"10\"]`"));
+            };
+            out.default = Some(lit.clone());
+        }
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("connector")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("label") {
+                    out.label = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("description") {
+                    out.description = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("group") {
+                    out.group = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("optional") {
+                    out.optional = true;
+                } else if meta.path.is_ident("secret") {
+                    out.secret = true;
+                } else if meta.path.is_ident("default") {
+                    out.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("feel") {
+                    // A bare `feel` lets modelers type either a literal or a FEEL expression.
+                    let feel = match meta.input.peek(syn::Token![=]) {
+                        true => meta.value()?.parse::<LitStr>()?,
+                        false => LitStr::new("optional", meta.path.get_ident().unwrap().span()),
+                    };
+                    if !matches!(feel.value().as_str(), "optional" | "required") {
+                        return Err(Error::new_spanned(feel, "`feel` is either \"optional\" or \"required\""));
+                    }
+                    out.feel = Some(feel);
+                } else {
+                    return Err(meta.error("Unknown `connector` field option"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// Rejects a default that its field's property type can't take, e.g. `"ten"` for a number. `Text` defaults are
+/// used as JSON when they parse as such, and as strings otherwise.
+fn check_default(default: &LitStr, kind: &str) -> syn::Result<()> {
+    let value = default.value();
+    let valid = match kind {
+        "Number" => value.parse::<f64>().is_ok_and(f64::is_finite),
+        "Boolean" => matches!(value.as_str(), "true" | "false"),
+        _ => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(Error::new_spanned(default, format!("Expected a {} default", kind.to_lowercase()))),
+    }
+}
+
+/// The element template property type of a field, and whether its Rust type makes it optional.
+fn field_kind(ty: &Type) -> (&'static str, bool) {
+    let Type::Path(path) = ty else {
+        return ("Text", false);
+    };
+    let Some(last) = path.path.segments.last() else {
+        return ("Text", false);
+    };
+    if last.ident == "Option" {
+        if let syn::PathArguments::AngleBracketed(args) = &last.arguments
+            && let Some(syn::GenericArgument::Type(inner)) = args.args.first()
+        {
+            return (field_kind(inner).0, true);
+        }
+        return ("Text", true);
+    }
+    let kind = match last.ident.to_string().as_str() {
+        "String" | "str" => "String",
+        "bool" => "Boolean",
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize" | "f32" | "f64" => "Number",
+        // Lists, maps and nested structs are entered as JSON or FEEL.
+        _ => "Text",
+    };
+    (kind, false)
+}
+
+/// Implements `connectors::ConnectorInput` for a struct: the metadata of each field, as seen by serde, for element
+/// templates and for validating inputs before they are decoded. Defaults are filled into the input before decoding,
+/// so that a `u32` field with `#[default = "10"]` needs no `Option` or `#[serde(default)]`.
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "`ConnectorInput` can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(&input.ident, "`ConnectorInput` requires named fields"));
+    };
+    let serde = SerdeAttrs::parse(&input.attrs)?;
+
+    let mut metas = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().unwrap();
+        let name = match SerdeAttrs::parse(&field.attrs)?.rename {
+            Some(rename) => rename,
+            None => apply_rename_all(&ident.to_string(), serde.rename_all.as_deref(), ident)?,
+        };
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let (kind, optional_type) = field_kind(&field.ty);
+        let label = attrs.label.map(|l| l.value()).unwrap_or_else(|| name.clone());
+        if let Some(default) = &attrs.default {
+            check_default(default, kind)?;
+            if attrs.secret {
+                return Err(Error::new_spanned(default, "Secret fields can't have a default"));
+            }
+        }
+        // A field with a default is filled in when missing, so it may be left empty.
+        let optional = attrs.optional || optional_type || attrs.default.is_some();
+        let secret = attrs.secret;
+        let some = |lit: Option<LitStr>| match lit {
+            Some(lit) => quote! { Some(#lit) },
+            None => quote! { None },
+        };
+        // Doc comments describe the field unless `description` says otherwise.
+        let description = attrs.description.or_else(|| doc_comment(&field.attrs).map(|doc| LitStr::new(&doc, ident.span())));
+        let (description, group, feel, default) = (some(description), some(attrs.group), some(attrs.feel), some(attrs.default));
+        metas.push(quote! {
+            crate::connectors::FieldMeta {
+                name: #name,
+                label: #label,
+                description: #description,
+                group: #group,
+                kind: #kind,
+                optional: #optional,
+                feel: #feel,
+                secret: #secret,
+                default: #default,
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::connectors::ConnectorInput for #ident #ty_generics #where_clause {
+            const FIELDS: &'static [crate::connectors::FieldMeta] = &[#(#metas),*];
+        }
+    })
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, ItemFn, LitStr, Token};
+
+use crate::args::parse_duration;
+
+/// `#[connector_health(connector = "crm", timeout = "2s")]`, or without `connector` for a dependency of the whole
+/// process, such as a database.
+pub struct HealthArgs {
+    connector: Option<LitStr>,
+    timeout: Duration,
+}
+
+impl Parse for HealthArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut connector = None;
+        let mut timeout = Duration::from_secs(5);
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "connector" {
+                connector = Some(input.parse()?);
+            } else if key == "timeout" {
+                let lit = input.parse()?;
+                timeout = parse_duration(&lit)?;
+                if timeout.is_zero() {
+                    return Err(Error::new_spanned(lit, "`timeout` must not be zero"));
+                }
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(HealthArgs { connector, timeout })
+    }
+}
+
+/// Registers `async fn() -> Result<(), String>` as a health check, e.g. a ping of a connector's downstream API, that
+/// `/readyz` waits on. A check that outlives `timeout` fails.
+pub fn expand(args: HealthArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    if input_fn.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Health checks must be async"));
+    }
+    if !input_fn.sig.inputs.is_empty() {
+        return Err(Error::new_spanned(&input_fn.sig.inputs, "Expected `async fn() -> Result<(), String>`"));
+    }
+    let fn_name = &input_fn.sig.ident;
+    let name = fn_name.to_string();
+    let connector = match &args.connector {
+        Some(connector) => quote! { Some(#connector) },
+        None => quote! { None },
+    };
+    let timeout_ms = args.timeout.as_millis() as u64;
+
+    Ok(quote! {
+        #input_fn
+
+        const _: () = {
+            fn check() -> crate::connectors::HookFuture {
+                Box::pin(#fn_name())
+            }
+
+            ::inventory::submit! {
+                crate::connectors::HealthRecipe {
+                    name: #name,
+                    connector: #connector,
+                    timeout: std::time::Duration::from_millis(#timeout_ms),
+                    check,
+                }
+            }
+        };
+    })
+}
@@ -0,0 +1,495 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::{Error, FnArg, ItemFn, ItemImpl, LitInt, LitStr, Token};
+
+use crate::args::{handler_args, parse_duration, parse_str_list};
+
+/// Where an inbound handler's messages come from, one variant per `camunda_inbound_*` attribute.
+enum Source {
+    Amqp { queue: LitStr, prefetch: u16 },
+    Mqtt { topics: Vec<LitStr>, qos: u8 },
+    Sqs { queue_url: SqsQueue, max_messages: i32, wait_time_secs: i32, visibility_timeout_secs: i32 },
+    Webhook { path: LitStr, verification: Option<Verification>, dedup_header: Option<LitStr> },
+    WebSocket { path: LitStr, token_env: Option<LitStr>, max_in_flight: usize },
+}
+
+/// Provider handshakes proving ownership of a webhook URL.
+enum Verification {
+    Slack,
+    MicrosoftGraph,
+    Meta { verify_token_env: LitStr },
+    Asana,
+}
+
+const VERIFICATIONS: &[&str] = &["slack", "microsoft_graph", "meta", "asana"];
+
+enum SqsQueue {
+    Url(LitStr),
+    Env(LitStr),
+}
+
+/// The message an inbound handler's output is published as, from `message = "..."`.
+struct Correlation {
+    message: LitStr,
+    /// FEEL expressions, evaluated against the handler's output.
+    correlation_key: LitStr,
+    ttl: std::time::Duration,
+    message_id: Option<LitStr>,
+}
+
+pub struct InboundArgs {
+    source: Source,
+    correlation: Option<Correlation>,
+    /// FEEL expression, evaluated against the message, identifying its redeliveries.
+    dedup_id: Option<LitStr>,
+}
+
+impl InboundArgs {
+    /// `#[camunda_inbound_amqp(queue = "...", prefetch = 10)]`
+    pub fn parse_amqp(input: ParseStream) -> syn::Result<Self> {
+        let mut queue = None;
+        let mut prefetch = 10;
+        let (correlation, dedup_id) = parse_inbound_keys(input, |key, input| {
+            if key == "queue" {
+                queue = Some(input.parse::<LitStr>()?);
+            } else if key == "prefetch" {
+                let lit = input.parse::<LitInt>()?;
+                prefetch = lit.base10_parse()?;
+                if prefetch == 0 {
+                    return Err(Error::new_spanned(lit, "`prefetch` must be at least 1"));
+                }
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        let queue = queue.ok_or_else(|| Error::new(input.span(), "Missing 'queue' parameter"))?;
+        Ok(InboundArgs { source: Source::Amqp { queue, prefetch }, correlation, dedup_id })
+    }
+
+    /// `#[camunda_inbound_mqtt(topic = "...", qos = 1)]`, or `topics = ["...", "..."]`.
+    pub fn parse_mqtt(input: ParseStream) -> syn::Result<Self> {
+        let mut topics = Vec::new();
+        let mut qos = 1;
+        let (correlation, dedup_id) = parse_inbound_keys(input, |key, input| {
+            if key == "topic" {
+                topics.push(input.parse::<LitStr>()?);
+            } else if key == "topics" {
+                topics.extend(parse_str_list(input)?);
+            } else if key == "qos" {
+                let lit = input.parse::<LitInt>()?;
+                qos = lit.base10_parse()?;
+                if qos > 2 {
+                    return Err(Error::new_spanned(lit, "`qos` is 0, 1 or 2"));
+                }
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        if topics.is_empty() {
+            return Err(Error::new(input.span(), "Missing 'topic' or 'topics' parameter"));
+        }
+        for topic in &topics {
+            check_topic_filter(topic)?;
+        }
+        Ok(InboundArgs { source: Source::Mqtt { topics, qos }, correlation, dedup_id })
+    }
+
+    /// `#[camunda_inbound_sqs(queue_url = "...", max_messages = 10, wait_time = "20s", visibility_timeout = "30s")]`,
+    /// or `queue_url_env = "..."` to read the URL from the environment.
+    pub fn parse_sqs(input: ParseStream) -> syn::Result<Self> {
+        let mut queue_url = None;
+        let mut max_messages = 10;
+        let mut wait_time = std::time::Duration::from_secs(20);
+        let mut visibility_timeout = std::time::Duration::from_secs(30);
+        let (correlation, dedup_id) = parse_inbound_keys(input, |key, input| {
+            let queue = if key == "queue_url" {
+                SqsQueue::Url(input.parse()?)
+            } else if key == "queue_url_env" {
+                SqsQueue::Env(input.parse()?)
+            } else if key == "max_messages" {
+                let lit = input.parse::<LitInt>()?;
+                max_messages = lit.base10_parse()?;
+                if !(1..=10).contains(&max_messages) {
+                    return Err(Error::new_spanned(lit, "SQS receives 1 to 10 messages at a time"));
+                }
+                return Ok(true);
+            } else if key == "wait_time" {
+                let lit = input.parse()?;
+                wait_time = parse_duration(&lit)?;
+                if wait_time.as_secs() > 20 || wait_time.subsec_nanos() != 0 {
+                    return Err(Error::new_spanned(lit, "SQS waits whole seconds, up to 20s"));
+                }
+                return Ok(true);
+            } else if key == "visibility_timeout" {
+                let lit = input.parse()?;
+                visibility_timeout = parse_duration(&lit)?;
+                if visibility_timeout.as_secs() < 2 || visibility_timeout.as_secs() > 12 * 3600 || visibility_timeout.subsec_nanos() != 0 {
+                    return Err(Error::new_spanned(lit, "SQS visibility timeouts are whole seconds, from 2s to 12h"));
+                }
+                return Ok(true);
+            } else {
+                return Ok(false);
+            };
+            if queue_url.replace(queue).is_some() {
+                return Err(Error::new_spanned(key, "Only one of `queue_url` and `queue_url_env` may be set"));
+            }
+            Ok(true)
+        })?;
+        let queue_url = queue_url.ok_or_else(|| Error::new(input.span(), "Missing 'queue_url' or 'queue_url_env' parameter"))?;
+        Ok(InboundArgs {
+            source: Source::Sqs {
+                queue_url,
+                max_messages,
+                wait_time_secs: wait_time.as_secs() as i32,
+                visibility_timeout_secs: visibility_timeout.as_secs() as i32,
+            },
+            correlation,
+            dedup_id,
+        })
+    }
+
+    /// `#[camunda_inbound_webhook(path = "...", verification = "slack", dedup_header = "...")]`, with
+    /// `verify_token_env = "..."` for `meta`.
+    pub fn parse_webhook(input: ParseStream) -> syn::Result<Self> {
+        let mut path = None;
+        let mut verification = None;
+        let mut verify_token_env = None;
+        let mut dedup_header = None;
+        let (correlation, dedup_id) = parse_inbound_keys(input, |key, input| {
+            if key == "path" {
+                path = Some(parse_inbound_path(input)?);
+            } else if key == "verification" {
+                let lit = input.parse::<LitStr>()?;
+                if !VERIFICATIONS.contains(&lit.value().as_str()) {
+                    return Err(Error::new_spanned(lit, format!("Unknown verification, expected one of {}", VERIFICATIONS.join(", "))));
+                }
+                verification = Some(lit);
+            } else if key == "verify_token_env" {
+                verify_token_env = Some(input.parse::<LitStr>()?);
+            } else if key == "dedup_header" {
+                let lit = input.parse::<LitStr>()?;
+                let name = lit.value();
+                if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)) {
+                    return Err(Error::new_spanned(lit, "Expected a header name such as \"X-GitHub-Delivery\""));
+                }
+                dedup_header = Some(lit);
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        let path = path.ok_or_else(|| Error::new(input.span(), "Missing 'path' parameter"))?;
+        let verification = match (verification.as_ref().map(LitStr::value).as_deref(), verify_token_env) {
+            (Some("meta"), Some(verify_token_env)) => Some(Verification::Meta { verify_token_env }),
+            (Some("meta"), None) => return Err(Error::new_spanned(verification, "`meta` verification requires `verify_token_env`")),
+            (_, Some(env)) => return Err(Error::new_spanned(env, "`verify_token_env` is only used by `verification = \"meta\"`")),
+            (Some("slack"), None) => Some(Verification::Slack),
+            (Some("microsoft_graph"), None) => Some(Verification::MicrosoftGraph),
+            (Some(_), None) => Some(Verification::Asana),
+            (None, None) => None,
+        };
+        Ok(InboundArgs { source: Source::Webhook { path, verification, dedup_header }, correlation, dedup_id })
+    }
+
+    /// `#[camunda_inbound_websocket(path = "...", token_env = "...", max_in_flight = 16)]`
+    pub fn parse_websocket(input: ParseStream) -> syn::Result<Self> {
+        let mut path = None;
+        let mut token_env = None;
+        let mut max_in_flight = 16;
+        let (correlation, dedup_id) = parse_inbound_keys(input, |key, input| {
+            if key == "path" {
+                path = Some(parse_inbound_path(input)?);
+            } else if key == "token_env" {
+                token_env = Some(input.parse::<LitStr>()?);
+            } else if key == "max_in_flight" {
+                let lit = input.parse::<LitInt>()?;
+                max_in_flight = lit.base10_parse()?;
+                if max_in_flight == 0 {
+                    return Err(Error::new_spanned(lit, "`max_in_flight` must be at least 1"));
+                }
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        let path = path.ok_or_else(|| Error::new(input.span(), "Missing 'path' parameter"))?;
+        Ok(InboundArgs { source: Source::WebSocket { path, token_env, max_in_flight }, correlation, dedup_id })
+    }
+}
+
+/// A path under `/inbound/` or `/ws/`, such as `"slack/events"`.
+fn parse_inbound_path(input: ParseStream) -> syn::Result<LitStr> {
+    let lit = input.parse::<LitStr>()?;
+    let value = lit.value();
+    if value.is_empty() || value.starts_with('/') || value.ends_with('/') || value.contains(['{', '}', '?', '#']) {
+        return Err(Error::new_spanned(lit, "Expected a path such as \"slack/events\", without leading or trailing `/`"));
+    }
+    Ok(lit)
+}
+
+/// `#[camunda_inbound_polling(interval = "1m", dedup_capacity = 10000)]`
+pub struct PollingArgs {
+    interval: std::time::Duration,
+    dedup_capacity: usize,
+}
+
+impl syn::parse::Parse for PollingArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut interval = None;
+        let mut dedup_capacity = 10_000;
+        parse_keys(input, |key, input| {
+            if key == "interval" {
+                let lit = input.parse()?;
+                let value = parse_duration(&lit)?;
+                if value.is_zero() {
+                    return Err(Error::new_spanned(lit, "`interval` must not be zero"));
+                }
+                interval = Some(value);
+            } else if key == "dedup_capacity" {
+                let lit = input.parse::<LitInt>()?;
+                dedup_capacity = lit.base10_parse()?;
+                if dedup_capacity == 0 {
+                    return Err(Error::new_spanned(lit, "`dedup_capacity` must be at least 1"));
+                }
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        let interval = interval.ok_or_else(|| Error::new(input.span(), "Missing 'interval' parameter"))?;
+        Ok(PollingArgs { interval, dedup_capacity })
+    }
+}
+
+/// `+` must be a whole level and `#` the last one, as MQTT brokers reject other filters.
+fn check_topic_filter(lit: &LitStr) -> syn::Result<()> {
+    let filter = lit.value();
+    let levels: Vec<&str> = filter.split('/').collect();
+    let valid = !filter.is_empty()
+        && levels.iter().enumerate().all(|(i, level)| match *level {
+            "+" => true,
+            "#" => i == levels.len() - 1,
+            level => !level.contains(['+', '#']),
+        });
+    if !valid {
+        return Err(Error::new_spanned(lit, "Invalid topic filter: `+` must be a whole level and `#` the last one"));
+    }
+    Ok(())
+}
+
+/// `parse_keys` for the `camunda_inbound_*` attributes, which also take the keys publishing the handler's output as a
+/// message: `message`, `correlation_key`, `message_ttl` and `message_id`, and `dedup_id`.
+fn parse_inbound_keys(
+    input: ParseStream,
+    mut source_key: impl FnMut(&syn::Ident, ParseStream) -> syn::Result<bool>,
+) -> syn::Result<(Option<Correlation>, Option<LitStr>)> {
+    let mut message = None;
+    let mut correlation_key = None;
+    let mut ttl = None;
+    let mut message_id = None;
+    let mut dedup_id = None;
+    parse_keys(input, |key, input| {
+        if key == "dedup_id" {
+            dedup_id = Some(input.parse::<LitStr>()?);
+        } else if key == "message" {
+            message = Some(input.parse::<LitStr>()?);
+        } else if key == "correlation_key" {
+            correlation_key = Some(input.parse::<LitStr>()?);
+        } else if key == "message_ttl" {
+            let lit = input.parse::<LitStr>()?;
+            ttl = Some((parse_duration(&lit)?, lit));
+        } else if key == "message_id" {
+            message_id = Some(input.parse::<LitStr>()?);
+        } else {
+            return source_key(key, input);
+        }
+        Ok(true)
+    })?;
+    let Some(message) = message else {
+        if let Some(lit) = correlation_key.as_ref().or(message_id.as_ref()).or(ttl.as_ref().map(|(_, lit)| lit)) {
+            return Err(Error::new_spanned(lit, "`correlation_key`, `message_ttl` and `message_id` require `message`"));
+        }
+        return Ok((None, dedup_id));
+    };
+    let correlation_key = correlation_key.ok_or_else(|| Error::new_spanned(&message, "`message` requires `correlation_key`"))?;
+    let correlation = Correlation {
+        message,
+        correlation_key,
+        ttl: ttl.map_or(std::time::Duration::ZERO, |(ttl, _)| ttl),
+        message_id,
+    };
+    Ok((Some(correlation), dedup_id))
+}
+
+/// Runs `key = value` pairs through `source_key`, which returns whether it knew the key.
+fn parse_keys(input: ParseStream, mut source_key: impl FnMut(&syn::Ident, ParseStream) -> syn::Result<bool>) -> syn::Result<()> {
+    while !input.is_empty() {
+        let key: syn::Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        if !source_key(&key, input)? {
+            return Err(Error::new_spanned(key, "Unknown attribute key"));
+        }
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+    }
+    Ok(())
+}
+
+/// Registers an inbound handler: `async fn(message: T)`, optionally taking `ctx: connectors::Context` and resources, where
+/// `T` is deserialized from the message's JSON body. An `Ok` settles the message, an error hands it back to its source
+/// (`HandlerError::retriable`) or discards it. With `message = "..."`, the output is published as that message before
+/// the message is settled. With `dedup_id = "..."`, redeliveries of a handled message are settled without running the
+/// handler again, when `connector_main!` sets `inbound_dedup`.
+pub fn expand(args: InboundArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    let fn_name = &input_fn.sig.ident;
+    let name = fn_name.to_string();
+    if input_fn.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Function must be async"));
+    }
+    if input_fn.sig.inputs.is_empty() {
+        return Err(Error::new_spanned(
+            &input_fn.sig,
+            "Expected 1 parameter: (message: T), optionally followed by `ctx: connectors::Context` and `connectors::Resource<T>`s",
+        ));
+    }
+    let message_ty = match &input_fn.sig.inputs[0] {
+        FnArg::Typed(pt) => &pt.ty,
+        arg => return Err(Error::new_spanned(arg, "Expected typed first param")),
+    };
+    let ctx_arg = handler_args(input_fn.sig.inputs.iter().skip(1), quote! { ctx }, None)?;
+
+    let source = match &args.source {
+        Source::Amqp { queue, prefetch } => quote! {
+            crate::connectors::InboundSource::Amqp { queue: #queue, prefetch: #prefetch }
+        },
+        Source::Mqtt { topics, qos } => quote! {
+            crate::connectors::InboundSource::Mqtt { topics: &[#(#topics),*], qos: #qos }
+        },
+        Source::Sqs { queue_url, max_messages, wait_time_secs, visibility_timeout_secs } => {
+            let (queue_url, queue_url_env) = match queue_url {
+                SqsQueue::Url(url) => (quote! { #url }, quote! { None }),
+                SqsQueue::Env(name) => (quote! { "" }, quote! { Some(#name) }),
+            };
+            quote! {
+                crate::connectors::InboundSource::Sqs {
+                    queue_url: #queue_url,
+                    queue_url_env: #queue_url_env,
+                    max_messages: #max_messages,
+                    wait_time_secs: #wait_time_secs,
+                    visibility_timeout_secs: #visibility_timeout_secs,
+                }
+            }
+        }
+        Source::Webhook { path, verification, dedup_header } => {
+            let verification = match verification {
+                Some(Verification::Slack) => quote! { Some(crate::connectors::WebhookVerification::Slack) },
+                Some(Verification::MicrosoftGraph) => quote! { Some(crate::connectors::WebhookVerification::MicrosoftGraph) },
+                Some(Verification::Meta { verify_token_env }) => {
+                    quote! { Some(crate::connectors::WebhookVerification::Meta { verify_token_env: #verify_token_env }) }
+                }
+                Some(Verification::Asana) => quote! { Some(crate::connectors::WebhookVerification::Asana) },
+                None => quote! { None },
+            };
+            let dedup_header = match dedup_header {
+                Some(name) => quote! { Some(#name) },
+                None => quote! { None },
+            };
+            quote! {
+                crate::connectors::InboundSource::Webhook { path: #path, verification: #verification, dedup_header: #dedup_header }
+            }
+        }
+        Source::WebSocket { path, token_env, max_in_flight } => {
+            let token_env = match token_env {
+                Some(name) => quote! { Some(#name) },
+                None => quote! { None },
+            };
+            quote! {
+                crate::connectors::InboundSource::WebSocket { path: #path, token_env: #token_env, max_in_flight: #max_in_flight }
+            }
+        }
+    };
+
+    let correlation = match &args.correlation {
+        Some(Correlation { message, correlation_key, ttl, message_id }) => {
+            let ttl_ms = ttl.as_millis() as u64;
+            let message_id = match message_id {
+                Some(message_id) => quote! { Some(#message_id) },
+                None => quote! { None },
+            };
+            quote! {
+                Some(crate::connectors::InboundCorrelation {
+                    message: #message,
+                    correlation_key: #correlation_key,
+                    ttl: std::time::Duration::from_millis(#ttl_ms),
+                    message_id: #message_id,
+                })
+            }
+        }
+        None => quote! { None },
+    };
+    let dedup_id = match &args.dedup_id {
+        Some(expression) => quote! { Some(#expression) },
+        None => quote! { None },
+    };
+
+    let exec_fn = format_ident!("inbound_raw_{}", fn_name);
+    Ok(quote! {
+        #input_fn
+
+        fn #exec_fn(bytes: axum::body::Bytes, ctx: crate::connectors::Context) -> crate::connectors::InboundFuture {
+            Box::pin(async move {
+                let message: #message_ty = serde_json::from_slice(&bytes)
+                    .map_err(|e| crate::connectors::HandlerError::fatal(format!("Bad message for `{}`: {}", #name, e)))?;
+                let out = #fn_name(message #ctx_arg).await.map_err(crate::connectors::HandlerError::from_error)?;
+                serde_json::to_value(out).map_err(|e| crate::connectors::HandlerError::fatal(e.to_string()))
+            })
+        }
+
+        ::inventory::submit! {
+            crate::connectors::InboundRecipe {
+                name: #name,
+                source: #source,
+                correlation: #correlation,
+                dedup_id: #dedup_id,
+                exec_raw: #exec_fn,
+            }
+        }
+    })
+}
+
+/// Registers `impl connectors::PollingConnector for T`, which the runtime polls every `interval` from a `T::default()`.
+pub fn expand_polling(args: PollingArgs, input_impl: ItemImpl) -> syn::Result<TokenStream> {
+    let is_polling = input_impl
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .is_some_and(|segment| segment.ident == "PollingConnector");
+    if !is_polling {
+        return Err(Error::new_spanned(input_impl.self_ty, "Expected `impl connectors::PollingConnector for T`"));
+    }
+    let self_ty = &input_impl.self_ty;
+    let name = match &**self_ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+    .ok_or_else(|| Error::new_spanned(self_ty, "Expected a named type"))?;
+    let interval_ms = args.interval.as_millis() as u64;
+    let dedup_capacity = args.dedup_capacity;
+
+    Ok(quote! {
+        #input_impl
+
+        ::inventory::submit! {
+            crate::connectors::PollerRecipe {
+                name: #name,
+                interval: std::time::Duration::from_millis(#interval_ms),
+                dedup_capacity: #dedup_capacity,
+                start: crate::connectors::spawn_poller::<#self_ty>,
+            }
+        }
+    })
+}
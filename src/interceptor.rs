@@ -0,0 +1,69 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, ItemImpl, LitInt, Token};
+
+/// `#[camunda_interceptor(order = 10)]`
+pub struct InterceptorArgs {
+    order: i32,
+}
+
+impl Parse for InterceptorArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut order = 0;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "order" {
+                order = input.parse::<LitInt>()?.base10_parse()?;
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(InterceptorArgs { order })
+    }
+}
+
+/// Registers `impl connectors::Interceptor for T` around every call, with a single `T::default()` for the process.
+pub fn expand(args: InterceptorArgs, input_impl: ItemImpl) -> syn::Result<TokenStream> {
+    let is_interceptor = input_impl
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .is_some_and(|segment| segment.ident == "Interceptor");
+    if !is_interceptor {
+        return Err(Error::new_spanned(input_impl.self_ty, "Expected `impl connectors::Interceptor for T`"));
+    }
+    if !input_impl.generics.params.is_empty() {
+        return Err(Error::new_spanned(input_impl.generics, "Interceptors cannot be generic"));
+    }
+    let self_ty = &input_impl.self_ty;
+    let name = match &**self_ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+    .ok_or_else(|| Error::new_spanned(self_ty, "Expected a named type"))?;
+    let order = args.order;
+
+    Ok(quote! {
+        #input_impl
+
+        const _: () = {
+            fn instance() -> &'static dyn crate::connectors::Interceptor {
+                static INSTANCE: std::sync::OnceLock<#self_ty> = std::sync::OnceLock::new();
+                INSTANCE.get_or_init(Default::default)
+            }
+
+            ::inventory::submit! {
+                crate::connectors::InterceptorRecipe {
+                    name: #name,
+                    order: #order,
+                    instance,
+                }
+            }
+        };
+    })
+}
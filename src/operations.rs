@@ -0,0 +1,43 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields};
+
+use crate::serde_attrs::{SerdeAttrs, apply_rename_all};
+
+/// Implements `connectors::Operations` for an enum tagged by `operation`, listing the operation of each variant.
+///
+/// The enum must be adjacently tagged (`#[serde(tag = "operation", content = "input")]`) so that it deserializes
+/// straight from an envelope's `params`. Operation names follow the same `rename`/`rename_all` rules as serde.
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "`ConnectorOperations` can only be derived for enums"));
+    };
+    let serde = SerdeAttrs::parse(&input.attrs)?;
+    if serde.tag.as_deref() != Some("operation") || serde.content.as_deref() != Some("input") {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Operation enums must be tagged with `#[serde(tag = \"operation\", content = \"input\")]`",
+        ));
+    }
+
+    let mut operations = Vec::new();
+    for variant in &data.variants {
+        if !matches!(&variant.fields, Fields::Unit) && !matches!(&variant.fields, Fields::Unnamed(f) if f.unnamed.len() == 1) {
+            return Err(Error::new_spanned(variant, "Operation variants take their input as a single field, e.g. `Add(AddInput)`"));
+        }
+        let attrs = SerdeAttrs::parse(&variant.attrs)?;
+        let operation = match attrs.rename {
+            Some(rename) => rename,
+            None => apply_rename_all(&variant.ident.to_string(), serde.rename_all.as_deref(), &input.ident)?,
+        };
+        operations.push(operation);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::connectors::Operations for #ident #ty_generics #where_clause {
+            const OPERATIONS: &'static [&'static str] = &[#(#operations),*];
+        }
+    })
+}
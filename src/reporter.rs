@@ -0,0 +1,42 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, ItemImpl};
+
+/// Registers `impl connectors::ErrorReporter for T`, with a single `T::default()` for the process.
+pub fn expand(input_impl: ItemImpl) -> syn::Result<TokenStream> {
+    let is_reporter = input_impl
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .is_some_and(|segment| segment.ident == "ErrorReporter");
+    if !is_reporter {
+        return Err(Error::new_spanned(input_impl.self_ty, "Expected `impl connectors::ErrorReporter for T`"));
+    }
+    if !input_impl.generics.params.is_empty() {
+        return Err(Error::new_spanned(input_impl.generics, "Error reporters cannot be generic"));
+    }
+    let self_ty = &input_impl.self_ty;
+    let name = match &**self_ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+    .ok_or_else(|| Error::new_spanned(self_ty, "Expected a named type"))?;
+
+    Ok(quote! {
+        #input_impl
+
+        const _: () = {
+            fn instance() -> &'static dyn crate::connectors::ErrorReporter {
+                static INSTANCE: std::sync::OnceLock<#self_ty> = std::sync::OnceLock::new();
+                INSTANCE.get_or_init(Default::default)
+            }
+
+            ::inventory::submit! {
+                crate::connectors::ErrorReporterRecipe {
+                    name: #name,
+                    instance,
+                }
+            }
+        };
+    })
+}
@@ -0,0 +1,91 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, ItemFn, LitInt, Token};
+
+/// `#[connector_resource(order = 10)]`
+pub struct ResourceArgs {
+    order: i32,
+}
+
+impl Parse for ResourceArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut order = 0;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "order" {
+                order = input.parse::<LitInt>()?.base10_parse()?;
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(ResourceArgs { order })
+    }
+}
+
+/// Registers `async fn() -> T` or `async fn() -> Result<T, E>` to build the process's single `T` at startup, which
+/// handlers then take as a `connectors::Resource<T>` parameter.
+pub fn expand(args: ResourceArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    if input_fn.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Resource initializers must be async"));
+    }
+    if !input_fn.sig.inputs.is_empty() {
+        return Err(Error::new_spanned(&input_fn.sig.inputs, "Expected `async fn() -> T` or `async fn() -> Result<T, E>`"));
+    }
+    let syn::ReturnType::Type(_, ty) = &input_fn.sig.output else {
+        return Err(Error::new_spanned(&input_fn.sig, "Expected the resource as the return type"));
+    };
+    // `Result<T, E>` builds a `T`, with `E: Display` failing the startup.
+    let ok = match &**ty {
+        syn::Type::Path(path) => path.path.segments.last().and_then(|segment| match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) if segment.ident == "Result" => match args.args.first() {
+                Some(syn::GenericArgument::Type(ok)) => Some(ok),
+                _ => None,
+            },
+            _ => None,
+        }),
+        _ => None,
+    };
+    let fn_name = &input_fn.sig.ident;
+    let (resource_ty, build) = match ok {
+        Some(ok) => (ok, quote! { #fn_name().await.map_err(|e| e.to_string())? }),
+        None => (&**ty, quote! { #fn_name().await }),
+    };
+    let name = fn_name.to_string();
+    let type_name = quote!(#resource_ty).to_string().replace(' ', "");
+    let order = args.order;
+
+    Ok(quote! {
+        #input_fn
+
+        const _: () = {
+            static INSTANCE: std::sync::OnceLock<#resource_ty> = std::sync::OnceLock::new();
+
+            impl crate::connectors::ConnectorResource for #resource_ty {
+                fn instance() -> &'static Self {
+                    INSTANCE.get().expect(concat!("resource `", #type_name, "` is used before startup built it"))
+                }
+            }
+
+            fn init() -> crate::connectors::HookFuture {
+                Box::pin(async {
+                    let _ = INSTANCE.set(#build);
+                    Ok(())
+                })
+            }
+
+            ::inventory::submit! {
+                crate::connectors::ResourceRecipe {
+                    name: #name,
+                    type_name: #type_name,
+                    order: #order,
+                    init,
+                }
+            }
+        };
+    })
+}
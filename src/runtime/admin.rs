@@ -0,0 +1,352 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the admin router nested under `/admin`: connector listing with live stats and health, toggling, draining and
+/// cache flushes. `log_level` adds `/admin/log-level`; `dead_letters` adds `/admin/dead-letters/requeue`; `jobs` adds
+/// `/admin/jobs`.
+///
+/// Every endpoint requires `Authorization: Bearer <token>` with the token from `CONNECTOR_ADMIN_TOKEN`; when the
+/// variable is unset, all admin calls are refused.
+pub fn expand(log_level: bool, dead_letters: bool, jobs: bool) -> TokenStream {
+    let mut log_level_route = quote! {};
+    if log_level {
+        log_level_route = quote! {
+            .route(
+                "/log-level",
+                axum::routing::get(admin_log_level).put(admin_set_log_level).delete(admin_reset_log_level),
+            )
+        };
+    }
+    let mut dead_letter_route = quote! {};
+    if dead_letters {
+        dead_letter_route = quote! {
+            .route("/dead-letters/requeue", axum::routing::post(admin_requeue_dead_letter))
+        };
+    }
+    let mut jobs_route = quote! {};
+    if jobs {
+        jobs_route = quote! { .route("/jobs", axum::routing::get(admin_jobs)) };
+    }
+    quote! {
+        /// Counters of one (connector, operation), for calls that got past load shedding and the circuit breaker.
+        #[derive(Default)]
+        struct OpStats {
+            in_flight: std::sync::atomic::AtomicUsize,
+            calls: std::sync::atomic::AtomicU64,
+            errors: std::sync::atomic::AtomicU64,
+        }
+
+        impl OpStats {
+            fn record(&self, failed: bool) {
+                self.calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                if failed {
+                    self.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+        }
+
+        fn admin_stats(name: &str, operation: &str, version: u32) -> Option<&'static OpStats> {
+            static STATS: std::sync::OnceLock<std::collections::HashMap<(String, String, u32), OpStats>> = std::sync::OnceLock::new();
+            STATS
+                .get_or_init(|| dispatch_table().keys().map(|key| (key.clone(), OpStats::default())).collect())
+                .get(&(name.to_string(), operation.to_string(), version))
+        }
+
+        /// Set by `POST /admin/drain`: new calls are shed while in-flight ones finish.
+        static DRAINING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+        fn total_in_flight() -> usize {
+            dispatch_table()
+                .keys()
+                .filter_map(|(name, operation, version)| admin_stats(name, operation, *version))
+                .map(|stats| stats.in_flight.load(std::sync::atomic::Ordering::Relaxed))
+                .sum()
+        }
+
+        async fn admin_auth(request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            static TOKEN: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+            let token = TOKEN.get_or_init(|| std::env::var("CONNECTOR_ADMIN_TOKEN").ok().filter(|t| !t.is_empty()));
+            let presented = request
+                .headers()
+                .get(axum::http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "));
+            // Compared in constant time, without short-circuiting on the first differing byte.
+            let authorized = match (token, presented) {
+                (Some(token), Some(presented)) => {
+                    token.len() == presented.len() && token.bytes().zip(presented.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+                }
+                _ => false,
+            };
+            if !authorized {
+                return (
+                    axum::http::StatusCode::UNAUTHORIZED,
+                    [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+                    "Missing or invalid admin token",
+                )
+                    .into_response();
+            }
+            next.run(request).await
+        }
+
+        async fn admin_connectors() -> axum::Json<serde_json::Value> {
+            let health = connector_health();
+            let mut entries: Vec<_> = dispatch_table().iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let connectors: Vec<serde_json::Value> = entries
+                .into_iter()
+                .map(|((name, operation, version), recipe)| {
+                    use std::sync::atomic::Ordering::Relaxed;
+                    let (in_flight, calls, errors) = admin_stats(name, operation, *version)
+                        .map_or((0, 0, 0), |s| (s.in_flight.load(Relaxed), s.calls.load(Relaxed), s.errors.load(Relaxed)));
+                    serde_json::json!({
+                        "name": name,
+                        "operation": operation,
+                        "version": version,
+                        "description": recipe.description,
+                        "deprecated": recipe.deprecated,
+                        "sunset": recipe.sunset,
+                        "disabled": connectors::is_disabled(name, operation),
+                        // After the connector's `#[connector_health]` checks, if it has any.
+                        "health": health.get(name.as_str()).map(|health| health["status"].clone()),
+                        "in_flight": in_flight,
+                        "calls": calls,
+                        "errors": errors,
+                        "error_rate": if calls == 0 { 0.0 } else { errors as f64 / calls as f64 },
+                    })
+                })
+                .collect();
+            axum::Json(serde_json::json!({ "connectors": connectors }))
+        }
+
+        async fn admin_status() -> axum::Json<serde_json::Value> {
+            axum::Json(serde_json::json!({
+                "draining": DRAINING.load(std::sync::atomic::Ordering::Acquire),
+                "in_flight": total_in_flight(),
+                "disabled": connectors::disabled_connectors(),
+                "cache_generation": connectors::cache_generation(),
+            }))
+        }
+
+        /// `entry` is a connector name, or `name.operation` for a single operation, as in `DISABLED_CONNECTORS`.
+        async fn admin_disable(axum::extract::Path(entry): axum::extract::Path<String>) -> axum::Json<serde_json::Value> {
+            connectors::disable_connector(&entry);
+            tracing::warn!(entry, "connector disabled through the admin API");
+            admin_status().await
+        }
+
+        async fn admin_enable(axum::extract::Path(entry): axum::extract::Path<String>) -> axum::Json<serde_json::Value> {
+            connectors::enable_connector(&entry);
+            tracing::info!(entry, "connector enabled through the admin API");
+            admin_status().await
+        }
+
+        async fn admin_drain() -> axum::Json<serde_json::Value> {
+            DRAINING.store(true, std::sync::atomic::Ordering::Release);
+            tracing::warn!("draining: new calls are refused until `POST /admin/resume`");
+            admin_status().await
+        }
+
+        async fn admin_resume() -> axum::Json<serde_json::Value> {
+            DRAINING.store(false, std::sync::atomic::Ordering::Release);
+            tracing::info!("resumed after drain");
+            admin_status().await
+        }
+
+        async fn admin_flush_caches() -> axum::Json<serde_json::Value> {
+            connectors::flush_caches();
+            admin_status().await
+        }
+
+        fn admin_router() -> axum::Router {
+            axum::Router::new()
+                .route("/connectors", axum::routing::get(admin_connectors))
+                .route("/connectors/{entry}/disable", axum::routing::post(admin_disable))
+                .route("/connectors/{entry}/enable", axum::routing::post(admin_enable))
+                .route("/status", axum::routing::get(admin_status))
+                .route("/drain", axum::routing::post(admin_drain))
+                .route("/resume", axum::routing::post(admin_resume))
+                .route("/caches/flush", axum::routing::post(admin_flush_caches))
+                #log_level_route
+                #dead_letter_route
+                #jobs_route
+                .route_layer(axum::middleware::from_fn(admin_auth))
+        }
+    }
+}
+
+/// Generates the endpoints of `admin_log_level = true`: `GET /admin/log-level` reports the log filter, `PUT` overrides
+/// it until a TTL runs out, and `DELETE` reverts early to the configured `RUST_LOG`. With `log_levels`, an override
+/// may replace `LOG_LEVELS` too.
+///
+/// The override lives in memory only, so that a forgotten one ends with its TTL or the process, and a `config_file`
+/// reload drops it for the configured filter.
+pub fn log_level(log_levels: bool) -> TokenStream {
+    let (override_filter, configured_levels) = match log_levels {
+        true => (
+            quote! {
+                fn override_log_filter(filter: &str, levels: Option<&str>) -> Result<LogFilter, String> {
+                    let levels = levels.map(str::to_string).or_else(|| connectors::setting("LOG_LEVELS")).unwrap_or_default();
+                    Ok(LogFilter::new(log_filter(filter)?, parse_log_levels(&levels)?))
+                }
+            },
+            quote! { connectors::setting("LOG_LEVELS") },
+        ),
+        false => (
+            quote! {
+                fn override_log_filter(filter: &str, levels: Option<&str>) -> Result<tracing_subscriber::EnvFilter, String> {
+                    if levels.is_some() {
+                        return Err("`levels` requires `log_levels = true` in `connector_main!`".to_string());
+                    }
+                    log_filter(filter)
+                }
+            },
+            quote! { None::<String> },
+        ),
+    };
+    quote! {
+        /// A log filter set through `PUT /admin/log-level`.
+        struct LogOverride {
+            filter: String,
+            levels: Option<String>,
+            until: std::time::Instant,
+            /// Tells the revert scheduled for this override from those of the overrides it replaced.
+            generation: u64,
+        }
+
+        static LOG_OVERRIDE: std::sync::Mutex<Option<LogOverride>> = std::sync::Mutex::new(None);
+
+        #[derive(serde::Deserialize)]
+        struct LogLevelRequest {
+            /// `RUST_LOG` directives.
+            filter: String,
+            levels: Option<String>,
+            /// How long the override lasts, 15 minutes by default.
+            ttl: Option<String>,
+        }
+
+        #override_filter
+
+        /// Reverts to the configured filter, if `generation` is still the override in place or is `None`.
+        fn revert_log_override(generation: Option<u64>) {
+            let mut current = LOG_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+            match (current.as_ref(), generation) {
+                (None, _) => return,
+                (Some(current), Some(generation)) if current.generation != generation => return,
+                _ => {}
+            }
+            match override_log_filter(&connectors::setting("RUST_LOG").unwrap_or_default(), None) {
+                Ok(filter) => {
+                    if let Some(handle) = LOG_FILTER.get() {
+                        let _ = handle.reload(filter);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("cannot restore the configured log filter, keeping the override: {}", e);
+                    return;
+                }
+            }
+            *current = None;
+            drop(current);
+            tracing::info!("log filter override ended, back to the configured filter");
+        }
+
+        async fn admin_log_level() -> axum::Json<serde_json::Value> {
+            let current = LOG_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+            let log_override = current.as_ref().map(|current| {
+                serde_json::json!({
+                    "filter": current.filter,
+                    "levels": current.levels,
+                    "expires_in_seconds": current.until.saturating_duration_since(std::time::Instant::now()).as_secs(),
+                })
+            });
+            axum::Json(serde_json::json!({
+                "configured": {
+                    "filter": connectors::setting("RUST_LOG").unwrap_or_default(),
+                    "levels": #configured_levels,
+                },
+                "override": log_override,
+            }))
+        }
+
+        async fn admin_set_log_level(
+            axum::Json(request): axum::Json<LogLevelRequest>,
+        ) -> Result<axum::Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+            static GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+            let bad_request = |message: String| (axum::http::StatusCode::BAD_REQUEST, message);
+            let ttl = match &request.ttl {
+                Some(ttl) => connectors::parse_duration(ttl).ok_or_else(|| bad_request(format!("`ttl` must be a duration such as `15m`, got `{}`", ttl)))?,
+                None => std::time::Duration::from_secs(15 * 60),
+            };
+            if ttl.is_zero() || ttl > std::time::Duration::from_secs(24 * 3600) {
+                return Err(bad_request("`ttl` must be positive and at most `24h`".to_string()));
+            }
+            let filter = override_log_filter(&request.filter, request.levels.as_deref()).map_err(bad_request)?;
+            let generation = GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            {
+                let mut current = LOG_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(handle) = LOG_FILTER.get() {
+                    handle.reload(filter).map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                }
+                *current = Some(LogOverride {
+                    filter: request.filter.clone(),
+                    levels: request.levels.clone(),
+                    until: std::time::Instant::now() + ttl,
+                    generation,
+                });
+            }
+            tracing::warn!(filter = request.filter, levels = request.levels, ttl_seconds = ttl.as_secs(), "log filter overridden through the admin API");
+            tokio::spawn(async move {
+                tokio::time::sleep(ttl).await;
+                revert_log_override(Some(generation));
+            });
+            Ok(admin_log_level().await)
+        }
+
+        async fn admin_reset_log_level() -> axum::Json<serde_json::Value> {
+            revert_log_override(None);
+            admin_log_level().await
+        }
+    }
+}
+
+/// Generates `POST /admin/dead-letters/requeue`, taking a dead letter as its sink holds it and accepting its job again as
+/// a new asynchronous execution, under its original token and callback URL.
+pub fn dead_letters() -> TokenStream {
+    quote! {
+        async fn admin_requeue_dead_letter(axum::Json(letter): axum::Json<DeadLetter>) -> axum::response::Result<axum::response::Response> {
+            let job = letter.into_job().map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+            tracing::info!(token = job.token, connector = job.name, "dead letter requeued through the admin API");
+            accept_async_job(job)
+        }
+    }
+}
+
+/// Generates `GET /admin/jobs`, listing the jobs held by the `job_queue` without their payloads. Its tokens are those
+/// callbacks are accepted with, so it is only served behind the admin token.
+pub fn jobs() -> TokenStream {
+    quote! {
+        async fn admin_jobs() -> axum::Json<serde_json::Value> {
+            let now = epoch_millis();
+            let jobs: Vec<serde_json::Value> = JOB_QUEUE
+                .get()
+                .into_iter()
+                .flat_map(|(queue, _)| queue.iter().flatten())
+                .filter_map(|(_, value)| serde_json::from_slice::<QueuedJob>(&value).ok())
+                .map(|queued| {
+                    serde_json::json!({
+                        "token": queued.job.token,
+                        "connector": queued.job.name,
+                        "operation": queued.job.operation,
+                        "status": if queued.failed { "failed" } else { "pending" },
+                        "attempts": queued.attempts,
+                        "visible_in_ms": queued.visible_at.saturating_sub(now),
+                        "last_error": queued.last_error,
+                    })
+                })
+                .collect();
+            axum::Json(serde_json::json!({ "jobs": jobs }))
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the append-only audit log: one JSON line per call, whichever way it arrives, recording who called what,
+/// a SHA-256 of the input and how the call ended. Records are written by a single task, in order, to the file or
+/// HTTP endpoint of `sink`.
+///
+/// The input is hashed once the `secret` fields of its `ConnectorInput` and the keys of `redact` (at any depth) are
+/// replaced, so the log never holds a hash that could be matched against guessed credentials.
+pub fn expand(sink: &LitStr, redact: &[LitStr]) -> TokenStream {
+    let url = sink.value();
+    let http = url.starts_with("http://") || url.starts_with("https://");
+    // A file is opened at startup, so that a misconfigured path stops the process before it serves unaudited calls.
+    let (open, write) = if http {
+        (
+            quote! {
+                let client = reqwest::Client::new();
+            },
+            quote! {
+                let mut backoff = std::time::Duration::from_millis(500);
+                for attempt in 1..=3 {
+                    let sent = client
+                        .post(#sink)
+                        .header("content-type", "application/x-ndjson")
+                        .body(lines.clone())
+                        .send()
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|res| res.error_for_status().map_err(|e| e.to_string()));
+                    match sent {
+                        Ok(_) => break,
+                        Err(e) if attempt == 3 => {
+                            tracing::error!(records = batch.len(), "cannot deliver audit records, dropping them: {}", e);
+                            break;
+                        }
+                        Err(e) => tracing::warn!(attempt, "cannot deliver audit records: {}", e),
+                    }
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            },
+        )
+    } else {
+        (
+            quote! {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(#sink)
+                    .unwrap_or_else(|e| panic!("cannot open audit log at `{}`: {}", #sink, e));
+            },
+            quote! {
+                use std::io::Write as _;
+                let written = tokio::task::block_in_place(|| file.write_all(&lines).and_then(|()| file.sync_data()));
+                if let Err(e) = written {
+                    tracing::error!(records = batch.len(), path = #sink, "cannot write audit records: {}", e);
+                }
+            },
+        )
+    };
+
+    quote! {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct AuditRecord {
+            /// Unix epoch milliseconds, when the call arrived.
+            timestamp: u64,
+            client_ip: Option<std::net::IpAddr>,
+            job_key: Option<String>,
+            process_instance_key: Option<String>,
+            connector: String,
+            operation: String,
+            version: u32,
+            /// Of the redacted input in canonical JSON, unset when the input can't be read.
+            input_sha256: Option<String>,
+            outcome: &'static str,
+            status: u16,
+            error: Option<String>,
+            latency_ms: u64,
+        }
+
+        /// Input keys redacted at any depth, on top of the `secret` fields.
+        const AUDIT_REDACTED_KEYS: &[&str] = &[#(#redact),*];
+
+        static AUDIT_LOG: std::sync::OnceLock<tokio::sync::mpsc::Sender<AuditRecord>> = std::sync::OnceLock::new();
+
+        fn start_audit_log() {
+            #open
+            let (sender, mut receiver) = tokio::sync::mpsc::channel::<AuditRecord>(10_000);
+            if AUDIT_LOG.set(sender).is_err() {
+                return;
+            }
+            tokio::spawn(async move {
+                let mut batch = Vec::new();
+                while receiver.recv_many(&mut batch, 256).await > 0 {
+                    let mut lines = Vec::new();
+                    for record in &batch {
+                        if serde_json::to_writer(&mut lines, record).is_ok() {
+                            lines.push(b'\n');
+                        }
+                    }
+                    #write
+                    batch.clear();
+                }
+            });
+        }
+
+        /// A call being audited, recorded once it completes.
+        struct AuditCall {
+            started: std::time::Instant,
+            record: AuditRecord,
+        }
+
+        impl AuditCall {
+            fn begin(name: &str, operation: &str, format: connectors::Format, body: &[u8], ctx: &connectors::Context) -> Self {
+                let name = resolve_alias(name);
+                let version = format.peek_version(body);
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+                AuditCall {
+                    started: std::time::Instant::now(),
+                    record: AuditRecord {
+                        timestamp,
+                        client_ip: ctx.client().ip,
+                        job_key: ctx.job().map(|job| job.job_key.clone()),
+                        process_instance_key: ctx.job().map(|job| job.process_instance_key.clone()),
+                        connector: name.to_string(),
+                        operation: operation.to_string(),
+                        version,
+                        input_sha256: audit_input_hash(name, operation, version, format, body),
+                        outcome: "ok",
+                        status: 200,
+                        error: None,
+                        latency_ms: 0,
+                    },
+                }
+            }
+
+            /// Waits for room in the log rather than dropping the record, so a slow sink slows calls down.
+            async fn finish(mut self, result: &Result<connectors::Output, connectors::ExecError>) {
+                self.record.latency_ms = self.started.elapsed().as_millis() as u64;
+                if let Err(e) = result {
+                    self.record.outcome = "error";
+                    self.record.status = e.status().as_u16();
+                    self.record.error = Some(e.to_string());
+                }
+                if let Some(log) = AUDIT_LOG.get() {
+                    if log.send(self.record).await.is_err() {
+                        tracing::error!("audit log is closed, dropping a record");
+                    }
+                }
+            }
+        }
+
+        fn audit_input_hash(name: &str, operation: &str, version: u32, format: connectors::Format, body: &[u8]) -> Option<String> {
+            use sha2::Digest as _;
+            let mut input: serde_json::Value = serde_json::from_slice(&format.peek_input(body).ok()?).ok()?;
+            let recipe = lookup(name, operation, version).ok().map(|(_, r)| r);
+            let secrets = recipe.and_then(|r| (r.input_fields)()).unwrap_or_default();
+            let rename_all = recipe.and_then(|r| r.rename_all);
+            if let Some(object) = input.as_object_mut() {
+                for field in secrets.iter().filter(|field| field.secret) {
+                    if let Some(value) = object.get_mut(&*connectors::wire_name(rename_all, field.name)) {
+                        *value = "[REDACTED]".into();
+                    }
+                }
+            }
+            audit_redact(&mut input);
+            let digest = sha2::Sha256::digest(serde_json::to_vec(&input).ok()?);
+            Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+        }
+
+        fn audit_redact(value: &mut serde_json::Value) {
+            match value {
+                serde_json::Value::Object(object) => {
+                    for (key, value) in object.iter_mut() {
+                        if AUDIT_REDACTED_KEYS.contains(&key.as_str()) {
+                            *value = "[REDACTED]".into();
+                        } else {
+                            audit_redact(value);
+                        }
+                    }
+                }
+                serde_json::Value::Array(values) => values.iter_mut().for_each(audit_redact),
+                _ => {}
+            }
+        }
+    }
+}
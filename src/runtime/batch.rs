@@ -0,0 +1,77 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the `/csp/{name}/batch` handler running an array of envelopes with at most `concurrency` in flight.
+pub fn expand(concurrency: usize) -> TokenStream {
+    quote! {
+        /// Aborts the items still running when the batch request is dropped, e.g. because the caller disconnected.
+        struct BatchTasks<T>(Vec<tokio::task::JoinHandle<T>>);
+
+        impl<T> Drop for BatchTasks<T> {
+            fn drop(&mut self) {
+                for task in &self.0 {
+                    task.abort();
+                }
+            }
+        }
+
+        async fn dispatch_batch(
+            axum::extract::Path(name): axum::extract::Path<String>,
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+            ) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+            let format = connectors::Format::from_headers(&headers);
+            let envelopes: Vec<serde_json::Value> = format.decode(&body)
+                .map_err(|_| (axum::http::StatusCode::BAD_REQUEST, format!("Invalid {} batch, expected an array of envelopes", format.name())))?;
+            let envelope_version = connectors::envelope_version_header(&headers).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+
+            // The caller's deadline covers the whole batch.
+            let ctx = connectors::Context::from_headers(&headers)
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?
+                .with_client(client);
+            let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(#concurrency));
+            let mut tasks = BatchTasks(Vec::with_capacity(envelopes.len()));
+            for envelope in envelopes {
+                let waiting = connectors::InFlight::acquire(gauge(Gauge::Waiting, &name), usize::MAX);
+                let permit = permits.clone().acquire_owned().await
+                    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                drop(waiting);
+                let name = name.clone();
+                let ctx = ctx.fork();
+                tasks.0.push(tokio::spawn(async move {
+                    let _permit = permit;
+                    let (envelope, _) = connectors::upgrade_envelope(envelope, envelope_version)
+                        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+                    let operation = envelope["params"]["operation"]
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| (axum::http::StatusCode::BAD_REQUEST, "Invalid JSON envelope".to_string()))?;
+                    let bytes = serde_json::to_vec(&envelope)
+                        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+                    execute(name, operation, bytes.into(), connectors::Format::Json, ctx)
+                        .await
+                        .and_then(|out| out.into_value().map_err(connectors::ExecError::Handler))
+                        .map_err(<(axum::http::StatusCode, String)>::from)
+                }));
+            }
+
+            // Results are collected in submission order, whatever order the items completed in.
+            let mut results = Vec::with_capacity(tasks.0.len());
+            for task in &mut tasks.0 {
+                let result = task.await.unwrap_or_else(|e| Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())));
+                results.push(match result {
+                    Ok(value) => serde_json::json!({ "result": value }),
+                    Err((status, message)) => serde_json::json!({ "error": message, "status": status.as_u16() }),
+                });
+            }
+
+            let bytes = format.encode(&serde_json::Value::Array(results))
+                .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            Ok(axum::response::IntoResponse::into_response((
+                [(axum::http::header::CONTENT_TYPE, format.content_type())],
+                bytes,
+            )))
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `GET /version` and the startup line logging the same build: the crate's name and version, its git
+/// commit and build time, and the compiled connectors.
+///
+/// The commit is read with `git` when the macro expands, unless the build sets `GIT_SHA`, and is suffixed with
+/// `-dirty` when the work tree has uncommitted changes. The build time honours `SOURCE_DATE_EPOCH` for reproducible
+/// builds. Both are only refreshed when the crate is recompiled.
+pub fn expand() -> TokenStream {
+    let git_sha = git_sha().unwrap_or_default();
+    let built_at = built_at();
+
+    quote! {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct BuildInfo {
+            name: &'static str,
+            version: &'static str,
+            git_sha: &'static str,
+            built_at: &'static str,
+            connectors: Vec<CompiledConnector>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct CompiledConnector {
+            name: &'static str,
+            operation: &'static str,
+            version: u32,
+        }
+
+        fn build_info() -> BuildInfo {
+            let mut connectors: Vec<CompiledConnector> = ::inventory::iter::<connectors::ConnectorRecipe>
+                .into_iter()
+                .flat_map(|r| r.operations.iter().map(move |operation| CompiledConnector { name: r.name, operation, version: r.version }))
+                .collect();
+            connectors.sort_by_key(|c| (c.name, c.operation, c.version));
+            BuildInfo {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+                git_sha: option_env!("GIT_SHA").unwrap_or(#git_sha),
+                built_at: #built_at,
+                connectors,
+            }
+        }
+
+        async fn version_info() -> axum::Json<BuildInfo> {
+            axum::Json(build_info())
+        }
+
+        fn log_build_info() {
+            let info = build_info();
+            let connectors: Vec<String> = info.connectors.iter().map(|c| format!("{}/{}@v{}", c.name, c.operation, c.version)).collect();
+            tracing::info!(
+                name = info.name,
+                version = info.version,
+                git_sha = info.git_sha,
+                built_at = info.built_at,
+                connectors = ?connectors,
+                "starting connector runtime",
+            );
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").ok()?;
+    let output = std::process::Command::new("git").args(args).current_dir(dir).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git_sha() -> Option<String> {
+    let sha = git(&["rev-parse", "HEAD"]).filter(|sha| !sha.is_empty())?;
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+    Some(if dirty { format!("{}-dirty", sha) } else { sha })
+}
+
+/// RFC 3339 in UTC, from `SOURCE_DATE_EPOCH` or the clock.
+fn built_at() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm), over 400-year eras.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3_600, time % 3_600 / 60, time % 60)
+}
@@ -0,0 +1,149 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates asynchronous execution: requests carrying `X-Callback-Url` are answered with `202 Accepted` and a job
+/// token, and the result is POSTed to the callback URL once the handler finishes.
+///
+/// Deliveries are signed with HMAC-SHA256 over the body when `CALLBACK_SIGNING_SECRET` is set, and retried with
+/// exponential backoff up to `retries` times. With `dead_letters`, a job whose callback is never acknowledged goes
+/// to the dead-letter sink.
+pub fn expand(retries: u32, queued: bool, dead_letters: bool) -> TokenStream {
+    // With a job queue the job is persisted before answering, and the queue worker runs it.
+    let submit = if queued {
+        quote! {
+            enqueue_async_job(job).map_err(|e| (axum::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+        }
+    } else {
+        let run = match dead_letters {
+            true => quote! {
+                if let Err(attempt) = run_async_job(job.clone()).await {
+                    dead_letter(&job, vec![attempt]).await;
+                }
+            },
+            false => quote! { let _ = run_async_job(job).await; },
+        };
+        quote! {
+            let queued = connectors::InFlight::acquire(gauge(Gauge::Queued, &job.name), usize::MAX);
+            tokio::spawn(async move {
+                let _queued = queued;
+                #run
+            });
+        }
+    };
+    quote! {
+        /// An accepted asynchronous execution, everything needed to run it and report back.
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        struct AsyncJob {
+            token: String,
+            name: String,
+            operation: String,
+            callback_url: String,
+            envelope: Vec<u8>,
+            content_type: String,
+        }
+
+        /// A run of an asynchronous job whose callback was not acknowledged.
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct JobAttempt {
+            /// Unix epoch milliseconds, when the delivery was given up on.
+            at: u64,
+            /// Set when the handler failed, the callback reporting that failure.
+            handler_error: Option<String>,
+            delivery_error: String,
+        }
+
+        fn job_token() -> String {
+            use std::hash::{BuildHasher, Hasher};
+            static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64;
+            let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+            format!("{:016x}{:016x}", nanos, hasher.finish())
+        }
+
+        fn callback_signature(secret: &str, body: &[u8]) -> String {
+            use hmac::Mac;
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(body);
+            let digest = mac.finalize().into_bytes();
+            let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("sha256={}", hex)
+        }
+
+        /// Accepts the job: validates that the connector exists, then runs it in the background.
+        fn accept_async_job(job: AsyncJob) -> axum::response::Result<axum::response::Response> {
+            let format = connectors::Format::from_content_type(&job.content_type);
+            lookup(&job.name, &job.operation, format.peek_version(&job.envelope))?;
+            let token = job.token.clone();
+            #submit
+            Ok(axum::response::IntoResponse::into_response((
+                axum::http::StatusCode::ACCEPTED,
+                axum::Json(serde_json::json!({ "token": token, "status": "accepted" })),
+            )))
+        }
+
+        /// Runs the handler and delivers the outcome, failing when the callback was not acknowledged.
+        async fn run_async_job(job: AsyncJob) -> Result<(), JobAttempt> {
+            let format = connectors::Format::from_content_type(&job.content_type);
+            let result = execute(job.name.clone(), job.operation.clone(), job.envelope.clone().into(), format, connectors::Context::default().with_request_id(job.token.clone()))
+                .await
+                .and_then(|out| out.into_value().map_err(connectors::ExecError::Handler));
+            let handler_error = result.as_ref().err().map(ToString::to_string);
+            let payload = match result {
+                Ok(value) => serde_json::json!({ "token": job.token, "status": "succeeded", "result": value }),
+                Err(e) => {
+                    let mut payload = serde_json::json!({ "token": job.token, "status": "failed", "error": e.to_string(), "retriable": e.retriable() });
+                    if let Some(metadata) = e.retry_metadata() {
+                        payload["retry"] = metadata;
+                    }
+                    payload
+                }
+            };
+            deliver_callback(&job.callback_url, &job.token, &payload).await.map_err(|delivery_error| JobAttempt {
+                at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64),
+                handler_error,
+                delivery_error,
+            })
+        }
+
+        /// Fails with the last delivery's error once the retries are exhausted.
+        async fn deliver_callback(url: &str, token: &str, payload: &serde_json::Value) -> Result<(), String> {
+            static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+            let client = CLIENT.get_or_init(reqwest::Client::new);
+            let body = serde_json::to_vec(payload).unwrap_or_default();
+            let secret = std::env::var("CALLBACK_SIGNING_SECRET").ok();
+
+            let mut backoff = std::time::Duration::from_millis(500);
+            let mut error = String::new();
+            for attempt in 0..=#retries {
+                let mut request = client
+                    .post(url)
+                    .header("content-type", "application/json")
+                    .header("x-connector-token", token)
+                    .body(body.clone());
+                if let Some(secret) = &secret {
+                    request = request.header("x-signature-256", callback_signature(secret, &body));
+                }
+                error = match request.send().await {
+                    Ok(res) if res.status().is_success() => return Ok(()),
+                    Ok(res) => format!("callback to {} answered {}", url, res.status()),
+                    Err(e) => format!("callback to {} failed: {}", url, e),
+                };
+                tracing::warn!("{} for job {} (attempt {})", error, token, attempt + 1);
+                if attempt < #retries {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(std::time::Duration::from_secs(60));
+                }
+            }
+            tracing::error!("giving up on callback for job {} to {}", token, url);
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the job-shaped execution path: `execute_job`, which runs a Zeebe job through the dispatch table and
+/// decides how the job ends, and with `outbound`, `POST /outbound`, which takes jobs as the Camunda connector bridge
+/// sends them.
+///
+/// The connector is the job type. Operation, input and version come from the job's variables as element templates bind
+/// them (`operation`, `input`, `version`), falling back to custom headers for the operation and version; without an
+/// `input` variable, all other variables are the input. `resultVariable`, `resultExpression` and `errorExpression`
+/// custom headers are applied as the Java Connector Runtime does.
+pub fn expand(outbound: bool) -> TokenStream {
+    let outbound = outbound.then(|| {
+        quote! {
+            /// Takes an activated job as JSON and answers with the request body of the Zeebe REST call that ends it:
+            /// completion (200), BPMN error (422) or failure (500).
+            async fn dispatch_outbound(
+                axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+                axum::Json(job): axum::Json<connectors::JobContext>,
+                ) -> axum::response::Response {
+                use axum::response::IntoResponse;
+                match execute_job(job, connectors::Context::default().with_client(client)).await {
+                    JobOutcome::Complete { variables } => axum::Json(serde_json::json!({ "variables": variables })).into_response(),
+                    JobOutcome::BpmnError { code, message, variables } => (
+                        axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                        axum::Json(serde_json::json!({ "errorCode": code, "errorMessage": message, "variables": variables })),
+                    )
+                        .into_response(),
+                    JobOutcome::Fail { message, retries, retry_backoff, variables } => (
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        axum::Json(serde_json::json!({
+                            "errorMessage": message,
+                            "retries": retries,
+                            "retryBackOff": retry_backoff.map_or(0, |backoff| backoff.as_millis() as u64),
+                            "variables": variables,
+                        })),
+                    )
+                        .into_response(),
+                }
+            }
+        }
+    });
+    quote! {
+        /// How a job ends, in the terms of the Zeebe job API.
+        #[derive(Debug)]
+        enum JobOutcome {
+            Complete { variables: serde_json::Value },
+            Fail { message: String, retries: u32, retry_backoff: Option<std::time::Duration>, variables: serde_json::Value },
+            BpmnError { code: String, message: String, variables: serde_json::Value },
+        }
+
+        impl JobOutcome {
+            fn fail(message: String, retries: u32) -> Self {
+                JobOutcome::Fail { message, retries, retry_backoff: None, variables: serde_json::json!({}) }
+            }
+
+            /// Handler errors decrement the job's retries unless the handler set them; errors retrying can't fix
+            /// use them all up, and refusals to run (load, breaker, disabling) leave them untouched.
+            fn from_error(e: connectors::ExecError, retries: u32) -> Self {
+                let message = e.to_string();
+                let (retries, retry_backoff) = match e {
+                    connectors::ExecError::Failed { error_code: Some(code), .. } => {
+                        return JobOutcome::BpmnError { code, message, variables: serde_json::json!({}) };
+                    }
+                    connectors::ExecError::Failed { retries: left, retry_backoff, .. } => (left.unwrap_or(retries.saturating_sub(1)), retry_backoff),
+                    connectors::ExecError::Unsupported(_) | connectors::ExecError::Input(_) => (0, None),
+                    connectors::ExecError::CircuitOpen { retry_after, .. } => (retries, Some(retry_after)),
+                    connectors::ExecError::Overloaded(_) => (retries, Some(std::time::Duration::from_secs(1))),
+                    connectors::ExecError::Disabled(_) => (retries, Some(std::time::Duration::from_secs(30))),
+                    connectors::ExecError::Handler(_) | connectors::ExecError::DeadlineExceeded(_) => (retries.saturating_sub(1), None),
+                };
+                JobOutcome::Fail { message, retries, retry_backoff, variables: serde_json::json!({}) }
+            }
+
+            /// The outcome an `errorExpression` result asks for, if it is a `bpmnError(...)` or `jobError(...)`.
+            fn from_error_expression(result: &serde_json::Value, retries: u32) -> Option<Self> {
+                let text = |key: &str| result.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                let variables = result.get("variables").cloned().unwrap_or_else(|| serde_json::json!({}));
+                match result.get("errorType").and_then(|v| v.as_str())? {
+                    "bpmnError" => Some(JobOutcome::BpmnError { code: text("code"), message: text("message"), variables }),
+                    "jobError" => Some(JobOutcome::Fail {
+                        message: text("message"),
+                        retries: result.get("retries").and_then(|v| v.as_u64()).map_or(retries.saturating_sub(1), |r| r as u32),
+                        retry_backoff: result.get("retryBackoff").and_then(job_backoff),
+                        variables,
+                    }),
+                    _ => None,
+                }
+            }
+        }
+
+        /// Milliseconds, or an ISO-8601 duration of hours, minutes and seconds such as `PT1M30S`.
+        fn job_backoff(value: &serde_json::Value) -> Option<std::time::Duration> {
+            if let Some(ms) = value.as_u64() {
+                return Some(std::time::Duration::from_millis(ms));
+            }
+            let mut rest = value.as_str()?.strip_prefix("PT")?;
+            let mut secs = 0.0;
+            while !rest.is_empty() {
+                let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+                let amount: f64 = rest[..end].parse().ok()?;
+                secs += amount * match &rest[end..end + 1] {
+                    "H" => 3600.0,
+                    "M" => 60.0,
+                    "S" => 1.0,
+                    _ => return None,
+                };
+                rest = &rest[end + 1..];
+            }
+            Some(std::time::Duration::from_secs_f64(secs))
+        }
+
+        async fn execute_job(job: connectors::JobContext, ctx: connectors::Context) -> JobOutcome {
+            let header = |key: &str| job.custom_headers.get(key).cloned();
+            let variables = serde_json::Value::Object(job.variables.clone());
+            let Some(operation) = variables.get("operation").and_then(|v| v.as_str()).map(str::to_string).or_else(|| header("operation")) else {
+                return JobOutcome::fail(format!("Job of type `{}` has no `operation` variable or header", job.job_type), 0);
+            };
+            let input = variables.get("input").cloned().unwrap_or_else(|| {
+                let mut input = job.variables.clone();
+                input.remove("operation");
+                input.remove("version");
+                serde_json::Value::Object(input)
+            });
+            let version = variables
+                .get("version")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str().and_then(|v| v.parse().ok())))
+                .or_else(|| header("version").and_then(|v| v.parse().ok()));
+            let envelope = serde_json::json!({
+                "id": job.job_key,
+                "variables": variables,
+                "params": { "operation": operation, "input": input, "version": version },
+            });
+            // Applied here rather than by `execute`, since `errorExpression` must see the unmapped output.
+            let mapping = connectors::ResultMapping { result_variable: header("resultVariable"), result_expression: header("resultExpression") };
+            let error_expression = header("errorExpression");
+            let (name, retries) = (job.job_type.clone(), job.retries);
+            let ctx = match job.deadline {
+                Some(deadline) => ctx.with_epoch_deadline(deadline),
+                None => ctx,
+            }
+            .with_job(job);
+
+            let body = serde_json::to_vec(&envelope).unwrap_or_default();
+            let result = execute(name, operation, body.into(), connectors::Format::Json, ctx)
+                .await
+                .and_then(|out| out.into_value().map_err(connectors::ExecError::Handler));
+
+            if let Some(expression) = error_expression {
+                let scope = match &result {
+                    Ok(output) => {
+                        let mut scope = output.as_object().cloned().unwrap_or_default();
+                        scope.insert("response".into(), output.clone());
+                        serde_json::Value::Object(scope)
+                    }
+                    Err(e) => {
+                        let code = e.retry_metadata().and_then(|m| m.get("errorCode").cloned()).unwrap_or(serde_json::Value::Null);
+                        serde_json::json!({ "error": { "code": code, "message": e.to_string() } })
+                    }
+                };
+                match connectors::feel::evaluate(&expression, &scope) {
+                    Ok(value) => {
+                        if let Some(outcome) = JobOutcome::from_error_expression(&value, retries) {
+                            return outcome;
+                        }
+                    }
+                    Err(e) => return JobOutcome::fail(format!("Bad errorExpression: {}", e), 0),
+                }
+            }
+
+            match result {
+                Ok(output) => match mapping.apply(output) {
+                    Ok(variables) => JobOutcome::Complete { variables },
+                    Err(e) => JobOutcome::fail(format!("Bad result mapping: {}", e), 0),
+                },
+                Err(e) => JobOutcome::from_error(e, retries),
+            }
+        }
+
+        #outbound
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates a circuit breaker per (connector, registered operation), a catch-all's calls sharing the breaker of `*`.
+///
+/// After `threshold` consecutive handler errors the circuit opens and calls fail fast with `503` and `Retry-After`
+/// for `cooldown`. Then a single probe call is let through: success closes the circuit, failure reopens it.
+pub fn expand(threshold: u32, cooldown: Duration) -> TokenStream {
+    let cooldown_ms = cooldown.as_millis() as u64;
+    quote! {
+        #[derive(Default)]
+        struct CircuitState {
+            failures: u32,
+            opened_at: Option<std::time::Instant>,
+            probe_started: Option<std::time::Instant>,
+        }
+
+        fn circuits() -> &'static std::sync::Mutex<std::collections::HashMap<(String, String), CircuitState>> {
+            static CIRCUITS: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<(String, String), CircuitState>>> =
+                std::sync::OnceLock::new();
+            CIRCUITS.get_or_init(Default::default)
+        }
+
+        fn circuit_check(name: &str, operation: &str) -> Result<(), connectors::ExecError> {
+            let cooldown = std::time::Duration::from_millis(#cooldown_ms);
+            let mut circuits = circuits().lock().unwrap();
+            let Some(state) = circuits.get_mut(&(name.to_string(), operation.to_string())) else {
+                return Ok(());
+            };
+            let Some(opened_at) = state.opened_at else {
+                return Ok(());
+            };
+            let open_for = opened_at.elapsed();
+            let retry_after = if open_for < cooldown {
+                cooldown - open_for
+            } else if state.probe_started.is_some_and(|probe| probe.elapsed() < cooldown) {
+                // Half-open with a probe already in flight.
+                std::time::Duration::from_secs(1)
+            } else {
+                state.probe_started = Some(std::time::Instant::now());
+                return Ok(());
+            };
+            Err(connectors::ExecError::CircuitOpen {
+                message: format!("Circuit open for `{}`/`{}`, downstream is failing", name, operation),
+                retry_after,
+            })
+        }
+
+        fn circuit_record(name: &str, operation: &str, failed: bool) {
+            let mut circuits = circuits().lock().unwrap();
+            let key = (name.to_string(), operation.to_string());
+            // Healthy circuits hold no state, so only failing operations take up room.
+            if !failed {
+                circuits.remove(&key);
+                return;
+            }
+            let state = circuits.entry(key).or_default();
+            state.failures += 1;
+            if state.failures >= #threshold || state.opened_at.is_some() {
+                state.opened_at = Some(std::time::Instant::now());
+                state.probe_started = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings `AwsSecrets` reads. Credentials and the region come from the AWS SDK's default chain instead, so
+/// from `AWS_*` environment variables, profiles, or the role of the task, pod or instance.
+pub const AWS_SETTINGS: [&str; 1] = ["SECRETS_CACHE_TTL"];
+
+/// The settings `GcpSecrets` reads.
+pub const GCP_SETTINGS: [&str; 4] = ["SECRETS_CACHE_TTL", "GCP_PROJECT", "GCP_SECRET_MANAGER_ENDPOINT", "GCE_METADATA_HOST"];
+
+/// Generates `SecretCache`, which keeps the secrets of the AWS and GCP providers for `SECRETS_CACHE_TTL`, unknown
+/// ones included, so that each resolution doesn't cost an API call.
+pub fn cache() -> TokenStream {
+    quote! {
+        struct SecretCache {
+            ttl: std::time::Duration,
+            entries: std::sync::Mutex<std::collections::HashMap<String, (std::time::Instant, Option<String>)>>,
+        }
+
+        impl SecretCache {
+            fn from_settings() -> Result<Self, String> {
+                Ok(SecretCache {
+                    ttl: duration_setting("SECRETS_CACHE_TTL", std::time::Duration::from_secs(300))?,
+                    entries: Default::default(),
+                })
+            }
+
+            async fn get<F: std::future::Future<Output = Result<Option<String>, String>>>(
+                &self,
+                name: &str,
+                fetch: F,
+            ) -> Result<Option<String>, String> {
+                if let Some((read_at, value)) = self.entries.lock().unwrap().get(name) {
+                    if read_at.elapsed() < self.ttl {
+                        return Ok(value.clone());
+                    }
+                }
+                let value = fetch.await?;
+                self.entries.lock().unwrap().insert(name.to_string(), (std::time::Instant::now(), value.clone()));
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Generates `AwsSecrets`, reading the `SecretString` (or UTF-8 `SecretBinary`) of the AWS Secrets Manager secret
+/// named after the secret, its current version.
+pub fn aws() -> TokenStream {
+    quote! {
+        struct AwsSecrets {
+            client: tokio::sync::OnceCell<aws_sdk_secretsmanager::Client>,
+            cache: SecretCache,
+        }
+
+        impl AwsSecrets {
+            fn from_settings() -> Result<Self, String> {
+                Ok(AwsSecrets { client: tokio::sync::OnceCell::new(), cache: SecretCache::from_settings()? })
+            }
+
+            async fn fetch(&self, name: &str) -> Result<Option<String>, String> {
+                let client = self
+                    .client
+                    .get_or_init(|| async { aws_sdk_secretsmanager::Client::new(&aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await) })
+                    .await;
+                match client.get_secret_value().secret_id(name).send().await {
+                    Ok(output) => match (output.secret_string(), output.secret_binary()) {
+                        (Some(value), _) => Ok(Some(value.to_string())),
+                        (None, Some(binary)) => String::from_utf8(binary.clone().into_inner()).map(Some).map_err(|_| format!("secret `{}` is not UTF-8", name)),
+                        (None, None) => Ok(None),
+                    },
+                    Err(e) if e.as_service_error().is_some_and(|e| e.is_resource_not_found_exception()) => Ok(None),
+                    Err(e) => Err(aws_sdk_secretsmanager::error::DisplayErrorContext(&e).to_string()),
+                }
+            }
+        }
+
+        impl SecretsProvider for AwsSecrets {
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a> {
+                Box::pin(self.cache.get(name, self.fetch(name)))
+            }
+        }
+    }
+}
+
+/// Generates `GcpSecrets`, reading the latest version of the GCP Secret Manager secret named after the secret. It
+/// authenticates as the service account of the metadata server, that of the GKE workload or the Compute Engine
+/// instance, and the project defaults to that of the metadata server too.
+pub fn gcp() -> TokenStream {
+    quote! {
+        struct GcpToken {
+            token: String,
+            expires_at: std::time::Instant,
+        }
+
+        struct GcpSecrets {
+            client: reqwest::Client,
+            metadata: String,
+            endpoint: String,
+            project: tokio::sync::OnceCell<String>,
+            token: tokio::sync::Mutex<Option<GcpToken>>,
+            cache: SecretCache,
+        }
+
+        impl GcpSecrets {
+            fn from_settings() -> Result<Self, String> {
+                let project = tokio::sync::OnceCell::new();
+                if let Some(configured) = setting("GCP_PROJECT") {
+                    let _ = project.set(configured);
+                }
+                Ok(GcpSecrets {
+                    client: reqwest::Client::builder().timeout(std::time::Duration::from_secs(10)).build().map_err(|e| e.to_string())?,
+                    metadata: format!("http://{}", setting("GCE_METADATA_HOST").unwrap_or_else(|| "metadata.google.internal".to_string())),
+                    endpoint: setting("GCP_SECRET_MANAGER_ENDPOINT")
+                        .unwrap_or_else(|| "https://secretmanager.googleapis.com".to_string())
+                        .trim_end_matches('/')
+                        .to_string(),
+                    project,
+                    token: tokio::sync::Mutex::new(None),
+                    cache: SecretCache::from_settings()?,
+                })
+            }
+
+            async fn metadata_get(&self, path: &str) -> Result<axum::body::Bytes, String> {
+                let response = self
+                    .client
+                    .get(format!("{}/computeMetadata/v1/{}", self.metadata, path))
+                    .header("Metadata-Flavor", "Google")
+                    .send()
+                    .await
+                    .map_err(|e| format!("cannot reach the metadata server: {}", e))?;
+                if !response.status().is_success() {
+                    return Err(format!("the metadata server answered {} for `{}`", response.status(), path));
+                }
+                response.bytes().await.map_err(|e| e.to_string())
+            }
+
+            /// An access token of the service account, fetched again a minute before it expires.
+            async fn token(&self) -> Result<String, String> {
+                let mut current = self.token.lock().await;
+                if let Some(token) = current.as_ref().filter(|token| token.expires_at > std::time::Instant::now()) {
+                    return Ok(token.token.clone());
+                }
+                let body = self.metadata_get("instance/service-accounts/default/token").await?;
+                let body: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+                let token = body["access_token"].as_str().ok_or("the metadata server returned no access token")?.to_string();
+                let expires_in = body["expires_in"].as_u64().unwrap_or(0).saturating_sub(60);
+                *current = Some(GcpToken { token: token.clone(), expires_at: std::time::Instant::now() + std::time::Duration::from_secs(expires_in) });
+                Ok(token)
+            }
+
+            async fn project(&self) -> Result<&str, String> {
+                self.project
+                    .get_or_try_init(|| async {
+                        let body = self.metadata_get("project/project-id").await?;
+                        String::from_utf8(body.to_vec()).map_err(|e| e.to_string())
+                    })
+                    .await
+                    .map(String::as_str)
+            }
+
+            async fn fetch(&self, name: &str) -> Result<Option<String>, String> {
+                let url = format!("{}/v1/projects/{}/secrets/{}/versions/latest:access", self.endpoint, self.project().await?, name);
+                let response = self.client.get(url).bearer_auth(self.token().await?).send().await.map_err(|e| e.to_string())?;
+                match response.status() {
+                    reqwest::StatusCode::NOT_FOUND => Ok(None),
+                    status if status.is_success() => {
+                        let body = response.bytes().await.map_err(|e| e.to_string())?;
+                        let body: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+                        let data = body["payload"]["data"].as_str().ok_or("Secret Manager returned no payload")?;
+                        let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, data).map_err(|e| e.to_string())?;
+                        String::from_utf8(data).map(Some).map_err(|_| format!("secret `{}` is not UTF-8", name))
+                    }
+                    status => {
+                        // A revoked token is replaced on the next call.
+                        if status == reqwest::StatusCode::UNAUTHORIZED {
+                            *self.token.lock().await = None;
+                        }
+                        Err(format!("Secret Manager answered {} for `{}`", status, name))
+                    }
+                }
+            }
+        }
+
+        impl SecretsProvider for GcpSecrets {
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a> {
+                Box::pin(self.cache.get(name, self.fetch(name)))
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the `/cloudevents` handler accepting CloudEvents 1.0 in structured or binary HTTP mode.
+///
+/// The event `type` is read as `[prefix.]<name>.<operation>` and `data` becomes the connector input.
+pub fn expand() -> TokenStream {
+    quote! {
+        async fn dispatch_cloudevent(
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+            ) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+            let bad_request = |msg: &str| (axum::http::StatusCode::BAD_REQUEST, msg.to_string());
+            let header = |key: &str| headers.get(key).and_then(|v| v.to_str().ok()).map(str::to_string);
+
+            let structured = header("content-type")
+                .is_some_and(|ct| ct.starts_with("application/cloudevents+json"));
+            let (spec_version, id, event_type, data) = if structured {
+                let mut event: serde_json::Value = serde_json::from_slice(&body)
+                    .map_err(|_| bad_request("Invalid CloudEvent"))?;
+                if event.get("data_base64").is_some() {
+                    return Err(bad_request("CloudEvents `data_base64` is not supported, send JSON `data`"));
+                }
+                let attr = |key: &str| event.get(key).and_then(|v| v.as_str()).map(str::to_string);
+                (attr("specversion"), attr("id"), attr("type"), event["data"].take())
+            } else {
+                let data = if body.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    serde_json::from_slice(&body).map_err(|_| bad_request("Invalid CloudEvent data, expected JSON"))?
+                };
+                (header("ce-specversion"), header("ce-id"), header("ce-type"), data)
+            };
+
+            if spec_version.as_deref() != Some("1.0") {
+                return Err(bad_request("Unsupported CloudEvents specversion, expected `1.0`"));
+            }
+            let id = id.ok_or_else(|| bad_request("Missing CloudEvents `id`"))?;
+            let event_type = event_type.ok_or_else(|| bad_request("Missing CloudEvents `type`"))?;
+            let mut segments = event_type.rsplitn(3, '.');
+            let (operation, name) = match (segments.next(), segments.next()) {
+                (Some(operation), Some(name)) => (operation.to_string(), name.to_string()),
+                _ => return Err(bad_request("CloudEvents `type` must end with `<connector>.<operation>`")),
+            };
+
+            let envelope = serde_json::json!({
+                "id": id,
+                "params": { "operation": operation, "input": data },
+            });
+            let envelope = serde_json::to_vec(&envelope)
+                .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+            let ctx = connectors::Context::from_headers(&headers)
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?
+                .with_client(client);
+            let source = format!("/csp/{}", name);
+            let bytes = execute(name, operation, envelope.into(), connectors::Format::Json, ctx)
+                .await?
+                .encode(connectors::Format::Json)
+                .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+            // Reply in binary mode so eventing brokers can route the result as a follow-up event.
+            Ok(axum::response::IntoResponse::into_response((
+                [
+                    ("ce-specversion", "1.0".to_string()),
+                    ("ce-id", id),
+                    ("ce-type", format!("{}.result", event_type)),
+                    ("ce-source", source),
+                    ("content-type", "application/json".to_string()),
+                ],
+                bytes,
+            )))
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+/// Generates the typed connector configurations of `#[camunda_connector(config = T)]`, loaded at startup from the
+/// settings prefixed with the connector's name: `SMTP_HOST` and `SMTP_PORT` fill the `host` and `port` fields of
+/// connector `smtp`'s config. Values are read into each field's type, lists comma-separated.
+pub fn expand() -> TokenStream {
+    let numbers = ["i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128", "f32", "f64"].map(|ty| {
+        let ty = format_ident!("{}", ty);
+        let deserialize = format_ident!("deserialize_{}", ty);
+        let visit = format_ident!("visit_{}", ty);
+        quote! {
+            fn #deserialize<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse::<#ty>() {
+                    Ok(value) => visitor.#visit(value),
+                    Err(_) => Err(serde::de::Error::invalid_value(serde::de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        }
+    });
+
+    quote! {
+        /// A connector's configuration, handed to handlers taking a `connectors::Config<T>` parameter after their
+        /// input, with `config = T` declared on the connector.
+        pub struct Config<T: 'static>(pub &'static T);
+
+        impl<T: Send + Sync + 'static> Config<T> {
+            pub fn get(connector: &str) -> Self {
+                let config = configs().read().unwrap().get(&(connector.to_string(), std::any::TypeId::of::<T>())).copied();
+                let config = config.unwrap_or_else(|| panic!("the config of connector `{}` is used before startup loaded it", connector));
+                Config(config.downcast_ref().expect("configs are stored by type"))
+            }
+        }
+
+        impl<T> Clone for Config<T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T> Copy for Config<T> {}
+
+        impl<T> std::ops::Deref for Config<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                self.0
+            }
+        }
+
+        pub struct ConfigRecipe {
+            pub connector: &'static str,
+            /// The config's type, as written.
+            pub type_name: &'static str,
+            pub load: fn(&'static str) -> Result<(), String>,
+        }
+
+        ::inventory::collect!(ConfigRecipe);
+
+        type ConfigStore = std::collections::HashMap<(String, std::any::TypeId), &'static (dyn std::any::Any + Send + Sync)>;
+
+        fn configs() -> &'static std::sync::RwLock<ConfigStore> {
+            static CONFIGS: std::sync::OnceLock<std::sync::RwLock<ConfigStore>> = std::sync::OnceLock::new();
+            CONFIGS.get_or_init(Default::default)
+        }
+
+        /// The prefix of connector `name`'s settings: `CRM_SYNC_` for `crm-sync`.
+        pub fn config_prefix(connector: &str) -> String {
+            let mut prefix: String = connector.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+            prefix.push('_');
+            prefix
+        }
+
+        /// Whether `key` is a setting of a connector's config.
+        pub fn is_config_key(key: &str) -> bool {
+            ::inventory::iter::<ConfigRecipe>.into_iter().any(|r| key.starts_with(&config_prefix(r.connector)))
+        }
+
+        /// Connector `connector`'s settings, by field name: the configuration file's over the environment's.
+        fn config_settings(connector: &str) -> std::collections::BTreeMap<String, String> {
+            let prefix = config_prefix(connector);
+            let file = config_file_settings().read().unwrap().clone();
+            std::env::vars()
+                .chain(file)
+                .filter(|(_, value)| !value.is_empty())
+                .filter_map(|(key, value)| Some((key.strip_prefix(&prefix)?.to_ascii_lowercase(), value)))
+                .collect()
+        }
+
+        pub fn load_config<T: serde::de::DeserializeOwned + Send + Sync + 'static>(connector: &'static str) -> Result<(), String> {
+            let settings = config_settings(connector).into_iter().map(|(field, value)| (field, SettingValue(value)));
+            let config = T::deserialize(serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(settings))
+                .map_err(|e| format!("{} (settings prefixed with {})", e, config_prefix(connector)))?;
+            let config: &'static T = Box::leak(Box::new(config));
+            configs().write().unwrap().insert((connector.to_string(), std::any::TypeId::of::<T>()), config);
+            Ok(())
+        }
+
+        /// Loads every connector's config, returning the problems of those missing or invalid.
+        pub fn load_configs() -> Vec<String> {
+            let mut problems = Vec::new();
+            let mut loaded = std::collections::HashSet::new();
+            for r in ::inventory::iter::<ConfigRecipe> {
+                // Each handler of a connector declares its config.
+                if !loaded.insert((r.connector, r.type_name)) {
+                    continue;
+                }
+                if let Err(e) = (r.load)(r.connector) {
+                    problems.push(format!("config of connector `{}` ({}): {}", r.connector, r.type_name, e));
+                }
+            }
+            problems
+        }
+
+        /// A setting's value, read into whichever type the config's field has.
+        struct SettingValue(String);
+
+        impl<'de> serde::de::IntoDeserializer<'de, serde::de::value::Error> for SettingValue {
+            type Deserializer = Self;
+
+            fn into_deserializer(self) -> Self {
+                self
+            }
+        }
+
+        impl<'de> serde::Deserializer<'de> for SettingValue {
+            type Error = serde::de::value::Error;
+
+            fn deserialize_any<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.visit_string(self.0)
+            }
+
+            fn deserialize_bool<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.as_str() {
+                    "true" => visitor.visit_bool(true),
+                    "false" => visitor.visit_bool(false),
+                    _ => Err(serde::de::Error::invalid_value(serde::de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+
+            #(#numbers)*
+
+            // Empty settings count as unset, so a present one is always `Some`.
+            fn deserialize_option<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.visit_some(self)
+            }
+
+            fn deserialize_seq<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let items = self.0.split(',').map(|item| SettingValue(item.trim().to_string()));
+                visitor.visit_seq(serde::de::value::SeqDeserializer::new(items))
+            }
+
+            fn deserialize_newtype_struct<V: serde::de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.visit_newtype_struct(self)
+            }
+
+            fn deserialize_enum<V: serde::de::Visitor<'de>>(
+                self,
+                _name: &'static str,
+                _variants: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                visitor.visit_enum(serde::de::IntoDeserializer::<Self::Error>::into_deserializer(self.0))
+            }
+
+            serde::forward_to_deserialize_any! {
+                char str string bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// Headers the runtime itself reads, allowed when `cors_headers` is not set.
+const DEFAULT_HEADERS: &[&str] = &[
+    "content-type",
+    "idempotency-key",
+    "x-callback-url",
+    "x-envelope-version",
+    "x-request-deadline",
+    "x-request-id",
+];
+
+/// Response headers the runtime sets, readable by browser callers.
+const EXPOSED_HEADERS: &[&str] = &[
+    "deprecation",
+    "idempotent-replayed",
+    "retry-after",
+    "sunset",
+    "x-envelope-version",
+    "x-error-retriable",
+    "x-job-error-code",
+    "x-job-retries",
+    "x-job-retry-backoff",
+];
+
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+pub struct CorsArgs {
+    pub origins: Vec<LitStr>,
+    pub methods: Option<Vec<LitStr>>,
+    pub headers: Option<Vec<LitStr>>,
+    pub credentials: bool,
+}
+
+/// Generates the `CorsLayer` wrapping the router, checking the configured values at compile time.
+pub fn expand(args: &CorsArgs) -> syn::Result<TokenStream> {
+    let any_origin = args.origins.iter().any(|o| o.value() == "*");
+    if any_origin && args.credentials {
+        return Err(Error::new_spanned(&args.origins[0], "`cors_credentials = true` cannot be combined with the `*` origin"));
+    }
+    if let Some(origin) = args.origins.iter().find(|o| o.value() != "*" && !o.value().contains("://")) {
+        return Err(Error::new_spanned(origin, "Expected an origin such as \"https://console.example.com\", or \"*\""));
+    }
+    let origins = if any_origin {
+        quote! { tower_http::cors::AllowOrigin::any() }
+    } else {
+        let origins = &args.origins;
+        quote! { tower_http::cors::AllowOrigin::list([#(axum::http::HeaderValue::from_static(#origins)),*]) }
+    };
+
+    let methods = match &args.methods {
+        Some(methods) => {
+            if let Some(method) = methods.iter().find(|m| !METHODS.contains(&m.value().as_str())) {
+                return Err(Error::new_spanned(method, format!("Unknown method, expected one of {}", METHODS.join(", "))));
+            }
+            methods.iter().map(LitStr::value).collect()
+        }
+        None => vec!["GET".to_string(), "POST".to_string()],
+    };
+    let methods = methods.iter().map(|m| quote! { axum::http::Method::from_bytes(#m.as_bytes()).unwrap() });
+
+    let headers = match &args.headers {
+        Some(headers) => {
+            if let Some(header) = headers.iter().find(|h| h.value().is_empty() || !h.value().chars().all(|c| c.is_ascii_alphanumeric() || c == '-')) {
+                return Err(Error::new_spanned(header, "Invalid header name"));
+            }
+            headers.iter().map(|h| h.value().to_ascii_lowercase()).collect()
+        }
+        None => DEFAULT_HEADERS.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+    };
+    let exposed = EXPOSED_HEADERS;
+    let credentials = args.credentials;
+
+    Ok(quote! {
+        .layer(
+            tower_http::cors::CorsLayer::new()
+                .allow_origin(#origins)
+                .allow_methods([#(#methods),*])
+                .allow_headers([#(axum::http::HeaderName::from_static(#headers)),*])
+                .expose_headers([#(axum::http::HeaderName::from_static(#exposed)),*])
+                .allow_credentials(#credentials),
+        )
+    })
+}
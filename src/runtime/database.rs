@@ -0,0 +1,169 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// The settings `Database` reads, from the configuration file or the environment.
+pub const SETTINGS: [&str; 5] = [
+    "DATABASE_URL",
+    "DATABASE_MAX_CONNECTIONS",
+    "DATABASE_MIN_CONNECTIONS",
+    "DATABASE_ACQUIRE_TIMEOUT",
+    "DATABASE_IDLE_TIMEOUT",
+];
+
+/// `database = "..."`: the pool to build and the crate building it.
+pub enum Backend {
+    /// `postgres`, `mysql` or `sqlite`, an `sqlx` pool of that database.
+    Sqlx(TokenStream, Dialect),
+    /// `deadpool-postgres`, a `deadpool_postgres` pool of `tokio_postgres` clients.
+    DeadpoolPostgres,
+}
+
+/// The SQL the database speaks, for the runtime's own queries.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+impl Backend {
+    pub fn parse(lit: &LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "postgres" => Ok(Backend::Sqlx(quote! { sqlx::Postgres }, Dialect::Postgres)),
+            "mysql" => Ok(Backend::Sqlx(quote! { sqlx::MySql }, Dialect::MySql)),
+            "sqlite" => Ok(Backend::Sqlx(quote! { sqlx::Sqlite }, Dialect::Sqlite)),
+            "deadpool-postgres" => Ok(Backend::DeadpoolPostgres),
+            _ => Err(Error::new_spanned(lit, "Expected `postgres`, `mysql`, `sqlite` or `deadpool-postgres`")),
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Backend::Sqlx(_, dialect) => *dialect,
+            Backend::DeadpoolPostgres => Dialect::Postgres,
+        }
+    }
+}
+
+/// Generates `connectors::Database`, the pool handlers take as `connectors::Resource<connectors::Database>`, built at
+/// startup from `DATABASE_URL`. Connections are opened on demand, so a database that is down at startup fails the
+/// `database` health check, keeping `/readyz` unready, rather than stopping the process.
+pub fn expand(backend: &Backend) -> TokenStream {
+    let (pool, build, ping) = match backend {
+        Backend::Sqlx(database, _) => (
+            quote! { sqlx::Pool<#database> },
+            quote! {
+                sqlx::pool::PoolOptions::<#database>::new()
+                    .max_connections(max_connections)
+                    .min_connections(parse_setting("DATABASE_MIN_CONNECTIONS", 0)?)
+                    .acquire_timeout(acquire_timeout)
+                    .idle_timeout(duration_setting("DATABASE_IDLE_TIMEOUT", std::time::Duration::from_secs(600))?)
+                    .connect_lazy(&url)
+                    .map_err(|e| format!("invalid DATABASE_URL: {}", e))
+            },
+            quote! {
+                sqlx::query("SELECT 1").execute(&pool.0).await.map_err(|e| e.to_string())?;
+            },
+        ),
+        Backend::DeadpoolPostgres => (
+            quote! { deadpool_postgres::Pool },
+            quote! {
+                let mut config = deadpool_postgres::Config::new();
+                config.url = Some(url);
+                config.pool = Some(deadpool_postgres::PoolConfig {
+                    max_size: max_connections as usize,
+                    timeouts: deadpool_postgres::Timeouts::wait_millis(acquire_timeout.as_millis() as u64),
+                    ..Default::default()
+                });
+                config
+                    .create_pool(Some(deadpool_postgres::Runtime::Tokio1), deadpool_postgres::tokio_postgres::NoTls)
+                    .map_err(|e| format!("invalid DATABASE_URL: {}", e))
+            },
+            quote! {
+                let client = pool.get().await.map_err(|e| e.to_string())?;
+                client.simple_query("SELECT 1").await.map_err(|e| e.to_string())?;
+            },
+        ),
+    };
+
+    quote! {
+        /// The database pool (`database = "..."`), taken by handlers as `connectors::Resource<connectors::Database>`.
+        pub struct Database(pub #pool);
+
+        impl std::ops::Deref for Database {
+            type Target = #pool;
+
+            fn deref(&self) -> &#pool {
+                &self.0
+            }
+        }
+
+        static DATABASE: std::sync::OnceLock<Database> = std::sync::OnceLock::new();
+
+        impl ConnectorResource for Database {
+            fn instance() -> &'static Self {
+                DATABASE.get().expect("the database pool is used before startup built it")
+            }
+        }
+
+        fn parse_setting<T: std::str::FromStr>(key: &str, default: T) -> Result<T, String> {
+            match setting(key) {
+                Some(value) => value.parse().map_err(|_| format!("{} must be an integer, got `{}`", key, value)),
+                None => Ok(default),
+            }
+        }
+
+        fn build_database() -> Result<#pool, String> {
+            let url = setting("DATABASE_URL").ok_or("DATABASE_URL is not set")?;
+            let max_connections: u32 = parse_setting("DATABASE_MAX_CONNECTIONS", 10)?;
+            let acquire_timeout = duration_setting("DATABASE_ACQUIRE_TIMEOUT", std::time::Duration::from_secs(5))?;
+            #build
+        }
+
+        fn init_database() -> HookFuture {
+            Box::pin(async {
+                let _ = DATABASE.set(Database(build_database()?));
+                Ok(())
+            })
+        }
+
+        fn database_health() -> HookFuture {
+            Box::pin(async {
+                let pool = Database::instance();
+                #ping
+                Ok(())
+            })
+        }
+
+        fn check_database_settings() -> Vec<String> {
+            build_database().err().into_iter().collect()
+        }
+
+        ::inventory::submit! {
+            StartupCheck {
+                name: "database",
+                check: check_database_settings,
+            }
+        }
+
+        // Built before the user's resources, which may use it.
+        ::inventory::submit! {
+            ResourceRecipe {
+                name: "database",
+                type_name: "Database",
+                order: i32::MIN,
+                init: init_database,
+            }
+        }
+
+        ::inventory::submit! {
+            HealthRecipe {
+                name: "database",
+                connector: None,
+                timeout: std::time::Duration::from_secs(5),
+                check: database_health,
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// Where `dead_letter` writes the asynchronous jobs whose callback could not be delivered.
+pub enum Sink {
+    /// One JSON line per job, appended to a file.
+    File(LitStr),
+    /// One `<token>.json` object per job, under an `object_store` URL such as `s3://bucket/prefix`.
+    ObjectStore(LitStr),
+    /// One message per job, keyed by its token.
+    Kafka { brokers: String, topic: String },
+}
+
+impl Sink {
+    /// Parses `dead_letter`: a path, `"file://<path>"`, an `object_store` URL or `"kafka://<brokers>/<topic>"`.
+    pub fn parse(lit: &LitStr) -> syn::Result<Self> {
+        let value = lit.value();
+        match value.split_once("://") {
+            None => Ok(Sink::File(lit.clone())),
+            Some(("file", path)) => Ok(Sink::File(LitStr::new(path, lit.span()))),
+            Some(("s3" | "s3a" | "gs" | "az" | "azure" | "abfs" | "abfss", _)) => Ok(Sink::ObjectStore(lit.clone())),
+            Some(("kafka", rest)) => match rest.split_once('/') {
+                Some((brokers, topic)) if !brokers.is_empty() && !topic.is_empty() && !topic.contains('/') => {
+                    Ok(Sink::Kafka { brokers: brokers.to_string(), topic: topic.to_string() })
+                }
+                _ => Err(Error::new_spanned(lit, "Expected a Kafka sink such as \"kafka://broker1:9092,broker2:9092/dead-letters\"")),
+            },
+            Some((scheme, _)) => Err(Error::new_spanned(
+                lit,
+                format!("Unsupported dead-letter sink `{}://`, expected a path, `file://`, `s3://`, `gs://`, `az://` or `kafka://`", scheme),
+            )),
+        }
+    }
+}
+
+/// Generates the dead-letter sink of `dead_letter`: an asynchronous job whose callback is still not acknowledged
+/// once its retries are exhausted is written with its envelope and the errors of each attempt, rather than dropped.
+///
+/// The sink is opened at startup, so that a misconfigured one stops the process before it accepts jobs it can't keep.
+pub fn expand(sink: &Sink) -> TokenStream {
+    let (open, write) = match sink {
+        Sink::File(path) => (
+            quote! {
+                static DEAD_LETTERS: std::sync::OnceLock<std::sync::Mutex<std::fs::File>> = std::sync::OnceLock::new();
+
+                fn open_dead_letters() {
+                    let file = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(#path)
+                        .unwrap_or_else(|e| panic!("cannot open dead letters at `{}`: {}", #path, e));
+                    let _ = DEAD_LETTERS.set(std::sync::Mutex::new(file));
+                }
+            },
+            quote! {
+                use std::io::Write as _;
+                let file = DEAD_LETTERS.get().ok_or("dead letters are not open")?;
+                let mut line = content;
+                line.push(b'\n');
+                tokio::task::block_in_place(|| {
+                    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                    file.write_all(&line).and_then(|()| file.sync_data())
+                })
+                .map_err(|e| format!("cannot write to `{}`: {}", #path, e))
+            },
+        ),
+        Sink::ObjectStore(url) => (
+            quote! {
+                static DEAD_LETTERS: std::sync::OnceLock<(Box<dyn object_store::ObjectStore>, object_store::path::Path)> = std::sync::OnceLock::new();
+
+                fn open_dead_letters() {
+                    let url = url::Url::parse(#url).unwrap_or_else(|e| panic!("invalid dead-letter URL `{}`: {}", #url, e));
+                    // Credentials and options come from the environment, as for the document store.
+                    let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+                    let store = object_store::parse_url_opts(&url, options)
+                        .unwrap_or_else(|e| panic!("cannot open dead letters at `{}`: {}", #url, e));
+                    let _ = DEAD_LETTERS.set(store);
+                }
+            },
+            quote! {
+                let (store, prefix) = DEAD_LETTERS.get().ok_or("dead letters are not open")?;
+                let path = prefix.child(format!("{}.json", letter.token));
+                store.put(&path, content.into()).await.map(|_| ()).map_err(|e| format!("cannot upload `{}`: {}", path, e))
+            },
+        ),
+        Sink::Kafka { brokers, topic } => (
+            quote! {
+                static DEAD_LETTERS: std::sync::OnceLock<rdkafka::producer::FutureProducer> = std::sync::OnceLock::new();
+
+                fn open_dead_letters() {
+                    let mut config = rdkafka::ClientConfig::new();
+                    config.set("bootstrap.servers", #brokers);
+                    // librdkafka properties, e.g. `KAFKA_SECURITY_PROTOCOL` for `security.protocol`.
+                    for (key, value) in std::env::vars() {
+                        if let Some(property) = key.strip_prefix("KAFKA_") {
+                            config.set(property.to_ascii_lowercase().replace('_', "."), value);
+                        }
+                    }
+                    let producer = config
+                        .create()
+                        .unwrap_or_else(|e| panic!("cannot create the dead-letter producer for `{}`: {}", #brokers, e));
+                    let _ = DEAD_LETTERS.set(producer);
+                }
+            },
+            quote! {
+                let producer = DEAD_LETTERS.get().ok_or("dead letters are not open")?;
+                let record = rdkafka::producer::FutureRecord::to(#topic).key(&letter.token).payload(&content);
+                producer
+                    .send(record, std::time::Duration::from_secs(10))
+                    .await
+                    .map(|_| ())
+                    .map_err(|(e, _)| format!("cannot produce to `{}`: {}", #topic, e))
+            },
+        ),
+    };
+    quote! {
+        /// An asynchronous job given up on, as written to the dead-letter sink and taken back by
+        /// `POST /admin/dead-letters/requeue`.
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct DeadLetter {
+            token: String,
+            connector: String,
+            operation: String,
+            callback_url: String,
+            content_type: String,
+            /// The envelope as sent: JSON as it is, other formats as an array of bytes.
+            envelope: serde_json::Value,
+            /// Oldest first, one per run of the job.
+            attempts: Vec<JobAttempt>,
+            /// Unix epoch milliseconds.
+            dead_lettered_at: u64,
+        }
+
+        impl DeadLetter {
+            fn new(job: &AsyncJob, attempts: Vec<JobAttempt>) -> Self {
+                let envelope = match connectors::Format::from_content_type(&job.content_type) {
+                    connectors::Format::Json => serde_json::from_slice(&job.envelope).ok(),
+                    _ => None,
+                };
+                DeadLetter {
+                    token: job.token.clone(),
+                    connector: job.name.clone(),
+                    operation: job.operation.clone(),
+                    callback_url: job.callback_url.clone(),
+                    content_type: job.content_type.clone(),
+                    envelope: envelope.unwrap_or_else(|| job.envelope.clone().into()),
+                    attempts,
+                    dead_lettered_at: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_millis() as u64),
+                }
+            }
+
+            /// The job to run again, under its original token so that its callback is recognized.
+            fn into_job(self) -> Result<AsyncJob, String> {
+                let envelope = match self.envelope {
+                    serde_json::Value::Array(_) => serde_json::from_value(self.envelope).map_err(|e| format!("bad envelope bytes: {}", e))?,
+                    envelope => serde_json::to_vec(&envelope).map_err(|e| e.to_string())?,
+                };
+                Ok(AsyncJob {
+                    token: self.token,
+                    name: self.connector,
+                    operation: self.operation,
+                    callback_url: self.callback_url,
+                    envelope,
+                    content_type: self.content_type,
+                })
+            }
+        }
+
+        #open
+
+        async fn write_dead_letter(letter: &DeadLetter) -> Result<(), String> {
+            let content = serde_json::to_vec(letter).map_err(|e| e.to_string())?;
+            #write
+        }
+
+        /// Writes the job to the dead-letter sink; returns whether it is kept there.
+        async fn dead_letter(job: &AsyncJob, attempts: Vec<JobAttempt>) -> bool {
+            let letter = DeadLetter::new(job, attempts);
+            match write_dead_letter(&letter).await {
+                Ok(()) => {
+                    tracing::warn!(token = job.token, connector = job.name, attempts = letter.attempts.len(), "job dead-lettered");
+                    true
+                }
+                Err(e) => {
+                    tracing::error!(token = job.token, connector = job.name, "cannot dead-letter job: {}", e);
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// How long a delivery owns a message it started handling, in milliseconds. A process that dies while handling it
+/// leaves it to be handled again once the claim lapses.
+const CLAIM_MS: u64 = 5 * 60 * 1000;
+
+/// How many message ids `inbound_dedup = "memory"` remembers, least recently seen first out.
+const MEMORY_CAPACITY: usize = 100_000;
+
+/// Where `inbound_dedup` remembers the inbound messages already handled.
+pub enum Store {
+    /// In the process, bounded to `MEMORY_CAPACITY` ids.
+    Memory,
+    /// In the Redis server at `REDIS_URL`, shared by every replica.
+    Redis,
+    /// In a SQLite file, kept across restarts.
+    Sqlite(LitStr),
+}
+
+impl Store {
+    /// Parses `inbound_dedup`: `"memory"`, `"redis"` or `"sqlite:<path>"`.
+    pub fn parse(lit: &LitStr) -> syn::Result<Self> {
+        let value = lit.value();
+        match value.as_str() {
+            "memory" => Ok(Store::Memory),
+            "redis" => Ok(Store::Redis),
+            _ => match value.strip_prefix("sqlite:").map(|path| path.strip_prefix("//").unwrap_or(path)) {
+                Some(path) if !path.is_empty() => Ok(Store::Sqlite(LitStr::new(path, lit.span()))),
+                _ => Err(Error::new_spanned(lit, "Expected `memory`, `redis` or `sqlite:<path>`, e.g. \"sqlite:data/dedup.db\"")),
+            },
+        }
+    }
+}
+
+/// Generates the dedup store of `inbound_dedup`, which `run_inbound` claims a message's id in before its handler
+/// runs: `inbound_dedup_claim`, then `inbound_dedup_complete` once the handler succeeded, keeping the id for `ttl`, or
+/// `inbound_dedup_release` once it failed, so that a redelivery is handled again.
+///
+/// A delivery arriving while another still handles the same message is told so, rather than running twice.
+pub fn expand(store: &Store, ttl: Duration) -> TokenStream {
+    let ttl_ms = ttl.as_millis() as u64;
+    let store = match store {
+        Store::Memory => quote! {
+            struct DedupEntry {
+                done: bool,
+                until: std::time::Instant,
+                /// Where the entry is in `recency`.
+                seen: u64,
+            }
+
+            #[derive(Default)]
+            struct DedupStore {
+                entries: std::collections::HashMap<(String, String), DedupEntry>,
+                /// Keys by when they were last seen, to evict the least recently seen when the store is full.
+                recency: std::collections::BTreeMap<u64, (String, String)>,
+                clock: u64,
+            }
+
+            impl DedupStore {
+                fn set(&mut self, key: (String, String), done: bool, until: std::time::Instant) {
+                    self.clock += 1;
+                    if let Some(old) = self.entries.insert(key.clone(), DedupEntry { done, until, seen: self.clock }) {
+                        self.recency.remove(&old.seen);
+                    }
+                    self.recency.insert(self.clock, key);
+                    while self.entries.len() > #MEMORY_CAPACITY {
+                        let Some((_, oldest)) = self.recency.pop_first() else { break };
+                        self.entries.remove(&oldest);
+                    }
+                }
+            }
+
+            fn dedup_store() -> std::sync::MutexGuard<'static, DedupStore> {
+                static STORE: std::sync::OnceLock<std::sync::Mutex<DedupStore>> = std::sync::OnceLock::new();
+                STORE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner())
+            }
+
+            async fn inbound_dedup_claim(handler: &str, id: &str) -> Result<DedupClaim, String> {
+                let mut store = dedup_store();
+                let key = (handler.to_string(), id.to_string());
+                let now = std::time::Instant::now();
+                if let Some(&DedupEntry { done, until, .. }) = store.entries.get(&key).filter(|entry| entry.until > now) {
+                    store.set(key, done, until);
+                    return Ok(if done { DedupClaim::Done } else { DedupClaim::InFlight });
+                }
+                store.set(key, false, now + std::time::Duration::from_millis(#CLAIM_MS));
+                Ok(DedupClaim::New)
+            }
+
+            async fn inbound_dedup_complete(handler: &str, id: &str) -> Result<(), String> {
+                let until = std::time::Instant::now() + std::time::Duration::from_millis(#ttl_ms);
+                dedup_store().set((handler.to_string(), id.to_string()), true, until);
+                Ok(())
+            }
+
+            async fn inbound_dedup_release(handler: &str, id: &str) -> Result<(), String> {
+                let mut store = dedup_store();
+                let key = (handler.to_string(), id.to_string());
+                if store.entries.get(&key).is_some_and(|entry| !entry.done) {
+                    if let Some(entry) = store.entries.remove(&key) {
+                        store.recency.remove(&entry.seen);
+                    }
+                }
+                Ok(())
+            }
+        },
+        Store::Redis => quote! {
+            fn dedup_redis_client() -> Result<redis::Client, String> {
+                let url = connectors::setting("REDIS_URL").unwrap_or_else(|| "redis://127.0.0.1:6379".to_string());
+                redis::Client::open(url).map_err(|e| format!("invalid REDIS_URL: {}", e))
+            }
+
+            /// Connected on first use, and reconnected by the manager after failures. Calls time out rather than hold
+            /// up the message while Redis is unreachable.
+            async fn dedup_redis() -> Result<redis::aio::ConnectionManager, String> {
+                static CONNECTION: tokio::sync::OnceCell<redis::aio::ConnectionManager> = tokio::sync::OnceCell::const_new();
+                CONNECTION
+                    .get_or_try_init(|| async {
+                        let config = redis::aio::ConnectionManagerConfig::new()
+                            .set_number_of_retries(1)
+                            .set_connection_timeout(std::time::Duration::from_secs(5))
+                            .set_response_timeout(std::time::Duration::from_secs(5));
+                        dedup_redis_client()?.get_connection_manager_with_config(config).await.map_err(|e| e.to_string())
+                    })
+                    .await
+                    .cloned()
+            }
+
+            fn dedup_redis_key(handler: &str, id: &str) -> String {
+                format!("connector-dedup:{}:{}", handler, id)
+            }
+
+            async fn inbound_dedup_claim(handler: &str, id: &str) -> Result<DedupClaim, String> {
+                let mut redis = dedup_redis().await?;
+                let key = dedup_redis_key(handler, id);
+                let claimed: Option<String> = redis::cmd("SET")
+                    .arg(&key)
+                    .arg("in-flight")
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(#CLAIM_MS)
+                    .query_async(&mut redis)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if claimed.is_some() {
+                    return Ok(DedupClaim::New);
+                }
+                let state: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut redis).await.map_err(|e| e.to_string())?;
+                Ok(if state.as_deref() == Some("done") { DedupClaim::Done } else { DedupClaim::InFlight })
+            }
+
+            async fn inbound_dedup_complete(handler: &str, id: &str) -> Result<(), String> {
+                let mut redis = dedup_redis().await?;
+                redis::cmd("SET")
+                    .arg(dedup_redis_key(handler, id))
+                    .arg("done")
+                    .arg("PX")
+                    .arg(#ttl_ms)
+                    .query_async::<()>(&mut redis)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+
+            async fn inbound_dedup_release(handler: &str, id: &str) -> Result<(), String> {
+                let mut redis = dedup_redis().await?;
+                redis::cmd("DEL").arg(dedup_redis_key(handler, id)).query_async::<()>(&mut redis).await.map_err(|e| e.to_string())
+            }
+
+            fn check_dedup_settings() -> Vec<String> {
+                dedup_redis_client().err().into_iter().collect()
+            }
+
+            fn dedup_health() -> connectors::HookFuture {
+                Box::pin(async {
+                    let mut redis = dedup_redis().await?;
+                    redis::cmd("PING").query_async::<()>(&mut redis).await.map_err(|e| e.to_string())
+                })
+            }
+
+            ::inventory::submit! {
+                crate::connectors::StartupCheck {
+                    name: "inbound_dedup",
+                    check: check_dedup_settings,
+                }
+            }
+
+            ::inventory::submit! {
+                crate::connectors::HealthRecipe {
+                    name: "inbound_dedup",
+                    connector: None,
+                    timeout: std::time::Duration::from_secs(5),
+                    check: dedup_health,
+                }
+            }
+        },
+        Store::Sqlite(path) => quote! {
+            fn dedup_millis() -> i64 {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as i64
+            }
+
+            /// Opened and its table created on first use.
+            async fn dedup_sqlite() -> Result<&'static sqlx::SqlitePool, String> {
+                static POOL: tokio::sync::OnceCell<sqlx::SqlitePool> = tokio::sync::OnceCell::const_new();
+                POOL.get_or_try_init(|| async {
+                    let options = sqlx::sqlite::SqliteConnectOptions::new().filename(#path).create_if_missing(true);
+                    let pool = sqlx::SqlitePool::connect_with(options)
+                        .await
+                        .map_err(|e| format!("cannot open `{}`: {}", #path, e))?;
+                    sqlx::query(
+                        "CREATE TABLE IF NOT EXISTS connector_inbound_dedup (handler TEXT NOT NULL, id TEXT NOT NULL, \
+                         done INTEGER NOT NULL, expires_at INTEGER NOT NULL, PRIMARY KEY (handler, id))",
+                    )
+                    .execute(&pool)
+                    .await
+                    .map_err(|e| format!("cannot create the dedup table in `{}`: {}", #path, e))?;
+                    Ok(pool)
+                })
+                .await
+            }
+
+            async fn inbound_dedup_claim(handler: &str, id: &str) -> Result<DedupClaim, String> {
+                let db = dedup_sqlite().await?;
+                let now = dedup_millis();
+                // Takes over entries whose claim or retention lapsed.
+                let claimed = sqlx::query(
+                    "INSERT INTO connector_inbound_dedup (handler, id, done, expires_at) VALUES (?, ?, 0, ?) \
+                     ON CONFLICT (handler, id) DO UPDATE SET done = 0, expires_at = excluded.expires_at \
+                     WHERE connector_inbound_dedup.expires_at <= ?",
+                )
+                .bind(handler)
+                .bind(id)
+                .bind(now + #CLAIM_MS as i64)
+                .bind(now)
+                .execute(db)
+                .await
+                .map_err(|e| e.to_string())?;
+                if claimed.rows_affected() == 1 {
+                    return Ok(DedupClaim::New);
+                }
+                let done: Option<bool> = sqlx::query_scalar("SELECT done FROM connector_inbound_dedup WHERE handler = ? AND id = ?")
+                    .bind(handler)
+                    .bind(id)
+                    .fetch_optional(db)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(if done == Some(true) { DedupClaim::Done } else { DedupClaim::InFlight })
+            }
+
+            async fn inbound_dedup_complete(handler: &str, id: &str) -> Result<(), String> {
+                let db = dedup_sqlite().await?;
+                let now = dedup_millis();
+                sqlx::query("UPDATE connector_inbound_dedup SET done = 1, expires_at = ? WHERE handler = ? AND id = ?")
+                    .bind(now + #ttl_ms as i64)
+                    .bind(handler)
+                    .bind(id)
+                    .execute(db)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                sqlx::query("DELETE FROM connector_inbound_dedup WHERE expires_at <= ?")
+                    .bind(now)
+                    .execute(db)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+
+            async fn inbound_dedup_release(handler: &str, id: &str) -> Result<(), String> {
+                let db = dedup_sqlite().await?;
+                sqlx::query("DELETE FROM connector_inbound_dedup WHERE handler = ? AND id = ? AND done = 0")
+                    .bind(handler)
+                    .bind(id)
+                    .execute(db)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+
+            fn dedup_health() -> connectors::HookFuture {
+                Box::pin(async {
+                    sqlx::query("SELECT 1").execute(dedup_sqlite().await?).await.map(|_| ()).map_err(|e| e.to_string())
+                })
+            }
+
+            ::inventory::submit! {
+                crate::connectors::HealthRecipe {
+                    name: "inbound_dedup",
+                    connector: None,
+                    timeout: std::time::Duration::from_secs(5),
+                    check: dedup_health,
+                }
+            }
+        },
+    };
+    quote! {
+        /// What `inbound_dedup_claim` found of a message.
+        enum DedupClaim {
+            /// Not handled yet, and now claimed by this delivery.
+            New,
+            /// Handled, within the retention.
+            Done,
+            /// Being handled by another delivery.
+            InFlight,
+        }
+
+        #store
+    }
+}
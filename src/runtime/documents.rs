@@ -0,0 +1,152 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the document store keeping large payloads out of process variables, on S3, GCS or Azure Blob through
+/// `object_store`, addressed by a URL such as `s3://bucket/prefix`.
+///
+/// Outputs over `threshold` bytes of JSON are uploaded and answered with a reference in the shape of Camunda's
+/// document references. References to this store found in an envelope's `params` are resolved before the handler
+/// runs, so handlers only ever see values. Documents are keyed by the SHA-256 of their content, and only keys under
+/// the store's prefix are resolved, so callers can't read other objects of the bucket.
+pub fn expand(store: &LitStr, threshold: usize) -> TokenStream {
+    quote! {
+        static DOCUMENT_STORE: std::sync::OnceLock<(Box<dyn object_store::ObjectStore>, object_store::path::Path)> = std::sync::OnceLock::new();
+
+        fn open_document_store() {
+            let url = url::Url::parse(#store).unwrap_or_else(|e| panic!("invalid document store URL `{}`: {}", #store, e));
+            // Credentials and options come from the environment, e.g. `AWS_ACCESS_KEY_ID` or `AZURE_STORAGE_ACCOUNT_KEY`.
+            let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+            let store = object_store::parse_url_opts(&url, options)
+                .unwrap_or_else(|e| panic!("cannot open document store `{}`: {}", #store, e));
+            let _ = DOCUMENT_STORE.set(store);
+        }
+
+        fn document_store() -> Result<&'static (Box<dyn object_store::ObjectStore>, object_store::path::Path), connectors::ExecError> {
+            DOCUMENT_STORE.get().ok_or_else(|| connectors::ExecError::Handler("document store is not open".to_string()))
+        }
+
+        /// Resolves the envelope's document references, runs the call and offloads a large output.
+        async fn with_documents(
+            name: String,
+            operation: String,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+            ) -> Result<connectors::Output, connectors::ExecError> {
+            let body = resolve_envelope_documents(format, body).await?;
+            let result = intercept(name, operation, body, format, ctx).await;
+            match result {
+                Ok(connectors::Output::Value(value)) => offload_document(value).await.map(connectors::Output::Value),
+                result => result,
+            }
+        }
+
+        async fn resolve_envelope_documents(format: connectors::Format, body: axum::body::Bytes) -> Result<axum::body::Bytes, connectors::ExecError> {
+            // The key is written out in JSON and MessagePack alike, which spares decoding envelopes without references.
+            const MARKER: &[u8] = b"camunda.document.type";
+            if !body.windows(MARKER.len()).any(|window| window == MARKER) {
+                return Ok(body);
+            }
+            let Ok(mut envelope) = format.decode::<serde_json::Value>(&body) else {
+                return Ok(body);
+            };
+            resolve_documents(&mut envelope["params"]).await?;
+            format.encode(&envelope).map(Into::into).map_err(connectors::ExecError::Handler)
+        }
+
+        /// Replaces the references to this store within `value` by the documents' content.
+        async fn resolve_documents(value: &mut serde_json::Value) -> Result<(), connectors::ExecError> {
+            let mut ids = Vec::new();
+            collect_documents(value, &mut ids);
+            if ids.is_empty() {
+                return Ok(());
+            }
+            let (store, _) = document_store()?;
+            let mut documents = std::collections::HashMap::new();
+            for id in ids {
+                if documents.contains_key(&id) {
+                    continue;
+                }
+                let path = object_store::path::Path::parse(&id)
+                    .map_err(|e| connectors::ExecError::Input(format!("Bad document id `{}`: {}", id, e)))?;
+                let content = match store.get(&path).await {
+                    Ok(object) => object.bytes().await,
+                    Err(e) => Err(e),
+                };
+                let content = match content {
+                    Ok(content) => content,
+                    Err(object_store::Error::NotFound { .. }) => {
+                        return Err(connectors::ExecError::Input(format!("Document `{}` not found", id)));
+                    }
+                    Err(e) => return Err(connectors::HandlerError::retriable(format!("Cannot read document `{}`: {}", id, e)).into()),
+                };
+                let document: serde_json::Value = serde_json::from_slice(&content)
+                    .map_err(|e| connectors::ExecError::Input(format!("Document `{}` is not JSON: {}", id, e)))?;
+                documents.insert(id, document);
+            }
+            replace_documents(value, &documents);
+            Ok(())
+        }
+
+        /// The id of a reference to a document of this store, under its prefix.
+        fn document_id(value: &serde_json::Value) -> Option<&str> {
+            let object = value.as_object()?;
+            object.get("camunda.document.type")?;
+            if object.get("storeId").and_then(|id| id.as_str()) != Some(#store) {
+                return None;
+            }
+            let id = object.get("documentId")?.as_str()?;
+            let prefix = DOCUMENT_STORE.get().map(|(_, prefix)| prefix.as_ref()).unwrap_or_default();
+            (prefix.is_empty() || id.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))).then_some(id)
+        }
+
+        fn collect_documents(value: &serde_json::Value, ids: &mut Vec<String>) {
+            if let Some(id) = document_id(value) {
+                ids.push(id.to_string());
+                return;
+            }
+            match value {
+                serde_json::Value::Object(object) => object.values().for_each(|value| collect_documents(value, ids)),
+                serde_json::Value::Array(values) => values.iter().for_each(|value| collect_documents(value, ids)),
+                _ => {}
+            }
+        }
+
+        fn replace_documents(value: &mut serde_json::Value, documents: &std::collections::HashMap<String, serde_json::Value>) {
+            if let Some(document) = document_id(value).and_then(|id| documents.get(id)) {
+                *value = document.clone();
+                return;
+            }
+            match value {
+                serde_json::Value::Object(object) => object.values_mut().for_each(|value| replace_documents(value, documents)),
+                serde_json::Value::Array(values) => values.iter_mut().for_each(|value| replace_documents(value, documents)),
+                _ => {}
+            }
+        }
+
+        /// Uploads `value` when its JSON is over the threshold, returning the reference to answer with instead.
+        async fn offload_document(value: serde_json::Value) -> Result<serde_json::Value, connectors::ExecError> {
+            use sha2::Digest as _;
+            let content = serde_json::to_vec(&value).map_err(|e| connectors::ExecError::Handler(e.to_string()))?;
+            if content.len() <= #threshold {
+                return Ok(value);
+            }
+            let (store, prefix) = document_store()?;
+            let hash: String = sha2::Sha256::digest(&content).iter().map(|b| format!("{:02x}", b)).collect();
+            let path = prefix.child(format!("{}.json", hash));
+            let size = content.len();
+            store
+                .put(&path, content.into())
+                .await
+                .map_err(|e| connectors::HandlerError::retriable(format!("Cannot upload the output to the document store: {}", e)))?;
+            Ok(serde_json::json!({
+                "camunda.document.type": "camunda",
+                "storeId": #store,
+                "documentId": path.as_ref(),
+                "contentHash": hash,
+                "metadata": { "contentType": "application/json", "size": size },
+            }))
+        }
+    }
+}
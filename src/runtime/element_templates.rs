@@ -0,0 +1,245 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `connectors::element_templates`: one Camunda element template per (connector, operation, version) whose
+/// input derives `ConnectorInput`, so the modeler UI follows the Rust input struct, and the golden-file check keeping
+/// them from changing unnoticed.
+pub fn expand() -> TokenStream {
+    quote! {
+        /// The element templates of `element_templates = true`, and checks of them against golden files.
+        pub mod element_templates {
+            fn element_template(r: &super::ConnectorRecipe, operation: &str, fields: &[super::FieldMeta]) -> serde_json::Value {
+                let mut groups: Vec<&str> = Vec::new();
+                for group in fields.iter().filter_map(|field| field.group) {
+                    if !groups.contains(&group) {
+                        groups.push(group);
+                    }
+                }
+                let mut properties = vec![
+                    serde_json::json!({
+                        "type": "Hidden",
+                        "value": r.name,
+                        "binding": { "type": "zeebe:taskDefinition", "property": "type" },
+                    }),
+                    serde_json::json!({
+                        "type": "Hidden",
+                        "value": operation,
+                        "binding": { "type": "zeebe:input", "name": "operation" },
+                    }),
+                ];
+                if r.version > 1 {
+                    properties.push(serde_json::json!({
+                        "type": "Hidden",
+                        "value": r.version.to_string(),
+                        "binding": { "type": "zeebe:input", "name": "version" },
+                    }));
+                }
+                for field in fields {
+                    let name = super::wire_name(r.rename_all, field.name);
+                    let mut property = serde_json::json!({
+                        "id": name,
+                        "label": field.label,
+                        "type": field.kind,
+                        "binding": { "type": "zeebe:input", "name": format!("input.{}", name) },
+                    });
+                    let object = property.as_object_mut().unwrap();
+                    if let Some(description) = field.description {
+                        object.insert("description".into(), description.into());
+                    }
+                    if let Some(group) = field.group {
+                        object.insert("group".into(), group.into());
+                    }
+                    if let Some(feel) = field.feel {
+                        object.insert("feel".into(), feel.into());
+                    }
+                    // Toggles and numbers take typed values, other properties the text modelers would have typed.
+                    if let Some(default) = field.default {
+                        let value = match field.default_value() {
+                            Some(value) if matches!(field.kind, "Boolean" | "Number") => value,
+                            _ => default.into(),
+                        };
+                        object.insert("value".into(), value);
+                    }
+                    let mut constraints = serde_json::Map::new();
+                    // Secret fields take a reference to a secret, never the secret itself.
+                    if field.secret {
+                        object.insert("placeholder".into(), "{{secrets.MY_SECRET}}".into());
+                        constraints.insert("pattern".into(), serde_json::json!({
+                            "value": "^.*\\{\\{secrets\\..+\\}\\}.*$",
+                            "message": "Reference a secret, such as {{secrets.MY_SECRET}}",
+                        }));
+                    }
+                    // Toggles always have a value, only text and number properties can be left empty or required.
+                    if field.kind != "Boolean" {
+                        match field.optional {
+                            true => {
+                                object.insert("optional".into(), true.into());
+                            }
+                            false => {
+                                constraints.insert("notEmpty".into(), true.into());
+                            }
+                        }
+                    }
+                    if !constraints.is_empty() {
+                        object.insert("constraints".into(), constraints.into());
+                    }
+                    properties.push(property);
+                }
+                let id = match r.version {
+                    1 => format!("{}.{}", r.name, operation),
+                    version => format!("{}.{}.v{}", r.name, operation, version),
+                };
+                let mut template = serde_json::json!({
+                    "$schema": "https://unpkg.com/@camunda/zeebe-element-templates-json-schema/resources/schema.json",
+                    "name": format!("{} {}", r.name, operation),
+                    "id": id,
+                    "version": r.version,
+                    "appliesTo": ["bpmn:Task"],
+                    "elementType": { "value": "bpmn:ServiceTask" },
+                    "groups": groups.iter().map(|group| serde_json::json!({ "id": group, "label": group })).collect::<Vec<_>>(),
+                    "properties": properties,
+                });
+                if let Some(description) = r.description {
+                    template["description"] = description.into();
+                }
+                template
+            }
+
+            /// Every element template, ordered by id, as served by `GET /element-templates`.
+            pub fn render() -> Vec<serde_json::Value> {
+                let mut templates = Vec::new();
+                for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                    let Some(fields) = (r.input_fields)() else { continue };
+                    for operation in r.operations {
+                        templates.push(element_template(r, operation, fields));
+                    }
+                }
+                templates.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+                templates
+            }
+
+            /// Compares the templates with the golden files of `dir`, one pretty-printed `<id>.json` per template, and
+            /// describes every difference, missing and left-over file with a line diff. With `UPDATE_ELEMENT_TEMPLATES=1`
+            /// set, the golden files are rewritten to match instead.
+            pub fn check_golden(dir: impl AsRef<std::path::Path>) -> Result<(), String> {
+                let dir = dir.as_ref();
+                let update = std::env::var("UPDATE_ELEMENT_TEMPLATES").is_ok_and(|value| !value.is_empty() && value != "0");
+                if update {
+                    std::fs::create_dir_all(dir).map_err(|e| format!("cannot create `{}`: {}", dir.display(), e))?;
+                }
+                let mut problems = Vec::new();
+                let mut rendered = std::collections::HashSet::new();
+                for template in render() {
+                    let file = format!("{}.json", template["id"].as_str().unwrap_or_default());
+                    let path = dir.join(&file);
+                    let actual = serde_json::to_string_pretty(&template).map_err(|e| e.to_string())? + "\n";
+                    match std::fs::read_to_string(&path) {
+                        Ok(expected) if expected.replace("\r\n", "\n") == actual => {}
+                        Ok(_) | Err(_) if update => {
+                            std::fs::write(&path, actual).map_err(|e| format!("cannot write `{}`: {}", path.display(), e))?;
+                        }
+                        Ok(expected) => problems.push(format!("{} differs from its golden file (- golden, + rendered):{}", file, diff(&expected, &actual))),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => problems.push(format!("{} has no golden file", file)),
+                        Err(e) => problems.push(format!("cannot read `{}`: {}", path.display(), e)),
+                    }
+                    rendered.insert(file);
+                }
+                let mut left_over: Vec<String> = std::fs::read_dir(dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .filter(|file| file.ends_with(".json") && !rendered.contains(file))
+                    .collect();
+                left_over.sort();
+                for file in left_over {
+                    let path = dir.join(&file);
+                    match update {
+                        true => std::fs::remove_file(&path).map_err(|e| format!("cannot remove `{}`: {}", path.display(), e))?,
+                        false => problems.push(format!("{} is a golden file no connector renders", file)),
+                    }
+                }
+                if problems.is_empty() {
+                    return Ok(());
+                }
+                Err(format!(
+                    "The element templates don't match the golden files in `{}`, {} problem(s):\n\n{}\n\nRerun with UPDATE_ELEMENT_TEMPLATES=1 to accept the rendered templates.",
+                    dir.display(),
+                    problems.len(),
+                    problems.join("\n\n"),
+                ))
+            }
+
+            /// `check_golden`, panicking with its report, for tests:
+            ///
+            /// ```ignore
+            /// #[test]
+            /// fn element_templates_match_golden_files() {
+            ///     connectors::element_templates::assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/element-templates"));
+            /// }
+            /// ```
+            #[track_caller]
+            pub fn assert_golden(dir: impl AsRef<std::path::Path>) {
+                if let Err(report) = check_golden(dir) {
+                    panic!("{}", report);
+                }
+            }
+
+            /// The changed lines of `actual` against `expected`, `-` for removed and `+` for added ones, with two lines of
+            /// context around each change.
+            fn diff(expected: &str, actual: &str) -> String {
+                let old: Vec<&str> = expected.lines().collect();
+                let new: Vec<&str> = actual.lines().collect();
+                // The length of the longest common subsequence of `old[i..]` and `new[j..]`.
+                let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+                for i in (0..old.len()).rev() {
+                    for j in (0..new.len()).rev() {
+                        common[i][j] = match old[i] == new[j] {
+                            true => common[i + 1][j + 1] + 1,
+                            false => common[i + 1][j].max(common[i][j + 1]),
+                        };
+                    }
+                }
+                let mut lines = Vec::new();
+                let (mut i, mut j) = (0, 0);
+                while i < old.len() || j < new.len() {
+                    if i < old.len() && j < new.len() && old[i] == new[j] {
+                        lines.push((' ', old[i]));
+                        (i, j) = (i + 1, j + 1);
+                    } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+                        lines.push(('-', old[i]));
+                        i += 1;
+                    } else {
+                        lines.push(('+', new[j]));
+                        j += 1;
+                    }
+                }
+                let mut shown = vec![false; lines.len()];
+                for (n, _) in lines.iter().enumerate().filter(|(_, (tag, _))| *tag != ' ') {
+                    for near in shown.iter_mut().take(n + 3).skip(n.saturating_sub(2)) {
+                        *near = true;
+                    }
+                }
+                let mut out = String::new();
+                let mut last = None;
+                for (n, (tag, line)) in lines.iter().enumerate().filter(|(n, _)| shown[*n]) {
+                    if last.is_some_and(|last| last + 1 != n) {
+                        out.push_str("\n  ...");
+                    }
+                    out.push_str(&format!("\n{} {}", tag, line));
+                    last = Some(n);
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Generates `GET /element-templates`, returning every template as a JSON array.
+pub fn route() -> TokenStream {
+    quote! {
+        async fn element_templates() -> axum::Json<Vec<serde_json::Value>> {
+            axum::Json(connectors::element_templates::render())
+        }
+    }
+}
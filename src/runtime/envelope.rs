@@ -0,0 +1,126 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the envelope versions the dispatcher accepts, picked by the envelope's `envelopeVersion` or else the
+/// `X-Envelope-Version` header, 1 without either:
+///
+/// - 1, `{"id", "params": {"operation", "input", "version"}}`, which handlers decode.
+/// - 2, `{"envelopeVersion": 2, "id", "operation", "input", "version", "tenant", "headers", "metadata"}`, upgraded to
+///   version 1 on arrival. Its `tenant`, `headers` and `metadata` ride along under the envelope's `envelope` key
+///   until `execute` hands them to the call's `Context`.
+pub fn expand() -> TokenStream {
+    quote! {
+        /// The envelope versions this runtime accepts.
+        pub const ENVELOPE_VERSIONS: &[u32] = &[1, 2];
+
+        /// What a version 2 envelope carries beyond the call itself.
+        #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+        pub struct EnvelopeMeta {
+            pub version: u32,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            pub tenant: Option<String>,
+            #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+            pub headers: std::collections::BTreeMap<String, String>,
+            #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+            pub metadata: serde_json::Map<String, serde_json::Value>,
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct EnvelopeV2 {
+            #[serde(default)]
+            id: serde_json::Value,
+            operation: String,
+            #[serde(default)]
+            input: serde_json::Value,
+            #[serde(default)]
+            version: Option<u32>,
+            #[serde(default)]
+            tenant: Option<String>,
+            #[serde(default)]
+            headers: std::collections::BTreeMap<String, String>,
+            #[serde(default)]
+            metadata: serde_json::Map<String, serde_json::Value>,
+            #[serde(default)]
+            result_variable: Option<String>,
+            #[serde(default)]
+            result_expression: Option<String>,
+        }
+
+        /// The `X-Envelope-Version` header, `Ok(None)` without one.
+        pub fn envelope_version_header(headers: &axum::http::HeaderMap) -> Result<Option<u32>, String> {
+            let Some(value) = headers.get("x-envelope-version") else { return Ok(None) };
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .map(Some)
+                .ok_or_else(|| "Invalid X-Envelope-Version, expected a number".to_string())
+        }
+
+        /// An envelope of any accepted version as version 1, along with the version it came in.
+        pub fn upgrade_envelope(envelope: serde_json::Value, header: Option<u32>) -> Result<(serde_json::Value, u32), String> {
+            let version = match envelope.get("envelopeVersion") {
+                Some(version) => version.as_u64().and_then(|v| u32::try_from(v).ok()).ok_or("`envelopeVersion` must be a number")?,
+                None => header.unwrap_or(1),
+            };
+            match version {
+                1 => Ok((envelope, 1)),
+                2 => {
+                    let v2: EnvelopeV2 = serde_json::from_value(envelope).map_err(|e| format!("Bad version 2 envelope: {}", e))?;
+                    let meta = EnvelopeMeta { version, tenant: v2.tenant, headers: v2.headers, metadata: v2.metadata };
+                    let mut v1 = serde_json::json!({
+                        "id": v2.id,
+                        "params": { "operation": v2.operation, "input": v2.input },
+                        "envelope": meta,
+                    });
+                    if let Some(version) = v2.version {
+                        v1["params"]["version"] = version.into();
+                    }
+                    if let Some(variable) = v2.result_variable {
+                        v1["resultVariable"] = variable.into();
+                    }
+                    if let Some(expression) = v2.result_expression {
+                        v1["resultExpression"] = expression.into();
+                    }
+                    Ok((v1, version))
+                }
+                version => Err(format!(
+                    "Unsupported envelope version {}, expected one of {}",
+                    version,
+                    ENVELOPE_VERSIONS.iter().map(u32::to_string).collect::<Vec<_>>().join(", ")
+                )),
+            }
+        }
+
+        /// `upgrade_envelope` on an encoded envelope, left as is when it is version 1. Envelopes that don't decode,
+        /// Protobuf ones included, are left for the dispatcher to reject or read.
+        pub fn upgrade_envelope_bytes(format: Format, header: Option<u32>, bytes: axum::body::Bytes) -> Result<(axum::body::Bytes, u32), String> {
+            let Ok(peek) = format.decode::<EnvelopeVersionPeek>(&bytes) else { return Ok((bytes, 1)) };
+            if peek.envelope_version.is_none() && header.unwrap_or(1) == 1 {
+                return Ok((bytes, 1));
+            }
+            let envelope: serde_json::Value = format.decode(&bytes)?;
+            let (envelope, version) = upgrade_envelope(envelope, header)?;
+            Ok((format.encode(&envelope)?.into(), version))
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct EnvelopeVersionPeek {
+            #[serde(default)]
+            envelope_version: Option<serde_json::Value>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct EnvelopeMetaPeek {
+            #[serde(default)]
+            envelope: Option<EnvelopeMeta>,
+        }
+
+        /// The extras of a version 2 envelope, once upgraded.
+        pub fn peek_envelope_meta(format: Format, bytes: &[u8]) -> Option<EnvelopeMeta> {
+            format.decode::<EnvelopeMetaPeek>(bytes).ok()?.envelope
+        }
+    }
+}
@@ -0,0 +1,857 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `connectors::feel`, the evaluator for the FEEL expressions found in envelopes (`resultExpression`, and
+/// `=`-prefixed inputs with `feel_inputs = true`).
+///
+/// It implements the subset of FEEL that mappings are written in: literals, contexts and lists, paths with 1-based
+/// and negative indices, list filters, arithmetic, comparisons, `and`/`or`, `if`/`then`/`else`, `for`/`in`/`return`,
+/// `some`/`every`, the common built-in functions on strings, numbers, lists and contexts, and Camunda's `bpmnError` and
+/// `jobError`. Dates, times and durations are not supported.
+pub fn expand() -> TokenStream {
+    quote! {
+        pub mod feel {
+            use serde_json::Value;
+
+            /// The longest expression evaluated, in bytes, and the deepest nesting it may have; both keep a crafted input from
+            /// exhausting the stack of the parser and the evaluator, which recurse.
+            const MAX_LENGTH: usize = 8192;
+            const MAX_DEPTH: usize = 64;
+
+            /// Evaluates `expression`, with or without its leading `=`, against the variables of `context`.
+            pub fn evaluate(expression: &str, context: &Value) -> Result<Value, String> {
+                let expression = expression.trim();
+                let expression = expression.strip_prefix('=').unwrap_or(expression);
+                if expression.len() > MAX_LENGTH {
+                    return Err(format!("FEEL expression is longer than {} bytes", MAX_LENGTH));
+                }
+                let tokens = tokenize(expression)?;
+                let mut parser = Parser { tokens, pos: 0, depth: 0 };
+                let expr = parser.expression()?;
+                if let Some(token) = parser.peek() {
+                    return Err(format!("Unexpected `{}` in FEEL expression", token));
+                }
+                eval(&expr, context)
+            }
+
+            /// Replaces each string of the envelope's `params.input` starting with `=` by the value of that FEEL
+            /// expression, evaluated against the envelope's `variables` and the input's own fields.
+            pub fn evaluate_inputs(envelope: &mut Value) -> Result<(), String> {
+                let mut scope = match envelope.get("variables") {
+                    Some(Value::Object(variables)) => variables.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                if let Some(Value::Object(fields)) = envelope.pointer("/params/input") {
+                    for (key, value) in fields {
+                        scope.entry(key.clone()).or_insert_with(|| value.clone());
+                    }
+                }
+                let scope = Value::Object(scope);
+                if let Some(input) = envelope.pointer_mut("/params/input") {
+                    evaluate_strings(input, &scope)?;
+                }
+                Ok(())
+            }
+
+            fn evaluate_strings(value: &mut Value, scope: &Value) -> Result<(), String> {
+                match value {
+                    Value::String(s) if s.starts_with('=') => *value = evaluate(s, scope)?,
+                    Value::Array(items) => {
+                        for item in items {
+                            evaluate_strings(item, scope)?;
+                        }
+                    }
+                    Value::Object(fields) => {
+                        for field in fields.values_mut() {
+                            evaluate_strings(field, scope)?;
+                        }
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+
+            #[derive(Debug, Clone, PartialEq)]
+            enum Token {
+                Name(String),
+                Str(String),
+                Num(f64),
+                Punct(&'static str),
+            }
+
+            impl std::fmt::Display for Token {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    match self {
+                        Token::Name(name) => f.write_str(name),
+                        Token::Str(s) => write!(f, "\"{}\"", s),
+                        Token::Num(n) => write!(f, "{}", n),
+                        Token::Punct(p) => f.write_str(p),
+                    }
+                }
+            }
+
+            const PUNCTUATION: &[&str] = &["**", "!=", "<=", ">=", "{", "}", "[", "]", "(", ")", ",", ":", ".", "+", "-", "*", "/", "=", "<", ">"];
+
+            fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+                let mut tokens = Vec::new();
+                let mut rest = input;
+                while let Some(c) = rest.chars().next() {
+                    if c.is_whitespace() {
+                        rest = &rest[c.len_utf8()..];
+                    } else if c == '"' {
+                        let mut s = String::new();
+                        let mut chars = rest[1..].char_indices();
+                        let end = loop {
+                            match chars.next() {
+                                Some((i, '"')) => break i + 2,
+                                Some((_, '\\')) => match chars.next() {
+                                    Some((_, 'n')) => s.push('\n'),
+                                    Some((_, 't')) => s.push('\t'),
+                                    Some((_, c)) => s.push(c),
+                                    None => return Err("Unterminated string in FEEL expression".to_string()),
+                                },
+                                Some((_, c)) => s.push(c),
+                                None => return Err("Unterminated string in FEEL expression".to_string()),
+                            }
+                        };
+                        tokens.push(Token::Str(s));
+                        rest = &rest[end..];
+                    } else if c.is_ascii_digit() {
+                        let mut end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                        // A dot is a decimal point only when digits follow, `items[1].name` is a path.
+                        if rest[end..].starts_with('.') && rest[end + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                            end += 1 + rest[end + 1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - end - 1);
+                        }
+                        let number = &rest[..end];
+                        tokens.push(Token::Num(number.parse().map_err(|_| format!("Invalid number `{}` in FEEL expression", number))?));
+                        rest = &rest[end..];
+                    } else if c.is_alphabetic() || c == '_' || c == '?' {
+                        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '?')).unwrap_or(rest.len());
+                        tokens.push(Token::Name(rest[..end].to_string()));
+                        rest = &rest[end..];
+                    } else if let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+                        tokens.push(Token::Punct(p));
+                        rest = &rest[p.len()..];
+                    } else {
+                        return Err(format!("Unexpected `{}` in FEEL expression", c));
+                    }
+                }
+                Ok(tokens)
+            }
+
+            #[derive(Debug, Clone)]
+            enum Expr {
+                Literal(Value),
+                Name(String),
+                Member(Box<Expr>, String),
+                Index(Box<Expr>, Box<Expr>),
+                Context(Vec<(String, Expr)>),
+                List(Vec<Expr>),
+                Call(String, Vec<Expr>),
+                Neg(Box<Expr>),
+                Binary(&'static str, Box<Expr>, Box<Expr>),
+                And(Box<Expr>, Box<Expr>),
+                Or(Box<Expr>, Box<Expr>),
+                If(Box<Expr>, Box<Expr>, Box<Expr>),
+                /// `for x in list return expr`, and `some`/`every x in list satisfies expr`.
+                Iterate(&'static str, String, Box<Expr>, Box<Expr>),
+            }
+
+            /// Built-in functions; names can span several words, and the longest match wins.
+            const FUNCTIONS: &[&str] = &[
+                "string length", "upper case", "lower case", "starts with", "ends with", "substring before",
+                "substring after", "string join", "get value", "get entries", "distinct values", "index of",
+                "list contains", "is defined", "substring", "contains", "string", "number", "split", "count", "sum",
+                "min", "max", "mean", "append", "concatenate", "flatten", "reverse", "abs", "floor", "ceiling",
+                "decimal", "modulo", "not", "sort", "bpmnError", "jobError",
+            ];
+
+            struct Parser {
+                tokens: Vec<Token>,
+                pos: usize,
+                /// How deeply the expression parsed so far nests, bounded by `MAX_DEPTH`.
+                depth: usize,
+            }
+
+            impl Parser {
+                fn peek(&self) -> Option<&Token> {
+                    self.tokens.get(self.pos)
+                }
+
+                fn next(&mut self) -> Option<Token> {
+                    let token = self.tokens.get(self.pos).cloned();
+                    self.pos += 1;
+                    token
+                }
+
+                fn eat(&mut self, p: &str) -> bool {
+                    if matches!(self.peek(), Some(Token::Punct(q)) if *q == p) {
+                        self.pos += 1;
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                fn eat_keyword(&mut self, keyword: &str) -> bool {
+                    if matches!(self.peek(), Some(Token::Name(name)) if name == keyword) {
+                        self.pos += 1;
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                fn expect(&mut self, p: &str) -> Result<(), String> {
+                    match self.eat(p) {
+                        true => Ok(()),
+                        false => Err(self.unexpected(p)),
+                    }
+                }
+
+                fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+                    match self.eat_keyword(keyword) {
+                        true => Ok(()),
+                        false => Err(self.unexpected(keyword)),
+                    }
+                }
+
+                /// Enters one more level of nesting; the caller restores `depth` once the nested expression is parsed.
+                fn descend(&mut self) -> Result<(), String> {
+                    self.depth += 1;
+                    match self.depth > MAX_DEPTH {
+                        true => Err(format!("FEEL expression nests deeper than {} levels", MAX_DEPTH)),
+                        false => Ok(()),
+                    }
+                }
+
+                fn unexpected(&self, expected: &str) -> String {
+                    match self.peek() {
+                        Some(token) => format!("Expected `{}` in FEEL expression, found `{}`", expected, token),
+                        None => format!("Expected `{}` at the end of the FEEL expression", expected),
+                    }
+                }
+
+                fn expression(&mut self) -> Result<Expr, String> {
+                    self.descend()?;
+                    let expr = self.nested_expression()?;
+                    self.depth -= 1;
+                    Ok(expr)
+                }
+
+                fn nested_expression(&mut self) -> Result<Expr, String> {
+                    if self.eat_keyword("if") {
+                        let condition = self.expression()?;
+                        self.expect_keyword("then")?;
+                        let then = self.expression()?;
+                        self.expect_keyword("else")?;
+                        let otherwise = self.expression()?;
+                        return Ok(Expr::If(Box::new(condition), Box::new(then), Box::new(otherwise)));
+                    }
+                    for (keyword, body) in [("for", "return"), ("some", "satisfies"), ("every", "satisfies")] {
+                        // Only a following `x in` makes these keywords, so they stay usable as variable names.
+                        let is_loop = matches!(
+                            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1), self.tokens.get(self.pos + 2)),
+                            (Some(Token::Name(k)), Some(Token::Name(_)), Some(Token::Name(i))) if k == keyword && i == "in"
+                        );
+                        if is_loop {
+                            self.pos += 1;
+                            let Some(Token::Name(variable)) = self.next() else { unreachable!() };
+                            self.pos += 1;
+                            let list = self.disjunction()?;
+                            self.expect_keyword(body)?;
+                            let expr = self.expression()?;
+                            return Ok(Expr::Iterate(keyword, variable, Box::new(list), Box::new(expr)));
+                        }
+                    }
+                    self.disjunction()
+                }
+
+                fn disjunction(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.conjunction()?;
+                    while self.eat_keyword("or") {
+                        self.descend()?;
+                        expr = Expr::Or(Box::new(expr), Box::new(self.conjunction()?));
+                    }
+                    self.depth = depth;
+                    Ok(expr)
+                }
+
+                fn conjunction(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.comparison()?;
+                    while self.eat_keyword("and") {
+                        self.descend()?;
+                        expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+                    }
+                    self.depth = depth;
+                    Ok(expr)
+                }
+
+                fn comparison(&mut self) -> Result<Expr, String> {
+                    let left = self.additive()?;
+                    for op in ["=", "!=", "<=", ">=", "<", ">"] {
+                        if self.eat(op) {
+                            return Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)));
+                        }
+                    }
+                    if self.eat_keyword("between") {
+                        let low = self.additive()?;
+                        self.expect_keyword("and")?;
+                        let high = self.additive()?;
+                        // `x between a and b` is `x >= a and x <= b`.
+                        return Ok(Expr::And(
+                            Box::new(Expr::Binary(">=", Box::new(left.clone()), Box::new(low))),
+                            Box::new(Expr::Binary("<=", Box::new(left), Box::new(high))),
+                        ));
+                    }
+                    Ok(left)
+                }
+
+                fn additive(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.multiplicative()?;
+                    loop {
+                        let op = if self.eat("+") {
+                            "+"
+                        } else if self.eat("-") {
+                            "-"
+                        } else {
+                            self.depth = depth;
+                            return Ok(expr);
+                        };
+                        self.descend()?;
+                        expr = Expr::Binary(op, Box::new(expr), Box::new(self.multiplicative()?));
+                    }
+                }
+
+                fn multiplicative(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.exponent()?;
+                    loop {
+                        let op = if self.eat("*") {
+                            "*"
+                        } else if self.eat("/") {
+                            "/"
+                        } else {
+                            self.depth = depth;
+                            return Ok(expr);
+                        };
+                        self.descend()?;
+                        expr = Expr::Binary(op, Box::new(expr), Box::new(self.exponent()?));
+                    }
+                }
+
+                fn exponent(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.unary()?;
+                    while self.eat("**") {
+                        self.descend()?;
+                        expr = Expr::Binary("**", Box::new(expr), Box::new(self.unary()?));
+                    }
+                    self.depth = depth;
+                    Ok(expr)
+                }
+
+                fn unary(&mut self) -> Result<Expr, String> {
+                    if !self.eat("-") {
+                        return self.postfix();
+                    }
+                    self.descend()?;
+                    let expr = self.unary()?;
+                    self.depth -= 1;
+                    Ok(Expr::Neg(Box::new(expr)))
+                }
+
+                fn postfix(&mut self) -> Result<Expr, String> {
+                    let depth = self.depth;
+                    let mut expr = self.primary()?;
+                    loop {
+                        if self.eat(".") {
+                            self.descend()?;
+                            match self.next() {
+                                Some(Token::Name(name)) => expr = Expr::Member(Box::new(expr), name),
+                                _ => return Err("Expected a name after `.` in FEEL expression".to_string()),
+                            }
+                        } else if self.eat("[") {
+                            self.descend()?;
+                            let index = self.expression()?;
+                            self.expect("]")?;
+                            expr = Expr::Index(Box::new(expr), Box::new(index));
+                        } else {
+                            self.depth = depth;
+                            return Ok(expr);
+                        }
+                    }
+                }
+
+                /// A built-in function name spanning the next tokens, when a `(` follows it.
+                fn function_name(&self) -> Option<(String, usize)> {
+                    let mut words = Vec::new();
+                    for token in &self.tokens[self.pos..] {
+                        match token {
+                            Token::Name(word) if words.len() < 3 => words.push(word.as_str()),
+                            _ => break,
+                        }
+                    }
+                    (1..=words.len()).rev().find_map(|n| {
+                        let name = words[..n].join(" ");
+                        let call = matches!(self.tokens.get(self.pos + n), Some(Token::Punct("(")));
+                        (call && FUNCTIONS.contains(&name.as_str())).then_some((name, n))
+                    })
+                }
+
+                fn primary(&mut self) -> Result<Expr, String> {
+                    if let Some((name, words)) = self.function_name() {
+                        self.pos += words + 1;
+                        let mut args = Vec::new();
+                        while !self.eat(")") {
+                            args.push(self.expression()?);
+                            if !self.eat(",") {
+                                self.expect(")")?;
+                                break;
+                            }
+                        }
+                        return Ok(Expr::Call(name, args));
+                    }
+                    match self.next() {
+                        Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+                        Some(Token::Num(n)) => Ok(Expr::Literal(number(n))),
+                        Some(Token::Name(name)) if matches!(self.peek(), Some(Token::Punct("("))) => {
+                            Err(format!("Unknown FEEL function `{}`", name))
+                        }
+                        Some(Token::Name(name)) => Ok(match name.as_str() {
+                            "true" => Expr::Literal(Value::Bool(true)),
+                            "false" => Expr::Literal(Value::Bool(false)),
+                            "null" => Expr::Literal(Value::Null),
+                            _ => Expr::Name(name),
+                        }),
+                        Some(Token::Punct("(")) => {
+                            let expr = self.expression()?;
+                            self.expect(")")?;
+                            Ok(expr)
+                        }
+                        Some(Token::Punct("{")) => {
+                            let mut entries = Vec::new();
+                            while !self.eat("}") {
+                                let key = match self.next() {
+                                    Some(Token::Name(key) | Token::Str(key)) => key,
+                                    _ => return Err("Expected a context key in FEEL expression".to_string()),
+                                };
+                                self.expect(":")?;
+                                entries.push((key, self.expression()?));
+                                if !self.eat(",") {
+                                    self.expect("}")?;
+                                    break;
+                                }
+                            }
+                            Ok(Expr::Context(entries))
+                        }
+                        Some(Token::Punct("[")) => {
+                            let mut items = Vec::new();
+                            while !self.eat("]") {
+                                items.push(self.expression()?);
+                                if !self.eat(",") {
+                                    self.expect("]")?;
+                                    break;
+                                }
+                            }
+                            Ok(Expr::List(items))
+                        }
+                        Some(token) => Err(format!("Unexpected `{}` in FEEL expression", token)),
+                        None => Err("Unexpected end of FEEL expression".to_string()),
+                    }
+                }
+            }
+
+            /// Integral numbers stay integers in JSON.
+            fn number(n: f64) -> Value {
+                if n.fract() == 0.0 && n.abs() < 9e15 {
+                    Value::from(n as i64)
+                } else {
+                    serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)
+                }
+            }
+
+            /// The scope of a filter or loop body: the outer variables, plus `item` (or the loop variable) and, for
+            /// contexts, the item's own entries.
+            fn with_item(scope: &Value, name: &str, item: &Value) -> Value {
+                let mut inner = match scope {
+                    Value::Object(fields) => fields.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                if let Value::Object(fields) = item {
+                    inner.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+                inner.insert(name.to_string(), item.clone());
+                Value::Object(inner)
+            }
+
+            fn equal(a: &Value, b: &Value) -> bool {
+                match (a.as_f64(), b.as_f64()) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => a == b,
+                }
+            }
+
+            fn compare(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+                match (a, b) {
+                    (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+                    _ => None,
+                }
+            }
+
+            fn eval(expr: &Expr, scope: &Value) -> Result<Value, String> {
+                Ok(match expr {
+                    Expr::Literal(value) => value.clone(),
+                    // Unknown variables and members are null, as in FEEL.
+                    Expr::Name(name) => scope.get(name).cloned().unwrap_or(Value::Null),
+                    Expr::Member(target, name) => match eval(target, scope)? {
+                        // A path on a list maps over its items.
+                        Value::Array(items) => Value::Array(items.iter().map(|item| item.get(name).cloned().unwrap_or(Value::Null)).collect()),
+                        target => target.get(name).cloned().unwrap_or(Value::Null),
+                    },
+                    Expr::Index(target, index) => {
+                        let target = eval(target, scope)?;
+                        let Some(list) = target.as_array() else {
+                            return Ok(Value::Null);
+                        };
+                        match eval(index, scope)? {
+                            Value::Number(n) => {
+                                // 1-based, negative indices count from the end.
+                                let position = match n.as_i64() {
+                                    Some(i) if i > 0 => (i - 1) as usize,
+                                    Some(i) if i < 0 && (-i) as usize <= list.len() => list.len() - (-i) as usize,
+                                    _ => return Ok(Value::Null),
+                                };
+                                list.get(position).cloned().unwrap_or(Value::Null)
+                            }
+                            // Anything else is a filter, evaluated for each item.
+                            _ => {
+                                let mut kept = Vec::new();
+                                for item in list {
+                                    if eval(index, &with_item(scope, "item", item))? == Value::Bool(true) {
+                                        kept.push(item.clone());
+                                    }
+                                }
+                                Value::Array(kept)
+                            }
+                        }
+                    }
+                    Expr::Context(entries) => {
+                        // Later entries can refer to earlier ones.
+                        let mut object = serde_json::Map::new();
+                        for (key, value) in entries {
+                            let mut inner = match scope {
+                                Value::Object(fields) => fields.clone(),
+                                _ => serde_json::Map::new(),
+                            };
+                            inner.extend(object.iter().map(|(k, v): (&String, &Value)| (k.clone(), v.clone())));
+                            let value = eval(value, &Value::Object(inner))?;
+                            object.insert(key.clone(), value);
+                        }
+                        Value::Object(object)
+                    }
+                    Expr::List(items) => Value::Array(items.iter().map(|item| eval(item, scope)).collect::<Result<_, _>>()?),
+                    Expr::Call(name, args) => {
+                        let args = args.iter().map(|arg| eval(arg, scope)).collect::<Result<Vec<_>, _>>()?;
+                        call(name, &args)?
+                    }
+                    Expr::Neg(expr) => match eval(expr, scope)?.as_f64() {
+                        Some(n) => number(-n),
+                        None => Value::Null,
+                    },
+                    Expr::Binary(op, left, right) => {
+                        let (left, right) = (eval(left, scope)?, eval(right, scope)?);
+                        match *op {
+                            "=" => Value::Bool(equal(&left, &right)),
+                            "!=" => Value::Bool(!equal(&left, &right)),
+                            "<" | "<=" | ">" | ">=" => match compare(&left, &right) {
+                                Some(ordering) => Value::Bool(match *op {
+                                    "<" => ordering.is_lt(),
+                                    "<=" => ordering.is_le(),
+                                    ">" => ordering.is_gt(),
+                                    _ => ordering.is_ge(),
+                                }),
+                                None => Value::Null,
+                            },
+                            "+" if left.is_string() || right.is_string() => match (&left, &right) {
+                                (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+                                _ => Value::Null,
+                            },
+                            _ => match (left.as_f64(), right.as_f64()) {
+                                (Some(a), Some(b)) => match *op {
+                                    "+" => number(a + b),
+                                    "-" => number(a - b),
+                                    "*" => number(a * b),
+                                    "/" if b == 0.0 => Value::Null,
+                                    "/" => number(a / b),
+                                    _ => number(a.powf(b)),
+                                },
+                                _ => Value::Null,
+                            },
+                        }
+                    }
+                    // Three-valued logic: `false and null` is false, `true and null` is null.
+                    Expr::And(left, right) => match (eval(left, scope)?, eval(right, scope)?) {
+                        (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                        (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                        _ => Value::Null,
+                    },
+                    Expr::Or(left, right) => match (eval(left, scope)?, eval(right, scope)?) {
+                        (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+                        (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+                        _ => Value::Null,
+                    },
+                    Expr::If(condition, then, otherwise) => match eval(condition, scope)? {
+                        Value::Bool(true) => eval(then, scope)?,
+                        _ => eval(otherwise, scope)?,
+                    },
+                    Expr::Iterate(kind, variable, list, body) => {
+                        let list = match eval(list, scope)? {
+                            Value::Array(items) => items,
+                            Value::Null => Vec::new(),
+                            item => vec![item],
+                        };
+                        let mut results = Vec::with_capacity(list.len());
+                        for item in &list {
+                            results.push(eval(body, &with_item(scope, variable, item))?);
+                        }
+                        match *kind {
+                            "for" => Value::Array(results),
+                            "some" => Value::Bool(results.contains(&Value::Bool(true))),
+                            _ => Value::Bool(results.iter().all(|r| *r == Value::Bool(true))),
+                        }
+                    }
+                })
+            }
+
+            fn to_string(value: &Value) -> String {
+                match value {
+                    Value::String(s) => s.clone(),
+                    Value::Null => "null".to_string(),
+                    other => other.to_string(),
+                }
+            }
+
+            fn numbers(args: &[Value]) -> Option<Vec<f64>> {
+                // List functions take either one list or the items as arguments.
+                let items = match args {
+                    [Value::Array(items)] => items.as_slice(),
+                    items => items,
+                };
+                items.iter().map(Value::as_f64).collect()
+            }
+
+            fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+                let str_arg = |i: usize| args.get(i).and_then(Value::as_str);
+                let num_arg = |i: usize| args.get(i).and_then(Value::as_f64);
+                let list_arg = |i: usize| args.get(i).and_then(Value::as_array);
+                let arity = |n: usize| match args.len() == n {
+                    true => Ok(()),
+                    false => Err(format!("FEEL function `{}` takes {} argument(s), got {}", name, n, args.len())),
+                };
+                Ok(match name {
+                    "not" => {
+                        arity(1)?;
+                        args[0].as_bool().map_or(Value::Null, |b| Value::Bool(!b))
+                    }
+                    "is defined" => {
+                        arity(1)?;
+                        Value::Bool(!args[0].is_null())
+                    }
+                    "string" => {
+                        arity(1)?;
+                        match &args[0] {
+                            Value::Null => Value::Null,
+                            value => Value::String(to_string(value)),
+                        }
+                    }
+                    "number" => {
+                        arity(1)?;
+                        str_arg(0).and_then(|s| s.trim().parse::<f64>().ok()).map_or(Value::Null, number)
+                    }
+                    "string length" => str_arg(0).map_or(Value::Null, |s| Value::from(s.chars().count())),
+                    "upper case" => str_arg(0).map_or(Value::Null, |s| Value::String(s.to_uppercase())),
+                    "lower case" => str_arg(0).map_or(Value::Null, |s| Value::String(s.to_lowercase())),
+                    "contains" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(part)) => Value::Bool(s.contains(part)),
+                        _ => Value::Null,
+                    },
+                    "starts with" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(part)) => Value::Bool(s.starts_with(part)),
+                        _ => Value::Null,
+                    },
+                    "ends with" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(part)) => Value::Bool(s.ends_with(part)),
+                        _ => Value::Null,
+                    },
+                    "substring" => {
+                        let (Some(s), Some(start)) = (str_arg(0), num_arg(1)) else {
+                            return Ok(Value::Null);
+                        };
+                        let chars: Vec<char> = s.chars().collect();
+                        // 1-based start, negative from the end.
+                        let start = match start as i64 {
+                            i if i > 0 => (i - 1) as usize,
+                            i if i < 0 => chars.len().saturating_sub((-i) as usize),
+                            _ => 0,
+                        }
+                        .min(chars.len());
+                        let end = num_arg(2).map_or(chars.len(), |len| (start + len.max(0.0) as usize).min(chars.len()));
+                        Value::String(chars[start..end].iter().collect())
+                    }
+                    "substring before" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(part)) => Value::String(s.find(part).map_or("", |i| &s[..i]).to_string()),
+                        _ => Value::Null,
+                    },
+                    "substring after" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(part)) => Value::String(s.find(part).map_or("", |i| &s[i + part.len()..]).to_string()),
+                        _ => Value::Null,
+                    },
+                    "split" => match (str_arg(0), str_arg(1)) {
+                        (Some(s), Some(delimiter)) => Value::Array(s.split(delimiter).map(|part| Value::String(part.to_string())).collect()),
+                        _ => Value::Null,
+                    },
+                    "string join" => match list_arg(0) {
+                        Some(items) => {
+                            let parts: Vec<String> = items.iter().filter(|item| !item.is_null()).map(to_string).collect();
+                            Value::String(parts.join(str_arg(1).unwrap_or("")))
+                        }
+                        None => Value::Null,
+                    },
+                    "count" => list_arg(0).map_or(Value::Null, |items| Value::from(items.len())),
+                    "sum" => numbers(args).map_or(Value::Null, |n| number(n.iter().sum())),
+                    "mean" => match numbers(args) {
+                        Some(n) if !n.is_empty() => number(n.iter().sum::<f64>() / n.len() as f64),
+                        _ => Value::Null,
+                    },
+                    "min" => numbers(args).and_then(|n| n.into_iter().reduce(f64::min)).map_or(Value::Null, number),
+                    "max" => numbers(args).and_then(|n| n.into_iter().reduce(f64::max)).map_or(Value::Null, number),
+                    "append" => match list_arg(0) {
+                        Some(items) => Value::Array(items.iter().chain(&args[1..]).cloned().collect()),
+                        None => Value::Null,
+                    },
+                    "concatenate" => {
+                        let mut items = Vec::new();
+                        for arg in args {
+                            match arg {
+                                Value::Array(list) => items.extend(list.iter().cloned()),
+                                _ => return Ok(Value::Null),
+                            }
+                        }
+                        Value::Array(items)
+                    }
+                    "flatten" => {
+                        fn flatten(value: &Value, out: &mut Vec<Value>) {
+                            match value {
+                                Value::Array(items) => items.iter().for_each(|item| flatten(item, out)),
+                                other => out.push(other.clone()),
+                            }
+                        }
+                        let mut items = Vec::new();
+                        match args.first() {
+                            Some(list @ Value::Array(_)) => flatten(list, &mut items),
+                            _ => return Ok(Value::Null),
+                        }
+                        Value::Array(items)
+                    }
+                    "reverse" => list_arg(0).map_or(Value::Null, |items| Value::Array(items.iter().rev().cloned().collect())),
+                    "sort" => match list_arg(0) {
+                        Some(items) => {
+                            let mut items = items.clone();
+                            items.sort_by(|a, b| compare(a, b).unwrap_or(std::cmp::Ordering::Equal));
+                            Value::Array(items)
+                        }
+                        None => Value::Null,
+                    },
+                    "distinct values" => match list_arg(0) {
+                        Some(items) => {
+                            let mut distinct: Vec<Value> = Vec::new();
+                            for item in items {
+                                if !distinct.iter().any(|d| equal(d, item)) {
+                                    distinct.push(item.clone());
+                                }
+                            }
+                            Value::Array(distinct)
+                        }
+                        None => Value::Null,
+                    },
+                    "list contains" => match list_arg(0) {
+                        Some(items) => Value::Bool(args.get(1).is_some_and(|needle| items.iter().any(|item| equal(item, needle)))),
+                        None => Value::Null,
+                    },
+                    "index of" => match list_arg(0) {
+                        Some(items) => Value::Array(
+                            items
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, item)| args.get(1).is_some_and(|needle| equal(item, needle)))
+                                .map(|(i, _)| Value::from(i + 1))
+                                .collect(),
+                        ),
+                        None => Value::Null,
+                    },
+                    "get value" => match (args.first(), str_arg(1)) {
+                        (Some(Value::Object(context)), Some(key)) => context.get(key).cloned().unwrap_or(Value::Null),
+                        _ => Value::Null,
+                    },
+                    "get entries" => match args.first() {
+                        Some(Value::Object(context)) => Value::Array(
+                            context.iter().map(|(key, value)| serde_json::json!({ "key": key, "value": value })).collect(),
+                        ),
+                        _ => Value::Null,
+                    },
+                    "abs" => num_arg(0).map_or(Value::Null, |n| number(n.abs())),
+                    "floor" => num_arg(0).map_or(Value::Null, |n| number(n.floor())),
+                    "ceiling" => num_arg(0).map_or(Value::Null, |n| number(n.ceil())),
+                    "decimal" => match (num_arg(0), num_arg(1)) {
+                        // Half-even rounding to `scale` digits, as FEEL's `decimal`.
+                        (Some(n), Some(scale)) => {
+                            let factor = 10f64.powi(scale as i32);
+                            let scaled = n * factor;
+                            let rounded = if (scaled.fract().abs() - 0.5).abs() < 1e-9 {
+                                let floor = scaled.floor();
+                                if floor % 2.0 == 0.0 { floor } else { floor + 1.0 }
+                            } else {
+                                scaled.round()
+                            };
+                            number(rounded / factor)
+                        }
+                        _ => Value::Null,
+                    },
+                    "modulo" => match (num_arg(0), num_arg(1)) {
+                        (Some(_), Some(divisor)) if divisor == 0.0 => Value::Null,
+                        // The result takes the sign of the divisor.
+                        (Some(dividend), Some(divisor)) => number(dividend - divisor * (dividend / divisor).floor()),
+                        _ => Value::Null,
+                    },
+                    // The results of a Camunda `errorExpression`.
+                    "bpmnError" => match (str_arg(0), str_arg(1)) {
+                        (Some(code), Some(message)) => serde_json::json!({
+                            "errorType": "bpmnError",
+                            "code": code,
+                            "message": message,
+                            "variables": args.get(2).cloned().unwrap_or_else(|| serde_json::json!({})),
+                        }),
+                        _ => Value::Null,
+                    },
+                    "jobError" => match str_arg(0) {
+                        Some(message) => serde_json::json!({
+                            "errorType": "jobError",
+                            "message": message,
+                            "variables": args.get(1).cloned().unwrap_or_else(|| serde_json::json!({})),
+                            "retries": args.get(2).cloned().unwrap_or(Value::Null),
+                            "retryBackoff": args.get(3).cloned().unwrap_or(Value::Null),
+                        }),
+                        None => Value::Null,
+                    },
+                    _ => return Err(format!("Unknown FEEL function `{}`", name)),
+                })
+            }
+        }
+    }
+}
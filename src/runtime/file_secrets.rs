@@ -0,0 +1,80 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings `FileSecrets` reads.
+pub const SETTINGS: [&str; 1] = ["SECRETS_DIR"];
+
+/// Generates `FileSecrets`, reading secrets from a directory of files such as a mounted Kubernetes Secret: the file
+/// `CRM_API_KEY` holds the secret of that name, without its trailing newline, and `crm/API_KEY` the secret
+/// `crm/API_KEY`. Entries starting with `..`, the kubelet's own, are skipped.
+///
+/// The directory is watched, so that rotated secrets are picked up without a restart: the kubelet swaps the
+/// whole volume at once, and the files are read again on any change.
+pub fn expand() -> TokenStream {
+    quote! {
+        struct FileSecrets {
+            values: std::sync::Arc<std::sync::RwLock<std::collections::HashMap<String, String>>>,
+            _watcher: notify::RecommendedWatcher,
+        }
+
+        impl FileSecrets {
+            fn from_settings() -> Result<Self, String> {
+                let dir = std::path::PathBuf::from(setting("SECRETS_DIR").ok_or("SECRETS_DIR is not set")?);
+                let values = std::sync::Arc::new(std::sync::RwLock::new(
+                    Self::read_dir(&dir).map_err(|e| format!("cannot read SECRETS_DIR `{}`: {}", dir.display(), e))?,
+                ));
+                let watched = values.clone();
+                let watched_dir = dir.clone();
+                let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                    if event.is_err() {
+                        return;
+                    }
+                    match Self::read_dir(&watched_dir) {
+                        Ok(read) => {
+                            let mut values = watched.write().unwrap();
+                            if *values != read {
+                                tracing::info!(dir = %watched_dir.display(), secrets = read.len(), "secrets reloaded");
+                                *values = read;
+                            }
+                        }
+                        // Kept as they were, a swap may be under way.
+                        Err(e) => tracing::warn!(dir = %watched_dir.display(), "cannot read the secrets again: {}", e),
+                    }
+                })
+                .map_err(|e| e.to_string())?;
+                notify::Watcher::watch(&mut watcher, &dir, notify::RecursiveMode::Recursive).map_err(|e| format!("cannot watch SECRETS_DIR `{}`: {}", dir.display(), e))?;
+                Ok(FileSecrets { values, _watcher: watcher })
+            }
+
+            fn read_dir(dir: &std::path::Path) -> std::io::Result<std::collections::HashMap<String, String>> {
+                let mut values = std::collections::HashMap::new();
+                let mut pending = vec![(dir.to_path_buf(), String::new())];
+                while let Some((path, prefix)) = pending.pop() {
+                    for entry in std::fs::read_dir(&path)? {
+                        let entry = entry?;
+                        let name = entry.file_name().to_string_lossy().into_owned();
+                        if name.starts_with("..") {
+                            continue;
+                        }
+                        // Following the kubelet's symlinks into the current `..data`.
+                        let metadata = std::fs::metadata(entry.path())?;
+                        if metadata.is_dir() {
+                            pending.push((entry.path(), format!("{}{}/", prefix, name)));
+                        } else if metadata.is_file() {
+                            let value = std::fs::read_to_string(entry.path())?;
+                            let value = value.strip_suffix('\n').map(|value| value.strip_suffix('\r').unwrap_or(value)).unwrap_or(&value);
+                            values.insert(format!("{}{}", prefix, name), value.to_string());
+                        }
+                    }
+                }
+                Ok(values)
+            }
+        }
+
+        impl SecretsProvider for FileSecrets {
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a> {
+                Box::pin(async move { Ok(self.values.read().unwrap().get(name).cloned()) })
+            }
+        }
+    }
+}
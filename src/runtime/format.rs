@@ -0,0 +1,313 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::MainArgs;
+
+/// Generates `connectors::Format` and `connectors::Output`, with one arm per wire format enabled in `connector_main!`.
+pub fn expand(args: &MainArgs) -> TokenStream {
+    let mut variants = Vec::new();
+    let mut detect = Vec::new();
+    let mut content_types = Vec::new();
+    let mut names = Vec::new();
+    let mut decode = Vec::new();
+    let mut encode = Vec::new();
+    let mut peek = Vec::new();
+    let mut peek_input = Vec::new();
+    let mut peek_version = Vec::new();
+    let mut peek_mapping = Vec::new();
+    let mut outputs = Vec::new();
+    let mut output_encode = Vec::new();
+    let mut output_value = Vec::new();
+    let mut extra = Vec::new();
+
+    // MessagePack is opt-in so that crates not using it don't need `rmp-serde`.
+    if args.msgpack {
+        variants.push(quote! { MsgPack, });
+        detect.push(quote! {
+            ct if ct.starts_with("application/msgpack") || ct.starts_with("application/x-msgpack") => Format::MsgPack,
+        });
+        content_types.push(quote! { Format::MsgPack => "application/msgpack", });
+        names.push(quote! { Format::MsgPack => "MessagePack", });
+        decode.push(quote! { Format::MsgPack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()), });
+        encode.push(quote! { Format::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()), });
+    }
+
+    // Protobuf envelopes carry the input as nested bytes, only connectors declared with `protobuf = true` decode them.
+    if args.protobuf {
+        variants.push(quote! { Protobuf, });
+        detect.push(quote! {
+            ct if ct.starts_with("application/x-protobuf") || ct.starts_with("application/protobuf") => Format::Protobuf,
+        });
+        content_types.push(quote! { Format::Protobuf => "application/x-protobuf", });
+        names.push(quote! { Format::Protobuf => "Protobuf", });
+        decode.push(quote! { Format::Protobuf => Err("Protobuf payloads require a connector declared with `protobuf = true`".to_string()), });
+        encode.push(quote! { Format::Protobuf => Err("Protobuf responses require a connector declared with `protobuf = true`".to_string()), });
+        peek.push(quote! {
+            Format::Protobuf => <ProtoEnvelope as prost::Message>::decode(bytes)
+                .map(|env| env.operation)
+                .map_err(|e| e.to_string()),
+        });
+        peek_input.push(quote! {
+            Format::Protobuf => <ProtoEnvelope as prost::Message>::decode(bytes)
+                .map(|env| env.input)
+                .map_err(|e| e.to_string()),
+        });
+        peek_version.push(quote! {
+            Format::Protobuf => <ProtoEnvelope as prost::Message>::decode(bytes).map_or(0, |env| env.version),
+        });
+        // Protobuf outputs are opaque bytes, there is nothing to map.
+        peek_mapping.push(quote! { Format::Protobuf => ResultMapping::default(), });
+        outputs.push(quote! { Protobuf(Vec<u8>), });
+        output_encode.push(quote! { Output::Protobuf(bytes) => Ok(bytes), });
+        output_value.push(quote! { Output::Protobuf(_) => Err("Protobuf output cannot be converted to JSON".to_string()), });
+        extra.push(quote! {
+            /// Protobuf counterpart of the JSON envelope:
+            /// `{ uint64 id = 1; string operation = 2; bytes input = 3; uint32 version = 4; }`.
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct ProtoEnvelope {
+                #[prost(uint64, tag = "1")]
+                pub id: u64,
+                #[prost(string, tag = "2")]
+                pub operation: String,
+                #[prost(bytes = "vec", tag = "3")]
+                pub input: Vec<u8>,
+                /// 0 (unset) stands for version 1.
+                #[prost(uint32, tag = "4")]
+                pub version: u32,
+            }
+        });
+    }
+
+    // Streamed outputs need `futures-util`, and only make sense to callers reading the HTTP response as it comes.
+    if args.streaming {
+        outputs.push(quote! { Stream(ItemStream), });
+        output_encode.push(quote! { Output::Stream(_) => Err("Streamed output is only served on `/csp/{name}`".to_string()), });
+        output_value.push(quote! { Output::Stream(_) => Err("Streamed output cannot be converted to a single JSON value".to_string()), });
+        extra.push(quote! {
+            pub type BoxItemStream = futures_util::stream::BoxStream<'static, Result<serde_json::Value, String>>;
+
+            /// The items of a `stream = true` connector, serialized one by one as the handler yields them. Clones
+            /// share the stream, which is consumed once.
+            #[derive(Clone)]
+            pub struct ItemStream(std::sync::Arc<std::sync::Mutex<Option<BoxItemStream>>>);
+
+            impl ItemStream {
+                pub fn new<S, T, E>(stream: S) -> Self
+                where
+                    S: futures_util::Stream<Item = Result<T, E>> + Send + 'static,
+                    T: serde::Serialize,
+                    E: std::fmt::Display,
+                {
+                    use futures_util::StreamExt as _;
+                    let items = stream.map(|item| {
+                        item.map_err(|e| e.to_string())
+                            .and_then(|item| serde_json::to_value(item).map_err(|e| e.to_string()))
+                    });
+                    ItemStream(std::sync::Arc::new(std::sync::Mutex::new(Some(items.boxed()))))
+                }
+
+                /// Keeps `value` alive until the stream is dropped, e.g. a guard cancelling the call's context.
+                pub fn hold<V: Send + 'static>(self, value: V) -> Self {
+                    use futures_util::StreamExt as _;
+                    match self.take() {
+                        Some(items) => {
+                            let items = items.map(move |item| {
+                                let _held = &value;
+                                item
+                            });
+                            ItemStream(std::sync::Arc::new(std::sync::Mutex::new(Some(items.boxed()))))
+                        }
+                        None => self,
+                    }
+                }
+
+                /// The stream, `None` once taken.
+                pub fn take(&self) -> Option<BoxItemStream> {
+                    self.0.lock().unwrap().take()
+                }
+            }
+        });
+    }
+
+    quote! {
+        /// Handler result as produced by `exec_raw`, either a value to encode in the request format, pre-encoded bytes or a
+        /// stream of items.
+        #[derive(Clone)]
+        pub enum Output {
+            Value(serde_json::Value),
+            #(#outputs)*
+        }
+
+        impl Output {
+            pub fn encode(self, format: Format) -> Result<Vec<u8>, String> {
+                match self {
+                    Output::Value(value) => format.encode(&value),
+                    #(#output_encode)*
+                }
+            }
+
+            pub fn into_value(self) -> Result<serde_json::Value, String> {
+                match self {
+                    Output::Value(value) => Ok(value),
+                    #(#output_value)*
+                }
+            }
+        }
+
+        /// Wire serialization of the request envelope and of the response, negotiated via `Content-Type`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Format {
+            Json,
+            #(#variants)*
+        }
+
+        impl Format {
+            pub fn from_headers(headers: &axum::http::HeaderMap) -> Self {
+                let content_type = headers
+                    .get(axum::http::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default();
+                Self::from_content_type(content_type)
+            }
+
+            pub fn from_content_type(content_type: &str) -> Self {
+                match content_type {
+                    #(#detect)*
+                    _ => Format::Json,
+                }
+            }
+
+            pub fn content_type(self) -> &'static str {
+                match self {
+                    Format::Json => "application/json",
+                    #(#content_types)*
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    Format::Json => "JSON",
+                    #(#names)*
+                }
+            }
+
+            pub fn decode<T: serde::de::DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+                match self {
+                    Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+                    #(#decode)*
+                }
+            }
+
+            pub fn encode(self, value: &serde_json::Value) -> Result<Vec<u8>, String> {
+                match self {
+                    Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+                    #(#encode)*
+                }
+            }
+
+            /// Reads only the operation out of an envelope, leaving the typed decoding to the connector.
+            pub fn peek_operation(self, bytes: &[u8]) -> Result<String, String> {
+                match self {
+                    #(#peek)*
+                    _ => self.decode::<OpPeek>(bytes).map(|peek| peek.params.operation),
+                }
+            }
+
+            /// The connector version the envelope asks for, 1 when it doesn't say (or can't be read, which the
+            /// connector's own decoding then reports).
+            pub fn peek_version(self, bytes: &[u8]) -> u32 {
+                let version = match self {
+                    #(#peek_version)*
+                    _ => self.decode::<VersionPeek>(bytes).ok().and_then(|peek| peek.params.version).unwrap_or(1),
+                };
+                version.max(1)
+            }
+
+            /// The envelope's `resultVariable` and `resultExpression`, unset when it can't be read.
+            pub fn peek_result_mapping(self, bytes: &[u8]) -> ResultMapping {
+                match self {
+                    #(#peek_mapping)*
+                    _ => self.decode(bytes).unwrap_or_default(),
+                }
+            }
+
+            /// The envelope's input alone, in a canonical encoding (JSON with sorted keys) whatever the request's id.
+            pub fn peek_input(self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+                match self {
+                    #(#peek_input)*
+                    _ => self
+                        .decode::<InputPeek>(bytes)
+                        .and_then(|peek| serde_json::to_vec(&peek.params.input).map_err(|e| e.to_string())),
+                }
+            }
+        }
+
+        #[derive(serde::Deserialize)]
+        struct OpPeek {
+            params: OpPeekParams,
+        }
+        #[derive(serde::Deserialize)]
+        struct OpPeekParams {
+            operation: String,
+        }
+        #[derive(serde::Deserialize)]
+        struct VersionPeek {
+            params: VersionPeekParams,
+        }
+        #[derive(serde::Deserialize)]
+        struct VersionPeekParams {
+            #[serde(default)]
+            version: Option<u32>,
+        }
+        #[derive(serde::Deserialize)]
+        struct InputPeek {
+            params: InputPeekParams,
+        }
+        #[derive(serde::Deserialize)]
+        struct InputPeekParams {
+            #[serde(default)]
+            input: serde_json::Value,
+        }
+
+        /// How the handler's output becomes process variables, as the Camunda connector protocol defines it.
+        #[derive(Debug, Default, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct ResultMapping {
+            /// The whole output goes under this variable.
+            #[serde(default)]
+            pub result_variable: Option<String>,
+            /// A FEEL expression evaluated against the output (its fields, or `response` for all of it), whose
+            /// resulting context entries become variables.
+            #[serde(default)]
+            pub result_expression: Option<String>,
+        }
+
+        impl ResultMapping {
+            pub fn is_empty(&self) -> bool {
+                self.result_variable.is_none() && self.result_expression.is_none()
+            }
+
+            pub fn apply(&self, output: serde_json::Value) -> Result<serde_json::Value, String> {
+                let mut variables = serde_json::Map::new();
+                if let Some(expression) = &self.result_expression {
+                    let mut scope = match &output {
+                        serde_json::Value::Object(fields) => fields.clone(),
+                        _ => serde_json::Map::new(),
+                    };
+                    scope.insert("response".into(), output.clone());
+                    match feel::evaluate(expression, &serde_json::Value::Object(scope))? {
+                        serde_json::Value::Object(entries) => variables.extend(entries),
+                        serde_json::Value::Null => {}
+                        other => return Err(format!("`resultExpression` must evaluate to a context, got `{}`", other)),
+                    }
+                }
+                if let Some(name) = &self.result_variable {
+                    variables.insert(name.clone(), output);
+                }
+                Ok(serde_json::Value::Object(variables))
+            }
+        }
+
+        #(#extra)*
+    }
+}
@@ -0,0 +1,99 @@
+use std::net::IpAddr;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// Parses `trusted_proxies` entries such as `"10.0.0.0/8"`, `"fd00::/8"` or a bare address.
+pub fn parse_cidrs(lits: &[LitStr]) -> syn::Result<Vec<(IpAddr, u8)>> {
+    lits.iter()
+        .map(|lit| {
+            let value = lit.value();
+            let (ip, prefix) = value.split_once('/').unwrap_or((&value, ""));
+            let ip: IpAddr = ip.parse().map_err(|_| Error::new_spanned(lit, "Expected a CIDR such as \"10.0.0.0/8\""))?;
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                "" => max,
+                prefix => prefix.parse().ok().filter(|p| *p <= max).ok_or_else(|| Error::new_spanned(lit, format!("Prefix length must be at most {}", max)))?,
+            };
+            Ok((ip, prefix))
+        })
+        .collect()
+}
+
+/// Generates the `client_info` middleware resolving the effective client of each request into a
+/// `connectors::ClientInfo` extension.
+///
+/// The TCP peer is the client unless it is one of the `trusted` proxies; then `X-Forwarded-For` is walked from the
+/// right, skipping trusted hops, and `X-Forwarded-Proto` is honoured. Unix socket peers are local and trusted.
+pub fn expand(trusted: &[(IpAddr, u8)]) -> TokenStream {
+    let networks = trusted.iter().map(|(ip, prefix)| {
+        let ip = ip.to_string();
+        quote! { (#ip.parse::<std::net::IpAddr>().unwrap(), #prefix) }
+    });
+    let forwarding = !trusted.is_empty();
+    quote! {
+        fn trusted_proxy(ip: std::net::IpAddr) -> bool {
+            static NETWORKS: std::sync::OnceLock<Vec<(std::net::IpAddr, u8)>> = std::sync::OnceLock::new();
+            let networks = NETWORKS.get_or_init(|| vec![#(#networks),*]);
+            // Dual-stack listeners report IPv4 peers as `::ffff:a.b.c.d`.
+            let ip = ip.to_canonical();
+            networks.iter().any(|(net, prefix)| match (ip, net) {
+                (std::net::IpAddr::V4(ip), std::net::IpAddr::V4(net)) => {
+                    let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                    u32::from(ip) & mask == u32::from(*net) & mask
+                }
+                (std::net::IpAddr::V6(ip), std::net::IpAddr::V6(net)) => {
+                    let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                    u128::from(ip) & mask == u128::from(*net) & mask
+                }
+                _ => false,
+            })
+        }
+
+        async fn client_info(mut request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+            let peer = request
+                .extensions()
+                .get::<axum::extract::ConnectInfo<connectors::ClientAddr>>()
+                .map(|info| info.0.0.ip().to_canonical());
+            let mut client = connectors::ClientInfo { ip: peer, proto: None };
+            if #forwarding && peer.is_none_or(trusted_proxy) {
+                let headers = request.headers();
+                // Unparsable hops are kept as `None` so positions still line up with `X-Forwarded-Proto`.
+                let hops: Vec<Option<std::net::IpAddr>> = headers
+                    .get_all("x-forwarded-for")
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(','))
+                    .map(|hop| hop.trim().parse().ok())
+                    .collect();
+                // The rightmost hop not added by one of our proxies is the client; hops further left are
+                // client-supplied and can't be trusted.
+                let client_hop = hops
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find_map(|(i, ip)| ip.filter(|ip| !trusted_proxy(*ip)).map(|ip| (i, ip)))
+                    .or_else(|| hops.iter().enumerate().find_map(|(i, ip)| ip.map(|ip| (i, ip))));
+                let protos: Vec<&str> = headers
+                    .get_all("x-forwarded-proto")
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(','))
+                    .collect();
+                // Each proxy appends to both headers, so the proto recorded for the client hop sits at the same
+                // distance from the right. Without a client hop the peer is the client and its entry is the last.
+                let proto_index = match client_hop {
+                    Some((index, ip)) => {
+                        client.ip = Some(ip.to_canonical());
+                        (protos.len() + index).checked_sub(hops.len())
+                    }
+                    None => protos.len().checked_sub(1),
+                };
+                client.proto = proto_index.and_then(|i| protos.get(i)).map(|proto| proto.trim().to_ascii_lowercase());
+            }
+            request.extensions_mut().insert(client);
+            next.run(request).await
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `/graphql`, an async-graphql schema built from the dispatch table at the first request: one mutation
+/// per connector, operation and version, named `{connector}_{operation}` (suffixed `_v{version}` past the first).
+///
+/// The arguments are the fields of `#[derive(ConnectorInput)]` inputs, typed after their element template property
+/// type; other inputs are taken whole, as the `input` argument. Outputs are `JSON` scalars. `GET /graphql` serves
+/// GraphiQL to explore and run them.
+pub fn expand() -> TokenStream {
+    quote! {
+        /// A GraphQL name from a connector, operation or field name, with every other character replaced by `_`.
+        fn graphql_name(name: &str) -> String {
+            let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            if name.starts_with(|c: char| c.is_ascii_digit()) { format!("_{}", name) } else { name }
+        }
+
+        fn graphql_error(e: connectors::ExecError) -> async_graphql::Error {
+            use async_graphql::ErrorExtensions as _;
+            let (status, retriable) = (e.status().as_u16(), e.retriable());
+            let error_code = match &e {
+                connectors::ExecError::Failed { error_code, .. } => error_code.clone(),
+                _ => None,
+            };
+            async_graphql::Error::new(e.to_string()).extend_with(|_, extensions| {
+                extensions.set("status", status);
+                extensions.set("retriable", retriable);
+                if let Some(code) = error_code {
+                    extensions.set("errorCode", code);
+                }
+            })
+        }
+
+        /// The mutation running `operation` of `name`, with the input assembled from its arguments.
+        fn graphql_mutation(
+            field_name: String,
+            (name, operation, version): (String, String, u32),
+            r: &'static connectors::ConnectorRecipe,
+        ) -> async_graphql::dynamic::Field {
+            use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
+            // GraphQL argument names, and the input fields they fill.
+            let fields: Vec<(String, &'static str)> = match (r.input_fields)() {
+                Some(fields) => fields.iter().map(|field| (graphql_name(field.name), field.name)).collect(),
+                None => Vec::new(),
+            };
+            let whole_input = (r.input_fields)().is_none();
+            let arguments = fields.clone();
+            let mut mutation = Field::new(field_name, TypeRef::named("JSON"), move |resolver| {
+                let (name, operation, arguments) = (name.clone(), operation.clone(), arguments.clone());
+                FieldFuture::new(async move {
+                    let argument = |arg: &str| resolver.args.get(arg).map(|value| value.as_value().clone().into_json());
+                    let input = if whole_input {
+                        argument("input").transpose()?.unwrap_or_default()
+                    } else {
+                        let mut input = serde_json::Map::new();
+                        for (arg, field) in &arguments {
+                            if let Some(value) = argument(arg).transpose()? {
+                                input.insert(field.to_string(), value);
+                            }
+                        }
+                        input.into()
+                    };
+                    let envelope = serde_json::json!({
+                        "params": { "operation": operation, "input": input, "version": version },
+                    });
+                    let body = serde_json::to_vec(&envelope)?;
+                    let ctx = resolver.data::<connectors::Context>()?.clone();
+                    let output = execute(name, operation, body.into(), connectors::Format::Json, ctx)
+                        .await
+                        .and_then(|out| out.into_value().map_err(connectors::ExecError::Handler))
+                        .map_err(graphql_error)?;
+                    Ok(Some(FieldValue::value(async_graphql::Value::from_json(output)?)))
+                })
+            });
+            if whole_input {
+                mutation = mutation.argument(InputValue::new("input", TypeRef::named("JSON")));
+            }
+            for ((arg, _), field) in fields.iter().zip((r.input_fields)().unwrap_or_default()) {
+                let kind = match field.kind {
+                    "String" => TypeRef::STRING,
+                    "Number" => TypeRef::FLOAT,
+                    "Boolean" => TypeRef::BOOLEAN,
+                    _ => "JSON",
+                };
+                let ty = if field.optional { TypeRef::named(kind) } else { TypeRef::named_nn(kind) };
+                let mut argument = InputValue::new(arg.clone(), ty);
+                if let Some(description) = field.description {
+                    argument = argument.description(description);
+                }
+                mutation = mutation.argument(argument);
+            }
+            if let Some(description) = r.description {
+                mutation = mutation.description(description);
+            }
+            if r.deprecated.is_some() {
+                mutation = mutation.deprecation(r.deprecated);
+            }
+            mutation
+        }
+
+        fn graphql_schema() -> Result<&'static async_graphql::dynamic::Schema, &'static str> {
+            use async_graphql::dynamic::{Field, FieldFuture, FieldValue, Object, Scalar, Schema, TypeRef};
+            static SCHEMA: std::sync::OnceLock<Result<Schema, String>> = std::sync::OnceLock::new();
+            let schema = SCHEMA.get_or_init(|| {
+                let mut entries: Vec<_> = dispatch_table().iter().filter(|((_, operation, _), _)| operation != "*").collect();
+                entries.sort_by_key(|(key, _)| *key);
+                let mut names = std::collections::HashSet::new();
+                let mut listing = Vec::new();
+                let mut mutation = Object::new("Mutation");
+                for ((name, operation, version), r) in entries {
+                    let mut field_name = format!("{}_{}", graphql_name(name), graphql_name(operation));
+                    if *version > 1 {
+                        field_name = format!("{}_v{}", field_name, version);
+                    }
+                    if !names.insert(field_name.clone()) {
+                        tracing::warn!(connector = name.as_str(), operation = operation.as_str(), version, field = field_name, "GraphQL mutation name is taken, skipping");
+                        continue;
+                    }
+                    listing.push(serde_json::json!({
+                        "connector": name,
+                        "operation": operation,
+                        "version": version,
+                        "mutation": field_name,
+                        "description": r.description,
+                    }));
+                    mutation = mutation.field(graphql_mutation(field_name, (name.clone(), operation.clone(), *version), r));
+                }
+                let query = Object::new("Query").field(Field::new("connectors", TypeRef::named_nn("JSON"), move |_| {
+                    let listing = serde_json::Value::from(listing.clone());
+                    FieldFuture::new(async move { Ok(Some(FieldValue::value(async_graphql::Value::from_json(listing)?))) })
+                }));
+                // A schema can't have an empty mutation type.
+                let schema = match names.is_empty() {
+                    true => Schema::build("Query", None, None),
+                    false => Schema::build("Query", Some("Mutation"), None).register(mutation),
+                };
+                schema
+                    .register(Scalar::new("JSON").description("Any JSON value"))
+                    .register(query)
+                    .finish()
+                    .map_err(|e| {
+                        tracing::error!("cannot build the GraphQL schema: {}", e);
+                        e.to_string()
+                    })
+            });
+            schema.as_ref().map_err(|_| "The GraphQL schema is unavailable")
+        }
+
+        async fn graphiql() -> axum::response::Html<String> {
+            axum::response::Html(async_graphql::http::GraphiQLSource::build().endpoint("/graphql").finish())
+        }
+
+        async fn dispatch_graphql(
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            axum::Json(request): axum::Json<async_graphql::Request>,
+        ) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            let schema = match graphql_schema() {
+                Ok(schema) => schema,
+                Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+            };
+            let ctx = match connectors::Context::from_headers(&headers) {
+                Ok(ctx) => ctx.with_client(client),
+                Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+            };
+            axum::Json(schema.execute(request.data(ctx)).await).into_response()
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the `camunda.connectors.v1.Connectors` gRPC service, served through tonic on the same router:
+///
+/// ```proto
+/// service Connectors { rpc Execute(ExecuteRequest) returns (ExecuteResponse); }
+/// message ExecuteRequest { string name = 1; string operation = 2; bytes payload = 3; uint32 version = 4; string content_type = 5; }
+/// message ExecuteResponse { bytes payload = 1; string content_type = 2; }
+/// ```
+///
+/// The payload is the connector's input in the format of `content_type` (JSON when empty), and the output comes
+/// back in the same format. Calls run the same dispatch as `/csp/{name}`, errors map to gRPC status codes.
+pub fn expand(protobuf: bool) -> TokenStream {
+    // Protobuf connectors take their input as is, within the Protobuf envelope.
+    let protobuf_envelope = protobuf.then(|| {
+        quote! {
+            if format == connectors::Format::Protobuf {
+                let envelope = connectors::ProtoEnvelope { id: 0, operation: operation.to_string(), input: payload, version };
+                return Ok(prost::Message::encode_to_vec(&envelope).into());
+            }
+        }
+    });
+
+    quote! {
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct GrpcExecuteRequest {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(string, tag = "2")]
+            pub operation: String,
+            #[prost(bytes = "vec", tag = "3")]
+            pub payload: Vec<u8>,
+            /// 0 (unset) stands for version 1.
+            #[prost(uint32, tag = "4")]
+            pub version: u32,
+            #[prost(string, tag = "5")]
+            pub content_type: String,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct GrpcExecuteResponse {
+            #[prost(bytes = "vec", tag = "1")]
+            pub payload: Vec<u8>,
+            #[prost(string, tag = "2")]
+            pub content_type: String,
+        }
+
+        /// Wraps the payload in the envelope `/csp/{name}` would have been sent.
+        fn grpc_envelope(format: connectors::Format, operation: &str, payload: Vec<u8>, version: u32) -> Result<axum::body::Bytes, String> {
+            #protobuf_envelope
+            let input: serde_json::Value = if payload.is_empty() {
+                serde_json::Value::Null
+            } else {
+                format.decode(&payload).map_err(|e| format!("Bad {} payload: {}", format.name(), e))?
+            };
+            let envelope = serde_json::json!({
+                "params": { "operation": operation, "input": input, "version": version.max(1) },
+            });
+            format.encode(&envelope).map(Into::into)
+        }
+
+        /// `grpc-timeout`, such as `500m` or `2S`: at most 8 digits followed by a unit.
+        fn grpc_timeout(value: &str) -> Option<std::time::Duration> {
+            let (amount, unit) = value.split_at_checked(value.len().checked_sub(1)?)?;
+            let amount: u64 = amount.parse().ok().filter(|_| amount.len() <= 8)?;
+            Some(match unit {
+                "H" => std::time::Duration::from_secs(amount * 3_600),
+                "M" => std::time::Duration::from_secs(amount * 60),
+                "S" => std::time::Duration::from_secs(amount),
+                "m" => std::time::Duration::from_millis(amount),
+                "u" => std::time::Duration::from_micros(amount),
+                "n" => std::time::Duration::from_nanos(amount),
+                _ => return None,
+            })
+        }
+
+        fn grpc_status(e: connectors::ExecError) -> tonic::Status {
+            let message = e.to_string();
+            let retriable = if e.retriable() { "true" } else { "false" };
+            let mut status = match e {
+                connectors::ExecError::Unsupported(_) => tonic::Status::not_found(message),
+                connectors::ExecError::Input(_) => tonic::Status::invalid_argument(message),
+                connectors::ExecError::Handler(_) => tonic::Status::internal(message),
+                connectors::ExecError::CircuitOpen { .. } | connectors::ExecError::Overloaded(_) | connectors::ExecError::Disabled(_) => {
+                    tonic::Status::unavailable(message)
+                }
+                connectors::ExecError::DeadlineExceeded(_) => tonic::Status::deadline_exceeded(message),
+                // The same retry instructions as the HTTP headers, as metadata.
+                connectors::ExecError::Failed { retries, retry_backoff, error_code, .. } => {
+                    let mut status = tonic::Status::internal(message);
+                    let metadata = status.metadata_mut();
+                    if let Some(code) = error_code.and_then(|code| code.parse().ok()) {
+                        metadata.insert("x-job-error-code", code);
+                    }
+                    if let Some(retries) = retries {
+                        metadata.insert("x-job-retries", retries.into());
+                    }
+                    if let Some(backoff) = retry_backoff {
+                        metadata.insert("x-job-retry-backoff", (backoff.as_millis() as u64).into());
+                    }
+                    status
+                }
+            };
+            if let Ok(retriable) = retriable.parse() {
+                status.metadata_mut().insert("x-error-retriable", retriable);
+            }
+            status
+        }
+
+        async fn grpc_execute(request: tonic::Request<GrpcExecuteRequest>) -> Result<tonic::Response<GrpcExecuteResponse>, tonic::Status> {
+            let client = request.extensions().get::<connectors::ClientInfo>().cloned().unwrap_or_default();
+            let headers = request.metadata().clone().into_headers();
+            let mut ctx = connectors::Context::from_headers(&headers)
+                .map_err(tonic::Status::invalid_argument)?
+                .with_client(client);
+            // The earlier of `grpc-timeout` and `X-Request-Deadline` wins.
+            if let Some(timeout) = headers.get("grpc-timeout").and_then(|v| v.to_str().ok()).and_then(grpc_timeout) {
+                let deadline = std::time::Instant::now() + timeout;
+                if ctx.deadline().is_none_or(|current| deadline < current) {
+                    ctx = ctx.with_deadline(deadline);
+                }
+            }
+            let GrpcExecuteRequest { name, operation, payload, version, content_type } = request.into_inner();
+            let format = match content_type.as_str() {
+                "" => connectors::Format::Json,
+                content_type => connectors::Format::from_content_type(content_type),
+            };
+            let body = grpc_envelope(format, &operation, payload, version).map_err(tonic::Status::invalid_argument)?;
+            let payload = execute(name, operation, body, format, ctx)
+                .await
+                .map_err(grpc_status)?
+                .encode(format)
+                .map_err(tonic::Status::internal)?;
+            Ok(tonic::Response::new(GrpcExecuteResponse { payload, content_type: format.content_type().to_string() }))
+        }
+
+        #[derive(Clone)]
+        struct GrpcExecute;
+
+        impl tonic::server::UnaryService<GrpcExecuteRequest> for GrpcExecute {
+            type Response = GrpcExecuteResponse;
+            type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<tonic::Response<GrpcExecuteResponse>, tonic::Status>> + Send>>;
+
+            fn call(&mut self, request: tonic::Request<GrpcExecuteRequest>) -> Self::Future {
+                Box::pin(grpc_execute(request))
+            }
+        }
+
+        async fn dispatch_grpc(request: axum::extract::Request) -> axum::response::Response {
+            let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+            grpc.unary(GrpcExecute, request).await.map(axum::body::Body::new)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `/readyz`, which stays `503 Service Unavailable` until every `#[connector_health]` check has passed,
+/// with the status of each dependency and connector:
+///
+/// ```json
+/// {"status":"not ready","dependencies":[{"check":"zeebe","status":"down","error":"...","failures":3}],"connectors":{"crm":{"status":"up","checks":[...]}}}
+/// ```
+///
+/// Checks run in the background from startup: every `interval` once passing, and after a failure sooner, from one
+/// second doubling up to `interval`, so that a recovered dependency is noticed quickly without hammering a broken one.
+/// With a Zeebe client, reaching the gateway is a dependency too.
+pub fn expand(interval: Duration, zeebe: bool) -> TokenStream {
+    let interval_ms = interval.as_millis() as u64;
+    let zeebe_check = zeebe.then(|| {
+        quote! {
+            fn zeebe_health() -> connectors::HookFuture {
+                Box::pin(async { connectors::zeebe::client().topology().await.map_err(|e| e.to_string()) })
+            }
+
+            ::inventory::submit! {
+                crate::connectors::HealthRecipe {
+                    name: "zeebe",
+                    connector: None,
+                    timeout: std::time::Duration::from_secs(5),
+                    check: zeebe_health,
+                }
+            }
+        }
+    });
+
+    quote! {
+        #zeebe_check
+
+        /// The latest result of each check, `pending` until its first run completes.
+        fn health_states() -> &'static [(&'static connectors::HealthRecipe, std::sync::Mutex<serde_json::Value>)] {
+            static STATES: std::sync::OnceLock<Vec<(&'static connectors::HealthRecipe, std::sync::Mutex<serde_json::Value>)>> = std::sync::OnceLock::new();
+            STATES.get_or_init(|| {
+                let mut recipes: Vec<&'static connectors::HealthRecipe> = ::inventory::iter::<crate::connectors::HealthRecipe>.into_iter().collect();
+                recipes.sort_by_key(|r| (r.connector, r.name));
+                recipes
+                    .into_iter()
+                    .map(|r| (r, std::sync::Mutex::new(serde_json::json!({ "check": r.name, "status": "pending" }))))
+                    .collect()
+            })
+        }
+
+        fn start_health_checks() {
+            for (r, state) in health_states() {
+                if let Some(connector) = r.connector {
+                    if !::inventory::iter::<crate::connectors::ConnectorRecipe>.into_iter().any(|c| c.name == connector) {
+                        tracing::warn!(check = r.name, connector, "health check names no registered connector");
+                    }
+                }
+                tokio::spawn(async move {
+                    let interval = std::time::Duration::from_millis(#interval_ms);
+                    let mut failures: u32 = 0;
+                    loop {
+                        let result = run_health_check(r).await;
+                        let delay = match &result {
+                            Ok(_) => {
+                                if failures > 0 {
+                                    tracing::info!(check = r.name, connector = r.connector, failures, "health check passes again");
+                                }
+                                failures = 0;
+                                interval
+                            }
+                            Err(e) => {
+                                if failures == 0 {
+                                    tracing::warn!(check = r.name, connector = r.connector, "health check failed: {}", e);
+                                }
+                                failures += 1;
+                                std::time::Duration::from_secs(1).saturating_mul(1 << (failures - 1).min(16)).min(interval)
+                            }
+                        };
+                        *state.lock().unwrap_or_else(|e| e.into_inner()) = match result {
+                            Ok(duration_ms) => serde_json::json!({ "check": r.name, "status": "up", "duration_ms": duration_ms }),
+                            Err(e) => serde_json::json!({ "check": r.name, "status": "down", "error": e, "failures": failures }),
+                        };
+                        tokio::time::sleep(delay).await;
+                    }
+                });
+            }
+        }
+
+        /// Runs one check within its timeout, a panic failing it like an error would. Returns how long it took.
+        async fn run_health_check(r: &'static connectors::HealthRecipe) -> Result<u64, String> {
+            let started = std::time::Instant::now();
+            let mut task = tokio::spawn((r.check)());
+            match tokio::time::timeout(r.timeout, &mut task).await {
+                Ok(Ok(result)) => result.map(|()| started.elapsed().as_millis() as u64),
+                Ok(Err(_)) => Err("the check panicked".to_string()),
+                Err(_) => {
+                    task.abort();
+                    Err(format!("timed out after {}ms", r.timeout.as_millis()))
+                }
+            }
+        }
+
+        /// The health of every connector with checks: `down` when any last failed, `up` once all last passed.
+        fn connector_health() -> std::collections::BTreeMap<&'static str, serde_json::Value> {
+            let mut checks: std::collections::BTreeMap<&'static str, Vec<serde_json::Value>> = Default::default();
+            for (r, state) in health_states() {
+                if let Some(connector) = r.connector {
+                    checks.entry(connector).or_default().push(state.lock().unwrap_or_else(|e| e.into_inner()).clone());
+                }
+            }
+            checks
+                .into_iter()
+                .map(|(connector, checks)| {
+                    let status = ["down", "pending"]
+                        .into_iter()
+                        .find(|status| checks.iter().any(|check| check["status"] == *status))
+                        .unwrap_or("up");
+                    (connector, serde_json::json!({ "status": status, "checks": checks }))
+                })
+                .collect()
+        }
+
+        async fn readyz() -> (axum::http::StatusCode, axum::Json<serde_json::Value>) {
+            let dependencies: Vec<serde_json::Value> = health_states()
+                .iter()
+                .filter(|(r, _)| r.connector.is_none())
+                .map(|(_, state)| state.lock().unwrap_or_else(|e| e.into_inner()).clone())
+                .collect();
+            let connectors = connector_health();
+            let ready = dependencies.iter().all(|check| check["status"] == "up") && connectors.values().all(|health| health["status"] == "up");
+            // Draining instances get no new traffic, whatever their dependencies.
+            let (ready, label) = match shutting_down() {
+                true => (false, "shutting down"),
+                false => (ready, if ready { "ready" } else { "not ready" }),
+            };
+            let status = if ready { axum::http::StatusCode::OK } else { axum::http::StatusCode::SERVICE_UNAVAILABLE };
+            let body = serde_json::json!({
+                "status": label,
+                "dependencies": dependencies,
+                "connectors": connectors,
+            });
+            (status, axum::Json(body))
+        }
+    }
+}
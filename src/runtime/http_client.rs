@@ -0,0 +1,92 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings `HttpClient` reads, from the configuration file or the environment.
+pub const SETTINGS: [&str; 8] = [
+    "HTTP_CLIENT_TIMEOUT",
+    "HTTP_CLIENT_CONNECT_TIMEOUT",
+    "HTTP_CLIENT_POOL_IDLE_TIMEOUT",
+    "HTTP_CLIENT_POOL_MAX_IDLE_PER_HOST",
+    "HTTP_CLIENT_PROXY",
+    "HTTP_CLIENT_NO_PROXY",
+    "HTTP_CLIENT_CA_FILE",
+    "HTTP_CLIENT_USER_AGENT",
+];
+
+/// Generates `connectors::HttpClient`, one pooled `reqwest::Client` for every handler, built at startup from the
+/// runtime settings so that connectors share connections and timeouts rather than each building their own.
+pub fn expand() -> TokenStream {
+    quote! {
+        /// The shared HTTP client (`http_client = true`), taken by handlers as `connectors::Resource<connectors::HttpClient>`.
+        pub struct HttpClient(pub reqwest::Client);
+
+        impl std::ops::Deref for HttpClient {
+            type Target = reqwest::Client;
+
+            fn deref(&self) -> &reqwest::Client {
+                &self.0
+            }
+        }
+
+        static HTTP_CLIENT: std::sync::OnceLock<HttpClient> = std::sync::OnceLock::new();
+
+        impl ConnectorResource for HttpClient {
+            fn instance() -> &'static Self {
+                HTTP_CLIENT.get().expect("the HTTP client is used before startup built it")
+            }
+        }
+
+        fn build_http_client() -> Result<reqwest::Client, String> {
+            let mut builder = reqwest::Client::builder()
+                .timeout(duration_setting("HTTP_CLIENT_TIMEOUT", std::time::Duration::from_secs(30))?)
+                .connect_timeout(duration_setting("HTTP_CLIENT_CONNECT_TIMEOUT", std::time::Duration::from_secs(10))?)
+                .pool_idle_timeout(duration_setting("HTTP_CLIENT_POOL_IDLE_TIMEOUT", std::time::Duration::from_secs(90))?)
+                .user_agent(setting("HTTP_CLIENT_USER_AGENT").unwrap_or_else(|| concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string()));
+            if let Some(max) = setting("HTTP_CLIENT_POOL_MAX_IDLE_PER_HOST") {
+                let max = max.parse().map_err(|_| format!("HTTP_CLIENT_POOL_MAX_IDLE_PER_HOST must be an integer, got `{}`", max))?;
+                builder = builder.pool_max_idle_per_host(max);
+            }
+            // Without a proxy of its own, the client honours `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY`.
+            if let Some(url) = setting("HTTP_CLIENT_PROXY") {
+                let proxy = reqwest::Proxy::all(&url).map_err(|e| format!("invalid HTTP_CLIENT_PROXY `{}`: {}", url, e))?;
+                builder = builder.proxy(proxy.no_proxy(setting("HTTP_CLIENT_NO_PROXY").as_deref().and_then(reqwest::NoProxy::from_string)));
+            }
+            // Trusted besides the system's roots, e.g. for an internal CA.
+            if let Some(path) = setting("HTTP_CLIENT_CA_FILE") {
+                let pem = std::fs::read(&path).map_err(|e| format!("cannot read HTTP_CLIENT_CA_FILE `{}`: {}", path, e))?;
+                for certificate in reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| format!("invalid HTTP_CLIENT_CA_FILE `{}`: {}", path, e))? {
+                    builder = builder.add_root_certificate(certificate);
+                }
+            }
+            builder.build().map_err(|e| e.to_string())
+        }
+
+        fn init_http_client() -> HookFuture {
+            Box::pin(async {
+                let _ = HTTP_CLIENT.set(HttpClient(build_http_client()?));
+                Ok(())
+            })
+        }
+
+        fn check_http_client_settings() -> Vec<String> {
+            build_http_client().err().into_iter().collect()
+        }
+
+        ::inventory::submit! {
+            StartupCheck {
+                name: "http_client",
+                check: check_http_client_settings,
+            }
+        }
+
+        // Built before the user's resources, which may wrap it.
+        ::inventory::submit! {
+            ResourceRecipe {
+                name: "http_client",
+                type_name: "HttpClient",
+                order: i32::MIN,
+                init: init_http_client,
+            }
+        }
+    }
+}
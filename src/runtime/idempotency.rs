@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the `Idempotency-Key` middleware: the first successful response per (connector, key) is kept for
+/// `ttl` in a store bounded to `capacity` entries and replayed for duplicates.
+///
+/// A duplicate arriving while the first request is still running gets `409 Conflict` instead of running twice. Keys
+/// still running are never evicted, a store full of them answers `503 Service Unavailable`. Streams and bodies over
+/// `MAX_STORED_BODY` pass through unstored.
+pub fn expand(ttl: Duration, capacity: usize) -> TokenStream {
+    let ttl_ms = ttl.as_millis() as u64;
+    quote! {
+        /// The largest response body kept for replay, larger ones are passed through and not deduplicated.
+        const MAX_STORED_BODY: usize = 1 << 20;
+
+        enum IdempotentEntry {
+            InFlight,
+            Done {
+                at: std::time::Instant,
+                status: axum::http::StatusCode,
+                headers: axum::http::HeaderMap,
+                body: axum::body::Bytes,
+            },
+        }
+
+        #[derive(Default)]
+        struct IdempotencyStore {
+            /// Each entry with its position in `done`, when it is done.
+            entries: std::collections::HashMap<(String, String), (u64, IdempotentEntry)>,
+            /// The done entries by completion order, oldest first, to evict when the store is full.
+            done: std::collections::BTreeMap<u64, (String, String)>,
+            next: u64,
+        }
+
+        impl IdempotencyStore {
+            fn forget(&mut self, key: &(String, String)) {
+                if let Some((seq, IdempotentEntry::Done { .. })) = self.entries.remove(key) {
+                    self.done.remove(&seq);
+                }
+            }
+
+            /// Marks `key` as running, first evicting the oldest done entry when the store is full. Fails when every
+            /// entry is still running, as evicting one would let its duplicates run.
+            fn start(&mut self, key: (String, String), capacity: usize) -> bool {
+                self.forget(&key);
+                if self.entries.len() >= capacity {
+                    match self.done.pop_first() {
+                        Some((_, oldest)) => {
+                            self.entries.remove(&oldest);
+                        }
+                        None => return false,
+                    }
+                }
+                self.entries.insert(key, (0, IdempotentEntry::InFlight));
+                true
+            }
+
+            /// Stores the response of a request still marked as running.
+            fn finish(&mut self, key: &(String, String), entry: IdempotentEntry) {
+                if let Some(stored) = self.entries.get_mut(key).filter(|(_, entry)| matches!(entry, IdempotentEntry::InFlight)) {
+                    self.next += 1;
+                    *stored = (self.next, entry);
+                    self.done.insert(self.next, key.clone());
+                }
+            }
+        }
+
+        /// Releases the `InFlight` marker if the request ends without a stored response (failure, client gone).
+        struct InFlightGuard((String, String));
+
+        impl Drop for InFlightGuard {
+            fn drop(&mut self) {
+                let mut store = idempotency_store().lock().unwrap_or_else(|e| e.into_inner());
+                if matches!(store.entries.get(&self.0), Some((_, IdempotentEntry::InFlight))) {
+                    store.forget(&self.0);
+                }
+            }
+        }
+
+        fn idempotency_store() -> &'static std::sync::Mutex<IdempotencyStore> {
+            static STORE: std::sync::OnceLock<std::sync::Mutex<IdempotencyStore>> = std::sync::OnceLock::new();
+            STORE.get_or_init(Default::default)
+        }
+
+        /// Whether `response` can be kept for replay: a single body of known size, at most `MAX_STORED_BODY` long.
+        /// Streams have no size, and may never end.
+        fn storable(response: &axum::response::Response) -> bool {
+            use axum::body::HttpBody;
+            let stream = response
+                .headers()
+                .get(axum::http::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|ty| ty.starts_with("text/event-stream") || ty.starts_with("application/x-ndjson"));
+            !stream && response.body().size_hint().exact().is_some_and(|len| len <= MAX_STORED_BODY as u64)
+        }
+
+        /// Keyed by the path, which names the connector, as connectors with their own layer have static routes.
+        async fn idempotency(
+            request: axum::extract::Request,
+            next: axum::middleware::Next,
+            ) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            let ttl = std::time::Duration::from_millis(#ttl_ms);
+
+            let Some(key) = request.headers().get("idempotency-key").and_then(|v| v.to_str().ok()) else {
+                return next.run(request).await;
+            };
+            let key = (request.uri().path().to_string(), key.to_string());
+
+            {
+                let mut store = idempotency_store().lock().unwrap();
+                match store.entries.get(&key) {
+                    Some((_, IdempotentEntry::InFlight)) => {
+                        return (axum::http::StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress").into_response();
+                    }
+                    Some((_, IdempotentEntry::Done { at, status, headers, body })) if at.elapsed() < ttl => {
+                        let mut response = (*status, body.clone()).into_response();
+                        response.headers_mut().extend(headers.clone());
+                        response.headers_mut().insert("idempotent-replayed", axum::http::HeaderValue::from_static("true"));
+                        return response;
+                    }
+                    _ => {}
+                }
+                if !store.start(key.clone(), #capacity) {
+                    return (
+                        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                        [(axum::http::header::RETRY_AFTER, "1")],
+                        "Too many requests with an Idempotency-Key are in progress",
+                    )
+                        .into_response();
+                }
+            }
+            let guard = InFlightGuard(key);
+
+            let response = next.run(request).await;
+            // Only successes are replayed, a failed attempt may be retried with the same key.
+            if !response.status().is_success() || !storable(&response) {
+                return response;
+            }
+            let (mut parts, body) = response.into_parts();
+            let body = match axum::body::to_bytes(body, MAX_STORED_BODY).await {
+                Ok(body) => body,
+                Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            };
+            parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+            idempotency_store().lock().unwrap().finish(&guard.0, IdempotentEntry::Done {
+                at: std::time::Instant::now(),
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+            });
+            drop(guard);
+            axum::response::Response::from_parts(parts, axum::body::Body::from(body))
+        }
+    }
+}
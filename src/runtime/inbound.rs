@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `start_inbound`, which hands every inbound handler to the consumer of its source, and `run_inbound`,
+/// which consumers run messages through.
+///
+/// `consumers` are the match arms of the sources enabled in `connector_main!`; handlers of other sources are
+/// reported at startup rather than silently never called. With `zeebe_client`, `run_inbound` publishes the output of
+/// handlers declaring a `message`; without it, those handlers are reported at startup as well. With `dedup`, it skips
+/// the messages already handled, as told by the dedup store of `inbound_dedup`; without it, handlers declaring a
+/// `dedup_id` are reported.
+pub fn expand(consumers: &[TokenStream], zeebe_client: bool, dedup: bool) -> TokenStream {
+    let (check_correlation, publish) = if zeebe_client {
+        let publish = quote! {
+            match &r.correlation {
+                Some(correlation) => publish_inbound(r, correlation, output).await,
+                None => Ok(output),
+            }
+        };
+        (quote! {}, publish)
+    } else {
+        let check = quote! {
+            if r.correlation.is_some() {
+                tracing::error!(handler = r.name, "inbound handler publishes a message, which requires `zeebe_client = true` in `connector_main!`");
+            }
+        };
+        (check, quote! { Ok(output) })
+    };
+    let (check_dedup, run) = if dedup {
+        let run = quote! {
+            let Some(id) = inbound_dedup_id(r, &body, source_id)? else {
+                return handle_inbound(r, body, ctx).await;
+            };
+            match inbound_dedup_claim(r.name, &id).await {
+                Ok(DedupClaim::New) => {}
+                Ok(DedupClaim::Done) => {
+                    tracing::debug!(handler = r.name, dedup_id = id, "skipped a redelivered message");
+                    return Ok(serde_json::Value::Null);
+                }
+                Ok(DedupClaim::InFlight) => {
+                    return Err(connectors::HandlerError::retriable(format!("Message `{}` is already being handled", id)));
+                }
+                Err(e) => {
+                    return Err(connectors::HandlerError::retriable(format!("Cannot check message `{}` for duplicates: {}", id, e)));
+                }
+            }
+            let result = handle_inbound(r, body, ctx).await;
+            // A failed message may be handled again when it is redelivered.
+            let settled = match &result {
+                Ok(_) => inbound_dedup_complete(r.name, &id).await,
+                Err(_) => inbound_dedup_release(r.name, &id).await,
+            };
+            if let Err(e) = settled {
+                tracing::warn!(handler = r.name, dedup_id = id, "cannot record the message as handled: {}", e);
+            }
+            result
+        };
+        (quote! {}, run)
+    } else {
+        let check = quote! {
+            if r.dedup_id.is_some() {
+                tracing::error!(handler = r.name, "inbound handler declares a `dedup_id`, which requires `inbound_dedup` in `connector_main!`");
+            }
+        };
+        (check, quote! { let _ = source_id; handle_inbound(r, body, ctx).await })
+    };
+    let dedup_id = dedup.then(|| {
+        quote! {
+            /// The id telling the message's redeliveries apart: its `dedup_id` evaluated against it, or the id its
+            /// source gave it. Messages without one are not deduplicated.
+            fn inbound_dedup_id(
+                r: &'static connectors::InboundRecipe,
+                body: &[u8],
+                source_id: Option<String>,
+            ) -> Result<Option<String>, connectors::HandlerError> {
+                let Some(expression) = r.dedup_id else { return Ok(source_id) };
+                let message: serde_json::Value = serde_json::from_slice(body)
+                    .map_err(|e| connectors::HandlerError::fatal(format!("Bad message for `{}`: {}", r.name, e)))?;
+                match connectors::feel::evaluate(expression, &message) {
+                    Ok(serde_json::Value::String(id)) => Ok(Some(id)),
+                    Ok(id @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => Ok(Some(id.to_string())),
+                    Ok(serde_json::Value::Null) => Ok(None),
+                    Ok(id) => Err(connectors::HandlerError::fatal(format!("`dedup_id` of `{}` must be a string or a number, got `{}`", r.name, id))),
+                    Err(e) => Err(connectors::HandlerError::fatal(format!("Bad `dedup_id` for `{}`: {}", r.name, e))),
+                }
+            }
+        }
+    });
+    let publish_inbound = zeebe_client.then(|| {
+        quote! {
+            /// Publishes `output` as the handler's message, keyed by the correlation key and message id evaluated
+            /// against it. A message whose id is already buffered was published by an earlier delivery.
+            async fn publish_inbound(
+                r: &'static connectors::InboundRecipe,
+                correlation: &'static connectors::InboundCorrelation,
+                output: serde_json::Value,
+            ) -> Result<serde_json::Value, connectors::HandlerError> {
+                let evaluate = |what: &str, expression: &str| match connectors::feel::evaluate(expression, &output) {
+                    Ok(serde_json::Value::String(value)) => Ok(value),
+                    Ok(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => Ok(value.to_string()),
+                    Ok(value) => Err(connectors::HandlerError::fatal(format!("`{}` of `{}` must be a string or a number, got `{}`", what, r.name, value))),
+                    Err(e) => Err(connectors::HandlerError::fatal(format!("Bad `{}` for `{}`: {}", what, r.name, e))),
+                };
+                let correlation_key = evaluate("correlation_key", correlation.correlation_key)?;
+                let message_id = correlation.message_id.map(|expression| evaluate("message_id", expression)).transpose()?;
+                let published = connectors::zeebe::client()
+                    .publish_message(correlation.message, &correlation_key, &output, correlation.ttl, message_id.as_deref())
+                    .await;
+                match published {
+                    Ok(_) => {}
+                    Err(e) if e.code == connectors::zeebe::ZeebeError::ALREADY_EXISTS && message_id.is_some() => {
+                        tracing::debug!(handler = r.name, message_id, "message was already published");
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                Ok(output)
+            }
+        }
+    });
+    quote! {
+        fn start_inbound() {
+            for r in ::inventory::iter::<crate::connectors::InboundRecipe> {
+                #check_correlation
+                #check_dedup
+                #[allow(unreachable_patterns)]
+                match &r.source {
+                    #(#consumers)*
+                    source => tracing::error!(
+                        handler = r.name,
+                        "inbound handler is never called, enable its source with `{} = true` in `connector_main!`",
+                        source.option()
+                    ),
+                }
+            }
+        }
+
+        /// Runs a message through its handler; `source_id` is the id the message's source gave it, if any.
+        async fn run_inbound(
+            r: &'static connectors::InboundRecipe,
+            body: axum::body::Bytes,
+            ctx: connectors::Context,
+            source_id: Option<String>,
+            ) -> Result<serde_json::Value, connectors::HandlerError> {
+            #run
+        }
+
+        /// Runs the handler on its own task, so that a panic fails the message rather than the consumer.
+        async fn handle_inbound(
+            r: &'static connectors::InboundRecipe,
+            body: axum::body::Bytes,
+            ctx: connectors::Context,
+            ) -> Result<serde_json::Value, connectors::HandlerError> {
+            let output = match tokio::spawn((r.exec_raw)(body, ctx)).await {
+                Ok(result) => result?,
+                Err(_) => return Err(connectors::HandlerError::fatal(format!("Inbound handler `{}` panicked", r.name))),
+            };
+            #publish
+        }
+
+        #dedup_id
+
+        #publish_inbound
+    }
+}
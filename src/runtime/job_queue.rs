@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the durable job queue backing asynchronous executions, stored in an embedded sled database.
+///
+/// Jobs are written before the `202` is returned and removed only once their callback is acknowledged, so they
+/// survive restarts with at-least-once execution. At most `concurrency` jobs run at once. A claimed job stays invisible
+/// for `visibility_timeout`, renewed while it runs; if the process dies meanwhile, it is picked up again afterwards.
+/// With `dead_letters`, a job out of attempts is moved to the dead-letter sink rather than kept as failed.
+pub fn expand(visibility_timeout: Duration, max_attempts: u32, concurrency: usize, dead_letters: bool) -> TokenStream {
+    let visibility_ms = visibility_timeout.as_millis() as u64;
+    let renew_ms = (visibility_ms / 2).max(1);
+    let mut exhausted = quote! {};
+    if dead_letters {
+        exhausted = quote! {
+            if dead_letter(&queued.job, queued.history.clone()).await {
+                let _ = queue.remove(&key);
+                let _ = queue.flush_async().await;
+                return;
+            }
+        };
+    }
+    quote! {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct QueuedJob {
+            job: AsyncJob,
+            attempts: u32,
+            /// Epoch millis before which the job must not be picked up.
+            visible_at: u64,
+            failed: bool,
+            last_error: Option<String>,
+            /// Oldest first; jobs queued by earlier versions have none.
+            #[serde(default)]
+            history: Vec<JobAttempt>,
+        }
+
+        static JOB_QUEUE: std::sync::OnceLock<(sled::Tree, tokio::sync::Notify)> = std::sync::OnceLock::new();
+
+        /// Keys of the jobs this process runs, which the worker must not claim again, even once their lease is due.
+        fn running_jobs() -> &'static std::sync::Mutex<std::collections::HashSet<sled::IVec>> {
+            static RUNNING: std::sync::OnceLock<std::sync::Mutex<std::collections::HashSet<sled::IVec>>> = std::sync::OnceLock::new();
+            RUNNING.get_or_init(Default::default)
+        }
+
+        /// Unmarks a job as running once it is done with, and wakes the worker to take its slot.
+        struct RunningJob(sled::IVec);
+
+        impl Drop for RunningJob {
+            fn drop(&mut self) {
+                running_jobs().lock().unwrap_or_else(|e| e.into_inner()).remove(&self.0);
+                if let Some((_, notify)) = JOB_QUEUE.get() {
+                    notify.notify_one();
+                }
+            }
+        }
+
+        fn epoch_millis() -> u64 {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        }
+
+        fn open_job_queue(path: &str) {
+            let db = sled::open(path).unwrap_or_else(|e| panic!("cannot open job queue at `{}`: {}", path, e));
+            let tree = db.open_tree("jobs").expect("cannot open job queue tree");
+            for queued in tree.iter().values().flatten().filter_map(|value| serde_json::from_slice::<QueuedJob>(&value).ok()) {
+                if !queued.failed {
+                    gauge(Gauge::Queued, &queued.job.name).fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+            if JOB_QUEUE.set((tree, tokio::sync::Notify::new())).is_ok() {
+                tokio::spawn(job_queue_worker());
+            }
+        }
+
+        fn enqueue_async_job(job: AsyncJob) -> Result<(), String> {
+            let (queue, notify) = JOB_QUEUE.get().ok_or("job queue is not open")?;
+            let queued = QueuedJob { job, attempts: 0, visible_at: 0, failed: false, last_error: None, history: Vec::new() };
+            let value = serde_json::to_vec(&queued).map_err(|e| e.to_string())?;
+            queue.insert(queued.job.token.as_bytes(), value).map_err(|e| e.to_string())?;
+            queue.flush().map_err(|e| e.to_string())?;
+            gauge(Gauge::Queued, &queued.job.name).fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            notify.notify_one();
+            Ok(())
+        }
+
+        async fn job_queue_worker() {
+            let (queue, notify) = JOB_QUEUE.get().expect("job queue is open");
+            let slots = std::sync::Arc::new(tokio::sync::Semaphore::new(#concurrency));
+            loop {
+                let now = epoch_millis();
+                for (key, value) in queue.iter().flatten() {
+                    let Ok(mut queued) = serde_json::from_slice::<QueuedJob>(&value) else { continue };
+                    if queued.failed || queued.visible_at > now || running_jobs().lock().unwrap().contains(&key) {
+                        continue;
+                    }
+                    // Jobs past the limit stay queued until a running one finishes.
+                    let Ok(slot) = slots.clone().try_acquire_owned() else { break };
+                    queued.attempts += 1;
+                    queued.visible_at = now + #visibility_ms;
+                    if let Ok(value) = serde_json::to_vec(&queued) {
+                        if queue.insert(&key, value).is_ok() {
+                            running_jobs().lock().unwrap().insert(key.clone());
+                            let running = RunningJob(key.clone());
+                            tokio::spawn(async move {
+                                process_queued_job(key, queued).await;
+                                drop((running, slot));
+                            });
+                        }
+                    }
+                }
+                tokio::select! {
+                    _ = notify.notified() => {}
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                }
+            }
+        }
+
+        async fn process_queued_job(key: sled::IVec, mut queued: QueuedJob) {
+            let (queue, _) = JOB_QUEUE.get().expect("job queue is open");
+            let queued_gauge = gauge(Gauge::Queued, &queued.job.name);
+            let delivered = || queued_gauge.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            // The lease is renewed while the job runs, so that a slow handler or callback isn't taken for a crash.
+            let run = run_async_job(queued.job.clone());
+            tokio::pin!(run);
+            let mut renew = tokio::time::interval(std::time::Duration::from_millis(#renew_ms));
+            renew.tick().await;
+            let result = loop {
+                tokio::select! {
+                    result = &mut run => break result,
+                    _ = renew.tick() => {
+                        queued.visible_at = epoch_millis() + #visibility_ms;
+                        if let Ok(value) = serde_json::to_vec(&queued) {
+                            let _ = queue.insert(&key, value);
+                        }
+                    }
+                }
+            };
+            if let Err(attempt) = result {
+                queued.last_error = Some(attempt.delivery_error.clone());
+                queued.history.push(attempt);
+                if queued.attempts >= #max_attempts {
+                    delivered();
+                    #exhausted
+                    queued.failed = true;
+                } else {
+                    let backoff = 1000u64 << queued.attempts.min(10);
+                    queued.visible_at = epoch_millis() + backoff;
+                }
+                if let Ok(value) = serde_json::to_vec(&queued) {
+                    let _ = queue.insert(&key, value);
+                }
+            } else {
+                delivered();
+                let _ = queue.remove(&key);
+            }
+            let _ = queue.flush_async().await;
+        }
+    }
+}
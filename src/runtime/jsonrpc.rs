@@ -0,0 +1,91 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the JSON-RPC 2.0 `/rpc` handler, where `method` is `<name>.<operation>` and `params` is the input.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn jsonrpc_error(id: serde_json::Value, code: i64, message: String) -> serde_json::Value {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "error": { "code": code, "message": message },
+                "id": id,
+            })
+        }
+
+        /// Runs a single call; notifications (no `id`) are executed but produce no reply.
+        async fn jsonrpc_call(call: serde_json::Value, ctx: connectors::Context) -> Option<serde_json::Value> {
+            let id = call.get("id").cloned();
+            let reply_id = id.clone().unwrap_or(serde_json::Value::Null);
+            let method = match (call.get("jsonrpc").and_then(|v| v.as_str()), call.get("method").and_then(|v| v.as_str())) {
+                (Some("2.0"), Some(method)) => method.to_string(),
+                _ => return Some(jsonrpc_error(reply_id, -32600, "Invalid Request".to_string())),
+            };
+            let Some((name, operation)) = method.rsplit_once('.') else {
+                return id.map(|id| jsonrpc_error(id, -32601, format!("Method not found: `{}`", method)));
+            };
+
+            // Passed as sent, for handlers to type; see `connectors::envelope_id`.
+            let envelope = serde_json::json!({
+                "id": reply_id,
+                "params": {
+                    "operation": operation,
+                    "input": call.get("params").cloned().unwrap_or(serde_json::Value::Null),
+                },
+            });
+            let envelope = match serde_json::to_vec(&envelope) {
+                Ok(bytes) => bytes,
+                Err(e) => return id.map(|id| jsonrpc_error(id, -32603, e.to_string())),
+            };
+
+            let result = execute(name.to_string(), operation.to_string(), envelope.into(), connectors::Format::Json, ctx)
+                .await
+                .and_then(|out| out.into_value().map_err(connectors::ExecError::Handler));
+            let id = id?;
+            Some(match result {
+                Ok(value) => serde_json::json!({ "jsonrpc": "2.0", "result": value, "id": id }),
+                Err(e) => {
+                    let code = match e {
+                        connectors::ExecError::Unsupported(_) => -32601,
+                        connectors::ExecError::Input(_) => -32602,
+                        _ => -32000,
+                    };
+                    let mut data = e.retry_metadata().unwrap_or_else(|| serde_json::json!({}));
+                    data["retriable"] = e.retriable().into();
+                    let mut reply = jsonrpc_error(id, code, e.to_string());
+                    reply["error"]["data"] = data;
+                    reply
+                }
+            })
+        }
+
+        async fn dispatch_jsonrpc(
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+            ) -> axum::response::Response {
+            // The caller's request id and deadline cover every call of a batch, each one cancelled on its own.
+            let ctx = match connectors::Context::from_headers(&headers) {
+                Ok(ctx) => ctx.with_client(client),
+                Err(e) => return axum::response::IntoResponse::into_response((axum::http::StatusCode::BAD_REQUEST, e)),
+            };
+            let reply = match serde_json::from_slice::<serde_json::Value>(&body) {
+                Err(_) => Some(jsonrpc_error(serde_json::Value::Null, -32700, "Parse error".to_string())),
+                Ok(serde_json::Value::Array(calls)) if calls.is_empty() => {
+                    Some(jsonrpc_error(serde_json::Value::Null, -32600, "Invalid Request".to_string()))
+                }
+                Ok(serde_json::Value::Array(calls)) => {
+                    let mut replies = Vec::new();
+                    for call in calls {
+                        replies.extend(jsonrpc_call(call, ctx.fork()).await);
+                    }
+                    (!replies.is_empty()).then_some(serde_json::Value::Array(replies))
+                }
+                Ok(call) => jsonrpc_call(call, ctx).await,
+            };
+            match reply {
+                Some(reply) => axum::response::IntoResponse::into_response(axum::Json(reply)),
+                None => axum::response::IntoResponse::into_response(axum::http::StatusCode::NO_CONTENT),
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::net::SocketAddr;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// A socket the generated router is served on.
+pub enum Listener {
+    Tcp {
+        addr: SocketAddr,
+        tls: Option<(LitStr, LitStr)>,
+        http2: bool,
+        proxy_protocol: bool,
+        reuse_port: bool,
+    },
+    Unix(LitStr),
+}
+
+impl Listener {
+    /// Parses a `listeners` entry: `"http://<addr>:<port>"`, `"https://<addr>:<port>"` or `"unix:<path>"`.
+    pub fn parse(lit: &LitStr, tls: Option<&(LitStr, LitStr)>, http2: bool, proxy_protocol: bool, reuse_port: bool) -> syn::Result<Self> {
+        let value = lit.value();
+        if let Some(path) = value.strip_prefix("unix:") {
+            return Ok(Listener::Unix(LitStr::new(path, lit.span())));
+        }
+        let (tls, addr) = if let Some(addr) = value.strip_prefix("https://") {
+            let tls = tls.ok_or_else(|| Error::new_spanned(lit, "https listeners require `tls_cert` and `tls_key`"))?;
+            (Some(tls.clone()), addr)
+        } else if let Some(addr) = value.strip_prefix("http://") {
+            (None, addr)
+        } else {
+            return Err(Error::new_spanned(lit, "Expected a listener such as \"http://127.0.0.1:8080\", \"https://0.0.0.0:8443\" or \"unix:/run/connector.sock\""));
+        };
+        let addr = addr
+            .parse()
+            .map_err(|_| Error::new_spanned(lit, "Expected `<ip>:<port>` after the scheme"))?;
+        Ok(Listener::Tcp { addr, tls, http2, proxy_protocol, reuse_port })
+    }
+}
+
+/// Binds `addr` into a non-blocking `std::net::TcpListener` named `listener`.
+///
+/// The IPv6 wildcard `::` is bound dual-stack, accepting IPv4 clients too whatever the host's `bindv6only`
+/// default, and `reuse_port` sets `SO_REUSEPORT`; both take `socket2`.
+fn bind_tcp(addr: &SocketAddr, reuse_port: bool) -> TokenStream {
+    let addr_str = addr.to_string();
+    let dual_stack = addr.is_ipv6() && addr.ip().is_unspecified();
+    let bind = if dual_stack || reuse_port {
+        let only_v6 = dual_stack.then(|| quote! { socket.set_only_v6(false)?; });
+        let reuse_port = reuse_port.then(|| quote! { socket.set_reuse_port(true)?; });
+        quote! {
+            let socket = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::STREAM, Some(socket2::Protocol::TCP))?;
+            #only_v6
+            #reuse_port
+            socket.set_reuse_address(true)?;
+            socket.bind(&addr.into())?;
+            socket.listen(1024)?;
+            let listener: std::net::TcpListener = socket.into();
+        }
+    } else {
+        quote! { let listener = std::net::TcpListener::bind(addr)?; }
+    };
+    quote! {
+        let addr: ::std::net::SocketAddr = #addr_str.parse().unwrap();
+        let listener = (|| -> std::io::Result<std::net::TcpListener> {
+            #bind
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        })()
+        .unwrap_or_else(|e| panic!("Failed to bind {}: {}", addr, e));
+    }
+}
+
+fn serve(listener: &Listener) -> TokenStream {
+    match listener {
+        // TLS negotiates HTTP/2 via ALPN; in cleartext, `http2` additionally accepts h2c with prior knowledge.
+        Listener::Tcp { addr, tls, http2, proxy_protocol, reuse_port } => {
+            let bind = bind_tcp(addr, *reuse_port);
+            if *proxy_protocol {
+                return quote! {
+                    #bind
+                    let listener = ProxyListener::new(tokio::net::TcpListener::from_std(listener).unwrap());
+                    tracing::info!("🚀 Listening on {addr} (PROXY protocol)");
+                    axum::serve(listener, app.into_make_service_with_connect_info::<connectors::ClientAddr>())
+                        .with_graceful_shutdown(shutdown_requested())
+                        .await
+                        .unwrap();
+                };
+            }
+            match (tls, http2) {
+                (Some((cert, key)), _) => quote! {
+                    #bind
+                    let tls = axum_server::tls_rustls::RustlsConfig::from_pem_file(#cert, #key)
+                        .await
+                        .unwrap_or_else(|e| panic!("Failed to load TLS certificate `{}` and key `{}`: {}", #cert, #key, e));
+                    tracing::info!("🚀 Listening on https://{addr}");
+                    axum_server::from_tcp_rustls(listener, tls)
+                        .handle(graceful_handle())
+                        .serve(app.into_make_service_with_connect_info::<connectors::ClientAddr>())
+                        .await
+                        .unwrap();
+                },
+                (None, true) => quote! {
+                    #bind
+                    tracing::info!("🚀 Listening on {addr} (HTTP/1.1 and h2c)");
+                    axum_server::from_tcp(listener)
+                        .handle(graceful_handle())
+                        .serve(app.into_make_service_with_connect_info::<connectors::ClientAddr>())
+                        .await
+                        .unwrap();
+                },
+                (None, false) => quote! {
+                    #bind
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    tracing::info!("🚀 Listening on {addr}");
+                    axum::serve(listener, app.into_make_service_with_connect_info::<connectors::ClientAddr>())
+                        .with_graceful_shutdown(shutdown_requested())
+                        .await
+                        .unwrap();
+                },
+            }
+        }
+        Listener::Unix(path) => quote! {
+            // A socket file left over by a previous run would make the bind fail; anything else at the path is kept.
+            match std::fs::symlink_metadata(#path) {
+                Ok(meta) if std::os::unix::fs::FileTypeExt::is_socket(&meta.file_type()) => {
+                    std::fs::remove_file(#path)
+                        .unwrap_or_else(|e| panic!("Failed to remove stale Unix socket `{}`: {}", #path, e));
+                }
+                Ok(_) => panic!("Failed to bind Unix socket `{}`: the path exists and is not a socket", #path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => panic!("Failed to inspect Unix socket path `{}`: {}", #path, e),
+            }
+            let listener = tokio::net::UnixListener::bind(#path)
+                .unwrap_or_else(|e| panic!("Failed to bind Unix socket `{}`: {}", #path, e));
+            tracing::info!("🚀 Listening on unix:{}", #path);
+            axum::serve(listener, app.into_make_service()).with_graceful_shutdown(shutdown_requested()).await.unwrap();
+        },
+    }
+}
+
+/// Generates `ProxyListener`, which strips the HAProxy PROXY protocol (v1 or v2) preamble off each connection
+/// and reports the client address it carries instead of the load balancer's.
+fn proxy_listener() -> TokenStream {
+    quote! {
+        struct ProxyListener {
+            connections: tokio::sync::mpsc::Receiver<(tokio::net::TcpStream, std::net::SocketAddr)>,
+            local_addr: std::net::SocketAddr,
+        }
+
+        impl ProxyListener {
+            fn new(listener: tokio::net::TcpListener) -> Self {
+                let local_addr = listener.local_addr().unwrap();
+                let (tx, connections) = tokio::sync::mpsc::channel(1024);
+                tokio::spawn(async move {
+                    loop {
+                        let (mut stream, peer) = match listener.accept().await {
+                            Ok(accepted) => accepted,
+                            Err(e) => {
+                                tracing::warn!("accept failed on {}: {}", local_addr, e);
+                                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                                continue;
+                            }
+                        };
+                        // Preambles are read off the accept loop, so a slow client can't hold up the others.
+                        let tx = tx.clone();
+                        tokio::spawn(async move {
+                            let header = tokio::time::timeout(std::time::Duration::from_secs(5), read_proxy_header(&mut stream)).await;
+                            match header {
+                                Ok(Ok(client)) => {
+                                    let _ = tx.send((stream, client.unwrap_or(peer))).await;
+                                }
+                                Ok(Err(e)) => tracing::warn!("rejected connection from {}: {}", peer, e),
+                                Err(_) => tracing::warn!("rejected connection from {}: no PROXY header within 5s", peer),
+                            }
+                        });
+                    }
+                });
+                ProxyListener { connections, local_addr }
+            }
+        }
+
+        impl axum::serve::Listener for ProxyListener {
+            type Io = tokio::net::TcpStream;
+            type Addr = std::net::SocketAddr;
+
+            async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+                match self.connections.recv().await {
+                    Some(connection) => connection,
+                    None => std::future::pending().await,
+                }
+            }
+
+            fn local_addr(&self) -> std::io::Result<Self::Addr> {
+                Ok(self.local_addr)
+            }
+        }
+
+        impl axum::extract::connect_info::Connected<axum::serve::IncomingStream<'_, ProxyListener>> for connectors::ClientAddr {
+            fn connect_info(stream: axum::serve::IncomingStream<'_, ProxyListener>) -> Self {
+                connectors::ClientAddr(*stream.remote_addr())
+            }
+        }
+
+        /// Reads a PROXY protocol header, consuming nothing past it. `None` means the connection is the load
+        /// balancer's own (v2 `LOCAL`, v1 `UNKNOWN`), so the peer address stands.
+        async fn read_proxy_header(stream: &mut tokio::net::TcpStream) -> std::io::Result<Option<std::net::SocketAddr>> {
+            use tokio::io::AsyncReadExt;
+            const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+            let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+
+            let mut head = [0u8; 5];
+            stream.read_exact(&mut head).await?;
+            if &head == b"PROXY" {
+                // v1: a text line of at most 107 bytes, read byte by byte so the HTTP request stays unread.
+                let mut line = head.to_vec();
+                while !line.ends_with(b"\r\n") {
+                    if line.len() >= 107 {
+                        return Err(invalid("PROXY v1 header too long"));
+                    }
+                    line.push(stream.read_u8().await?);
+                }
+                let line = std::str::from_utf8(&line).map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+                let fields: Vec<&str> = line.trim_end().split(' ').collect();
+                return match fields.as_slice() {
+                    ["PROXY", "UNKNOWN", ..] => Ok(None),
+                    ["PROXY", "TCP4" | "TCP6", src, _dst, src_port, _dst_port] => {
+                        let ip: std::net::IpAddr = src.parse().map_err(|_| invalid("bad PROXY v1 source address"))?;
+                        let port: u16 = src_port.parse().map_err(|_| invalid("bad PROXY v1 source port"))?;
+                        Ok(Some(std::net::SocketAddr::new(ip, port)))
+                    }
+                    _ => Err(invalid("malformed PROXY v1 header")),
+                };
+            }
+
+            let mut fixed = [0u8; 16];
+            fixed[..5].copy_from_slice(&head);
+            stream.read_exact(&mut fixed[5..]).await?;
+            if fixed[..12] != V2_SIGNATURE || fixed[12] >> 4 != 2 {
+                return Err(invalid("missing PROXY protocol header"));
+            }
+            let mut payload = vec![0u8; u16::from_be_bytes([fixed[14], fixed[15]]) as usize];
+            stream.read_exact(&mut payload).await?;
+            let port = |at: usize| u16::from_be_bytes([payload[at], payload[at + 1]]);
+            match (fixed[12] & 0x0f, fixed[13] >> 4) {
+                // LOCAL command: health checks from the load balancer itself.
+                (0x0, _) => Ok(None),
+                (0x1, 0x1) if payload.len() >= 12 => {
+                    let ip: [u8; 4] = payload[..4].try_into().unwrap();
+                    Ok(Some(std::net::SocketAddr::new(ip.into(), port(8))))
+                }
+                (0x1, 0x2) if payload.len() >= 36 => {
+                    let ip: [u8; 16] = payload[..16].try_into().unwrap();
+                    Ok(Some(std::net::SocketAddr::new(ip.into(), port(32))))
+                }
+                (0x1, _) => Ok(None),
+                _ => Err(invalid("unknown PROXY v2 command")),
+            }
+        }
+    }
+}
+
+/// Items the listeners need next to `main`.
+pub fn items(listeners: &[Listener]) -> TokenStream {
+    let proxy = listeners.iter().any(|l| matches!(l, Listener::Tcp { proxy_protocol: true, .. })).then(proxy_listener);
+    let axum_server = listeners.iter().any(|l| {
+        matches!(l, Listener::Tcp { tls, http2, proxy_protocol: false, .. } if tls.is_some() || *http2)
+    });
+    // axum_server listeners are drained through a handle rather than a future.
+    let graceful_handle = axum_server.then(|| {
+        quote! {
+            fn graceful_handle() -> axum_server::Handle {
+                let handle = axum_server::Handle::new();
+                tokio::spawn({
+                    let handle = handle.clone();
+                    async move {
+                        shutdown_requested().await;
+                        handle.graceful_shutdown(None);
+                    }
+                });
+                handle
+            }
+        }
+    });
+    quote! {
+        #proxy
+        #graceful_handle
+    }
+}
+
+/// Serves `app` on every listener at once until the shutdown has drained them; the process stops if any of them
+/// fails.
+pub fn expand(listeners: &[Listener]) -> TokenStream {
+    let servers = listeners.iter().map(serve);
+    quote! {
+        let mut servers = tokio::task::JoinSet::new();
+        #(
+            servers.spawn({
+                let app = app.clone();
+                async move { #servers }
+            });
+        )*
+        tokio::select! {
+            _ = async {
+                while let Some(result) = servers.join_next().await {
+                    result.unwrap();
+                }
+            } => {}
+            _ = drain_expired() => {
+                tracing::warn!("connections still open after the drain timeout, closing them");
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The formatting layer of the subscriber, writing through `writer`: the default human-readable lines, or with
+/// `json` one JSON object per line, the event's fields at the top level and those of its innermost span under `span`.
+pub fn layer(json: bool, writer: &TokenStream) -> TokenStream {
+    let json = match json {
+        true => quote! { .json().flatten_event(true).with_current_span(true).with_span_list(false) },
+        false => quote! {},
+    };
+    quote! { tracing_subscriber::fmt::layer() #json .with_writer(#writer) }
+}
+
+/// Subscriber setup replacing `tracing_subscriber::fmt::init()`, with the same `RUST_LOG` filter, formatted by
+/// `layer`. With `log_levels`, the filter is a `LogFilter` taking the `LOG_LEVELS` of the environment. A
+/// `reloadable` filter is held in `LOG_FILTER`, for `reload_config` and the admin API to swap.
+pub fn log_init(layer: &TokenStream, log_levels: bool, reloadable: bool) -> TokenStream {
+    let mut filter = quote! { tracing_subscriber::EnvFilter::from_default_env() };
+    if reloadable {
+        filter = quote! { log_filter(&std::env::var("RUST_LOG").unwrap_or_default()).unwrap_or_else(|_| log_filter("").unwrap()) };
+    }
+    if log_levels {
+        filter = quote! { LogFilter::new(#filter, parse_log_levels(&std::env::var("LOG_LEVELS").unwrap_or_default()).unwrap_or_default()) };
+    }
+    let mut install = quote! { tracing_subscriber::registry().with(#filter).with(#layer).init(); };
+    if reloadable {
+        install = quote! {
+            let (filter, handle) = tracing_subscriber::reload::Layer::new(#filter);
+            tracing_subscriber::registry().with(filter).with(#layer).init();
+            let _ = LOG_FILTER.set(handle);
+        };
+    }
+    quote! {
+        {
+            use tracing_subscriber::layer::SubscriberExt;
+            use tracing_subscriber::util::SubscriberInitExt;
+            #install
+        }
+    }
+}
+
+/// Generates `LOG_FILTER`, the handle swapping the filter of a `reloadable` `log_init`, and `log_filter`, parsing
+/// `RUST_LOG` directives.
+pub fn reloadable(log_levels: bool) -> TokenStream {
+    let mut filter_type = quote! { tracing_subscriber::EnvFilter };
+    if log_levels {
+        filter_type = quote! { LogFilter };
+    }
+    quote! {
+        static LOG_FILTER: std::sync::OnceLock<tracing_subscriber::reload::Handle<#filter_type, tracing_subscriber::Registry>> =
+            std::sync::OnceLock::new();
+
+        fn log_filter(directives: &str) -> Result<tracing_subscriber::EnvFilter, String> {
+            tracing_subscriber::EnvFilter::builder()
+                .with_default_directive(tracing_subscriber::filter::LevelFilter::INFO.into())
+                .parse(directives)
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Generates `LogFilter`, the filter of `log_levels = true`: events within the `call` span of a connector listed in
+/// `LOG_LEVELS`, as `math=debug,crm=warn`, are filtered at its level, whether above or below `RUST_LOG`'s, and
+/// every other event by `RUST_LOG`.
+pub fn log_levels() -> TokenStream {
+    quote! {
+        /// `LOG_LEVELS`, as levels by connector name.
+        fn parse_log_levels(levels: &str) -> Result<std::collections::HashMap<String, tracing_subscriber::filter::LevelFilter>, String> {
+            levels
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    let (name, level) = entry
+                        .split_once('=')
+                        .ok_or_else(|| format!("LOG_LEVELS must be comma-separated `connector=level` pairs, got `{}`", entry))?;
+                    let level = level
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("LOG_LEVELS: `{}` is not a level, expected off, error, warn, info, debug or trace", level.trim()))?;
+                    Ok((resolve_alias(name.trim()).to_string(), level))
+                })
+                .collect()
+        }
+
+        fn check_log_levels() -> Vec<String> {
+            let levels = match parse_log_levels(&connectors::setting("LOG_LEVELS").unwrap_or_default()) {
+                Ok(levels) => levels,
+                Err(e) => return vec![e],
+            };
+            let mut unknown: Vec<String> = levels
+                .into_keys()
+                .filter(|name| !dispatch_table().keys().any(|(n, _, _)| n == name))
+                .map(|name| format!("LOG_LEVELS names `{}`, which is no registered connector", name))
+                .collect();
+            unknown.sort();
+            unknown
+        }
+
+        ::inventory::submit! {
+            crate::connectors::StartupCheck {
+                name: "log levels",
+                check: check_log_levels,
+            }
+        }
+
+        /// The connector of a `call` span, kept in the span's extensions.
+        struct CallConnector(String);
+
+        impl tracing::field::Visit for CallConnector {
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                if field.name() == "connector" {
+                    self.0 = value.to_string();
+                }
+            }
+
+            fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+        }
+
+        /// `RUST_LOG`'s filter, overridden within the calls of the connectors given a level.
+        struct LogFilter {
+            env: tracing_subscriber::EnvFilter,
+            levels: std::collections::HashMap<String, tracing_subscriber::filter::LevelFilter>,
+        }
+
+        impl LogFilter {
+            fn new(env: tracing_subscriber::EnvFilter, levels: std::collections::HashMap<String, tracing_subscriber::filter::LevelFilter>) -> Self {
+                LogFilter { env, levels }
+            }
+
+            fn is_call_span(metadata: &tracing::Metadata<'_>) -> bool {
+                metadata.is_span() && metadata.name() == "call" && metadata.fields().field("connector").is_some()
+            }
+        }
+
+        impl<S> tracing_subscriber::Layer<S> for LogFilter
+        where
+            S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+        {
+            fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+                match self.levels.is_empty() {
+                    true => tracing_subscriber::Layer::<S>::register_callsite(&self.env, metadata),
+                    // Whether an event is enabled depends on the call it happens in.
+                    false => tracing::subscriber::Interest::sometimes(),
+                }
+            }
+
+            fn max_level_hint(&self) -> Option<tracing_subscriber::filter::LevelFilter> {
+                let env = tracing_subscriber::Layer::<S>::max_level_hint(&self.env)?;
+                Some(self.levels.values().copied().fold(env, std::cmp::max))
+            }
+
+            fn enabled(&self, metadata: &tracing::Metadata<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) -> bool {
+                if !self.levels.is_empty() {
+                    // The span must exist for the events within to be told apart.
+                    if LogFilter::is_call_span(metadata) {
+                        return true;
+                    }
+                    let connector = ctx
+                        .lookup_current()
+                        .and_then(|span| span.scope().find_map(|span| span.extensions().get::<CallConnector>().map(|c| c.0.clone())));
+                    if let Some(level) = connector.and_then(|connector| self.levels.get(&connector)) {
+                        return *level >= *metadata.level();
+                    }
+                }
+                tracing_subscriber::Layer::<S>::enabled(&self.env, metadata, ctx)
+            }
+
+            fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, id: &tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+                if LogFilter::is_call_span(attrs.metadata()) {
+                    let mut connector = CallConnector(String::new());
+                    attrs.record(&mut connector);
+                    if let Some(span) = ctx.span(id) {
+                        span.extensions_mut().insert(connector);
+                    }
+                }
+                self.env.on_new_span(attrs, id, ctx);
+            }
+
+            fn on_record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+                tracing_subscriber::Layer::<S>::on_record(&self.env, id, values, ctx);
+            }
+
+            fn on_enter(&self, id: &tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+                tracing_subscriber::Layer::<S>::on_enter(&self.env, id, ctx);
+            }
+
+            fn on_exit(&self, id: &tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+                tracing_subscriber::Layer::<S>::on_exit(&self.env, id, ctx);
+            }
+
+            fn on_close(&self, id: tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+                tracing_subscriber::Layer::<S>::on_close(&self.env, id, ctx);
+            }
+        }
+    }
+}
+
+/// Generates `execute` running `execute_call` within a `call` span, which carries the connector, operation and request
+/// id into every event the call logs, the handler's included, and which `LogFilter` picks levels by. Calls that came
+/// without a request id get a generated one. With `json`, calls end with a `call finished` event with their latency
+/// and outcome.
+///
+/// Calls slower than their connector's `slow_call`, else `slow_call`, end with a `slow call` warning: the time spent
+/// decoding the input, in the handler and encoding its output, and a hash of the input telling repeated inputs apart
+/// without logging them. With `normalize_operations`, the connector's threshold is found for mis-cased operations too.
+pub fn call_log(json: bool, slow_call: Option<Duration>, normalize_operations: bool) -> TokenStream {
+    let finished = match json {
+        true => quote! {
+            let latency_ms = elapsed.as_secs_f64() * 1000.0;
+            match &result {
+                Ok(_) => tracing::info!(latency_ms, outcome = "success", "call finished"),
+                Err(e) => tracing::warn!(latency_ms, outcome = "error", status = e.status().as_u16(), error = %e, "call finished"),
+            }
+        },
+        false => quote! {},
+    };
+    let slow_call = match slow_call {
+        Some(threshold) => {
+            let threshold_ms = threshold.as_millis() as u64;
+            quote! { Some(std::time::Duration::from_millis(#threshold_ms)) }
+        }
+        None => quote! { None },
+    };
+    let canonical = match normalize_operations {
+        true => quote! { let operation = canonical_operation(name, operation).unwrap_or(operation); },
+        false => quote! {},
+    };
+    quote! {
+        /// `connector_main!`'s `slow_call`.
+        const SLOW_CALL: Option<std::time::Duration> = #slow_call;
+
+        async fn execute(
+            name: String,
+            operation: String,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+            ) -> Result<connectors::Output, connectors::ExecError> {
+            use tracing::Instrument as _;
+            let ctx = match ctx.request_id() {
+                Some(_) => ctx,
+                None => ctx.with_request_id(connectors::new_request_id()),
+            };
+            let span = tracing::info_span!("call", connector = resolve_alias(&name), operation = operation.as_str(), request_id = ctx.request_id());
+            let call = (name.clone(), operation.clone(), body.clone(), format, ctx.clone());
+            let started = std::time::Instant::now();
+            let result = execute_call(name, operation, body, format, ctx).instrument(span.clone()).await;
+            let elapsed = started.elapsed();
+            span.in_scope(|| {
+                warn_slow_call(&call, elapsed, &result);
+                #finished
+            });
+            result
+        }
+
+        /// The lowest threshold of any connector, below which calls needn't be looked up.
+        fn slow_call_floor() -> Option<std::time::Duration> {
+            static FLOOR: std::sync::OnceLock<Option<std::time::Duration>> = std::sync::OnceLock::new();
+            *FLOOR.get_or_init(|| dispatch_table().values().filter_map(|recipe| recipe.slow_call).chain(SLOW_CALL).min())
+        }
+
+        fn warn_slow_call(
+            (name, operation, body, format, ctx): &(String, String, axum::body::Bytes, connectors::Format, connectors::Context),
+            elapsed: std::time::Duration,
+            result: &Result<connectors::Output, connectors::ExecError>,
+        ) {
+            use std::hash::{Hash as _, Hasher as _};
+            if !slow_call_floor().is_some_and(|floor| elapsed > floor) {
+                return;
+            }
+            #canonical
+            let threshold = match lookup(name, operation, format.peek_version(body)) {
+                Ok((_, recipe)) => recipe.slow_call.or(SLOW_CALL),
+                Err(_) => SLOW_CALL,
+            };
+            let Some(threshold) = threshold.filter(|threshold| elapsed > *threshold) else { return };
+            // The input alone, so that the same input sent for two jobs hashes the same.
+            let input_hash = format.peek_input(body).ok().map(|input| {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                input.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            });
+            let ms = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+            let timings = ctx.timings();
+            tracing::warn!(
+                latency_ms = ms(elapsed),
+                threshold_ms = ms(threshold),
+                deserialize_ms = ms(timings.deserialize),
+                handler_ms = ms(timings.handler),
+                serialize_ms = ms(timings.serialize),
+                input_hash,
+                outcome = if result.is_ok() { "success" } else { "error" },
+                "slow call"
+            );
+        }
+    }
+}
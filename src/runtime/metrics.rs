@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Prometheus's default buckets, in seconds.
+pub const DEFAULT_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Generates the per-connector gauges of work under way, kept whether or not `/metrics` reports them. Names that
+/// aren't registered connectors share `unknown`, so that callers can't grow the label sets.
+pub fn gauges() -> TokenStream {
+    quote! {
+        /// What a connector's gauges count.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        enum Gauge {
+            /// Handlers running.
+            Running,
+            /// Asynchronous executions accepted and not yet delivered.
+            Queued,
+            /// Batch items and Zeebe jobs waiting for a permit.
+            Waiting,
+        }
+
+        impl Gauge {
+            /// The gauge's name on `/metrics`.
+            fn family(self) -> &'static str {
+                match self {
+                    Gauge::Running => "connector_handlers_in_flight",
+                    Gauge::Queued => "connector_async_jobs_queued",
+                    Gauge::Waiting => "connector_permit_waiters",
+                }
+            }
+        }
+
+        fn gauge_table() -> &'static std::sync::RwLock<std::collections::BTreeMap<(Gauge, String), &'static std::sync::atomic::AtomicUsize>> {
+            static GAUGES: std::sync::OnceLock<std::sync::RwLock<std::collections::BTreeMap<(Gauge, String), &'static std::sync::atomic::AtomicUsize>>> = std::sync::OnceLock::new();
+            GAUGES.get_or_init(Default::default)
+        }
+
+        /// The gauge of connector `name`, held with `connectors::InFlight::acquire(gauge(..), usize::MAX)`.
+        fn gauge(gauge: Gauge, name: &str) -> &'static std::sync::atomic::AtomicUsize {
+            let name = resolve_alias(name);
+            if let Some(counter) = gauge_table().read().unwrap_or_else(|e| e.into_inner()).get(&(gauge, name.to_string())) {
+                return counter;
+            }
+            let name = match dispatch_table().keys().any(|(n, _, _)| n == name) {
+                true => name,
+                false => "unknown",
+            };
+            gauge_table().write().unwrap_or_else(|e| e.into_inner()).entry((gauge, name.to_string())).or_insert_with(|| Box::leak(Box::default()))
+        }
+    }
+}
+
+/// Generates the collection of the metrics `/metrics` and StatsD report, latency histograms labelled by connector,
+/// operation and outcome:
+///
+/// - `connector_handler_duration_seconds`, the handler's run, decoding its input included.
+/// - `connector_dispatch_duration_seconds`, the whole call from the dispatcher's point of view: interceptors, the
+///   audit log, secrets and FEEL inputs, and the time spent waiting for a slot.
+///
+/// Failed calls are counted in `connector_errors_total`, labelled by connector, operation and `connectors::ErrorClass`,
+/// so that alerts can tell the connector's own failures from the callers' mistakes.
+///
+/// Operations are labelled as registered, `*` for catch-all handlers, and calls naming no registered operation count
+/// under connector and operation `unknown`, so that callers can't grow the label sets. With `statsd`, each
+/// observation is also sent to the StatsD agent.
+pub fn expand(buckets: &[Duration], statsd: bool, otlp: bool) -> TokenStream {
+    let buckets = buckets.iter().map(Duration::as_secs_f64);
+    let (mut statsd_timing, mut statsd_error) = (quote! {}, quote! {});
+    if statsd {
+        statsd_timing = quote! { statsd_timing(metric, labels, outcome, elapsed); };
+        statsd_error = quote! { statsd_error(labels, class); };
+    }
+    let mut dotted_names = quote! {};
+    if statsd || otlp {
+        dotted_names = quote! {
+            /// The name of a `/metrics` family for exporters naming metrics with dots, less the `connector.` prefix.
+            fn dotted_metric_name(family: &str) -> &str {
+                match family {
+                    "connector_handler_duration_seconds" => "handler.duration",
+                    "connector_dispatch_duration_seconds" => "dispatch.duration",
+                    "connector_errors_total" => "errors",
+                    "connector_handlers_in_flight" => "handlers.in_flight",
+                    "connector_async_jobs_queued" => "async_jobs.queued",
+                    "connector_permit_waiters" => "permit_waiters",
+                    family => family,
+                }
+            }
+        };
+    }
+    quote! {
+        const LATENCY_BUCKETS: &[f64] = &[#(#buckets),*];
+
+        /// A cumulative histogram in `LATENCY_BUCKETS`, its sum kept in microseconds.
+        struct Histogram {
+            buckets: Vec<std::sync::atomic::AtomicU64>,
+            count: std::sync::atomic::AtomicU64,
+            sum_micros: std::sync::atomic::AtomicU64,
+        }
+
+        impl Histogram {
+            fn new() -> Self {
+                Histogram {
+                    buckets: LATENCY_BUCKETS.iter().map(|_| Default::default()).collect(),
+                    count: Default::default(),
+                    sum_micros: Default::default(),
+                }
+            }
+
+            fn observe(&self, elapsed: std::time::Duration) {
+                use std::sync::atomic::Ordering::Relaxed;
+                let seconds = elapsed.as_secs_f64();
+                for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+                    if seconds <= *bound {
+                        bucket.fetch_add(1, Relaxed);
+                    }
+                }
+                self.count.fetch_add(1, Relaxed);
+                self.sum_micros.fetch_add(elapsed.as_micros() as u64, Relaxed);
+            }
+        }
+
+        /// (metric, connector, operation, outcome)
+        type LatencyKey = (&'static str, String, String, &'static str);
+
+        fn latencies() -> &'static std::sync::RwLock<std::collections::BTreeMap<LatencyKey, Histogram>> {
+            static LATENCIES: std::sync::OnceLock<std::sync::RwLock<std::collections::BTreeMap<LatencyKey, Histogram>>> = std::sync::OnceLock::new();
+            LATENCIES.get_or_init(Default::default)
+        }
+
+        fn observe_latency(metric: &'static str, labels: &(String, String), outcome: &'static str, elapsed: std::time::Duration) {
+            #statsd_timing
+            let key = (metric, labels.0.clone(), labels.1.clone(), outcome);
+            if let Some(histogram) = latencies().read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+                return histogram.observe(elapsed);
+            }
+            latencies().write().unwrap_or_else(|e| e.into_inner()).entry(key).or_insert_with(Histogram::new).observe(elapsed);
+        }
+
+        /// (connector, operation, class)
+        type ErrorKey = (String, String, connectors::ErrorClass);
+
+        fn errors() -> &'static std::sync::RwLock<std::collections::BTreeMap<ErrorKey, std::sync::atomic::AtomicU64>> {
+            static ERRORS: std::sync::OnceLock<std::sync::RwLock<std::collections::BTreeMap<ErrorKey, std::sync::atomic::AtomicU64>>> = std::sync::OnceLock::new();
+            ERRORS.get_or_init(Default::default)
+        }
+
+        fn count_error(labels: &(String, String), class: connectors::ErrorClass) {
+            #statsd_error
+            let key = (labels.0.clone(), labels.1.clone(), class);
+            if let Some(count) = errors().read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+                count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                return;
+            }
+            errors().write().unwrap_or_else(|e| e.into_inner()).entry(key).or_default().fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        #dotted_names
+
+        /// The connector and operation a call is labelled with.
+        fn metric_labels(name: &str, operation: &str, version: u32) -> (String, String) {
+            match lookup(name, operation, version) {
+                Ok((registered, recipe)) => (recipe.name.to_string(), registered.to_string()),
+                Err(_) => ("unknown".to_string(), "unknown".to_string()),
+            }
+        }
+
+        fn outcome<T, E>(result: &Result<T, E>) -> &'static str {
+            match result {
+                Ok(_) => "success",
+                Err(_) => "error",
+            }
+        }
+    }
+}
+
+/// Generates `GET /metrics`, rendering what `expand` collects and the gauges in the Prometheus text format.
+pub fn endpoint() -> TokenStream {
+    quote! {
+        /// A label value, escaped as the text format requires.
+        fn metric_label(value: &str) -> String {
+            value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+        }
+
+        fn render_latencies(out: &mut String) {
+            use std::fmt::Write as _;
+            use std::sync::atomic::Ordering::Relaxed;
+            let latencies = latencies().read().unwrap_or_else(|e| e.into_inner());
+            let families = [
+                ("connector_handler_duration_seconds", "Time handlers took, decoding their input included."),
+                ("connector_dispatch_duration_seconds", "Time calls took from dispatch to result."),
+            ];
+            for (family, help) in families {
+                let _ = writeln!(out, "# HELP {} {}", family, help);
+                let _ = writeln!(out, "# TYPE {} histogram", family);
+                for ((metric, connector, operation, outcome), histogram) in latencies.iter().filter(|((metric, ..), _)| *metric == family) {
+                    let labels = format!("connector=\"{}\",operation=\"{}\",outcome=\"{}\"", metric_label(connector), metric_label(operation), outcome);
+                    for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                        let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", metric, labels, bound, bucket.load(Relaxed));
+                    }
+                    let count = histogram.count.load(Relaxed);
+                    let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", metric, labels, count);
+                    let _ = writeln!(out, "{}_sum{{{}}} {}", metric, labels, histogram.sum_micros.load(Relaxed) as f64 / 1e6);
+                    let _ = writeln!(out, "{}_count{{{}}} {}", metric, labels, count);
+                }
+            }
+        }
+
+        fn render_errors(out: &mut String) {
+            use std::fmt::Write as _;
+            let _ = writeln!(out, "# HELP connector_errors_total Failed calls, by class of failure.");
+            let _ = writeln!(out, "# TYPE connector_errors_total counter");
+            for ((connector, operation, class), count) in errors().read().unwrap_or_else(|e| e.into_inner()).iter() {
+                let _ = writeln!(
+                    out,
+                    "connector_errors_total{{connector=\"{}\",operation=\"{}\",class=\"{}\"}} {}",
+                    metric_label(connector),
+                    metric_label(operation),
+                    class.label(),
+                    count.load(std::sync::atomic::Ordering::Relaxed)
+                );
+            }
+        }
+
+        fn render_gauges(out: &mut String) {
+            use std::fmt::Write as _;
+            let gauges = gauge_table().read().unwrap_or_else(|e| e.into_inner());
+            let families = [
+                (Gauge::Running, "Handlers running."),
+                (Gauge::Queued, "Asynchronous executions accepted and not yet delivered."),
+                (Gauge::Waiting, "Batch items and Zeebe jobs waiting for a permit."),
+            ];
+            for (kind, help) in families {
+                let _ = writeln!(out, "# HELP {} {}", kind.family(), help);
+                let _ = writeln!(out, "# TYPE {} gauge", kind.family());
+                for ((_, connector), value) in gauges.iter().filter(|((gauge, _), _)| *gauge == kind) {
+                    let _ = writeln!(out, "{}{{connector=\"{}\"}} {}", kind.family(), metric_label(connector), value.load(std::sync::atomic::Ordering::Relaxed));
+                }
+            }
+        }
+
+        async fn metrics() -> impl axum::response::IntoResponse {
+            let mut out = String::new();
+            render_latencies(&mut out);
+            render_errors(&mut out);
+            render_gauges(&mut out);
+            ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+        }
+    }
+}
@@ -0,0 +1,2789 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, LitBool, LitInt, LitStr, Token};
+
+use crate::args::{parse_duration, parse_str_list};
+use crate::serde_attrs::check_rename_rule;
+
+mod admin;
+mod amqp;
+mod audit;
+mod batch;
+mod build_info;
+mod callbacks;
+mod camunda;
+mod circuit_breaker;
+mod cloudevents;
+mod cloud_secrets;
+mod config;
+mod cors;
+mod database;
+mod dead_letter;
+mod dedup;
+mod documents;
+mod element_templates;
+mod envelope;
+mod feel;
+mod file_secrets;
+mod format;
+mod graphql;
+mod grpc;
+mod health;
+mod forwarded;
+mod http_client;
+mod idempotency;
+mod inbound;
+mod job_queue;
+mod jsonrpc;
+mod listen;
+mod logging;
+mod metrics;
+mod mqtt;
+mod native;
+mod otlp;
+mod outbox;
+mod plugin;
+mod polling;
+mod reload;
+mod rename;
+mod resources;
+mod response;
+mod rhai;
+mod routes;
+mod secrets;
+mod shutdown;
+mod webhook;
+mod websocket;
+mod sqs;
+mod statsd;
+mod streaming;
+mod validation;
+mod vault;
+mod wasm;
+mod zeebe;
+
+pub struct MainArgs {
+    listeners: Vec<listen::Listener>,
+    msgpack: bool,
+    protobuf: bool,
+    streaming: bool,
+    cloudevents: bool,
+    jsonrpc: bool,
+    grpc: bool,
+    graphql: bool,
+    batch: bool,
+    batch_concurrency: usize,
+    callbacks: bool,
+    callback_retries: u32,
+    job_queue: Option<LitStr>,
+    job_visibility_timeout: Duration,
+    job_max_attempts: u32,
+    job_concurrency: usize,
+    dead_letter: Option<dead_letter::Sink>,
+    idempotency: bool,
+    idempotency_ttl: Duration,
+    idempotency_capacity: usize,
+    health_interval: Duration,
+    drain_timeout: Duration,
+    circuit_breaker: Option<u32>,
+    circuit_breaker_cooldown: Duration,
+    max_in_flight: Option<usize>,
+    compression: bool,
+    compression_min_size: u16,
+    cors: Option<cors::CorsArgs>,
+    trusted_proxies: Vec<(std::net::IpAddr, u8)>,
+    worker_threads: Option<usize>,
+    max_blocking_threads: Option<usize>,
+    thread_name: Option<LitStr>,
+    rename_all: Option<LitStr>,
+    config_file: Option<LitStr>,
+    admin: bool,
+    admin_log_level: bool,
+    element_templates: bool,
+    feel_inputs: bool,
+    camunda_compat: bool,
+    job_worker: Option<zeebe::WorkerArgs>,
+    zeebe_client: bool,
+    http_client: bool,
+    database: Option<database::Backend>,
+    outbox: bool,
+    secrets: Vec<secrets::Provider>,
+    amqp: bool,
+    mqtt: bool,
+    sqs: bool,
+    websocket: bool,
+    inbound_dedup: Option<dedup::Store>,
+    inbound_dedup_ttl: Duration,
+    polling_state: Option<LitStr>,
+    wasm_plugins: Option<LitStr>,
+    rhai_scripts: Option<LitStr>,
+    native_plugins: Option<LitStr>,
+    audit_log: Option<LitStr>,
+    audit_redact: Vec<LitStr>,
+    required_settings: Vec<LitStr>,
+    require_connectors: bool,
+    normalize_operations: bool,
+    metrics: bool,
+    metrics_buckets: Vec<Duration>,
+    statsd: bool,
+    otlp: bool,
+    json_logs: bool,
+    log_levels: bool,
+    slow_call: Option<Duration>,
+    document_store: Option<LitStr>,
+    document_threshold: usize,
+}
+
+impl Parse for MainArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut port = None;
+        let mut bind = None;
+        let mut unix_socket = None;
+        let mut msgpack = false;
+        let mut protobuf = false;
+        let mut streaming = false;
+        let mut cloudevents = false;
+        let mut jsonrpc = false;
+        let mut grpc = false;
+        let mut graphql = false;
+        let mut batch = false;
+        let mut batch_concurrency = 16;
+        let mut callbacks = false;
+        let mut callback_retries = 5;
+        let mut job_queue = None;
+        let mut job_visibility_timeout = Duration::from_secs(300);
+        let mut job_max_attempts = 10;
+        let mut job_concurrency = 16;
+        let mut dead_letter = None;
+        let mut idempotency = false;
+        let mut idempotency_ttl = Duration::from_secs(24 * 3600);
+        let mut health_interval = Duration::from_secs(30);
+        let mut drain_timeout = Duration::from_secs(20);
+        let mut idempotency_capacity = 10_000;
+        let mut circuit_breaker = None;
+        let mut circuit_breaker_cooldown = Duration::from_secs(30);
+        let mut max_in_flight = None;
+        let mut compression = false;
+        let mut compression_min_size = 1024;
+        let mut cors_origins = None;
+        let mut cors_methods = None;
+        let mut cors_headers = None;
+        let mut cors_credentials = false;
+        let mut http2 = false;
+        let mut tls_cert = None;
+        let mut tls_key = None;
+        let mut listener_urls = Vec::new();
+        let mut proxy_protocol = false;
+        let mut reuse_port = false;
+        let mut trusted_proxies = Vec::new();
+        let mut worker_threads = None;
+        let mut max_blocking_threads = None;
+        let mut thread_name = None;
+        let mut rename_all = None;
+        let mut config_file = None;
+        let mut admin = false;
+        let mut admin_log_level = None;
+        let mut element_templates = false;
+        let mut feel_inputs = false;
+        let mut camunda_compat = false;
+        let mut zeebe_client = false;
+        let mut http_client = false;
+        let mut database = None;
+        let mut outbox = None;
+        let mut secrets = Vec::new();
+        let mut amqp = false;
+        let mut mqtt = false;
+        let mut websocket = false;
+        let mut sqs = false;
+        let mut inbound_dedup = None;
+        let mut inbound_dedup_ttl = None;
+        let mut polling_state = None;
+        let mut wasm_plugins = None;
+        let mut rhai_scripts = None;
+        let mut native_plugins = None;
+        let mut audit_log = None;
+        let mut audit_redact = Vec::new();
+        let mut required_settings = Vec::new();
+        let mut require_connectors = false;
+        let mut normalize_operations = false;
+        let mut metrics = false;
+        let mut metrics_buckets = metrics::DEFAULT_BUCKETS.map(Duration::from_secs_f64).to_vec();
+        let mut statsd = false;
+        let mut otlp = false;
+        let mut json_logs = false;
+        let mut log_levels = false;
+        let mut slow_call = None;
+        let mut document_store = None;
+        let mut document_threshold = None;
+        let mut job_worker = false;
+        let mut job_streaming = true;
+        let mut job_worker_timeout = Duration::from_secs(300);
+        let mut job_worker_max_jobs = 32;
+        let mut job_poll_timeout = Duration::from_secs(30);
+        let mut job_stream_timeout = Duration::from_secs(3600);
+        let mut job_reconnect_backoff = Duration::from_secs(1);
+        // The first job worker option seen, reported if the worker is off.
+        let mut job_worker_option = None;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "port" {
+                port = Some(input.parse::<LitInt>()?);
+            } else if key == "bind" {
+                bind = Some(input.parse::<LitStr>()?);
+            } else if key == "unix_socket" {
+                unix_socket = Some(input.parse::<LitStr>()?);
+            } else if key == "msgpack" {
+                msgpack = input.parse::<LitBool>()?.value;
+            } else if key == "protobuf" {
+                protobuf = input.parse::<LitBool>()?.value;
+            } else if key == "streaming" {
+                streaming = input.parse::<LitBool>()?.value;
+            } else if key == "cloudevents" {
+                cloudevents = input.parse::<LitBool>()?.value;
+            } else if key == "jsonrpc" {
+                jsonrpc = input.parse::<LitBool>()?.value;
+            } else if key == "grpc" {
+                grpc = input.parse::<LitBool>()?.value;
+            } else if key == "graphql" {
+                graphql = input.parse::<LitBool>()?.value;
+            } else if key == "batch" {
+                batch = input.parse::<LitBool>()?.value;
+            } else if key == "batch_concurrency" {
+                batch_concurrency = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "callbacks" {
+                callbacks = input.parse::<LitBool>()?.value;
+            } else if key == "callback_retries" {
+                callback_retries = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "job_queue" {
+                job_queue = Some(input.parse::<LitStr>()?);
+            } else if key == "job_visibility_timeout" {
+                job_visibility_timeout = parse_duration(&input.parse()?)?;
+            } else if key == "job_max_attempts" {
+                job_max_attempts = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "job_concurrency" {
+                let lit = input.parse::<LitInt>()?;
+                job_concurrency = lit.base10_parse()?;
+                if job_concurrency == 0 {
+                    return Err(Error::new_spanned(lit, "`job_concurrency` must be at least 1"));
+                }
+            } else if key == "dead_letter" {
+                dead_letter = Some(input.parse::<LitStr>()?);
+            } else if key == "idempotency" {
+                idempotency = input.parse::<LitBool>()?.value;
+            } else if key == "idempotency_ttl" {
+                idempotency_ttl = parse_duration(&input.parse()?)?;
+            } else if key == "idempotency_capacity" {
+                idempotency_capacity = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "health_interval" {
+                let lit = input.parse()?;
+                health_interval = parse_duration(&lit)?;
+                if health_interval.is_zero() {
+                    return Err(Error::new_spanned(lit, "`health_interval` must not be zero"));
+                }
+            } else if key == "drain_timeout" {
+                drain_timeout = parse_duration(&input.parse()?)?;
+            } else if key == "circuit_breaker" {
+                circuit_breaker = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if key == "circuit_breaker_cooldown" {
+                circuit_breaker_cooldown = parse_duration(&input.parse()?)?;
+            } else if key == "max_in_flight" {
+                max_in_flight = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if key == "compression" {
+                compression = input.parse::<LitBool>()?.value;
+            } else if key == "compression_min_size" {
+                compression_min_size = input.parse::<LitInt>()?.base10_parse()?;
+            } else if key == "cors_origins" {
+                cors_origins = Some(parse_str_list(input)?);
+            } else if key == "cors_methods" {
+                cors_methods = Some(parse_str_list(input)?);
+            } else if key == "cors_headers" {
+                cors_headers = Some(parse_str_list(input)?);
+            } else if key == "cors_credentials" {
+                cors_credentials = input.parse::<LitBool>()?.value;
+            } else if key == "http2" {
+                http2 = input.parse::<LitBool>()?.value;
+            } else if key == "tls_cert" {
+                tls_cert = Some(input.parse::<LitStr>()?);
+            } else if key == "tls_key" {
+                tls_key = Some(input.parse::<LitStr>()?);
+            } else if key == "proxy_protocol" {
+                proxy_protocol = input.parse::<LitBool>()?.value;
+            } else if key == "reuse_port" {
+                reuse_port = input.parse::<LitBool>()?.value;
+            } else if key == "trusted_proxies" {
+                trusted_proxies = forwarded::parse_cidrs(&parse_str_list(input)?)?;
+            } else if key == "worker_threads" {
+                worker_threads = Some(parse_thread_count(input)?);
+            } else if key == "max_blocking_threads" {
+                max_blocking_threads = Some(parse_thread_count(input)?);
+            } else if key == "thread_name" {
+                thread_name = Some(input.parse::<LitStr>()?);
+            } else if key == "rename_all" {
+                let rule = input.parse::<LitStr>()?;
+                check_rename_rule(&rule)?;
+                rename_all = Some(rule);
+            } else if key == "config_file" {
+                config_file = Some(input.parse::<LitStr>()?);
+            } else if key == "admin" {
+                admin = input.parse::<LitBool>()?.value;
+            } else if key == "admin_log_level" {
+                admin_log_level = Some(input.parse::<LitBool>()?).filter(|lit| lit.value);
+            } else if key == "element_templates" {
+                element_templates = input.parse::<LitBool>()?.value;
+            } else if key == "feel_inputs" {
+                feel_inputs = input.parse::<LitBool>()?.value;
+            } else if key == "camunda_compat" {
+                camunda_compat = input.parse::<LitBool>()?.value;
+            } else if key == "amqp" {
+                amqp = input.parse::<LitBool>()?.value;
+            } else if key == "mqtt" {
+                mqtt = input.parse::<LitBool>()?.value;
+            } else if key == "websocket" {
+                websocket = input.parse::<LitBool>()?.value;
+            } else if key == "sqs" {
+                sqs = input.parse::<LitBool>()?.value;
+            } else if key == "inbound_dedup" {
+                inbound_dedup = Some(dedup::Store::parse(&input.parse()?)?);
+            } else if key == "inbound_dedup_ttl" {
+                let lit = input.parse::<LitStr>()?;
+                inbound_dedup_ttl = Some((parse_duration(&lit)?, lit));
+            } else if key == "polling_state" {
+                polling_state = Some(input.parse::<LitStr>()?);
+            } else if key == "wasm_plugins" {
+                wasm_plugins = Some(input.parse::<LitStr>()?);
+            } else if key == "rhai_scripts" {
+                rhai_scripts = Some(input.parse::<LitStr>()?);
+            } else if key == "native_plugins" {
+                native_plugins = Some(input.parse::<LitStr>()?);
+            } else if key == "audit_log" {
+                audit_log = Some(input.parse::<LitStr>()?);
+            } else if key == "audit_redact" {
+                audit_redact = parse_str_list(input)?;
+            } else if key == "required_settings" {
+                required_settings = parse_str_list(input)?;
+            } else if key == "require_connectors" {
+                require_connectors = input.parse::<LitBool>()?.value;
+            } else if key == "normalize_operations" {
+                normalize_operations = input.parse::<LitBool>()?.value;
+            } else if key == "metrics" {
+                metrics = input.parse::<LitBool>()?.value;
+            } else if key == "metrics_buckets" {
+                let lits = parse_str_list(input)?;
+                metrics_buckets = lits.iter().map(parse_duration).collect::<syn::Result<_>>()?;
+                if let Some((lit, _)) = lits.iter().zip(&metrics_buckets).find(|(_, bucket)| bucket.is_zero()) {
+                    return Err(Error::new_spanned(lit, "Buckets must not be zero"));
+                }
+                if let Some(lit) = lits.first().filter(|_| metrics_buckets.windows(2).any(|w| w[0] >= w[1])) {
+                    return Err(Error::new_spanned(lit, "Buckets must be in increasing order"));
+                }
+                if metrics_buckets.is_empty() {
+                    return Err(Error::new(key.span(), "`metrics_buckets` needs at least one bucket"));
+                }
+            } else if key == "statsd" {
+                statsd = input.parse::<LitBool>()?.value;
+            } else if key == "otlp" {
+                otlp = input.parse::<LitBool>()?.value;
+            } else if key == "log_format" {
+                let format = input.parse::<LitStr>()?;
+                json_logs = match format.value().as_str() {
+                    "text" => false,
+                    "json" => true,
+                    _ => return Err(Error::new_spanned(format, "Expected `text` or `json`")),
+                };
+            } else if key == "log_levels" {
+                log_levels = input.parse::<LitBool>()?.value;
+            } else if key == "slow_call" {
+                slow_call = Some(parse_duration(&input.parse()?)?);
+            } else if key == "document_store" {
+                document_store = Some(input.parse::<LitStr>()?);
+            } else if key == "document_threshold" {
+                document_threshold = Some(input.parse::<LitInt>()?);
+            } else if key == "zeebe_client" {
+                zeebe_client = input.parse::<LitBool>()?.value;
+            } else if key == "http_client" {
+                http_client = input.parse::<LitBool>()?.value;
+            } else if key == "database" {
+                database = Some(database::Backend::parse(&input.parse()?)?);
+            } else if key == "outbox" {
+                outbox = Some(input.parse::<LitBool>()?).filter(|lit| lit.value);
+            } else if key == "secrets" {
+                secrets = parse_str_list(input)?.iter().map(secrets::Provider::parse).collect::<syn::Result<_>>()?;
+            } else if key == "job_worker" {
+                job_worker = input.parse::<LitBool>()?.value;
+            } else if key == "job_streaming" {
+                job_streaming = input.parse::<LitBool>()?.value;
+                job_worker_option.get_or_insert(key);
+            } else if key == "job_worker_timeout" {
+                job_worker_timeout = parse_duration(&input.parse()?)?;
+                job_worker_option.get_or_insert(key);
+            } else if key == "job_worker_max_jobs" {
+                let lit = input.parse::<LitInt>()?;
+                job_worker_max_jobs = lit.base10_parse()?;
+                if job_worker_max_jobs == 0 {
+                    return Err(Error::new_spanned(lit, "`job_worker_max_jobs` must be at least 1"));
+                }
+                job_worker_option.get_or_insert(key);
+            } else if key == "job_poll_timeout" {
+                job_poll_timeout = parse_duration(&input.parse()?)?;
+                job_worker_option.get_or_insert(key);
+            } else if key == "job_stream_timeout" {
+                job_stream_timeout = parse_duration(&input.parse()?)?;
+                job_worker_option.get_or_insert(key);
+            } else if key == "job_reconnect_backoff" {
+                job_reconnect_backoff = parse_duration(&input.parse()?)?;
+                job_worker_option.get_or_insert(key);
+            } else if key == "listeners" {
+                listener_urls = parse_str_list(input)?;
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if let Some(path) = job_queue.as_ref().filter(|_| !callbacks) {
+            return Err(Error::new_spanned(path, "`job_queue` backs asynchronous executions and requires `callbacks = true`"));
+        }
+        if let Some(sink) = dead_letter.as_ref().filter(|_| !callbacks) {
+            return Err(Error::new_spanned(sink, "`dead_letter` keeps failed asynchronous executions and requires `callbacks = true`"));
+        }
+        let dead_letter = dead_letter.as_ref().map(dead_letter::Sink::parse).transpose()?;
+        if let Some(lit) = document_threshold.as_ref().filter(|_| document_store.is_none()) {
+            return Err(Error::new_spanned(lit, "`document_threshold` requires `document_store`"));
+        }
+        let document_threshold = match document_threshold {
+            Some(lit) => lit.base10_parse()?,
+            None => 1024 * 1024,
+        };
+        if let Some(lit) = admin_log_level.as_ref().filter(|_| !admin) {
+            return Err(Error::new_spanned(lit, "`admin_log_level` requires `admin = true`"));
+        }
+        let admin_log_level = admin_log_level.is_some();
+        if let Some(key) = audit_redact.first().filter(|_| audit_log.is_none()) {
+            return Err(Error::new_spanned(key, "`audit_redact` requires `audit_log`"));
+        }
+        if let Some(key) = job_worker_option.filter(|_| !job_worker) {
+            return Err(Error::new_spanned(key, "Job worker options require `job_worker = true`"));
+        }
+        let job_worker = job_worker.then_some(zeebe::WorkerArgs {
+            streaming: job_streaming,
+            timeout: job_worker_timeout,
+            max_jobs: job_worker_max_jobs,
+            poll_timeout: job_poll_timeout,
+            stream_timeout: job_stream_timeout,
+            reconnect_backoff: job_reconnect_backoff,
+        });
+        if let Some(lit) = outbox.as_ref().filter(|_| database.is_none()) {
+            return Err(Error::new_spanned(lit, "`outbox` keeps its messages in the database and requires `database`"));
+        }
+        if let Some(lit) = outbox.as_ref().filter(|_| !zeebe_client && job_worker.is_none()) {
+            return Err(Error::new_spanned(lit, "`outbox` publishes to Zeebe and requires `zeebe_client = true` or `job_worker = true`"));
+        }
+        let outbox = outbox.is_some();
+        if let Some((_, lit)) = inbound_dedup_ttl.as_ref().filter(|_| inbound_dedup.is_none()) {
+            return Err(Error::new_spanned(lit, "`inbound_dedup_ttl` requires `inbound_dedup`"));
+        }
+        let inbound_dedup_ttl = inbound_dedup_ttl.map_or(Duration::from_secs(24 * 3600), |(ttl, _)| ttl);
+        if cors_origins.is_none() && (cors_methods.is_some() || cors_headers.is_some() || cors_credentials) {
+            return Err(Error::new(input.span(), "CORS options require `cors_origins`"));
+        }
+        let cors = cors_origins.map(|origins| cors::CorsArgs {
+            origins,
+            methods: cors_methods,
+            headers: cors_headers,
+            credentials: cors_credentials,
+        });
+        let tls = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            (Some(path), None) | (None, Some(path)) => {
+                return Err(Error::new_spanned(path, "`tls_cert` and `tls_key` must be set together"));
+            }
+        };
+        // `port` and `unix_socket` are shorthands for one `listeners` entry each; with TLS configured, `port` is HTTPS.
+        let mut listeners = Vec::new();
+        if let Some(port) = &port {
+            let ip = match &bind {
+                Some(bind) => bind.value().parse().map_err(|_| Error::new_spanned(bind, "Expected an IP address such as \"::\" or \"127.0.0.1\""))?,
+                None => std::net::IpAddr::from([0, 0, 0, 0]),
+            };
+            let addr = std::net::SocketAddr::new(ip, port.base10_parse()?);
+            listeners.push(listen::Listener::Tcp { addr, tls: tls.clone(), http2, proxy_protocol, reuse_port });
+        }
+        if let Some(path) = unix_socket {
+            listeners.push(listen::Listener::Unix(path));
+        }
+        for url in &listener_urls {
+            listeners.push(listen::Listener::parse(url, tls.as_ref(), http2, proxy_protocol, reuse_port)?);
+        }
+        if let Some(bind) = bind.as_ref().filter(|_| port.is_none()) {
+            return Err(Error::new_spanned(bind, "`bind` sets the address of `port`, which is missing"));
+        }
+        if grpc && !http2 && tls.is_none() {
+            return Err(Error::new(input.span(), "`grpc = true` requires HTTP/2, set `http2 = true` or serve TLS"));
+        }
+        if proxy_protocol && (http2 || tls.is_some()) {
+            return Err(Error::new(input.span(), "`proxy_protocol` is only supported on cleartext HTTP/1.1 listeners for now"));
+        }
+        if listeners.is_empty() {
+            return Err(syn::Error::new(input.span(), "Missing 'port', 'unix_socket' or 'listeners' parameter"));
+        }
+        if let Some((cert, _)) = tls.as_ref().filter(|_| port.is_none() && !listener_urls.iter().any(|u| u.value().starts_with("https://"))) {
+            return Err(Error::new_spanned(cert, "TLS is unused without `port` or an https listener"));
+        }
+        Ok(MainArgs {
+            listeners,
+            msgpack,
+            protobuf,
+            streaming,
+            cloudevents,
+            jsonrpc,
+            grpc,
+            graphql,
+            batch,
+            batch_concurrency,
+            callbacks,
+            callback_retries,
+            job_queue,
+            job_visibility_timeout,
+            job_max_attempts,
+            job_concurrency,
+            dead_letter,
+            idempotency,
+            idempotency_ttl,
+            idempotency_capacity,
+            health_interval,
+            drain_timeout,
+            circuit_breaker,
+            circuit_breaker_cooldown,
+            max_in_flight,
+            compression,
+            compression_min_size,
+            cors,
+            trusted_proxies,
+            worker_threads,
+            max_blocking_threads,
+            thread_name,
+            rename_all,
+            config_file,
+            admin,
+            admin_log_level,
+            element_templates,
+            feel_inputs,
+            camunda_compat,
+            job_worker,
+            zeebe_client,
+            http_client,
+            database,
+            outbox,
+            secrets,
+            amqp,
+            mqtt,
+            sqs,
+            websocket,
+            inbound_dedup,
+            inbound_dedup_ttl,
+            polling_state,
+            wasm_plugins,
+            rhai_scripts,
+            native_plugins,
+            audit_log,
+            audit_redact,
+            required_settings,
+            require_connectors,
+            normalize_operations,
+            metrics,
+            metrics_buckets,
+            statsd,
+            otlp,
+            json_logs,
+            log_levels,
+            slow_call,
+            document_store,
+            document_threshold,
+        })
+    }
+}
+
+fn parse_thread_count(input: ParseStream) -> syn::Result<usize> {
+    let lit = input.parse::<LitInt>()?;
+    match lit.base10_parse()? {
+        0 => Err(Error::new_spanned(lit, "Thread counts must be at least 1")),
+        count => Ok(count),
+    }
+}
+
+pub fn expand(args: &MainArgs) -> TokenStream {
+    let format = format::expand(args);
+    let feel = feel::expand();
+
+    // Optional dispatch modes contribute their handler items and routes on top of `/csp/{name}`.
+    let mut items = Vec::new();
+    let mut routes = Vec::new();
+    let mut startup = Vec::new();
+    // Layers wrapping the `/csp/{name}` dispatch routes only.
+    let mut csp_layers = Vec::new();
+    // Layers wrapping the whole router.
+    let mut layers = Vec::new();
+    items.push(build_info::expand());
+    items.push(routes::expand());
+    items.push(metrics::gauges());
+    routes.push(quote! { .route("/version", axum::routing::get(version_info)) });
+    startup.push(quote! { log_build_info(); });
+    startup.push(quote! { report_connectors(); });
+    if args.cloudevents {
+        items.push(cloudevents::expand());
+        routes.push(quote! { .route("/cloudevents", axum::routing::post(dispatch_cloudevent)) });
+    }
+    if args.jsonrpc {
+        items.push(jsonrpc::expand());
+        routes.push(quote! { .route("/rpc", axum::routing::post(dispatch_jsonrpc)) });
+    }
+    if args.grpc {
+        items.push(grpc::expand(args.protobuf));
+        routes.push(quote! { .route("/camunda.connectors.v1.Connectors/Execute", axum::routing::post(dispatch_grpc)) });
+    }
+    if args.graphql {
+        items.push(graphql::expand());
+        routes.push(quote! { .route("/graphql", axum::routing::get(graphiql).post(dispatch_graphql)) });
+    }
+    let mut accept_async = quote! {};
+    if args.callbacks {
+        items.push(callbacks::expand(args.callback_retries, args.job_queue.is_some(), args.dead_letter.is_some()));
+        accept_async = quote! {
+            if let Some(callback_url) = headers.get("x-callback-url").and_then(|v| v.to_str().ok()) {
+                return accept_async_job(AsyncJob {
+                    token: job_token(),
+                    name,
+                    operation,
+                    callback_url: callback_url.to_string(),
+                    envelope: body.to_vec(),
+                    content_type: format.content_type().to_string(),
+                });
+            }
+        };
+    }
+    if let Some(sink) = &args.dead_letter {
+        items.push(dead_letter::expand(sink));
+        // Before the job queue, whose worker may dead-letter a job right away.
+        startup.push(quote! { open_dead_letters(); });
+    }
+    if let Some(path) = &args.job_queue {
+        items.push(job_queue::expand(args.job_visibility_timeout, args.job_max_attempts, args.job_concurrency, args.dead_letter.is_some()));
+        startup.push(quote! { open_job_queue(#path); });
+    }
+    if args.idempotency {
+        items.push(idempotency::expand(args.idempotency_ttl, args.idempotency_capacity));
+        csp_layers.push(quote! { .layer(axum::middleware::from_fn(idempotency)) });
+    }
+    let (mut breaker_check, mut breaker_record) = (quote! {}, quote! {});
+    // Breakers are kept per registered operation too, so all calls to a catch-all share one.
+    let mut registered = quote! { _ };
+    if let Some(threshold) = args.circuit_breaker {
+        registered = quote! { registered };
+        items.push(circuit_breaker::expand(threshold, args.circuit_breaker_cooldown));
+        breaker_check = quote! { circuit_check(&name, registered)?; };
+        // A shed call never reached the downstream, and a deadline is the caller's budget rather than a failure,
+        // so neither says anything about the downstream's health. Nor does a BPMN error, which is a business outcome.
+        breaker_record = quote! {
+            if !matches!(result, Err(connectors::ExecError::Overloaded(_) | connectors::ExecError::DeadlineExceeded(_))) {
+                let failed = matches!(result, Err(connectors::ExecError::Handler(_) | connectors::ExecError::Failed { error_code: None, .. }));
+                circuit_record(&name, registered, failed);
+            }
+        };
+    }
+    // Global in-flight cap, on top of the per-connector `max_in_flight` checked by each recipe.
+    let mut shed_check = quote! {};
+    if let Some(max) = args.max_in_flight {
+        // The limit itself can be changed by a configuration reload.
+        items.push(quote! {
+            static MAX_IN_FLIGHT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(#max);
+        });
+        shed_check = quote! {
+            static IN_FLIGHT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let _permit = connectors::InFlight::acquire(&IN_FLIGHT, MAX_IN_FLIGHT.load(std::sync::atomic::Ordering::Relaxed))
+                .ok_or_else(|| connectors::ExecError::Overloaded("Server is at capacity, try again later".to_string()))?;
+        };
+    }
+    if args.compression {
+        let min_size = args.compression_min_size;
+        layers.push(quote! { .layer(tower_http::decompression::RequestDecompressionLayer::new()) });
+        layers.push(quote! {
+            .layer(tower_http::compression::CompressionLayer::new().compress_when(
+                tower_http::compression::predicate::Predicate::and(
+                    tower_http::compression::predicate::DefaultPredicate::new(),
+                    tower_http::compression::predicate::SizeAbove::new(#min_size),
+                ),
+            ))
+        });
+    }
+    if let Some(cors) = &args.cors {
+        match cors::expand(cors) {
+            Ok(layer) => layers.push(layer),
+            Err(e) => return e.into_compile_error(),
+        }
+    }
+    let (mut stream_response, mut stream_hold) = (quote! {}, quote! {});
+    if args.streaming {
+        items.push(streaming::expand());
+        stream_response = quote! { connectors::Output::Stream(items) => stream_response(&headers, items), };
+        // The call is cancelled once its response is dropped rather than once the stream is produced.
+        stream_hold = quote! {
+            Ok(connectors::Output::Stream(items)) => Ok(connectors::Output::Stream(items.hold(_cancel))),
+        };
+    }
+    if args.batch {
+        items.push(batch::expand(args.batch_concurrency));
+        routes.push(quote! { .route("/csp/{name}/batch", axum::routing::post(dispatch_batch)) });
+    }
+
+    let (mut drain_check, mut stats_begin, mut stats_record) = (quote! {}, quote! {}, quote! {});
+    // Stats are kept per registered operation, so catch-all calls count towards `*`.
+    if args.admin {
+        registered = quote! { registered };
+        items.push(admin::expand(args.admin_log_level, args.dead_letter.is_some(), args.job_queue.is_some()));
+        if args.dead_letter.is_some() {
+            items.push(admin::dead_letters());
+        }
+        if args.job_queue.is_some() {
+            items.push(admin::jobs());
+        }
+        routes.push(quote! { .nest("/admin", admin_router()) });
+        drain_check = quote! {
+            if DRAINING.load(std::sync::atomic::Ordering::Acquire) {
+                return Err(connectors::ExecError::Overloaded("Server is draining, try again later".to_string()));
+            }
+        };
+        stats_begin = quote! {
+            let stats = admin_stats(&name, registered, version);
+            let _in_flight = stats.and_then(|stats| connectors::InFlight::acquire(&stats.in_flight, usize::MAX));
+        };
+        stats_record = quote! {
+            if let Some(stats) = stats {
+                stats.record(result.is_err());
+            }
+        };
+    }
+
+    let (mut dispatch_metrics_begin, mut dispatch_metrics_record) = (quote! {}, quote! {});
+    let (mut handler_metrics_begin, mut handler_metrics_record) = (quote! {}, quote! {});
+    if args.metrics {
+        items.push(metrics::endpoint());
+        routes.push(quote! { .route("/metrics", axum::routing::get(metrics)) });
+    }
+    if args.statsd {
+        items.push(statsd::expand());
+        startup.push(quote! { start_statsd(); });
+    }
+    if args.otlp {
+        items.push(otlp::expand());
+        startup.push(quote! { start_otlp_export(); });
+    }
+    if args.metrics || args.statsd || args.otlp {
+        registered = quote! { registered };
+        items.push(metrics::expand(&args.metrics_buckets, args.statsd, args.otlp));
+        dispatch_metrics_begin = quote! {
+            let metric_labels = metric_labels(&name, &operation, format.peek_version(&body));
+            let dispatch_started = std::time::Instant::now();
+            let metric_ctx = ctx.clone();
+        };
+        dispatch_metrics_record = quote! {
+            observe_latency("connector_dispatch_duration_seconds", &metric_labels, outcome(&result), dispatch_started.elapsed());
+            if let Err(error) = &result {
+                count_error(&metric_labels, metric_ctx.error_class(error));
+            }
+        };
+        handler_metrics_begin = quote! { let handler_started = std::time::Instant::now(); };
+        handler_metrics_record = quote! {
+            observe_latency("connector_handler_duration_seconds", &(name.clone(), registered.to_string()), outcome(&result), handler_started.elapsed());
+        };
+    }
+
+    let mut element_templates = quote! {};
+    if args.element_templates {
+        element_templates = element_templates::expand();
+        items.push(element_templates::route());
+        routes.push(quote! { .route("/element-templates", axum::routing::get(element_templates)) });
+    }
+
+    if args.camunda_compat || args.job_worker.is_some() {
+        items.push(camunda::expand(args.camunda_compat));
+    }
+    if args.camunda_compat {
+        routes.push(quote! { .route("/outbound", axum::routing::post(dispatch_outbound)) });
+    }
+
+    // The job worker brings its own client, others may use it as well.
+    let mut zeebe = quote! {};
+    let zeebe_client = args.zeebe_client || args.job_worker.is_some();
+    if zeebe_client {
+        zeebe = zeebe::client();
+    }
+    if let Some(worker) = &args.job_worker {
+        items.push(zeebe::worker(worker));
+        startup.push(quote! { start_job_worker(); });
+    }
+
+    // Protobuf inputs are opaque bytes, only self-describing envelopes carry expressions.
+    let mut feel_inputs = quote! {};
+    if args.feel_inputs {
+        feel_inputs = quote! {
+            let body = match format.decode::<serde_json::Value>(&body) {
+                Ok(mut envelope) => {
+                    connectors::feel::evaluate_inputs(&mut envelope).map_err(|e| {
+                        ctx.classify(connectors::ErrorClass::Validation);
+                        connectors::ExecError::Input(format!("Bad input expression for `{}`/`{}`: {}", name, operation, e))
+                    })?;
+                    format.encode(&envelope).map(axum::body::Bytes::from).unwrap_or(body)
+                }
+                Err(_) => body,
+            };
+        };
+    }
+
+    // Inbound handlers are fed by the consumers of the sources enabled here.
+    let mut consumers = Vec::new();
+    if args.amqp {
+        items.push(amqp::expand());
+        consumers.push(quote! { connectors::InboundSource::Amqp { .. } => start_amqp_consumer(r), });
+    }
+    // All MQTT handlers share one client, which subscribes to every topic and routes messages by filter.
+    if args.mqtt {
+        items.push(mqtt::expand());
+        consumers.push(quote! { connectors::InboundSource::Mqtt { .. } => {} });
+        startup.push(quote! { start_mqtt_client(); });
+    }
+    if args.sqs {
+        items.push(sqs::expand());
+        consumers.push(quote! { connectors::InboundSource::Sqs { .. } => start_sqs_poller(r), });
+    }
+    if args.websocket {
+        items.push(websocket::expand());
+        consumers.push(quote! { connectors::InboundSource::WebSocket { .. } => {} });
+        routes.push(quote! { .route("/ws/{*path}", axum::routing::get(dispatch_websocket)) });
+    }
+    items.push(webhook::expand());
+    consumers.push(quote! { connectors::InboundSource::Webhook { .. } => {} });
+    routes.push(quote! { .route("/inbound/{*path}", axum::routing::get(dispatch_webhook).post(dispatch_webhook)) });
+    items.push(inbound::expand(&consumers, zeebe_client, args.inbound_dedup.is_some()));
+    if let Some(store) = &args.inbound_dedup {
+        items.push(dedup::expand(store, args.inbound_dedup_ttl));
+    }
+    items.push(health::expand(args.health_interval, zeebe_client));
+    routes.push(quote! { .route("/readyz", axum::routing::get(readyz)) });
+    startup.push(quote! { start_health_checks(); });
+    items.push(shutdown::expand(args.drain_timeout));
+    startup.push(quote! { watch_shutdown_signals(); });
+    startup.push(quote! { start_inbound(); });
+    let mut run_call = quote! { intercept(name, operation, body, format, ctx).await };
+    let mut resolve_call_output = quote! {};
+    if let Some(store) = &args.document_store {
+        items.push(documents::expand(store, args.document_threshold));
+        startup.push(quote! { open_document_store(); });
+        run_call = quote! { with_documents(name, operation, body, format, ctx).await };
+        // In-process callers get the output itself, wherever it was offloaded to.
+        resolve_call_output = quote! {
+            let mut output = output;
+            super::resolve_documents(&mut output).await.map_err(HandlerError::from)?;
+        };
+    }
+    let (mut audit_begin, mut audit_finish) = (quote! {}, quote! {});
+    if let Some(sink) = &args.audit_log {
+        items.push(audit::expand(sink, &args.audit_redact));
+        startup.push(quote! { start_audit_log(); });
+        audit_begin = quote! { let audit = AuditCall::begin(&name, &operation, format, &body, &ctx); };
+        audit_finish = quote! { audit.finish(&result).await; };
+    }
+    // Operations sent as ` Lookup ` still find `lookup`, the envelope being rewritten for the handler's own check.
+    let mut normalize_operation = quote! {};
+    if args.normalize_operations {
+        items.push(quote! {
+            /// The registered spelling of an operation sent with surrounding whitespace or in another case.
+            fn canonical_operation(name: &str, operation: &str) -> Option<&'static str> {
+                let name = resolve_alias(name);
+                let table = dispatch_table();
+                if table.keys().any(|(n, op, _)| n == name && op == operation) {
+                    return None;
+                }
+                let trimmed = operation.trim();
+                table
+                    .keys()
+                    .filter(|(n, op, _)| n == name && op != "*" && op.eq_ignore_ascii_case(trimmed))
+                    .map(|(_, op, _)| op.as_str())
+                    .min()
+            }
+
+            fn normalize_operation(name: &str, operation: String, format: connectors::Format, body: axum::body::Bytes) -> (String, axum::body::Bytes) {
+                let Some(canonical) = canonical_operation(name, &operation) else { return (operation, body) };
+                // Protobuf envelopes don't decode here, and keep the operation as sent.
+                let Ok(mut envelope) = format.decode::<serde_json::Value>(&body) else { return (operation, body) };
+                envelope["params"]["operation"] = canonical.into();
+                match format.encode(&envelope) {
+                    Ok(bytes) => (canonical.to_string(), bytes.into()),
+                    Err(_) => (operation, body),
+                }
+            }
+        });
+        normalize_operation = quote! { let (operation, body) = normalize_operation(&name, operation, format, body); };
+    }
+    items.push(polling::expand(args.polling_state.as_ref()));
+    startup.push(quote! { start_pollers(); });
+
+    // Plugins and scripts join the dispatch table after compiled connectors, which win on a name clash.
+    let mut loaders = Vec::new();
+    if let Some(dir) = &args.wasm_plugins {
+        items.push(wasm::expand(dir));
+        loaders.push(quote! { load_wasm_plugins() });
+    }
+    if let Some(dir) = &args.native_plugins {
+        items.push(native::expand(dir));
+        loaders.push(quote! { load_native_plugins() });
+    }
+    if let Some(dir) = &args.rhai_scripts {
+        items.push(rhai::expand(dir));
+        loaders.push(quote! { load_rhai_scripts() });
+    }
+    let mut loaded_connectors = quote! {};
+    if !loaders.is_empty() {
+        items.push(plugin::expand(args.wasm_plugins.is_some() || args.native_plugins.is_some()));
+        loaded_connectors = quote! {
+            let compiled: std::collections::HashSet<String> = table.keys().map(|(name, _, _)| name.clone()).collect();
+            let mut loaded = std::collections::HashSet::new();
+            for r in [#(#loaders),*].into_iter().flatten() {
+                if compiled.contains(r.name) {
+                    tracing::error!(connector = r.name, "a compiled connector has this name, ignoring the loaded one");
+                    continue;
+                }
+                for operation in r.operations {
+                    if !loaded.insert((r.name, *operation, r.version)) {
+                        tracing::error!(connector = r.name, operation, version = r.version, "operation is loaded twice, keeping the first");
+                        continue;
+                    }
+                    table.insert((r.name.to_string(), operation.to_string(), r.version), r);
+                }
+            }
+        };
+    }
+
+    // Without connectors, inventory may have failed to link them, e.g. from a library crate nothing references.
+    let empty_registry = if args.require_connectors {
+        quote! {
+            tracing::error!("no connectors are registered, refusing to start (`require_connectors = true`)");
+            std::process::exit(1);
+        }
+    } else {
+        quote! {
+            tracing::warn!("no connectors are registered");
+            return;
+        }
+    };
+
+    // Keys of the configuration file read through `connectors::setting`, besides the reloadable ones.
+    let mut settings: Vec<String> = args.required_settings.iter().map(LitStr::value).collect();
+    items.push(response::expand());
+    settings.push("RESPONSE_ENVELOPE".to_string());
+    let config = config::expand();
+    let rename = rename::expand(args.rename_all.as_ref());
+    let envelope = envelope::expand();
+    let mut http_client = quote! {};
+    if args.http_client {
+        http_client = http_client::expand();
+        settings.extend(http_client::SETTINGS.map(String::from));
+    }
+    if args.statsd {
+        settings.extend(statsd::SETTINGS.map(String::from));
+    }
+    if args.otlp {
+        settings.extend(otlp::SETTINGS.map(String::from));
+    }
+    if args.log_levels {
+        settings.push("LOG_LEVELS".to_string());
+    }
+    let mut secrets = quote! {};
+    let mut secret_inputs = quote! {};
+    let mut redact_errors = quote! {};
+    if !args.secrets.is_empty() {
+        secrets = secrets::expand(&args.secrets);
+        secret_inputs = secrets::resolve_inputs();
+        redact_errors = quote! { let result = result.map_err(connectors::redact_error); };
+        settings.extend(["SECRETS_PROVIDER", "SECRETS_PREFIX", "SECRETS_DEFAULT_TENANT", "SECRETS_TRUST_ENVELOPE_TENANT"].map(String::from));
+        settings.extend(args.secrets.iter().flat_map(|p| p.settings()).map(|key| key.to_string()));
+    }
+    let mut database = quote! {};
+    if let Some(backend) = &args.database {
+        database = database::expand(backend);
+        settings.extend(database::SETTINGS.map(String::from));
+        if args.outbox {
+            database.extend(outbox::expand(backend));
+            items.push(outbox::publisher(backend));
+            startup.push(quote! { start_outbox_publisher(); });
+        }
+    }
+
+    // The settings are validated and resources built before anything that may call handlers starts, and after the
+    // configuration file is loaded.
+    items.push(resources::expand());
+    startup.insert(0, quote! { init_resources().await; });
+    items.push(validation::expand(&args.required_settings));
+    startup.insert(0, quote! { validate_startup(); });
+    let mut log_init = quote! { tracing_subscriber::fmt::init(); };
+    let mut log_writer = quote! { std::io::stdout };
+    if !args.secrets.is_empty() {
+        log_writer = quote! { connectors::redacting_stdout };
+    }
+    let log_layer = logging::layer(args.json_logs, &log_writer);
+    let reloadable = args.config_file.is_some() || args.admin_log_level;
+    if !args.secrets.is_empty() || args.json_logs || args.log_levels || reloadable {
+        log_init = logging::log_init(&log_layer, args.log_levels, reloadable);
+    }
+    if reloadable {
+        items.push(logging::reloadable(args.log_levels));
+    }
+    if args.admin_log_level {
+        items.push(admin::log_level(args.log_levels));
+    }
+    items.push(logging::call_log(args.json_logs, args.slow_call, args.normalize_operations));
+    if args.log_levels {
+        items.push(logging::log_levels());
+    }
+    if let Some(path) = &args.config_file {
+        items.push(reload::expand(path, args.max_in_flight, &settings, args.log_levels, args.admin_log_level));
+        startup.insert(0, reload::startup());
+    } else {
+        startup.push(quote! { report_disabled(); });
+    }
+
+    items.push(listen::items(&args.listeners));
+    items.push(forwarded::expand(&args.trusted_proxies));
+    layers.push(quote! { .layer(axum::middleware::from_fn(client_info)) });
+    let serve = listen::expand(&args.listeners);
+
+    // Tokio's defaults (one worker per core, 512 blocking threads) unless overridden.
+    let mut runtime = Vec::new();
+    if let Some(count) = args.worker_threads {
+        runtime.push(quote! { .worker_threads(#count) });
+    }
+    if let Some(count) = args.max_blocking_threads {
+        runtime.push(quote! { .max_blocking_threads(#count) });
+    }
+    if let Some(name) = &args.thread_name {
+        runtime.push(quote! { .thread_name(#name) });
+    }
+
+    quote! {
+        mod connectors {
+
+            pub type DispatcherFuture = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Output, ExecError>> + Send + 'static>>;
+
+            /// A function for compiled connectors, a closure over the plugin for loaded ones.
+            pub type ExecRaw = &'static (dyn Fn(axum::body::Bytes, Format, Context) -> DispatcherFuture + Send + Sync);
+
+            /// Why an envelope could not be turned into a handler result.
+            #[derive(Debug)]
+            pub enum ExecError {
+                /// No recipe is registered for the requested connector/operation.
+                Unsupported(String),
+                /// The envelope or the input did not match the connector's types.
+                Input(String),
+                /// The handler failed, or its output could not be serialized.
+                Handler(String),
+                /// The connector's circuit breaker is open, the call was not attempted.
+                CircuitOpen { message: String, retry_after: std::time::Duration },
+                /// Too many calls are in flight, globally or for this connector; the call was not attempted.
+                Overloaded(String),
+                /// The caller's deadline elapsed before or while the handler ran.
+                DeadlineExceeded(String),
+                /// The connector/operation was switched off through `DISABLED_CONNECTORS`.
+                Disabled(String),
+                /// The handler failed and said how the job should be retried (`HandlerError::with_retries`), or which
+                /// BPMN error to throw (`HandlerError::bpmn_error`).
+                Failed {
+                    message: String,
+                    retries: Option<u32>,
+                    retry_backoff: Option<std::time::Duration>,
+                    error_code: Option<String>,
+                },
+            }
+
+            impl ExecError {
+                pub fn status(&self) -> axum::http::StatusCode {
+                    match self {
+                        ExecError::Unsupported(_) => axum::http::StatusCode::BAD_REQUEST,
+                        ExecError::Input(_) | ExecError::Handler(_) | ExecError::Failed { .. } => {
+                            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                        }
+                        ExecError::CircuitOpen { .. } | ExecError::Overloaded(_) | ExecError::Disabled(_) => {
+                            axum::http::StatusCode::SERVICE_UNAVAILABLE
+                        }
+                        ExecError::DeadlineExceeded(_) => axum::http::StatusCode::GATEWAY_TIMEOUT,
+                    }
+                }
+
+                /// Retry instructions of a `Failed` error, as `{ "retries": 2, "retryBackoff": 5000 }` with the
+                /// backoff in milliseconds, like the fields of a Zeebe job failure, or `{ "errorCode": "..." }`.
+                pub fn retry_metadata(&self) -> Option<serde_json::Value> {
+                    let ExecError::Failed { retries, retry_backoff, error_code, .. } = self else {
+                        return None;
+                    };
+                    let mut metadata = serde_json::Map::new();
+                    if let Some(code) = error_code {
+                        metadata.insert("errorCode".into(), code.clone().into());
+                    }
+                    if let Some(retries) = retries {
+                        metadata.insert("retries".into(), (*retries).into());
+                    }
+                    if let Some(backoff) = retry_backoff {
+                        metadata.insert("retryBackoff".into(), (backoff.as_millis() as u64).into());
+                    }
+                    Some(metadata.into())
+                }
+
+                /// Whether trying the call again may succeed. BPMN errors, failures that left the job no retries
+                /// (`HandlerError::fatal`, `with_retries(0)`) and calls the envelope or input make impossible are not,
+                /// as the Zeebe worker fails such jobs for good.
+                pub fn retriable(&self) -> bool {
+                    !matches!(
+                        self,
+                        ExecError::Unsupported(_)
+                            | ExecError::Input(_)
+                            | ExecError::Failed { error_code: Some(_), .. }
+                            | ExecError::Failed { retries: Some(0), .. }
+                    )
+                }
+            }
+
+            impl axum::response::IntoResponse for ExecError {
+                fn into_response(self) -> axum::response::Response {
+                    let mut response = (self.status(), self.to_string()).into_response();
+                    let retriable = axum::http::HeaderValue::from_static(if self.retriable() { "true" } else { "false" });
+                    response.headers_mut().insert("x-error-retriable", retriable);
+                    match &self {
+                        ExecError::CircuitOpen { retry_after, .. } => {
+                            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                            response.headers_mut().insert(axum::http::header::RETRY_AFTER, secs.into());
+                        }
+                        ExecError::Overloaded(_) => {
+                            response.headers_mut().insert(axum::http::header::RETRY_AFTER, 1.into());
+                        }
+                        ExecError::Failed { retries, retry_backoff, error_code, .. } => {
+                            if let Some(code) = error_code.as_deref().and_then(|code| axum::http::HeaderValue::from_str(code).ok()) {
+                                response.headers_mut().insert("x-job-error-code", code);
+                            }
+                            if let Some(retries) = retries {
+                                response.headers_mut().insert("x-job-retries", (*retries).into());
+                            }
+                            if let Some(backoff) = retry_backoff {
+                                response.headers_mut().insert("x-job-retry-backoff", (backoff.as_millis() as u64).into());
+                            }
+                        }
+                        _ => {}
+                    }
+                    response
+                }
+            }
+
+            impl std::fmt::Display for ExecError {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    match self {
+                        ExecError::Unsupported(msg)
+                        | ExecError::Input(msg)
+                        | ExecError::Handler(msg)
+                        | ExecError::Overloaded(msg)
+                        | ExecError::DeadlineExceeded(msg)
+                        | ExecError::Disabled(msg) => f.write_str(msg),
+                        ExecError::CircuitOpen { message, .. } | ExecError::Failed { message, .. } => f.write_str(message),
+                    }
+                }
+            }
+
+            impl From<ExecError> for (axum::http::StatusCode, String) {
+                fn from(e: ExecError) -> Self {
+                    (e.status(), e.to_string())
+                }
+            }
+
+            /// What a failed call went wrong on, telling the caller's mistakes from the connector's own failures.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub enum ErrorClass {
+                /// The envelope or the input could not be decoded.
+                Deserialization,
+                /// The input decoded but was refused: an unknown operation, a missing required field, a literal
+                /// secret, a bad expression or result mapping.
+                Validation,
+                /// The handler returned an error.
+                Handler,
+                /// The caller's deadline elapsed.
+                Timeout,
+                /// The handler panicked.
+                Panic,
+                /// A service the handler called answered with a 5xx, as the handler says with `Context::classify`.
+                Downstream,
+                /// The call was refused before it ran: shed, circuit-broken, disabled or draining.
+                Unavailable,
+            }
+
+            impl ErrorClass {
+                pub fn label(self) -> &'static str {
+                    match self {
+                        ErrorClass::Deserialization => "deserialization",
+                        ErrorClass::Validation => "validation",
+                        ErrorClass::Handler => "handler",
+                        ErrorClass::Timeout => "timeout",
+                        ErrorClass::Panic => "panic",
+                        ErrorClass::Downstream => "downstream_5xx",
+                        ErrorClass::Unavailable => "unavailable",
+                    }
+                }
+
+                /// The class an error has when its call's context wasn't given one.
+                pub fn of(error: &ExecError) -> Self {
+                    match error {
+                        ExecError::Unsupported(_) => ErrorClass::Validation,
+                        ExecError::Input(_) => ErrorClass::Deserialization,
+                        ExecError::Handler(_) | ExecError::Failed { .. } => ErrorClass::Handler,
+                        ExecError::DeadlineExceeded(_) => ErrorClass::Timeout,
+                        ExecError::CircuitOpen { .. } | ExecError::Overloaded(_) | ExecError::Disabled(_) => ErrorClass::Unavailable,
+                    }
+                }
+            }
+
+            /// Error type handlers can return to control retries. Any other `Display` error is never retried in-process,
+            /// and the engine decrements the job's retries.
+            #[derive(Debug, Clone)]
+            pub struct HandlerError {
+                pub message: String,
+                pub retriable: bool,
+                /// Retries the engine should leave on the job, e.g. 0 to fail it for good.
+                pub retries: Option<u32>,
+                /// How long the engine should wait before retrying the job.
+                pub retry_backoff: Option<std::time::Duration>,
+                /// BPMN error to throw instead of failing the job, caught by a boundary or event subprocess.
+                pub error_code: Option<String>,
+            }
+
+            impl HandlerError {
+                /// A transient failure, retried up to the connector's `max_attempts`.
+                pub fn retriable(message: impl Into<String>) -> Self {
+                    HandlerError { message: message.into(), retriable: true, retries: None, retry_backoff: None, error_code: None }
+                }
+
+                /// A permanent failure, surfaced immediately, that leaves the job no retries.
+                pub fn fatal(message: impl Into<String>) -> Self {
+                    HandlerError { message: message.into(), retriable: false, retries: Some(0), retry_backoff: None, error_code: None }
+                }
+
+                /// A business error, thrown in the process as the BPMN error `code` rather than failing the job.
+                pub fn bpmn_error(code: impl Into<String>, message: impl Into<String>) -> Self {
+                    HandlerError { error_code: Some(code.into()), retries: None, ..HandlerError::fatal(message) }
+                }
+
+                /// A failure retrying in-process won't fix, leaving the engine's retries to count down.
+                fn failed(message: impl Into<String>) -> Self {
+                    HandlerError { message: message.into(), retriable: false, retries: None, retry_backoff: None, error_code: None }
+                }
+
+                /// Fails the job with `retries` retries left, instead of letting the engine decrement its count.
+                pub fn with_retries(mut self, retries: u32) -> Self {
+                    self.retries = Some(retries);
+                    self
+                }
+
+                pub fn with_retry_backoff(mut self, backoff: std::time::Duration) -> Self {
+                    self.retry_backoff = Some(backoff);
+                    self
+                }
+
+                pub fn from_error<E: std::fmt::Display + 'static>(e: E) -> Self {
+                    match (&e as &dyn std::any::Any).downcast_ref::<HandlerError>() {
+                        Some(e) => e.clone(),
+                        None => HandlerError::failed(e.to_string()),
+                    }
+                }
+            }
+
+            impl std::fmt::Display for HandlerError {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str(&self.message)
+                }
+            }
+
+            impl std::error::Error for HandlerError {}
+
+            impl From<HandlerError> for ExecError {
+                fn from(e: HandlerError) -> Self {
+                    match (e.retries, e.retry_backoff, e.error_code) {
+                        (None, None, None) => ExecError::Handler(e.message),
+                        (retries, retry_backoff, error_code) => ExecError::Failed { message: e.message, retries, retry_backoff, error_code },
+                    }
+                }
+            }
+
+            /// How a connector called through `call` failed, as seen by its caller: retry instructions and BPMN errors
+            /// pass through, a call that was not attempted (open breaker, overload, deadline) is retriable, and one the
+            /// envelope or input make impossible is fatal.
+            impl From<ExecError> for HandlerError {
+                fn from(e: ExecError) -> Self {
+                    match e {
+                        ExecError::Failed { message, retries, retry_backoff, error_code } => {
+                            HandlerError { message, retriable: false, retries, retry_backoff, error_code }
+                        }
+                        e @ (ExecError::CircuitOpen { .. } | ExecError::Overloaded(_) | ExecError::DeadlineExceeded(_)) => {
+                            HandlerError::retriable(e.to_string())
+                        }
+                        e @ (ExecError::Unsupported(_) | ExecError::Input(_)) => HandlerError::fatal(e.to_string()),
+                        e => HandlerError::failed(e.to_string()),
+                    }
+                }
+            }
+
+            /// Runs the registered connector `name` in-process, with `input` as its `params.input`, and decodes its
+            /// output. The call takes the same path as one over HTTP (aliases, disabling, breakers, stats), on a fork
+            /// of `ctx`, so a connector can chain others, or fan out to them with `tokio::try_join!`.
+            pub async fn call<T: serde::de::DeserializeOwned>(
+                ctx: &Context,
+                name: &str,
+                operation: &str,
+                input: impl serde::Serialize,
+            ) -> Result<T, HandlerError> {
+                let input = serde_json::to_value(input)
+                    .map_err(|e| HandlerError::fatal(format!("Bad input for `{}`/`{}`: {}", name, operation, e)))?;
+                let envelope = serde_json::json!({ "params": { "operation": operation, "input": input } });
+                let body = axum::body::Bytes::from(envelope.to_string());
+                let output = super::execute(name.to_string(), operation.to_string(), body, Format::Json, ctx.fork()).await?;
+                let output = output.into_value().map_err(HandlerError::fatal)?;
+                #resolve_call_output
+                serde_json::from_value(output)
+                    .map_err(|e| HandlerError::fatal(format!("Bad output from `{}`/`{}`: {}", name, operation, e)))
+            }
+
+            /// Exponential backoff (capped at 30s) with equal jitter for the given 1-based attempt.
+            pub fn retry_backoff(base: std::time::Duration, attempt: u32) -> std::time::Duration {
+                use std::hash::{BuildHasher, Hasher};
+                let exp = base.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(std::time::Duration::from_secs(30));
+                let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+                hasher.write_u32(attempt);
+                let jitter = (hasher.finish() % 1000) as u32;
+                exp / 2 + (exp / 2) * jitter / 1000
+            }
+
+            /// Maps a free-form caller id onto a `u64` id: numeric ids are passed through, anything else is hashed
+            /// (FNV-1a) so redeliveries of the same message reach the handler with the same id.
+            pub fn job_id(id: &str) -> u64 {
+                id.parse().unwrap_or_else(|_| {
+                    id.bytes().fold(0xcbf29ce484222325_u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+                })
+            }
+
+            /// The envelope's id as the handler's first parameter types it, `u64`, `i64`, `String` or `uuid::Uuid`:
+            /// numbers and strings are converted into each other, and ids that don't fit a numeric type are hashed
+            /// with `job_id`, keeping the low 63 bits for signed ones. A missing or null id is the type's default.
+            pub fn envelope_id<T: serde::de::DeserializeOwned + Default>(value: serde_json::Value) -> Result<T, String> {
+                let alternatives = match &value {
+                    serde_json::Value::Null => return Ok(T::default()),
+                    serde_json::Value::Number(n) => vec![serde_json::Value::String(n.to_string()), job_id(&n.to_string()).into()],
+                    serde_json::Value::String(s) => match serde_json::from_str::<serde_json::Number>(s.trim()) {
+                        Ok(n) => vec![serde_json::Value::Number(n)],
+                        Err(_) => vec![job_id(s).into(), (job_id(s) & i64::MAX as u64).into()],
+                    },
+                    _ => Vec::new(),
+                };
+                serde_json::from_value(value.clone()).or_else(|e| {
+                    alternatives
+                        .into_iter()
+                        .find_map(|value| serde_json::from_value(value).ok())
+                        .ok_or_else(|| format!("bad id {}: {}", value, e))
+                })
+            }
+
+            /// `#[serde(deserialize_with)]` of the `id` of generated requests, see `envelope_id`.
+            pub fn deserialize_id<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+                T: serde::de::DeserializeOwned + Default,
+            {
+                let value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+                envelope_id(value).map_err(serde::de::Error::custom)
+            }
+
+            fn config_file_settings() -> &'static std::sync::RwLock<std::collections::HashMap<String, String>> {
+                static SETTINGS: std::sync::OnceLock<std::sync::RwLock<std::collections::HashMap<String, String>>> = std::sync::OnceLock::new();
+                SETTINGS.get_or_init(Default::default)
+            }
+
+            /// A runtime setting: the line of the `config_file` for `key` as of its last load, else the environment
+            /// variable of the same name.
+            pub fn setting(key: &str) -> Option<String> {
+                let file = config_file_settings().read().unwrap().get(key).cloned();
+                file.or_else(|| std::env::var(key).ok()).filter(|value| !value.is_empty())
+            }
+
+            /// A duration setting such as `500ms`, `30s`, `5m` or `1h`, `default` when unset.
+            pub fn duration_setting(key: &str, default: std::time::Duration) -> Result<std::time::Duration, String> {
+                let Some(value) = setting(key) else { return Ok(default) };
+                parse_duration(&value).ok_or_else(|| format!("{} must be a duration such as `30s`, got `{}`", key, value))
+            }
+
+            /// A duration such as `500ms`, `30s`, `5m` or `1h`.
+            pub fn parse_duration(value: &str) -> Option<std::time::Duration> {
+                let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+                let (amount, unit) = value.split_at(split);
+                let amount: u64 = amount.parse().ok()?;
+                match unit {
+                    "ms" => Some(std::time::Duration::from_millis(amount)),
+                    "s" => Some(std::time::Duration::from_secs(amount)),
+                    "m" => Some(std::time::Duration::from_secs(amount * 60)),
+                    "h" => Some(std::time::Duration::from_secs(amount * 3600)),
+                    _ => None,
+                }
+            }
+
+            pub fn set_config_file_settings(settings: std::collections::HashMap<String, String>) {
+                *config_file_settings().write().unwrap() = settings;
+            }
+
+            /// Whether `DISABLED_CONNECTORS` (comma-separated `name` or `name.operation` entries) switches the call off.
+            pub fn is_disabled(name: &str, operation: &str) -> bool {
+                disabled().read().unwrap().iter().any(|entry| match entry.split_once('.') {
+                    Some((n, op)) => n == name && op == operation,
+                    None => entry == name,
+                })
+            }
+
+            pub fn disabled_connectors() -> Vec<String> {
+                disabled().read().unwrap().clone()
+            }
+
+            pub fn set_disabled_connectors(list: &str) {
+                *disabled().write().unwrap() = parse_disabled(list);
+            }
+
+            pub fn disable_connector(entry: &str) {
+                let mut disabled = disabled().write().unwrap();
+                if !disabled.iter().any(|e| e == entry) {
+                    disabled.push(entry.to_string());
+                }
+            }
+
+            pub fn enable_connector(entry: &str) {
+                disabled().write().unwrap().retain(|e| e != entry);
+            }
+
+            fn disabled() -> &'static std::sync::RwLock<Vec<String>> {
+                static DISABLED: std::sync::OnceLock<std::sync::RwLock<Vec<String>>> = std::sync::OnceLock::new();
+                DISABLED.get_or_init(|| std::sync::RwLock::new(parse_disabled(&std::env::var("DISABLED_CONNECTORS").unwrap_or_default())))
+            }
+
+            fn parse_disabled(list: &str) -> Vec<String> {
+                list.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(str::to_string).collect()
+            }
+
+            /// Bumped by every cache flush (a `config_file` reload, the admin API), so that caches of secrets or other
+            /// derived state can tell they are stale and refetch. Response caches of `cache = "..."` connectors follow it.
+            pub fn cache_generation() -> u64 {
+                CACHE_GENERATION.load(std::sync::atomic::Ordering::Acquire)
+            }
+
+            /// Resolves at the next cache flush, e.g. to clear a cache from a background task.
+            pub async fn caches_flushed() {
+                CACHES_FLUSHED.notified().await
+            }
+
+            pub fn flush_caches() {
+                CACHE_GENERATION.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+                CACHES_FLUSHED.notify_waiters();
+            }
+
+            static CACHE_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+            static CACHES_FLUSHED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+
+            /// Index of this process among those sharing a port with `reuse_port`, from `CONNECTOR_SHARD_INDEX`, so
+            /// per-shard resources (a partition, a file, a port range) can be picked without coordination.
+            pub fn shard_index() -> Option<usize> {
+                static SHARD: std::sync::OnceLock<Option<usize>> = std::sync::OnceLock::new();
+                *SHARD.get_or_init(|| std::env::var("CONNECTOR_SHARD_INDEX").ok().and_then(|v| v.parse().ok()))
+            }
+
+            /// A slot in an in-flight counter, released on drop.
+            pub struct InFlight(&'static std::sync::atomic::AtomicUsize);
+
+            impl InFlight {
+                /// Takes a slot unless `max` calls already hold one; callers shed the request instead of queueing it.
+                pub fn acquire(counter: &'static std::sync::atomic::AtomicUsize, max: usize) -> Option<Self> {
+                    use std::sync::atomic::Ordering;
+                    counter
+                        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1))
+                        .ok()
+                        .map(|_| InFlight(counter))
+                }
+            }
+
+            impl Drop for InFlight {
+                fn drop(&mut self) {
+                    self.0.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+                }
+            }
+
+            thread_local! {
+                static IN_HANDLER: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+                /// Location and backtrace of the last handler panic on this thread, left by the panic hook.
+                static LAST_PANIC: std::cell::Cell<Option<(String, std::backtrace::Backtrace)>> = const { std::cell::Cell::new(None) };
+            }
+
+            /// Captures the backtrace of panics raised while polling a handler; other panics go to the previous hook.
+            pub fn install_panic_hook() {
+                let previous = std::panic::take_hook();
+                std::panic::set_hook(Box::new(move |info| {
+                    if IN_HANDLER.get() {
+                        let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+                        LAST_PANIC.set(Some((location, std::backtrace::Backtrace::force_capture())));
+                    } else {
+                        previous(info);
+                    }
+                }));
+            }
+
+            /// Runs a recipe, turning a panic in the handler into a logged `ExecError::Handler` instead of
+            /// tearing down the task that serves the request. Panics and 5xx errors go to the error reporters.
+            pub async fn catch_panic(name: &str, operation: &str, ctx: &Context, mut exec: DispatcherFuture) -> Result<Output, ExecError> {
+                let caught = std::future::poll_fn(|cx| {
+                    // A handler awaiting another call polls a nested `catch_panic`, which must not clear the outer flag.
+                    let was_in_handler = IN_HANDLER.replace(true);
+                    let poll = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| exec.as_mut().poll(cx)));
+                    IN_HANDLER.set(was_in_handler);
+                    match poll {
+                        Ok(std::task::Poll::Ready(result)) => std::task::Poll::Ready(Ok(result)),
+                        Ok(std::task::Poll::Pending) => std::task::Poll::Pending,
+                        Err(payload) => std::task::Poll::Ready(Err(payload)),
+                    }
+                })
+                .await;
+                let payload = match caught {
+                    Ok(result) => {
+                        if let Err(error) = &result {
+                            if error.status().is_server_error() {
+                                report_error(&ErrorReport { name, operation, request_id: ctx.request_id(), error, panic: None });
+                            }
+                        }
+                        return result;
+                    }
+                    Err(payload) => payload,
+                };
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Box<dyn Any>".to_string());
+                let (location, backtrace) = LAST_PANIC.take().unzip();
+                let panic = HandlerPanic {
+                    message,
+                    location: location.unwrap_or_default(),
+                    backtrace: backtrace.map(|b| b.to_string()).unwrap_or_default(),
+                };
+                tracing::error!(
+                    connector = name,
+                    operation,
+                    location = panic.location,
+                    "handler panicked: {}\n{}",
+                    panic.message,
+                    panic.backtrace,
+                );
+                let error = ExecError::Handler(format!("Handler for `{}`/`{}` panicked: {}", name, operation, panic.message));
+                ctx.classify(ErrorClass::Panic);
+                report_error(&ErrorReport { name, operation, request_id: ctx.request_id(), error: &error, panic: Some(&panic) });
+                Err(error)
+            }
+
+            /// A handler's panic.
+            #[derive(Debug)]
+            pub struct HandlerPanic {
+                pub message: String,
+                /// `file:line:column` of the panic.
+                pub location: String,
+                pub backtrace: String,
+            }
+
+            /// A failed call, as error reporters see it.
+            pub struct ErrorReport<'a> {
+                pub name: &'a str,
+                pub operation: &'a str,
+                pub request_id: Option<&'a str>,
+                pub error: &'a ExecError,
+                /// Set when the handler panicked, `error` then being the `ExecError::Handler` the caller gets.
+                pub panic: Option<&'a HandlerPanic>,
+            }
+
+            /// Receives handler panics and 5xx errors (`#[camunda_error_reporter]`), e.g. to forward them to Sentry.
+            pub trait ErrorReporter: Send + Sync + 'static {
+                fn report(&self, report: &ErrorReport<'_>);
+            }
+
+            pub struct ErrorReporterRecipe {
+                /// The implementing type's name.
+                pub name: &'static str,
+                pub instance: fn() -> &'static dyn ErrorReporter,
+            }
+
+            ::inventory::collect!(ErrorReporterRecipe);
+
+            pub fn error_reporters() -> &'static [&'static dyn ErrorReporter] {
+                static REPORTERS: std::sync::OnceLock<Vec<&'static dyn ErrorReporter>> = std::sync::OnceLock::new();
+                REPORTERS.get_or_init(|| {
+                    let mut recipes: Vec<&'static ErrorReporterRecipe> = ::inventory::iter::<ErrorReporterRecipe>.into_iter().collect();
+                    recipes.sort_by_key(|r| r.name);
+                    recipes.into_iter().map(|r| (r.instance)()).collect()
+                })
+            }
+
+            fn report_error(report: &ErrorReport<'_>) {
+                for reporter in error_reporters() {
+                    reporter.report(report);
+                }
+            }
+
+            /// 32 hex digits, unique within the process and unlikely to collide across processes.
+            pub fn new_request_id() -> String {
+                use std::hash::{BuildHasher, Hasher};
+                static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64;
+                let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+                hasher.write_u64(COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+                format!("{:016x}{:016x}", nanos, hasher.finish())
+            }
+
+            /// Per-call context, handed to handlers that take a `ctx: connectors::Context` parameter after their input.
+            #[derive(Clone, Default)]
+            pub struct Context {
+                cancellation: std::sync::Arc<Cancellation>,
+                deadline: Option<std::time::Instant>,
+                client: ClientInfo,
+                job: Option<std::sync::Arc<JobContext>>,
+                request_id: Option<std::sync::Arc<str>>,
+                envelope: Option<std::sync::Arc<EnvelopeMeta>>,
+                warnings: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+                error_class: std::sync::Arc<std::sync::Mutex<Option<ErrorClass>>>,
+                timings: std::sync::Arc<std::sync::Mutex<CallTimings>>,
+            }
+
+            /// Where a call's time went, as the slow-call warning reports it. A retried handler adds up its attempts.
+            #[derive(Debug, Clone, Copy, Default)]
+            pub struct CallTimings {
+                /// Decoding the envelope into the handler's input.
+                pub deserialize: std::time::Duration,
+                pub handler: std::time::Duration,
+                /// Encoding the handler's output.
+                pub serialize: std::time::Duration,
+            }
+
+            /// The Zeebe job behind a call, for calls arriving as jobs rather than envelopes.
+            #[derive(Debug, Clone, Default, serde::Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            pub struct JobContext {
+                #[serde(default, deserialize_with = "key_string")]
+                pub job_key: String,
+                #[serde(rename = "type", default)]
+                pub job_type: String,
+                #[serde(default, deserialize_with = "key_string")]
+                pub process_instance_key: String,
+                #[serde(default, alias = "bpmnProcessId")]
+                pub process_definition_id: String,
+                #[serde(default)]
+                pub element_id: String,
+                #[serde(default)]
+                pub retries: u32,
+                /// Unix epoch milliseconds.
+                #[serde(default)]
+                pub deadline: Option<u64>,
+                #[serde(default)]
+                pub custom_headers: std::collections::HashMap<String, String>,
+                #[serde(default)]
+                pub variables: serde_json::Map<String, serde_json::Value>,
+                #[serde(default)]
+                pub tenant_id: Option<String>,
+            }
+
+            /// Zeebe keys are 64-bit numbers, sent as strings by the REST API.
+            fn key_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+                Ok(match <serde_json::Value as serde::Deserialize>::deserialize(deserializer)? {
+                    serde_json::Value::String(key) => key,
+                    serde_json::Value::Null => String::new(),
+                    other => other.to_string(),
+                })
+            }
+
+            /// Who the request is from, once trusted proxies in front of the runtime are accounted for.
+            #[derive(Debug, Clone, Default)]
+            pub struct ClientInfo {
+                /// Effective client IP, `None` on a Unix socket without forwarding headers.
+                pub ip: Option<std::net::IpAddr>,
+                /// Scheme the client used (`X-Forwarded-Proto`), when a trusted proxy said so.
+                pub proto: Option<String>,
+            }
+
+            #[derive(Default)]
+            struct Cancellation {
+                cancelled: std::sync::atomic::AtomicBool,
+                notify: tokio::sync::Notify,
+            }
+
+            impl Context {
+                /// Reads the caller's deadline from `X-Request-Deadline`, in Unix epoch milliseconds like a Camunda
+                /// job's `deadline`, and its `X-Request-Id`, generating one when it sent none.
+                pub fn from_headers(headers: &axum::http::HeaderMap) -> Result<Self, String> {
+                    let request_id = headers
+                        .get("x-request-id")
+                        .and_then(|v| v.to_str().ok())
+                        .filter(|id| !id.is_empty() && id.len() <= 128)
+                        .map_or_else(new_request_id, str::to_string);
+                    let ctx = Context { request_id: Some(request_id.into()), ..Context::default() };
+                    let Some(value) = headers.get("x-request-deadline") else {
+                        return Ok(ctx);
+                    };
+                    let deadline_ms: u64 = value
+                        .to_str()
+                        .ok()
+                        .and_then(|v| v.trim().parse().ok())
+                        .ok_or_else(|| "Invalid X-Request-Deadline, expected Unix epoch milliseconds".to_string())?;
+                    Ok(ctx.with_epoch_deadline(deadline_ms))
+                }
+
+                /// Sets the deadline from Unix epoch milliseconds, as found in headers and jobs.
+                pub fn with_epoch_deadline(self, deadline_ms: u64) -> Self {
+                    let now_ms = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+                    let remaining = std::time::Duration::from_millis(deadline_ms.saturating_sub(now_ms));
+                    self.with_deadline(std::time::Instant::now() + remaining)
+                }
+
+                pub fn with_deadline(mut self, deadline: std::time::Instant) -> Self {
+                    self.deadline = Some(deadline);
+                    self
+                }
+
+                pub fn with_client(mut self, client: ClientInfo) -> Self {
+                    self.client = client;
+                    self
+                }
+
+                /// Jobs without a request id of their own are identified by their key.
+                pub fn with_job(mut self, job: JobContext) -> Self {
+                    self.request_id.get_or_insert_with(|| job.job_key.as_str().into());
+                    self.job = Some(std::sync::Arc::new(job));
+                    self
+                }
+
+                /// Sets the id the call's logs and error reports carry, such as an asynchronous job's token.
+                pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+                    self.request_id = Some(request_id.into().into());
+                    self
+                }
+
+                /// The caller's `X-Request-Id`, one generated for the request, or the job's key.
+                pub fn request_id(&self) -> Option<&str> {
+                    self.request_id.as_deref()
+                }
+
+                /// The Zeebe job, with its custom headers and process variables, when the call is one.
+                pub fn job(&self) -> Option<&JobContext> {
+                    self.job.as_deref()
+                }
+
+                pub fn with_envelope(mut self, envelope: EnvelopeMeta) -> Self {
+                    self.envelope = Some(std::sync::Arc::new(envelope));
+                    self
+                }
+
+                /// The tenant, headers and metadata of a version 2 envelope, when the call came in one.
+                pub fn envelope(&self) -> Option<&EnvelopeMeta> {
+                    self.envelope.as_deref()
+                }
+
+                /// Adds a warning to the call's response envelope, see `RESPONSE_ENVELOPE`; sub-calls add theirs to
+                /// their caller's.
+                pub fn warn(&self, warning: impl Into<String>) {
+                    self.warnings.lock().unwrap_or_else(|e| e.into_inner()).push(warning.into());
+                }
+
+                pub fn warnings(&self) -> Vec<String> {
+                    self.warnings.lock().unwrap_or_else(|e| e.into_inner()).clone()
+                }
+
+                /// Classes the call's failure, should it fail, e.g. as `ErrorClass::Downstream` once the API the
+                /// handler called answered with a 5xx. The last class given wins.
+                pub fn classify(&self, class: ErrorClass) {
+                    *self.error_class.lock().unwrap_or_else(|e| e.into_inner()) = Some(class);
+                }
+
+                /// The class of the call's error: the one it was given, else the error's own.
+                pub fn error_class(&self, error: &ExecError) -> ErrorClass {
+                    self.error_class.lock().unwrap_or_else(|e| e.into_inner()).unwrap_or_else(|| ErrorClass::of(error))
+                }
+
+                /// Adds to the call's timings, as generated handlers do around decoding, the handler and encoding.
+                pub fn record_timings(&self, record: impl FnOnce(&mut CallTimings)) {
+                    record(&mut self.timings.lock().unwrap_or_else(|e| e.into_inner()));
+                }
+
+                pub fn timings(&self) -> CallTimings {
+                    *self.timings.lock().unwrap_or_else(|e| e.into_inner())
+                }
+
+                /// The tenant named by the envelope, else the job's. The envelope's is whatever the caller sent; secrets
+                /// only trust it with `SECRETS_TRUST_ENVELOPE_TENANT=true`.
+                pub fn tenant(&self) -> Option<&str> {
+                    let envelope = self.envelope().and_then(|envelope| envelope.tenant.as_deref());
+                    envelope.or_else(|| self.job().and_then(|job| job.tenant_id.as_deref())).filter(|tenant| !tenant.is_empty())
+                }
+
+                /// A context for a sub-call: same deadline, client, job and request id, but cancelled independently.
+                pub fn fork(&self) -> Self {
+                    Context {
+                        cancellation: Default::default(),
+                        deadline: self.deadline,
+                        client: self.client.clone(),
+                        job: self.job.clone(),
+                        request_id: self.request_id.clone(),
+                        envelope: self.envelope.clone(),
+                        warnings: self.warnings.clone(),
+                        error_class: Default::default(),
+                        timings: Default::default(),
+                    }
+                }
+
+                pub fn client(&self) -> &ClientInfo {
+                    &self.client
+                }
+
+                /// See [`shard_index`].
+                pub fn shard_index(&self) -> Option<usize> {
+                    shard_index()
+                }
+
+                /// When the caller stops waiting for the result, if it said so.
+                pub fn deadline(&self) -> Option<std::time::Instant> {
+                    self.deadline
+                }
+
+                /// Time left before the deadline, to pass on as the budget of downstream calls.
+                pub fn remaining(&self) -> Option<std::time::Duration> {
+                    self.deadline.map(|deadline| deadline.saturating_duration_since(std::time::Instant::now()))
+                }
+
+                /// Whether the call is over: it completed, or the caller went away and its result is no longer wanted.
+                pub fn is_cancelled(&self) -> bool {
+                    self.cancellation.cancelled.load(std::sync::atomic::Ordering::Acquire)
+                }
+
+                /// Resolves once the call is cancelled, e.g. to `select!` against work spawned by the handler.
+                pub async fn cancelled(&self) {
+                    let notified = self.cancellation.notify.notified();
+                    let mut notified = std::pin::pin!(notified);
+                    notified.as_mut().enable();
+                    if !self.is_cancelled() {
+                        notified.await;
+                    }
+                }
+
+                pub fn cancel(&self) {
+                    self.cancellation.cancelled.store(true, std::sync::atomic::Ordering::Release);
+                    self.cancellation.notify.notify_waiters();
+                }
+
+                /// Cancels the context when the returned guard is dropped, including when the call's future is.
+                pub fn cancel_on_drop(&self) -> CancelOnDrop {
+                    CancelOnDrop(self.clone())
+                }
+            }
+
+            pub struct CancelOnDrop(Context);
+
+            impl Drop for CancelOnDrop {
+                fn drop(&mut self) {
+                    self.0.cancel();
+                }
+            }
+
+            /// Successful results of a `cache = "..."` connector, keyed by a hash of the request format, operation, version
+            /// and input. The operation tells apart the variants of an operation enum, which share one handler and cache.
+            pub struct ResponseCache {
+                ttl: std::time::Duration,
+                capacity: usize,
+                entries: std::sync::Mutex<CacheEntries>,
+            }
+
+            #[derive(Default)]
+            struct CacheEntries {
+                /// The `cache_generation()` the entries were stored under, a flush empties the cache on the next insert.
+                generation: u64,
+                entries: std::collections::HashMap<u64, (std::time::Instant, Output)>,
+                /// Insertion order, oldest first, to evict expired entries and keep the cache within its capacity.
+                order: std::collections::VecDeque<(u64, std::time::Instant)>,
+            }
+
+            impl ResponseCache {
+                pub fn new(ttl: std::time::Duration, capacity: usize) -> Self {
+                    ResponseCache { ttl, capacity, entries: Default::default() }
+                }
+
+                /// `None` when the input can't be read out of the envelope; the call then bypasses the cache.
+                pub fn key(&self, format: Format, bytes: &[u8]) -> Option<u64> {
+                    use std::hash::{Hash, Hasher};
+                    let input = format.peek_input(bytes).ok()?;
+                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                    format.name().hash(&mut hasher);
+                    format.peek_operation(bytes).ok()?.hash(&mut hasher);
+                    format.peek_version(bytes).hash(&mut hasher);
+                    input.hash(&mut hasher);
+                    Some(hasher.finish())
+                }
+
+                pub fn get(&self, key: u64) -> Option<Output> {
+                    let cache = self.entries.lock().unwrap();
+                    if cache.generation != cache_generation() {
+                        return None;
+                    }
+                    cache.entries.get(&key).filter(|(at, _)| at.elapsed() < self.ttl).map(|(_, out)| out.clone())
+                }
+
+                pub fn insert(&self, key: u64, out: Output) {
+                    let mut cache = self.entries.lock().unwrap();
+                    let generation = cache_generation();
+                    if cache.generation != generation {
+                        cache.entries.clear();
+                        cache.order.clear();
+                        cache.generation = generation;
+                    }
+                    let now = std::time::Instant::now();
+                    cache.entries.insert(key, (now, out));
+                    cache.order.push_back((key, now));
+                    // Entries expire in insertion order, so the expired ones and those over capacity are all at the
+                    // front. A key stored again leaves its older position behind, which no longer evicts it.
+                    while let Some(&(oldest, at)) = cache.order.front() {
+                        if cache.order.len() <= self.capacity && at.elapsed() < self.ttl {
+                            break;
+                        }
+                        cache.order.pop_front();
+                        if cache.entries.get(&oldest).is_some_and(|(stored, _)| *stored == at) {
+                            cache.entries.remove(&oldest);
+                        }
+                    }
+                }
+            }
+
+            /// Address of the client on the other end of a TCP listener, as `axum::extract::ConnectInfo<ClientAddr>`.
+            /// Behind a load balancer speaking the PROXY protocol, this is the original client, not the balancer.
+            #[derive(Debug, Clone, Copy)]
+            pub struct ClientAddr(pub std::net::SocketAddr);
+
+            impl axum::extract::connect_info::Connected<axum::serve::IncomingStream<'_, tokio::net::TcpListener>> for ClientAddr {
+                fn connect_info(stream: axum::serve::IncomingStream<'_, tokio::net::TcpListener>) -> Self {
+                    ClientAddr(*stream.remote_addr())
+                }
+            }
+
+            impl axum::extract::connect_info::Connected<std::net::SocketAddr> for ClientAddr {
+                fn connect_info(addr: std::net::SocketAddr) -> Self {
+                    ClientAddr(addr)
+                }
+            }
+
+            /// Operations of an enum taking a whole connector, one per variant; see `#[derive(ConnectorOperations)]`.
+            pub trait Operations {
+                const OPERATIONS: &'static [&'static str];
+            }
+
+            /// Input of a catch-all (`operation = "*"`) handler: the operation as sent, and its input.
+            #[derive(Debug, serde::Deserialize)]
+            pub struct AnyOperation<T = serde_json::Value> {
+                pub operation: String,
+                pub input: T,
+            }
+
+            /// Modeler metadata of one input field; see `#[derive(ConnectorInput)]`.
+            #[derive(Debug, Clone, Copy)]
+            pub struct FieldMeta {
+                /// The field's name in the envelope's `input`, after serde renames.
+                pub name: &'static str,
+                pub label: &'static str,
+                pub description: Option<&'static str>,
+                pub group: Option<&'static str>,
+                /// Element template property type: `String`, `Number`, `Boolean`, or `Text` for JSON values.
+                pub kind: &'static str,
+                pub optional: bool,
+                /// `optional` or `required`, when the field takes FEEL expressions.
+                pub feel: Option<&'static str>,
+                /// Holds a credential, to be filled from a Camunda secret.
+                pub secret: bool,
+                /// Filled in when the input lacks the field, or leaves it null or empty, as typed by `kind`.
+                pub default: Option<&'static str>,
+            }
+
+            impl FieldMeta {
+                /// The default as a JSON value: numbers and booleans parsed, `Text` as JSON when it parses.
+                pub fn default_value(&self) -> Option<serde_json::Value> {
+                    let default = self.default?;
+                    Some(match self.kind {
+                        "Number" | "Text" => serde_json::from_str(default).unwrap_or_else(|_| default.into()),
+                        "Boolean" => (default == "true").into(),
+                        _ => default.into(),
+                    })
+                }
+            }
+
+            /// Input structs described field by field, for element templates and validation.
+            pub trait ConnectorInput {
+                const FIELDS: &'static [FieldMeta];
+            }
+
+            /// Picks `ConnectorInput::FIELDS` for inputs that implement it and `None` for others, through autoref
+            /// method resolution since recipes can't name the bound.
+            pub struct InputProbe<T>(pub std::marker::PhantomData<T>);
+
+            pub trait InputFields {
+                fn input_fields(&self) -> Option<&'static [FieldMeta]>;
+            }
+
+            impl<T: ConnectorInput> InputFields for InputProbe<T> {
+                fn input_fields(&self) -> Option<&'static [FieldMeta]> {
+                    Some(T::FIELDS)
+                }
+            }
+
+            pub trait NoInputFields {
+                fn input_fields(&self) -> Option<&'static [FieldMeta]>;
+            }
+
+            impl<T> NoInputFields for &InputProbe<T> {
+                fn input_fields(&self) -> Option<&'static [FieldMeta]> {
+                    None
+                }
+            }
+
+            /// The first required field that the envelope's input lacks, or leaves null or empty. Inputs that can't be
+            /// read as JSON are left to the typed decoding to reject.
+            pub fn missing_field(fields: &'static [FieldMeta], format: Format, bytes: &[u8]) -> Option<&'static FieldMeta> {
+                let input: serde_json::Value = serde_json::from_slice(&format.peek_input(bytes).ok()?).ok()?;
+                fields.iter().filter(|field| !field.optional).find(|field| match input.get(field.name) {
+                    None | Some(serde_json::Value::Null) => true,
+                    Some(serde_json::Value::String(s)) => s.is_empty(),
+                    Some(_) => false,
+                })
+            }
+
+            /// Fills the fields the envelope's input lacks, or leaves null or empty, with their defaults. The envelope is
+            /// only re-encoded when a default applies.
+            pub fn apply_defaults(fields: &'static [FieldMeta], format: Format, bytes: axum::body::Bytes) -> Result<axum::body::Bytes, ExecError> {
+                let unset = |value: Option<&serde_json::Value>| match value {
+                    None | Some(serde_json::Value::Null) => true,
+                    Some(serde_json::Value::String(s)) => s.is_empty(),
+                    Some(_) => false,
+                };
+                if fields.iter().all(|field| field.default.is_none()) {
+                    return Ok(bytes);
+                }
+                let Ok(input) = format.peek_input(&bytes).and_then(|input| serde_json::from_slice::<serde_json::Value>(&input).map_err(|e| e.to_string())) else {
+                    return Ok(bytes);
+                };
+                if !fields.iter().any(|field| field.default.is_some() && unset(input.get(field.name))) {
+                    return Ok(bytes);
+                }
+                let mut envelope: serde_json::Value = format
+                    .decode(&bytes)
+                    .map_err(|e| ExecError::Input(format!("Bad {}: {}", format.name(), e)))?;
+                let input = &mut envelope["params"]["input"];
+                if input.is_null() {
+                    *input = serde_json::Value::Object(Default::default());
+                }
+                // Inputs that aren't objects are left to the typed decoding to reject.
+                let Some(input) = input.as_object_mut() else { return Ok(bytes) };
+                for field in fields {
+                    if let Some(value) = field.default_value().filter(|_| unset(input.get(field.name))) {
+                        input.insert(field.name.to_string(), value);
+                    }
+                }
+                format.encode(&envelope).map(Into::into).map_err(ExecError::Handler)
+            }
+
+            pub struct ConnectorRecipe {
+                pub name: &'static str,
+                /// One operation, or each variant of an operation enum.
+                pub operations: &'static [&'static str],
+                /// Matched against the envelope's `params.version`, which defaults to 1.
+                pub version: u32,
+                /// Other names routed to this connector, e.g. its names before a rename.
+                pub aliases: &'static [&'static str],
+                /// The handler's doc comment.
+                pub description: Option<&'static str>,
+                /// Why and how callers should migrate, from `deprecated = "..."`.
+                pub deprecated: Option<&'static str>,
+                /// When the operation goes away, as an HTTP date for the `Sunset` header.
+                pub sunset: Option<&'static str>,
+                /// Wraps the connector's `/csp/{name}` routes, from `layer = ...`.
+                pub layer: Option<fn(axum::routing::MethodRouter) -> axum::routing::MethodRouter>,
+                /// The method of the operation's own route, `GET` alone also serving `GET /csp/{name}/{operation}`.
+                pub method: &'static str,
+                /// The operation's own route under `/csp/{name}/`, from `path = "..."`.
+                pub path: Option<&'static str>,
+                /// The handler's input and output types, as written in its signature.
+                pub input_type: &'static str,
+                pub output_type: &'static str,
+                /// Fields of the input when it derives `ConnectorInput`.
+                pub input_fields: fn() -> Option<&'static [FieldMeta]>,
+                /// The key case of the input and output, from `rename_all = "..."` or `connector_main!`'s.
+                pub rename_all: Option<&'static str>,
+                /// Calls slower than this are logged, from `slow_call = "..."`, else `connector_main!`'s.
+                pub slow_call: Option<std::time::Duration>,
+                pub exec_raw: ExecRaw,
+            }
+
+            ::inventory::collect!(ConnectorRecipe);
+
+            /// The call an interceptor sees, by the connector's own name rather than an alias.
+            pub struct InterceptedCall<'a> {
+                pub name: &'a str,
+                pub operation: &'a str,
+                pub format: Format,
+                pub ctx: &'a Context,
+            }
+
+            /// Hooks around every call, whichever way it arrives (`#[camunda_interceptor]`), e.g. for auditing,
+            /// enrichment or custom metrics.
+            pub trait Interceptor: Send + Sync + 'static {
+                /// Runs before the envelope is deserialized. It may rewrite the raw `body`, or reject the call.
+                fn before(&self, call: &InterceptedCall<'_>, body: &mut axum::body::Bytes) -> Result<(), HandlerError> {
+                    let _ = (call, body);
+                    Ok(())
+                }
+
+                /// Runs once the call completed, and may rewrite its `result`.
+                fn after(&self, call: &InterceptedCall<'_>, result: &mut Result<Output, ExecError>) {
+                    let _ = (call, result);
+                }
+            }
+
+            pub struct InterceptorRecipe {
+                /// The implementing type's name.
+                pub name: &'static str,
+                /// Lower orders run their `before` first and their `after` last.
+                pub order: i32,
+                pub instance: fn() -> &'static dyn Interceptor,
+            }
+
+            ::inventory::collect!(InterceptorRecipe);
+
+            pub fn interceptors() -> &'static [&'static dyn Interceptor] {
+                static INTERCEPTORS: std::sync::OnceLock<Vec<&'static dyn Interceptor>> = std::sync::OnceLock::new();
+                INTERCEPTORS.get_or_init(|| {
+                    let mut recipes: Vec<&'static InterceptorRecipe> = ::inventory::iter::<InterceptorRecipe>.into_iter().collect();
+                    recipes.sort_by_key(|r| (r.order, r.name));
+                    recipes.into_iter().map(|r| (r.instance)()).collect()
+                })
+            }
+
+            /// Rewrites the envelope of a connector's calls before it is deserialized (`#[camunda_transform]`), e.g. to
+            /// rename legacy fields or migrate an old envelope.
+            pub struct TransformRecipe {
+                /// The transform function's name.
+                pub name: &'static str,
+                pub connector: &'static str,
+                /// Only the envelopes of this operation, or all of the connector's.
+                pub operation: Option<&'static str>,
+                /// Lower orders run first.
+                pub order: i32,
+                pub transform: fn(&mut serde_json::Value) -> Result<(), String>,
+            }
+
+            ::inventory::collect!(TransformRecipe);
+
+            /// A `#[connector_health]` check or `#[connector_shutdown]` hook.
+            pub type HookFuture = std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send + 'static>>;
+
+            /// Checks a dependency (`#[connector_health]`), e.g. pings a connector's downstream API, for `/readyz`.
+            pub struct HealthRecipe {
+                /// The check function's name.
+                pub name: &'static str,
+                /// The connector depending on it, `None` for dependencies of the whole process.
+                pub connector: Option<&'static str>,
+                /// A check running longer fails.
+                pub timeout: std::time::Duration,
+                pub check: fn() -> HookFuture,
+            }
+
+            ::inventory::collect!(HealthRecipe);
+
+            /// Runs on shutdown once the server has drained (`#[connector_shutdown]`), e.g. to flush telemetry.
+            pub struct ShutdownRecipe {
+                /// The hook function's name.
+                pub name: &'static str,
+                /// Lower orders run first.
+                pub order: i32,
+                /// A hook running longer is abandoned.
+                pub timeout: std::time::Duration,
+                pub hook: fn() -> HookFuture,
+            }
+
+            ::inventory::collect!(ShutdownRecipe);
+
+            /// A singleton built once at startup by its `#[connector_resource]` function, such as an HTTP client or a
+            /// database pool.
+            pub trait ConnectorResource: Send + Sync + Sized + 'static {
+                fn instance() -> &'static Self;
+            }
+
+            /// A resource handed to handlers taking a `connectors::Resource<T>` parameter after their input.
+            pub struct Resource<T: ConnectorResource>(pub &'static T);
+
+            impl<T: ConnectorResource> Resource<T> {
+                pub fn get() -> Self {
+                    Resource(T::instance())
+                }
+            }
+
+            impl<T: ConnectorResource> Clone for Resource<T> {
+                fn clone(&self) -> Self {
+                    *self
+                }
+            }
+
+            impl<T: ConnectorResource> Copy for Resource<T> {}
+
+            impl<T: ConnectorResource> std::ops::Deref for Resource<T> {
+                type Target = T;
+
+                fn deref(&self) -> &T {
+                    self.0
+                }
+            }
+
+            pub struct ResourceRecipe {
+                /// The initializer function's name.
+                pub name: &'static str,
+                /// The resource's type, as written.
+                pub type_name: &'static str,
+                /// Lower orders are built first, so that a resource may use those before it.
+                pub order: i32,
+                pub init: fn() -> HookFuture,
+            }
+
+            ::inventory::collect!(ResourceRecipe);
+
+            /// A check of the settings run at startup, before any listener binds; see `validate_startup`.
+            pub struct StartupCheck {
+                pub name: &'static str,
+                /// Everything wrong, empty when the check passes.
+                pub check: fn() -> Vec<String>,
+            }
+
+            ::inventory::collect!(StartupCheck);
+
+            /// Runs the transforms of connector `name` over the envelope in `bytes`, in order. Envelopes of connectors
+            /// without transforms are passed through as they are.
+            pub fn transform_envelope(name: &str, format: Format, bytes: axum::body::Bytes) -> Result<axum::body::Bytes, ExecError> {
+                static TRANSFORMS: std::sync::OnceLock<std::collections::HashMap<&'static str, Vec<&'static TransformRecipe>>> = std::sync::OnceLock::new();
+                let transforms = TRANSFORMS.get_or_init(|| {
+                    let mut transforms: std::collections::HashMap<_, Vec<&'static TransformRecipe>> = std::collections::HashMap::new();
+                    for r in ::inventory::iter::<TransformRecipe> {
+                        transforms.entry(r.connector).or_default().push(r);
+                    }
+                    for recipes in transforms.values_mut() {
+                        recipes.sort_by_key(|r| (r.order, r.name));
+                    }
+                    transforms
+                });
+                let Some(recipes) = transforms.get(name) else { return Ok(bytes) };
+                let mut envelope: serde_json::Value = format
+                    .decode(&bytes)
+                    .map_err(|e| ExecError::Input(format!("Bad {} for `{}`: {}", format.name(), name, e)))?;
+                for r in recipes {
+                    if let Some(operation) = r.operation {
+                        if envelope["params"]["operation"].as_str() != Some(operation) {
+                            continue;
+                        }
+                    }
+                    (r.transform)(&mut envelope)
+                        .map_err(|e| ExecError::Input(format!("Transform `{}` rejected the envelope for `{}`: {}", r.name, name, e)))?;
+                }
+                format.encode(&envelope).map(Into::into).map_err(ExecError::Handler)
+            }
+
+            pub type InboundFuture = std::pin::Pin<Box<dyn std::future::Future<Output = Result<serde_json::Value, HandlerError>> + Send + 'static>>;
+
+            pub type InboundRaw = fn(axum::body::Bytes, Context) -> InboundFuture;
+
+            /// Where an inbound handler's messages come from.
+            #[derive(Debug)]
+            pub enum InboundSource {
+                /// A RabbitMQ queue (`#[camunda_inbound_amqp]`), consumed with `amqp = true`.
+                Amqp { queue: &'static str, prefetch: u16 },
+                /// MQTT topic filters (`#[camunda_inbound_mqtt]`), subscribed to with `mqtt = true`.
+                Mqtt { topics: &'static [&'static str], qos: u8 },
+                /// An SQS queue (`#[camunda_inbound_sqs]`), long-polled with `sqs = true`. The URL is either given or
+                /// read from the `queue_url_env` variable at startup.
+                Sqs {
+                    queue_url: &'static str,
+                    queue_url_env: Option<&'static str>,
+                    max_messages: i32,
+                    wait_time_secs: i32,
+                    visibility_timeout_secs: i32,
+                },
+                /// `POST /inbound/{path}` (`#[camunda_inbound_webhook]`), always served. Deliveries are told apart by
+                /// the `dedup_header` header, where the provider sets one.
+                Webhook { path: &'static str, verification: Option<WebhookVerification>, dedup_header: Option<&'static str> },
+                /// `GET /ws/{path}` (`#[camunda_inbound_websocket]`), served with `websocket = true`. Each frame is a
+                /// message, at most `max_in_flight` of a connection's are handled at once.
+                WebSocket { path: &'static str, token_env: Option<&'static str>, max_in_flight: usize },
+            }
+
+            /// The handshake a webhook provider runs to check that the URL belongs to its subscriber.
+            #[derive(Debug)]
+            pub enum WebhookVerification {
+                /// Answers Slack's `url_verification` event with its `challenge`.
+                Slack,
+                /// Echoes the `validationToken` query parameter Microsoft Graph subscriptions are validated with.
+                MicrosoftGraph,
+                /// Answers Meta's (WhatsApp, Messenger, Instagram) `GET ?hub.mode=subscribe` with its `hub.challenge`,
+                /// when `hub.verify_token` matches the variable `verify_token_env`.
+                Meta { verify_token_env: &'static str },
+                /// Echoes Asana's `X-Hook-Secret` header.
+                Asana,
+            }
+
+            impl InboundSource {
+                /// The `connector_main!` option that enables the source.
+                pub fn option(&self) -> &'static str {
+                    match self {
+                        InboundSource::Amqp { .. } => "amqp",
+                        InboundSource::Mqtt { .. } => "mqtt",
+                        InboundSource::Sqs { .. } => "sqs",
+                        InboundSource::Webhook { .. } => "webhook",
+                        InboundSource::WebSocket { .. } => "websocket",
+                    }
+                }
+            }
+
+            /// An inbound handler, fed by a message source rather than the dispatch table.
+            pub struct InboundRecipe {
+                /// The handler function's name.
+                pub name: &'static str,
+                pub source: InboundSource,
+                pub correlation: Option<InboundCorrelation>,
+                /// FEEL expression evaluated against the message, identifying it across redeliveries; the source's own
+                /// message id is used without one.
+                pub dedup_id: Option<&'static str>,
+                /// Decodes the message's body and runs the handler, yielding its output as JSON.
+                pub exec_raw: InboundRaw,
+            }
+
+            ::inventory::collect!(InboundRecipe);
+
+            /// Publishes an inbound handler's output as a message, from `message = "..."`.
+            #[derive(Debug)]
+            pub struct InboundCorrelation {
+                pub message: &'static str,
+                /// FEEL expression evaluated against the output.
+                pub correlation_key: &'static str,
+                pub ttl: std::time::Duration,
+                /// FEEL expression evaluated against the output; Zeebe rejects a second message with the same id
+                /// while the first is buffered.
+                pub message_id: Option<&'static str>,
+            }
+
+            /// One round of a `PollingConnector`.
+            pub struct Polled<T> {
+                pub items: Vec<T>,
+                /// Where the next round resumes, `None` to keep the current cursor. It is only kept once every item of
+                /// the round was handled.
+                pub cursor: Option<serde_json::Value>,
+            }
+
+            /// An inbound connector polling a source on a schedule (`#[camunda_inbound_polling]`), such as a REST API
+            /// listing new items. The runtime keeps the cursor and skips items whose id was already handled.
+            pub trait PollingConnector: Default + Send + Sync + 'static {
+                type Item: Send + 'static;
+
+                /// Fetches the items since `cursor`, which is `None` on the first round.
+                fn poll(
+                    &self,
+                    cursor: Option<serde_json::Value>,
+                    ctx: Context,
+                ) -> impl std::future::Future<Output = Result<Polled<Self::Item>, HandlerError>> + Send;
+
+                /// Identifies an item across rounds.
+                fn id(&self, item: &Self::Item) -> String;
+
+                /// Handles a new item, e.g. by starting a process for it.
+                fn handle(&self, item: Self::Item, ctx: Context) -> impl std::future::Future<Output = Result<(), HandlerError>> + Send;
+            }
+
+            /// A polling connector, run on its own task from `P::default()`.
+            pub struct PollerRecipe {
+                /// The implementing type's name.
+                pub name: &'static str,
+                pub interval: std::time::Duration,
+                /// How many handled ids are remembered to skip duplicates.
+                pub dedup_capacity: usize,
+                pub start: fn(&'static PollerRecipe),
+            }
+
+            ::inventory::collect!(PollerRecipe);
+
+            pub fn spawn_poller<P: PollingConnector>(r: &'static PollerRecipe) {
+                tokio::spawn(super::run_poller::<P>(r));
+            }
+
+            #feel
+
+            #zeebe
+
+            #config
+            #rename
+            #envelope
+
+            #http_client
+            #database
+            #secrets
+            #element_templates
+
+            #format
+        }
+
+        /// Recipes keyed by (name, operation, version).
+        type DispatchTable = std::collections::HashMap<(String, String, u32), &'static connectors::ConnectorRecipe>;
+
+        fn build_table() -> DispatchTable {
+            let mut table = std::collections::HashMap::new();
+            for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                for operation in r.operations {
+                    table.insert((r.name.to_string(), operation.to_string(), r.version), r);
+                }
+            }
+            #loaded_connectors
+            table
+        }
+
+        /// Logs the dispatch table, so that a connector missing from the binary shows at startup.
+        fn report_connectors() {
+            let mut entries: Vec<_> = dispatch_table().iter().collect();
+            if entries.is_empty() {
+                #empty_registry
+            }
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let mut rows = vec![["CONNECTOR", "OPERATION", "VERSION", "INPUT", "OUTPUT"].map(String::from)];
+            rows.extend(entries.into_iter().map(|((name, operation, version), r)| {
+                [name.clone(), operation.clone(), version.to_string(), r.input_type.to_string(), r.output_type.to_string()]
+            }));
+            let mut widths = [0; 5];
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            let table: String = rows
+                .iter()
+                .map(|row| {
+                    let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:width$}", cell, width = width)).collect();
+                    format!("\n  {}", cells.join("  ").trim_end())
+                })
+                .collect();
+            tracing::info!(count = rows.len() - 1, "registered connectors:{}", table);
+        }
+
+        fn report_disabled() {
+            for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                for operation in r.operations.iter().filter(|operation| connectors::is_disabled(r.name, operation)) {
+                    tracing::warn!(connector = r.name, operation, "connector disabled by DISABLED_CONNECTORS");
+                }
+            }
+            for entry in connectors::disabled_connectors() {
+                let (name, operation) = entry.split_once('.').map_or((entry.as_str(), None), |(n, op)| (n, Some(op)));
+                let known = ::inventory::iter::<crate::connectors::ConnectorRecipe>
+                    .into_iter()
+                    .any(|r| r.name == name && operation.is_none_or(|op| r.operations.contains(&op)));
+                if !known {
+                    tracing::warn!(entry = entry.as_str(), "DISABLED_CONNECTORS entry matches no connector");
+                }
+            }
+        }
+
+        /// Maps each alias to its connector's name. Aliases shadowed by a real connector name are ignored.
+        fn resolve_alias(name: &str) -> &str {
+            static ALIASES: std::sync::OnceLock<std::collections::HashMap<&'static str, &'static str>> = std::sync::OnceLock::new();
+            let aliases = ALIASES.get_or_init(|| {
+                let mut aliases = std::collections::HashMap::new();
+                for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                    for alias in r.aliases {
+                        if ::inventory::iter::<crate::connectors::ConnectorRecipe>.into_iter().any(|other| other.name == *alias) {
+                            tracing::error!(alias, connector = r.name, "alias is also the name of a connector, ignoring it");
+                        } else if let Some(previous) = aliases.insert(*alias, r.name).filter(|previous| *previous != r.name) {
+                            tracing::error!(alias, first = previous, second = r.name, "alias is declared by two connectors");
+                        }
+                    }
+                }
+                aliases
+            });
+            aliases.get(name).copied().unwrap_or(name)
+        }
+
+        fn dispatch_table() -> &'static DispatchTable {
+            static ONCE: std::sync::OnceLock<DispatchTable> = std::sync::OnceLock::new();
+            ONCE.get_or_init(build_table)
+        }
+
+        /// Returns the recipe along with the operation it was registered under, which is `*` when the connector's
+        /// catch-all handler takes the call.
+        fn lookup(name: &str, operation: &str, version: u32) -> Result<(&'static str, &'static connectors::ConnectorRecipe), connectors::ExecError> {
+            let name = resolve_alias(name);
+            let table = dispatch_table();
+            let entry = |op: &str| table.get_key_value(&(name.to_string(), op.to_string(), version));
+            let (key, recipe) = entry(operation).or_else(|| entry("*")).ok_or_else(|| {
+                if table.keys().any(|(n, op, _)| n == name && (op == operation || op == "*")) {
+                    connectors::ExecError::Unsupported(format!("Unsupported version {} of connector/operation `{}`/`{}`", version, name, operation))
+                } else {
+                    let mut known: Vec<&str> = table.keys().filter(|(n, _, _)| n == name).map(|(_, op, _)| op.as_str()).collect();
+                    known.sort_unstable();
+                    known.dedup();
+                    match known.is_empty() {
+                        true => connectors::ExecError::Unsupported(format!("Unsupported connector/operation `{}`", operation)),
+                        false => connectors::ExecError::Unsupported(format!(
+                            "Unsupported connector/operation `{}`, `{}` has {}",
+                            operation,
+                            name,
+                            known.iter().map(|op| format!("`{}`", op)).collect::<Vec<_>>().join(", ")
+                        )),
+                    }
+                }
+            })?;
+            if connectors::is_disabled(name, operation) || connectors::is_disabled(name, &key.1) {
+                return Err(connectors::ExecError::Disabled(format!("Connector/operation `{}`/`{}` is disabled", name, operation)));
+            }
+            Ok((key.1.as_str(), *recipe))
+        }
+
+        /// Runs a call, recorded in the audit log when there is one.
+        async fn execute_call(
+            name: String,
+            operation: String,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+            ) -> Result<connectors::Output, connectors::ExecError> {
+            let ctx = match connectors::peek_envelope_meta(format, &body) {
+                Some(envelope) => ctx.with_envelope(envelope),
+                None => ctx,
+            };
+            #normalize_operation
+            #dispatch_metrics_begin
+            #audit_begin
+            let result = #run_call;
+            #dispatch_metrics_record
+            #audit_finish
+            result
+        }
+
+        /// Runs a call through the interceptors, `before` hooks in order and `after` hooks in reverse. A `before`
+        /// error skips the handler, but every `after` hook still sees it.
+        async fn intercept(
+            name: String,
+            operation: String,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+            ) -> Result<connectors::Output, connectors::ExecError> {
+            let interceptors = connectors::interceptors();
+            if interceptors.is_empty() {
+                return execute_recipe(name, operation, body, format, ctx).await;
+            }
+            let name = resolve_alias(&name).to_string();
+            let call = connectors::InterceptedCall { name: &name, operation: &operation, format, ctx: &ctx };
+            let mut body = body;
+            let mut result = Ok(());
+            for interceptor in interceptors {
+                result = interceptor.before(&call, &mut body).map_err(connectors::ExecError::from);
+                if result.is_err() {
+                    break;
+                }
+            }
+            let mut result = match result {
+                Ok(()) => execute_recipe(name.clone(), operation.clone(), body, format, ctx.clone()).await,
+                Err(e) => Err(e),
+            };
+            for interceptor in interceptors.iter().rev() {
+                interceptor.after(&call, &mut result);
+            }
+            result
+        }
+
+        /// Looks up the recipe for (name, operation) and the envelope's version, and runs it on the raw envelope.
+        async fn execute_recipe(
+            name: String,
+            operation: String,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+            ) -> Result<connectors::Output, connectors::ExecError> {
+            // Dropped with this future when the caller disconnects, so the handler's own tasks can stop too.
+            let _cancel = ctx.cancel_on_drop();
+            // Everything past this point (disabling, breakers, stats, logs) sees the connector's own name.
+            let name = resolve_alias(&name).to_string();
+            #drain_check
+            // Inputs are renamed first, so that everything past this point sees the fields' Rust names.
+            let rename_all = lookup(&name, &operation, format.peek_version(&body)).ok().and_then(|(_, r)| r.rename_all);
+            let body = match rename_all {
+                Some(_) => connectors::snake_case_input(format, body),
+                None => body,
+            };
+            #secret_inputs
+            #feel_inputs
+            let version = format.peek_version(&body);
+            let mapping = format.peek_result_mapping(&body);
+            let (#registered, recipe) = lookup(&name, &operation, version)?;
+            if let Some(deprecated) = recipe.deprecated {
+                let client = ctx.client().ip.map(|ip| ip.to_string());
+                tracing::warn!(connector = name.as_str(), operation, version, client, sunset = recipe.sunset, "deprecated connector/operation called: {}", deprecated);
+                ctx.warn(format!("`{}`/`{}` is deprecated: {}", name, operation, deprecated));
+            }
+            #shed_check
+            #breaker_check
+            #stats_begin
+            let deadline_exceeded = || connectors::ExecError::DeadlineExceeded(format!("Deadline exceeded for `{}`/`{}`", name, operation));
+            let run = connectors::catch_panic(&name, &operation, &ctx, (recipe.exec_raw)(body, format, ctx.clone()));
+            let _running = connectors::InFlight::acquire(gauge(Gauge::Running, &name), usize::MAX);
+            #handler_metrics_begin
+            let result = match ctx.deadline() {
+                // Already late: don't start work nobody is waiting for.
+                Some(_) if ctx.remaining().is_some_and(|r| r.is_zero()) => Err(deadline_exceeded()),
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), run).await.unwrap_or_else(|_| Err(deadline_exceeded())),
+                None => run.await,
+            };
+            #handler_metrics_record
+            #breaker_record
+            #stats_record
+            #redact_errors
+            let result = match (result, rename_all) {
+                (Ok(connectors::Output::Value(value)), Some(rule)) => Ok(connectors::Output::Value(connectors::rename_keys(value, rule))),
+                (result, _) => result,
+            };
+            match result {
+                #stream_hold
+                Ok(connectors::Output::Value(value)) if !mapping.is_empty() => mapping
+                    .apply(value)
+                    .map(connectors::Output::Value)
+                    .map_err(|e| {
+                        ctx.classify(connectors::ErrorClass::Validation);
+                        connectors::ExecError::Input(format!("Bad result mapping for `{}`/`{}`: {}", name, operation, e))
+                    }),
+                result => result,
+            }
+        }
+
+        async fn dispatch(
+            axum::extract::Path(name): axum::extract::Path<String>,
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+            ) -> axum::response::Result<axum::response::Response> {
+            let format = connectors::Format::from_headers(&headers);
+            let (body, envelope_version) = connectors::envelope_version_header(&headers)
+                .and_then(|header| connectors::upgrade_envelope_bytes(format, header, body))
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+
+            // 1) Peek op
+            let operation = format.peek_operation(&body)
+                .map_err(|_| (axum::http::StatusCode::BAD_REQUEST, format!("Invalid {} envelope", format.name())))?;
+
+            #accept_async
+
+            let ctx = connectors::Context::from_headers(&headers)
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?
+                .with_client(client);
+            let deprecated = lookup(&name, &operation, format.peek_version(&body)).ok().map(|(_, r)| r).filter(|r| r.deprecated.is_some());
+            let meta = response_envelope_enabled().then(|| ResponseMeta {
+                connector: name.clone(),
+                operation: operation.clone(),
+                version: format.peek_version(&body),
+                started: std::time::Instant::now(),
+                ctx: ctx.clone(),
+            });
+            let output = match (execute(name, operation, body, format, ctx).await?, meta) {
+                (connectors::Output::Value(value), Some(meta)) => connectors::Output::Value(meta.wrap(value)),
+                (output, _) => output,
+            };
+            let mut response = match output {
+                #stream_response
+                output => {
+                    let bytes = output.encode(format).map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                    axum::response::IntoResponse::into_response(([(axum::http::header::CONTENT_TYPE, format.content_type())], bytes))
+                }
+            };
+            response.headers_mut().insert("x-envelope-version", envelope_version.into());
+            if let Some(recipe) = deprecated {
+                response.headers_mut().insert("deprecation", axum::http::HeaderValue::from_static("true"));
+                if let Some(sunset) = recipe.sunset {
+                    response.headers_mut().insert("sunset", axum::http::HeaderValue::from_static(sunset));
+                }
+            }
+            Ok(response)
+        }
+
+        #(#items)*
+
+        fn main() {
+            #log_init
+            connectors::install_panic_hook();
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                #(#runtime)*
+                .build()
+                .expect("Failed to build the Tokio runtime")
+                .block_on(async {
+                    #(#startup)*
+                    let mut app = axum::Router::new()
+                        .route("/csp/{name}", axum::routing::post(dispatch) #(#csp_layers)*)
+                        .route("/csp/{name}/{operation}", axum::routing::get(dispatch_get));
+                    // Connectors with their own layer get their own routes, which take precedence over `{name}`.
+                    let mut layered = std::collections::HashMap::new();
+                    for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                        let Some(layer) = r.layer else { continue };
+                        if layered.insert(r.name, layer).is_some() {
+                            tracing::warn!(connector = r.name, "several handlers of the connector declare a layer, applying only one");
+                        }
+                    }
+                    let mut routed = std::collections::HashSet::new();
+                    for r in ::inventory::iter::<crate::connectors::ConnectorRecipe> {
+                        let Some(&layer) = layered.get(r.name) else { continue };
+                        for name in std::iter::once(r.name).chain(r.aliases.iter().copied()) {
+                            if !routed.insert(name) {
+                                continue;
+                            }
+                            let route = axum::routing::post(move |client, headers, body| dispatch(axum::extract::Path(name.to_string()), client, headers, body));
+                            app = app.route(&format!("/csp/{}", name), layer(route #(#csp_layers)*));
+                            let get = axum::routing::get(move |axum::extract::Path(operation): axum::extract::Path<String>, query, client, headers| {
+                                dispatch_get(axum::extract::Path((name.to_string(), operation)), query, client, headers)
+                            });
+                            app = app.route(&format!("/csp/{}/{{operation}}", name), layer(get));
+                        }
+                    }
+                    for ((url, method), route) in path_routes() {
+                        let mut router = path_router(method, route.clone()) #(#csp_layers)*;
+                        if let Some(&layer) = layered.get(route.name) {
+                            router = layer(router);
+                        }
+                        app = app.route(&url, router);
+                    }
+                    let app = app
+                        #(#routes)*
+                        #(#layers)*;
+                    #serve
+                    run_shutdown_hooks().await;
+                })
+        }
+    }
+}
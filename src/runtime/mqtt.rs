@@ -0,0 +1,130 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the MQTT client behind `#[camunda_inbound_mqtt]`, built on rumqttc.
+///
+/// One connection to `MQTT_URL` subscribes to the topic filters of all handlers, resubscribing after each reconnect,
+/// and hands each message to every handler whose filter matches its topic. QoS 1 and 2 messages are acknowledged
+/// once their handlers are done; retriable errors are retried in place with backoff first.
+pub fn expand() -> TokenStream {
+    quote! {
+        /// Whether `topic` matches `filter`, with `+` standing for one level and `#` for all remaining ones. Shared
+        /// subscriptions (`$share/{group}/{filter}`) match like their filter.
+        fn mqtt_topic_matches(filter: &str, topic: &str) -> bool {
+            let filter = match filter.strip_prefix("$share/") {
+                Some(shared) => shared.split_once('/').map_or("", |(_, filter)| filter),
+                None => filter,
+            };
+            let mut levels = topic.split('/');
+            for expected in filter.split('/') {
+                match (expected, levels.next()) {
+                    ("#", _) => return true,
+                    ("+", Some(_)) => {}
+                    (expected, Some(level)) if expected == level => {}
+                    _ => return false,
+                }
+            }
+            levels.next().is_none()
+        }
+
+        fn mqtt_options() -> rumqttc::MqttOptions {
+            let url = std::env::var("MQTT_URL").unwrap_or_else(|_| "mqtt://localhost:1883".to_string());
+            let rest = url.strip_prefix("mqtt://").or_else(|| url.strip_prefix("tcp://")).unwrap_or(&url);
+            let (credentials, address) = match rest.rsplit_once('@') {
+                Some((credentials, address)) => (Some(credentials), address),
+                None => (None, rest),
+            };
+            let address = address.trim_end_matches('/');
+            let (host, port) = match address.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().unwrap_or(1883)),
+                None => (address, 1883),
+            };
+            let client_id = std::env::var("MQTT_CLIENT_ID")
+                .unwrap_or_else(|_| format!("{}-{}", env!("CARGO_PKG_NAME"), std::process::id()));
+            let mut options = rumqttc::MqttOptions::new(client_id, host, port);
+            options.set_keep_alive(std::time::Duration::from_secs(30));
+            options.set_manual_acks(true);
+            if let Some((username, password)) = credentials.and_then(|credentials| credentials.split_once(':')) {
+                options.set_credentials(username, password);
+            }
+            options
+        }
+
+        fn mqtt_qos(qos: u8) -> rumqttc::QoS {
+            match qos {
+                0 => rumqttc::QoS::AtMostOnce,
+                1 => rumqttc::QoS::AtLeastOnce,
+                _ => rumqttc::QoS::ExactlyOnce,
+            }
+        }
+
+        fn start_mqtt_client() {
+            let mut subscriptions: Vec<(&'static str, u8, &'static connectors::InboundRecipe)> = Vec::new();
+            for r in ::inventory::iter::<crate::connectors::InboundRecipe> {
+                if let connectors::InboundSource::Mqtt { topics, qos } = r.source {
+                    subscriptions.extend(topics.iter().map(|topic| (*topic, qos, r)));
+                }
+            }
+            if subscriptions.is_empty() {
+                return;
+            }
+            let (client, mut eventloop) = rumqttc::AsyncClient::new(mqtt_options(), 64);
+            tokio::spawn(async move {
+                let mut failures: u32 = 0;
+                loop {
+                    match eventloop.poll().await {
+                        Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                            failures = 0;
+                            // Clean sessions forget subscriptions, so they are renewed on every connection.
+                            for (topic, qos, r) in &subscriptions {
+                                if let Err(e) = client.subscribe(*topic, mqtt_qos(*qos)).await {
+                                    tracing::warn!(handler = r.name, topic, "cannot subscribe to MQTT topic: {}", e);
+                                }
+                            }
+                            tracing::info!(topics = subscriptions.len(), "MQTT client connected");
+                        }
+                        Ok(rumqttc::Event::Incoming(rumqttc::Packet::Publish(publish))) => {
+                            let handlers: Vec<_> = subscriptions
+                                .iter()
+                                .filter(|(filter, _, _)| mqtt_topic_matches(filter, &publish.topic))
+                                .map(|(_, _, r)| *r)
+                                .collect();
+                            tokio::spawn(handle_mqtt_message(client.clone(), publish, handlers));
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            // The next poll reconnects.
+                            tracing::warn!("MQTT connection failed, reconnecting: {}", e);
+                            let delay = std::time::Duration::from_millis(500).saturating_mul(1 << failures.min(6));
+                            tokio::time::sleep(delay.min(std::time::Duration::from_secs(30))).await;
+                            failures += 1;
+                        }
+                    }
+                }
+            });
+        }
+
+        /// Runs the message through its handlers, retrying retriable errors up to 5 attempts, then acknowledges it.
+        async fn handle_mqtt_message(client: rumqttc::AsyncClient, publish: rumqttc::Publish, handlers: Vec<&'static connectors::InboundRecipe>) {
+            for r in handlers {
+                let mut attempt: u32 = 1;
+                loop {
+                    match run_inbound(r, publish.payload.clone(), connectors::Context::default(), None).await {
+                        Ok(_) => break,
+                        Err(e) if e.retriable && attempt < 5 => {
+                            tokio::time::sleep(connectors::retry_backoff(std::time::Duration::from_millis(200), attempt)).await;
+                            attempt += 1;
+                        }
+                        Err(e) => {
+                            tracing::warn!(handler = r.name, topic = publish.topic.as_str(), attempt, "inbound handler failed, dropping the message: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+            if let Err(e) = client.ack(&publish).await {
+                tracing::warn!(topic = publish.topic.as_str(), "cannot acknowledge MQTT message: {}", e);
+            }
+        }
+    }
+}
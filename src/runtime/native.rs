@@ -0,0 +1,160 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the loader for the shared-library plugins of `dir`, opened with libloading.
+///
+/// A plugin exports `camunda_connector_plugin`, an `extern "C" fn() -> *const NativePluginTable` declaring its
+/// connectors. Each connector's `execute` takes the envelope as JSON and returns a reply in the format of
+/// `plugin::expand`, allocated by the plugin and handed back to its `free` once copied. Libraries stay loaded for the
+/// life of the process.
+pub fn expand(dir: &LitStr) -> TokenStream {
+    quote! {
+        /// The plugin ABI version this runtime loads.
+        const NATIVE_PLUGIN_ABI: u32 = 1;
+
+        #[repr(C)]
+        struct NativePluginTable {
+            abi_version: u32,
+            connectors: *const NativeConnector,
+            len: usize,
+        }
+
+        #[repr(C)]
+        struct NativeConnector {
+            /// NUL-terminated, as are the operations.
+            name: *const std::ffi::c_char,
+            operations: *const *const std::ffi::c_char,
+            operations_len: usize,
+            version: u32,
+            execute: unsafe extern "C" fn(input: *const u8, input_len: usize, reply_len: *mut usize) -> *mut u8,
+            free: unsafe extern "C" fn(reply: *mut u8, reply_len: usize),
+        }
+
+        struct NativePlugin {
+            name: &'static str,
+            execute: unsafe extern "C" fn(*const u8, usize, *mut usize) -> *mut u8,
+            free: unsafe extern "C" fn(*mut u8, usize),
+        }
+
+        /// Opens the libraries once, when the dispatch table is built. Libraries that fail to load, or declare
+        /// another ABI version, are reported and left out.
+        fn load_native_plugins() -> Vec<&'static connectors::ConnectorRecipe> {
+            let entries = match std::fs::read_dir(#dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::error!(dir = #dir, "cannot read native plugin directory: {}", e);
+                    return Vec::new();
+                }
+            };
+            let mut paths: Vec<std::path::PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+                .collect();
+            paths.sort();
+            let mut recipes = Vec::new();
+            for path in paths {
+                match load_native_plugin(&path) {
+                    Ok(loaded) => recipes.extend(loaded),
+                    Err(e) => tracing::error!(path = %path.display(), "cannot load native plugin: {}", e),
+                }
+            }
+            recipes
+        }
+
+        fn load_native_plugin(path: &std::path::Path) -> Result<Vec<&'static connectors::ConnectorRecipe>, String> {
+            let text = |ptr: *const std::ffi::c_char| -> Result<&'static str, String> {
+                if ptr.is_null() {
+                    return Err("null string in plugin table".to_string());
+                }
+                // SAFETY: the plugin declares NUL-terminated strings, which live as long as the library.
+                let text = unsafe { std::ffi::CStr::from_ptr(ptr) };
+                text.to_str().map_err(|e| e.to_string())
+            };
+            // SAFETY: loading runs the library's initializers, plugins are trusted like the binary itself.
+            let library = unsafe { libloading::Library::new(path) }.map_err(|e| e.to_string())?;
+            // SAFETY: the symbol is declared with this signature by the plugin ABI.
+            let table = unsafe {
+                let entry = library
+                    .get::<unsafe extern "C" fn() -> *const NativePluginTable>(b"camunda_connector_plugin\0")
+                    .map_err(|e| e.to_string())?;
+                entry().as_ref().ok_or("`camunda_connector_plugin` returned no table")?
+            };
+            if table.abi_version != NATIVE_PLUGIN_ABI {
+                return Err(format!("plugin ABI version {} is not supported, expected {}", table.abi_version, NATIVE_PLUGIN_ABI));
+            }
+            let connectors = match table.len {
+                0 => &[][..],
+                // SAFETY: the table points to `len` connectors.
+                len => unsafe { std::slice::from_raw_parts(table.connectors, len) },
+            };
+            let mut recipes = Vec::new();
+            for connector in connectors {
+                let name = text(connector.name)?;
+                let operations = match connector.operations_len {
+                    0 => Vec::new(),
+                    // SAFETY: the connector points to `operations_len` operations.
+                    len => unsafe { std::slice::from_raw_parts(connector.operations, len) }
+                        .iter()
+                        .map(|op| text(*op))
+                        .collect::<Result<Vec<_>, _>>()?,
+                };
+                let plugin: &'static NativePlugin = Box::leak(Box::new(NativePlugin { name, execute: connector.execute, free: connector.free }));
+                let exec_raw: connectors::ExecRaw = Box::leak(Box::new(move |body, format, ctx| run_native_plugin(plugin, body, format, ctx)));
+                recipes.push(&*Box::leak(Box::new(connectors::ConnectorRecipe {
+                    name,
+                    operations: operations.leak(),
+                    version: connector.version.max(1),
+                    aliases: &[],
+                    description: None,
+                    deprecated: None,
+                    sunset: None,
+                    layer: None,
+                    method: "POST",
+                    path: None,
+                    input_type: "serde_json::Value",
+                    output_type: "serde_json::Value",
+                    input_fields: || None,
+                    rename_all: None,
+                    slow_call: None,
+                    exec_raw,
+                })));
+                tracing::info!(connector = name, path = %path.display(), "loaded native plugin");
+            }
+            std::mem::forget(library);
+            Ok(recipes)
+        }
+
+        fn run_native_plugin(
+            plugin: &'static NativePlugin,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            _ctx: connectors::Context,
+        ) -> connectors::DispatcherFuture {
+            Box::pin(async move {
+                let envelope = format
+                    .decode::<serde_json::Value>(&body)
+                    .and_then(|envelope| serde_json::to_vec(&envelope).map_err(|e| e.to_string()))
+                    .map_err(|e| connectors::ExecError::Input(format!("Bad {} for `{}`: {}", format.name(), plugin.name, e)))?;
+                let reply = tokio::task::spawn_blocking(move || {
+                    let mut len = 0;
+                    // SAFETY: `execute` reads `envelope.len()` bytes and returns a buffer of `len` bytes, or null.
+                    unsafe {
+                        let reply = (plugin.execute)(envelope.as_ptr(), envelope.len(), &mut len);
+                        if reply.is_null() {
+                            return None;
+                        }
+                        let copy = std::slice::from_raw_parts(reply, len).to_vec();
+                        (plugin.free)(reply, len);
+                        Some(copy)
+                    }
+                })
+                .await
+                .map_err(|_| connectors::ExecError::Handler(format!("Native plugin `{}` panicked", plugin.name)))?
+                .ok_or_else(|| connectors::ExecError::Handler(format!("Native plugin `{}` returned no reply", plugin.name)))?;
+                plugin_reply(plugin.name, &reply)
+            })
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings the OTLP exporter reads, those of the OpenTelemetry SDKs, from the configuration file or the
+/// environment.
+pub const SETTINGS: [&str; 6] = [
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_HEADERS",
+    "OTEL_SERVICE_NAME",
+    "OTEL_RESOURCE_ATTRIBUTES",
+    "OTEL_METRIC_EXPORT_INTERVAL",
+];
+
+/// Generates the OTLP exporter of `otlp = true`, POSTing the metrics `/metrics` serves to an OpenTelemetry collector
+/// every `OTEL_METRIC_EXPORT_INTERVAL` over OTLP/HTTP with JSON, and once more on shutdown. Histograms and the error
+/// counter are cumulative since startup.
+///
+/// The `OTEL_*` settings are those of the OpenTelemetry SDKs, so that a tracing pipeline set up from them describes
+/// the same service: `telemetry_resource` is the resource both report under.
+pub fn expand() -> TokenStream {
+    let runtime_version = env!("CARGO_PKG_VERSION");
+    quote! {
+        /// `key=value` pairs separated by commas, as the `OTEL_*` lists are.
+        fn otel_pairs(key: &str) -> Result<Vec<(String, String)>, String> {
+            let Some(value) = connectors::setting(key) else { return Ok(Vec::new()) };
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| match pair.split_once('=') {
+                    Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_string(), v.trim().to_string())),
+                    _ => Err(format!("{} must be comma-separated `key=value` pairs, got `{}`", key, pair)),
+                })
+                .collect()
+        }
+
+        /// The OpenTelemetry resource of this process: `service.name`, `service.version`, the connector runtime's
+        /// version and the `OTEL_RESOURCE_ATTRIBUTES`.
+        fn telemetry_resource() -> Result<Vec<(String, String)>, String> {
+            let mut attributes = otel_pairs("OTEL_RESOURCE_ATTRIBUTES")?;
+            let service_name = connectors::setting("OTEL_SERVICE_NAME")
+                .or_else(|| attributes.iter().find(|(k, _)| k == "service.name").map(|(_, v)| v.clone()))
+                .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
+            attributes.retain(|(k, _)| k != "service.name");
+            attributes.insert(0, ("service.name".to_string(), service_name));
+            if !attributes.iter().any(|(k, _)| k == "service.version") {
+                attributes.push(("service.version".to_string(), env!("CARGO_PKG_VERSION").to_string()));
+            }
+            attributes.push(("camunda_connector.runtime.version".to_string(), #runtime_version.to_string()));
+            Ok(attributes)
+        }
+
+        /// (metrics endpoint, headers, export interval)
+        fn otlp_settings() -> Result<(String, Vec<(String, String)>, std::time::Duration), String> {
+            let endpoint = match connectors::setting("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT") {
+                Some(endpoint) => endpoint,
+                None => {
+                    let base = connectors::setting("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_else(|| "http://localhost:4318".to_string());
+                    format!("{}/v1/metrics", base.trim_end_matches('/'))
+                }
+            };
+            let url = reqwest::Url::parse(&endpoint).map_err(|e| format!("invalid OTLP metrics endpoint `{}`: {}", endpoint, e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!("the OTLP metrics endpoint must be an http(s) URL, got `{}`", endpoint));
+            }
+            let headers = otel_pairs("OTEL_EXPORTER_OTLP_HEADERS")?;
+            for (name, value) in &headers {
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid OTLP header name `{}`", name))?;
+                reqwest::header::HeaderValue::from_str(value).map_err(|_| format!("invalid value for OTLP header `{}`", name))?;
+            }
+            // Milliseconds, as the OpenTelemetry SDKs read it.
+            let interval = match connectors::setting("OTEL_METRIC_EXPORT_INTERVAL") {
+                Some(ms) => match ms.trim().parse::<u64>() {
+                    Ok(ms) if ms > 0 => std::time::Duration::from_millis(ms),
+                    _ => return Err(format!("OTEL_METRIC_EXPORT_INTERVAL must be a positive number of milliseconds, got `{}`", ms)),
+                },
+                None => std::time::Duration::from_secs(60),
+            };
+            Ok((endpoint, headers, interval))
+        }
+
+        fn check_otlp_settings() -> Vec<String> {
+            let mut problems: Vec<String> = otlp_settings().err().into_iter().collect();
+            problems.extend(telemetry_resource().err());
+            problems
+        }
+
+        ::inventory::submit! {
+            crate::connectors::StartupCheck {
+                name: "otlp",
+                check: check_otlp_settings,
+            }
+        }
+
+        /// Unix epoch nanoseconds when the cumulative metrics started, in the decimal string OTLP/JSON encodes 64-bit
+        /// integers as.
+        fn otlp_start_time() -> &'static str {
+            static START: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+            START.get_or_init(otlp_now)
+        }
+
+        fn otlp_now() -> String {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_string()
+        }
+
+        fn otlp_attributes(pairs: &[(&str, &str)]) -> serde_json::Value {
+            pairs.iter().map(|(key, value)| serde_json::json!({ "key": key, "value": { "stringValue": value } })).collect()
+        }
+
+        fn otlp_metrics() -> Vec<serde_json::Value> {
+            use std::sync::atomic::Ordering::Relaxed;
+            let (start, now) = (otlp_start_time(), otlp_now());
+            let mut metrics = Vec::new();
+
+            let latencies = latencies().read().unwrap_or_else(|e| e.into_inner());
+            for family in ["connector_handler_duration_seconds", "connector_dispatch_duration_seconds"] {
+                let points: Vec<serde_json::Value> = latencies
+                    .iter()
+                    .filter(|((metric, ..), _)| *metric == family)
+                    .map(|((_, connector, operation, outcome), histogram)| {
+                        // OTLP counts each bucket on its own, the last one being above every bound.
+                        let cumulative: Vec<u64> = histogram.buckets.iter().map(|bucket| bucket.load(Relaxed)).collect();
+                        let count = histogram.count.load(Relaxed);
+                        let counts: Vec<String> = cumulative
+                            .iter()
+                            .chain(std::iter::once(&count))
+                            .scan(0, |below, &n| {
+                                let own = n.saturating_sub(*below);
+                                *below = n;
+                                Some(own.to_string())
+                            })
+                            .collect();
+                        serde_json::json!({
+                            "attributes": otlp_attributes(&[("connector", connector), ("operation", operation), ("outcome", outcome)]),
+                            "startTimeUnixNano": start,
+                            "timeUnixNano": now,
+                            "count": count.to_string(),
+                            "sum": histogram.sum_micros.load(Relaxed) as f64 / 1e6,
+                            "bucketCounts": counts,
+                            "explicitBounds": LATENCY_BUCKETS,
+                        })
+                    })
+                    .collect();
+                if !points.is_empty() {
+                    metrics.push(serde_json::json!({
+                        "name": format!("connector.{}", dotted_metric_name(family)),
+                        "unit": "s",
+                        "histogram": { "aggregationTemporality": 2, "dataPoints": points },
+                    }));
+                }
+            }
+            drop(latencies);
+
+            let points: Vec<serde_json::Value> = errors()
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .map(|((connector, operation, class), count)| {
+                    serde_json::json!({
+                        "attributes": otlp_attributes(&[("connector", connector), ("operation", operation), ("class", class.label())]),
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "asInt": count.load(Relaxed).to_string(),
+                    })
+                })
+                .collect();
+            if !points.is_empty() {
+                metrics.push(serde_json::json!({
+                    "name": format!("connector.{}", dotted_metric_name("connector_errors_total")),
+                    "unit": "{call}",
+                    "sum": { "aggregationTemporality": 2, "isMonotonic": true, "dataPoints": points },
+                }));
+            }
+
+            let gauges = gauge_table().read().unwrap_or_else(|e| e.into_inner());
+            for kind in [Gauge::Running, Gauge::Queued, Gauge::Waiting] {
+                let points: Vec<serde_json::Value> = gauges
+                    .iter()
+                    .filter(|((gauge, _), _)| *gauge == kind)
+                    .map(|((_, connector), value)| {
+                        serde_json::json!({
+                            "attributes": otlp_attributes(&[("connector", connector)]),
+                            "timeUnixNano": now,
+                            "asInt": value.load(Relaxed).to_string(),
+                        })
+                    })
+                    .collect();
+                if !points.is_empty() {
+                    metrics.push(serde_json::json!({
+                        "name": format!("connector.{}", dotted_metric_name(kind.family())),
+                        "gauge": { "dataPoints": points },
+                    }));
+                }
+            }
+            metrics
+        }
+
+        async fn export_otlp_metrics() -> Result<(), String> {
+            static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+            let client = CLIENT.get_or_init(|| reqwest::Client::builder().timeout(std::time::Duration::from_secs(10)).build().unwrap_or_default());
+            let (endpoint, headers, _) = otlp_settings()?;
+            let attributes = telemetry_resource()?;
+            let resource: Vec<(&str, &str)> = attributes.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+            let body = serde_json::json!({
+                "resourceMetrics": [{
+                    "resource": { "attributes": otlp_attributes(&resource) },
+                    "scopeMetrics": [{
+                        "scope": { "name": "camunda_connector_rs", "version": #runtime_version },
+                        "metrics": otlp_metrics(),
+                    }],
+                }],
+            });
+            let body = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
+            let mut request = client.post(&endpoint).header("content-type", "application/json").body(body);
+            for (name, value) in &headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            let response = request.send().await.map_err(|e| format!("cannot export metrics to `{}`: {}", endpoint, e))?;
+            if !response.status().is_success() {
+                return Err(format!("`{}` refused the metrics with {}", endpoint, response.status()));
+            }
+            Ok(())
+        }
+
+        fn start_otlp_export() {
+            let (_, _, interval) = otlp_settings().expect("checked by validate_startup");
+            otlp_start_time();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                // The first tick is immediate, there is nothing to export yet.
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    if let Err(e) = export_otlp_metrics().await {
+                        tracing::warn!("{}", e);
+                    }
+                }
+            });
+        }
+
+        fn export_otlp_metrics_hook() -> connectors::HookFuture {
+            Box::pin(export_otlp_metrics())
+        }
+
+        // Last, so that the final export sees the calls drained before it.
+        ::inventory::submit! {
+            crate::connectors::ShutdownRecipe {
+                name: "export_otlp_metrics",
+                order: i32::MAX,
+                timeout: std::time::Duration::from_secs(10),
+                hook: export_otlp_metrics_hook,
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::database::{Backend, Dialect};
+
+/// How long a publisher owns the messages it claimed before another replica may publish them, in milliseconds.
+const CLAIM_MS: i64 = 30_000;
+
+/// How long published messages are kept in the table, in milliseconds.
+const RETENTION_MS: i64 = 24 * 3600 * 1000;
+
+/// The outbox table, created at startup when missing.
+fn schema(dialect: Dialect) -> String {
+    let (id, name) = match dialect {
+        Dialect::Postgres => ("BIGSERIAL PRIMARY KEY", "TEXT"),
+        Dialect::MySql => ("BIGINT AUTO_INCREMENT PRIMARY KEY", "VARCHAR(255)"),
+        Dialect::Sqlite => ("INTEGER PRIMARY KEY AUTOINCREMENT", "TEXT"),
+    };
+    format!(
+        "CREATE TABLE IF NOT EXISTS connector_outbox (id {id}, message_id {name} NOT NULL, message_name {name} NOT NULL, \
+         correlation_key TEXT NOT NULL, variables TEXT NOT NULL, ttl_ms BIGINT NOT NULL, created_at BIGINT NOT NULL, \
+         available_at BIGINT NOT NULL, attempts BIGINT NOT NULL DEFAULT 0, last_error TEXT, published_at BIGINT)"
+    )
+}
+
+/// `sql` with its `?` placeholders numbered as Postgres wants them.
+fn placeholders(dialect: Dialect, sql: &str) -> String {
+    if dialect != Dialect::Postgres {
+        return sql.to_string();
+    }
+    let mut numbered = String::new();
+    for (n, part) in sql.split('?').enumerate() {
+        if n > 0 {
+            numbered.push_str(&format!("${}", n));
+        }
+        numbered.push_str(part);
+    }
+    numbered
+}
+
+/// A statement run on `db`, evaluating to the rows it affected.
+fn execute(backend: &Backend, sql: &str, args: &[TokenStream], db: TokenStream) -> TokenStream {
+    let sql = placeholders(backend.dialect(), sql);
+    match backend {
+        Backend::Sqlx(..) => quote! { sqlx::query(#sql)#(.bind(#args))*.execute(#db).await.map(|done| done.rows_affected()) },
+        Backend::DeadpoolPostgres => quote! { #db.execute(#sql, &[#(&(#args)),*]).await },
+    }
+}
+
+/// Generates `connectors::outbox`, whose `publish_message` writes a message to the `connector_outbox` table within
+/// the caller's transaction, so that it is published if and only if the handler's own writes commit.
+pub fn expand(backend: &Backend) -> TokenStream {
+    let schema = schema(backend.dialect());
+    let conn = match backend {
+        Backend::Sqlx(database, _) => quote! { &mut <#database as sqlx::Database>::Connection },
+        Backend::DeadpoolPostgres => quote! { &impl deadpool_postgres::GenericClient },
+    };
+    let insert = execute(
+        backend,
+        "INSERT INTO connector_outbox (message_id, message_name, correlation_key, variables, ttl_ms, created_at, available_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        &[quote! { &message_id }, quote! { name }, quote! { correlation_key }, quote! { variables }, quote! { ttl_ms }, quote! { now }, quote! { now }],
+        quote! { conn },
+    );
+    quote! {
+        /// Publishing messages from the handler's own database transaction (`outbox = true`).
+        pub mod outbox {
+            /// The table the outbox keeps its messages in, for migrations; the runtime creates it when missing.
+            pub const SCHEMA: &str = #schema;
+
+            /// Writes a message to publish once the transaction of `conn` commits, and returns its message id.
+            /// A rolled-back message is never published.
+            pub async fn publish_message(
+                conn: #conn,
+                name: &str,
+                correlation_key: &str,
+                variables: impl serde::Serialize,
+                ttl: std::time::Duration,
+            ) -> Result<String, super::HandlerError> {
+                use std::hash::{BuildHasher, Hasher};
+                let variables = match serde_json::to_value(variables) {
+                    Ok(value @ (serde_json::Value::Object(_) | serde_json::Value::Null)) => value.to_string(),
+                    Ok(other) => return Err(super::HandlerError::fatal(format!("variables must be an object, got `{}`", other))),
+                    Err(e) => return Err(super::HandlerError::fatal(e.to_string())),
+                };
+                let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+                let now = elapsed.as_millis() as i64;
+                let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+                hasher.write_u128(elapsed.as_nanos());
+                let message_id = format!("outbox-{:016x}{:016x}", elapsed.as_nanos() as u64, hasher.finish());
+                let ttl_ms = ttl.as_millis().min(i64::MAX as u128) as i64;
+                #insert.map_err(|e| super::HandlerError::retriable(format!("Cannot write to the outbox: {}", e)))?;
+                Ok(message_id)
+            }
+        }
+    }
+}
+
+/// Generates the outbox publisher, publishing the committed messages of `connector_outbox` every second, oldest first.
+///
+/// A message is claimed before it is published, so that replicas sharing the database don't publish it twice, and
+/// marked once the broker took it. A publisher that fails after publishing leaves the message to be published again
+/// once its claim lapses, under the same message id, which the broker rejects while the first is buffered. Failed
+/// publishes are retried with backoff.
+pub fn publisher(backend: &Backend) -> TokenStream {
+    let db = match backend {
+        Backend::Sqlx(..) => quote! { let db = &<connectors::Database as connectors::ConnectorResource>::instance().0; },
+        Backend::DeadpoolPostgres => quote! {
+            let db = <connectors::Database as connectors::ConnectorResource>::instance().get().await.map_err(|e| e.to_string())?;
+        },
+    };
+    let create = execute(backend, &schema(backend.dialect()), &[], quote! { db });
+    let prune = execute(
+        backend,
+        "DELETE FROM connector_outbox WHERE published_at < ?",
+        &[quote! { now - #RETENTION_MS }],
+        quote! { db },
+    );
+    let select = placeholders(
+        backend.dialect(),
+        "SELECT id, message_id, message_name, correlation_key, variables, ttl_ms, attempts, available_at FROM connector_outbox \
+         WHERE published_at IS NULL AND available_at <= ? ORDER BY id LIMIT 100",
+    );
+    let due = match backend {
+        Backend::Sqlx(..) => quote! { sqlx::query_as(#select).bind(now).fetch_all(db).await.map_err(|e| e.to_string())? },
+        Backend::DeadpoolPostgres => quote! {
+            db.query(#select, &[&now])
+                .await
+                .map_err(|e| e.to_string())?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4), row.get(5), row.get(6), row.get(7)))
+                .collect()
+        },
+    };
+    let claim = execute(
+        backend,
+        "UPDATE connector_outbox SET available_at = ? WHERE id = ? AND available_at = ? AND published_at IS NULL",
+        &[quote! { now + #CLAIM_MS }, quote! { id }, quote! { available_at }],
+        quote! { db },
+    );
+    let mark = execute(
+        backend,
+        "UPDATE connector_outbox SET published_at = ? WHERE id = ?",
+        &[quote! { outbox_millis() }, quote! { id }],
+        quote! { db },
+    );
+    let retry = execute(
+        backend,
+        "UPDATE connector_outbox SET attempts = ?, available_at = ?, last_error = ? WHERE id = ?",
+        &[quote! { attempts }, quote! { retry_at }, quote! { e.to_string() }, quote! { id }],
+        quote! { db },
+    );
+    quote! {
+        fn outbox_millis() -> i64 {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64
+        }
+
+        /// Publishes the messages due, oldest first.
+        async fn publish_outbox() -> Result<(), String> {
+            static CREATED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+            #db
+            if !CREATED.load(std::sync::atomic::Ordering::Relaxed) {
+                #create.map_err(|e| format!("cannot create the outbox table: {}", e))?;
+                CREATED.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+            let now = outbox_millis();
+            #prune.map_err(|e| e.to_string())?;
+            // (id, message id, name, correlation key, variables, TTL, attempts, available at)
+            let due: Vec<(i64, String, String, String, String, i64, i64, i64)> = #due;
+            for (id, message_id, name, correlation_key, variables, ttl_ms, attempts, available_at) in due {
+                if #claim.map_err(|e| e.to_string())? != 1 {
+                    continue;
+                }
+                let variables: serde_json::Value = serde_json::from_str(&variables).unwrap_or_default();
+                let ttl = std::time::Duration::from_millis(ttl_ms.max(0) as u64);
+                match connectors::zeebe::client().publish_message(&name, &correlation_key, &variables, ttl, Some(&message_id)).await {
+                    Ok(_) => {}
+                    Err(e) if e.code == connectors::zeebe::ZeebeError::ALREADY_EXISTS => {
+                        tracing::debug!(message_name = name, message_id, "outbox message was already published");
+                    }
+                    Err(e) => {
+                        let attempts = attempts + 1;
+                        let backoff = connectors::retry_backoff(std::time::Duration::from_secs(1), attempts as u32);
+                        let retry_at = outbox_millis() + backoff.as_millis() as i64;
+                        tracing::warn!(message_name = name, message_id, attempts, "cannot publish outbox message: {}", e);
+                        #retry.map_err(|e| e.to_string())?;
+                        continue;
+                    }
+                }
+                #mark.map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+
+        fn start_outbox_publisher() {
+            tokio::spawn(async {
+                let mut ticks = tokio::time::interval(std::time::Duration::from_secs(1));
+                ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    ticks.tick().await;
+                    if let Err(e) = publish_outbox().await {
+                        tracing::warn!("outbox: {}", e);
+                    }
+                }
+            });
+        }
+    }
+}
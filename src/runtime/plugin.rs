@@ -0,0 +1,48 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the reply format shared by connectors loaded at startup (WASM and native plugins, Rhai scripts):
+/// `{"output": ...}`, or `{"error": {"message": "...", "retriable": true, "errorCode": "..."}}` where `retriable` and
+/// `errorCode` follow `HandlerError`. Scripts throw the error part only, `replies` adds the whole reply for plugins.
+pub fn expand(replies: bool) -> TokenStream {
+    let replies = replies.then(|| {
+        quote! {
+            #[derive(serde::Deserialize)]
+            struct PluginReply {
+                output: Option<serde_json::Value>,
+                error: Option<PluginError>,
+            }
+
+            fn plugin_reply(name: &str, reply: &[u8]) -> Result<connectors::Output, connectors::ExecError> {
+                let reply: PluginReply = serde_json::from_slice(reply)
+                    .map_err(|e| connectors::ExecError::Handler(format!("Bad reply from `{}`: {}", name, e)))?;
+                match reply.error {
+                    Some(e) => Err(e.into()),
+                    None => Ok(connectors::Output::Value(reply.output.unwrap_or_default())),
+                }
+            }
+        }
+    });
+    quote! {
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct PluginError {
+            message: String,
+            #[serde(default)]
+            retriable: bool,
+            error_code: Option<String>,
+        }
+
+        impl From<PluginError> for connectors::ExecError {
+            fn from(e: PluginError) -> Self {
+                let error = match e.retriable {
+                    true => connectors::HandlerError::retriable(e.message),
+                    false => connectors::HandlerError::fatal(e.message),
+                };
+                connectors::HandlerError { error_code: e.error_code, ..error }.into()
+            }
+        }
+
+        #replies
+    }
+}
@@ -0,0 +1,140 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the scheduler behind `#[camunda_inbound_polling]`: each poller runs on its own task, polling every
+/// interval from the cursor of its last complete round and handling the items whose id it has not seen yet.
+///
+/// A retriable error stops the round, so its items are polled again next round and the cursor does not move past
+/// them. With `state_path`, cursors and seen ids are kept in an embedded sled database rather than in memory, so a
+/// restart resumes where the previous process stopped.
+pub fn expand(state_path: Option<&LitStr>) -> TokenStream {
+    let mut open_state = quote! {};
+    let state_store = match state_path {
+        Some(path) => {
+            open_state = quote! { poll_state_tree(); };
+            quote! {
+                fn poll_state_tree() -> &'static sled::Tree {
+                    static TREE: std::sync::OnceLock<sled::Tree> = std::sync::OnceLock::new();
+                    TREE.get_or_init(|| {
+                        let db = sled::open(#path).unwrap_or_else(|e| panic!("cannot open polling state at `{}`: {}", #path, e));
+                        db.open_tree("pollers").expect("cannot open polling state tree")
+                    })
+                }
+
+                fn load_poll_state(name: &str) -> PollState {
+                    poll_state_tree()
+                        .get(name)
+                        .ok()
+                        .flatten()
+                        .and_then(|value| serde_json::from_slice(&value).ok())
+                        .unwrap_or_default()
+                }
+
+                async fn save_poll_state(name: &str, state: &PollState) {
+                    let tree = poll_state_tree();
+                    let saved = match serde_json::to_vec(state) {
+                        Ok(value) => tree.insert(name, value).map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    if let Err(e) = saved {
+                        tracing::error!(poller = name, "saving polling state failed: {}", e);
+                    }
+                    let _ = tree.flush_async().await;
+                }
+            }
+        }
+        None => quote! {
+            fn load_poll_state(_name: &str) -> PollState {
+                PollState::default()
+            }
+
+            async fn save_poll_state(_name: &str, _state: &PollState) {}
+        },
+    };
+
+    quote! {
+        #[derive(Default, serde::Serialize, serde::Deserialize)]
+        struct PollState {
+            cursor: Option<serde_json::Value>,
+            /// Ids of the handled items, oldest first, bounded to the poller's `dedup_capacity`.
+            seen: std::collections::VecDeque<String>,
+        }
+
+        #state_store
+
+        fn start_pollers() {
+            let mut pollers = ::inventory::iter::<crate::connectors::PollerRecipe>.into_iter().peekable();
+            if pollers.peek().is_some() {
+                #open_state
+            }
+            for r in pollers {
+                (r.start)(r);
+            }
+        }
+
+        async fn run_poller<P: connectors::PollingConnector>(r: &'static connectors::PollerRecipe) {
+            let poller = std::sync::Arc::new(P::default());
+            let mut state = load_poll_state(r.name);
+            let mut seen: std::collections::HashSet<String> = state.seen.iter().cloned().collect();
+            let mut ticker = tokio::time::interval(r.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tracing::info!(poller = r.name, interval_ms = r.interval.as_millis() as u64, "polling");
+            loop {
+                ticker.tick().await;
+                let polled = {
+                    let (poller, cursor) = (poller.clone(), state.cursor.clone());
+                    tokio::spawn(async move { poller.poll(cursor, connectors::Context::default()).await }).await
+                };
+                let polled = match polled {
+                    Ok(Ok(polled)) => polled,
+                    Ok(Err(e)) => {
+                        tracing::warn!(poller = r.name, retriable = e.retriable, "polling failed: {}", e);
+                        continue;
+                    }
+                    Err(_) => {
+                        tracing::error!(poller = r.name, "polling panicked");
+                        continue;
+                    }
+                };
+                let mut complete = true;
+                let mut changed = false;
+                for item in polled.items {
+                    let id = poller.id(&item);
+                    if seen.contains(&id) {
+                        continue;
+                    }
+                    let handled = {
+                        let poller = poller.clone();
+                        tokio::spawn(async move { poller.handle(item, connectors::Context::default()).await }).await
+                    };
+                    match handled {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) if e.retriable => {
+                            tracing::warn!(poller = r.name, item = id.as_str(), "handling item failed, retrying next round: {}", e);
+                            complete = false;
+                            break;
+                        }
+                        Ok(Err(e)) => tracing::error!(poller = r.name, item = id.as_str(), "handling item failed, skipping it: {}", e),
+                        Err(_) => tracing::error!(poller = r.name, item = id.as_str(), "handling item panicked, skipping it"),
+                    }
+                    seen.insert(id.clone());
+                    state.seen.push_back(id);
+                    while state.seen.len() > r.dedup_capacity {
+                        if let Some(oldest) = state.seen.pop_front() {
+                            seen.remove(&oldest);
+                        }
+                    }
+                    changed = true;
+                }
+                if let Some(cursor) = polled.cursor.filter(|_| complete) {
+                    changed |= state.cursor.as_ref() != Some(&cursor);
+                    state.cursor = Some(cursor);
+                }
+                if changed {
+                    save_poll_state(r.name, &state).await;
+                }
+            }
+        }
+    }
+}
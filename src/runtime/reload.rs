@@ -0,0 +1,110 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates `reload_config`, which applies the `KEY=VALUE` lines of `path` at startup and on every `SIGHUP`.
+///
+/// Each key falls back to the environment variable of the same name, so removing a line from the file and
+/// reloading restores the process's original setting. Settings that fail to parse keep their current value.
+/// In-flight calls and queued jobs are untouched.
+///
+/// Every line is kept for `connectors::setting`, through which features listed in `settings` read their own keys.
+/// With `log_levels`, `LOG_LEVELS` is applied along with `RUST_LOG`, and with `log_override` a filter set through the
+/// admin API is dropped for the configured one.
+pub fn expand(path: &LitStr, max_in_flight: Option<usize>, settings: &[String], log_levels: bool, log_override: bool) -> TokenStream {
+    let apply_max_in_flight = match max_in_flight {
+        Some(max) => quote! {
+            match setting("MAX_IN_FLIGHT").map(|v| v.parse::<usize>()) {
+                Some(Ok(0) | Err(_)) => tracing::error!("MAX_IN_FLIGHT must be a positive integer, keeping the current limit"),
+                Some(Ok(limit)) => MAX_IN_FLIGHT.store(limit, std::sync::atomic::Ordering::Relaxed),
+                None => MAX_IN_FLIGHT.store(#max, std::sync::atomic::Ordering::Relaxed),
+            }
+        },
+        None => quote! {
+            if setting("MAX_IN_FLIGHT").is_some() {
+                tracing::warn!("MAX_IN_FLIGHT is ignored, the global limit requires `max_in_flight` in `connector_main!`");
+            }
+        },
+    };
+    let mut apply_log_levels = quote! {};
+    if log_levels {
+        apply_log_levels = quote! {
+            let filter = match parse_log_levels(&setting("LOG_LEVELS").unwrap_or_default()) {
+                Ok(levels) => LogFilter::new(filter, levels),
+                Err(e) => {
+                    tracing::error!("invalid LOG_LEVELS, keeping the current log filter: {}", e);
+                    return None;
+                }
+            };
+        };
+    }
+    let mut drop_log_override = quote! {};
+    if log_override {
+        drop_log_override = quote! {
+            if LOG_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner()).take().is_some() {
+                tracing::info!("configuration reloaded, the log filter override is dropped");
+            }
+        };
+    }
+    quote! {
+        fn reload_config() {
+            let path = #path;
+            let text = match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::error!(path, "cannot read configuration file, keeping the current settings: {}", e);
+                    return;
+                }
+            };
+            let file: std::collections::HashMap<&str, &str> = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .collect();
+            let known = ["RUST_LOG", "DISABLED_CONNECTORS", "MAX_IN_FLIGHT", #(#settings),*];
+            for key in file.keys().filter(|key| !known.contains(*key) && !connectors::is_config_key(key)) {
+                tracing::warn!(path, key, "unknown configuration key");
+            }
+            connectors::set_config_file_settings(file.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect());
+            let setting = |key: &str| file.get(key).map(|v| v.to_string()).or_else(|| std::env::var(key).ok());
+
+            let filter = || {
+                let filter = match log_filter(&setting("RUST_LOG").unwrap_or_default()) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        tracing::error!("invalid RUST_LOG, keeping the current log filter: {}", e);
+                        return None;
+                    }
+                };
+                #apply_log_levels
+                Some(filter)
+            };
+            if let (Some(filter), Some(handle)) = (filter(), LOG_FILTER.get()) {
+                #drop_log_override
+                let _ = handle.reload(filter);
+            }
+            connectors::set_disabled_connectors(&setting("DISABLED_CONNECTORS").unwrap_or_default());
+            report_disabled();
+            #apply_max_in_flight
+            connectors::flush_caches();
+            tracing::info!(path, "configuration loaded");
+        }
+    }
+}
+
+/// Startup statements: the initial load, then a task reloading on `SIGHUP`.
+pub fn startup() -> TokenStream {
+    quote! {
+        reload_config();
+        #[cfg(unix)]
+        tokio::spawn(async {
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("Failed to install the SIGHUP handler");
+            while hangup.recv().await.is_some() {
+                reload_config();
+            }
+        });
+    }
+}
@@ -0,0 +1,87 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the key case policy of `rename_all = "camelCase"`: connectors under it take their input in that case and
+/// return their output in it, while their Rust structs keep snake_case fields and no serde attributes. Keys are
+/// renamed at any depth, those of maps included, before the input is decoded and after the output is encoded.
+pub fn expand(rule: Option<&LitStr>) -> TokenStream {
+    let rule = match rule {
+        Some(rule) => quote! { Some(#rule) },
+        None => quote! { None },
+    };
+    quote! {
+        /// The key case of connectors that don't declare their own, from `rename_all` in `connector_main!`.
+        pub const RENAME_ALL: Option<&str> = #rule;
+
+        /// `key`, in snake_case or any other case, under a serde `rename_all` rule.
+        pub fn rename_key(rule: &str, key: &str) -> String {
+            let mut words: Vec<String> = Vec::new();
+            let mut previous_upper = false;
+            for c in key.chars() {
+                if c == '_' || c == '-' {
+                    words.push(String::new());
+                    previous_upper = false;
+                    continue;
+                }
+                // Runs of capitals, as in `SCREAMING_SNAKE_CASE`, stay one word.
+                if (c.is_uppercase() && !previous_upper) || words.is_empty() {
+                    words.push(String::new());
+                }
+                previous_upper = c.is_uppercase();
+                words.last_mut().unwrap().extend(c.to_lowercase());
+            }
+            words.retain(|w| !w.is_empty());
+            let capitalize = |w: &String| {
+                let mut chars = w.chars();
+                chars.next().map(|c| c.to_uppercase().collect::<String>() + chars.as_str()).unwrap_or_default()
+            };
+            match rule {
+                // As serde has them for fields, which keep their underscores.
+                "lowercase" => words.join("_"),
+                "UPPERCASE" => words.join("_").to_uppercase(),
+                "PascalCase" => words.iter().map(capitalize).collect(),
+                "camelCase" => {
+                    let mut out = words.first().cloned().unwrap_or_default();
+                    out.extend(words.iter().skip(1).map(capitalize));
+                    out
+                }
+                "snake_case" => words.join("_"),
+                "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+                "kebab-case" => words.join("-"),
+                "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+                _ => key.to_string(),
+            }
+        }
+
+        /// The name a field goes by in envelopes, under the connector's key case.
+        pub fn wire_name(rule: Option<&str>, name: &'static str) -> std::borrow::Cow<'static, str> {
+            match rule {
+                Some(rule) => rename_key(rule, name).into(),
+                None => name.into(),
+            }
+        }
+
+        /// Renames the keys of a value's objects, at any depth.
+        pub fn rename_keys(value: serde_json::Value, rule: &str) -> serde_json::Value {
+            match value {
+                serde_json::Value::Object(object) => object
+                    .into_iter()
+                    .map(|(key, value)| (rename_key(rule, &key), rename_keys(value, rule)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+                serde_json::Value::Array(items) => items.into_iter().map(|item| rename_keys(item, rule)).collect(),
+                value => value,
+            }
+        }
+
+        /// The envelope with its `params.input` keys in snake_case, as the handler's structs name them. Envelopes
+        /// that don't decode, Protobuf ones included, are left to the handler to reject or read.
+        pub fn snake_case_input(format: Format, bytes: axum::body::Bytes) -> axum::body::Bytes {
+            let Ok(mut envelope) = format.decode::<serde_json::Value>(&bytes) else { return bytes };
+            let Some(input) = envelope.pointer_mut("/params/input") else { return bytes };
+            *input = rename_keys(input.take(), "snake_case");
+            format.encode(&envelope).map(Into::into).unwrap_or(bytes)
+        }
+    }
+}
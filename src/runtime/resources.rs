@@ -0,0 +1,21 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `init_resources`, which builds the `#[connector_resource]` singletons one after the other, lower
+/// `order` first, before the server starts. A resource failing to build stops the process.
+pub fn expand() -> TokenStream {
+    quote! {
+        async fn init_resources() {
+            let mut recipes: Vec<&'static connectors::ResourceRecipe> = ::inventory::iter::<crate::connectors::ResourceRecipe>.into_iter().collect();
+            recipes.sort_by_key(|r| (r.order, r.name));
+            for r in recipes {
+                let started = std::time::Instant::now();
+                if let Err(e) = (r.init)().await {
+                    tracing::error!(resource = r.type_name, init = r.name, "cannot build the resource: {}", e);
+                    std::process::exit(1);
+                }
+                tracing::info!(resource = r.type_name, duration_ms = started.elapsed().as_millis() as u64, "resource ready");
+            }
+        }
+    }
+}
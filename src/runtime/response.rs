@@ -0,0 +1,58 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the response envelope of `RESPONSE_ENVELOPE=true`, wrapping the outputs of `/csp/{name}` calls with
+/// how the call went:
+///
+/// ```json
+/// {"result":{...},"metadata":{"connector":"crm","operation":"lookup","version":1,"durationMs":12,"requestId":"...","warnings":[]}}
+/// ```
+///
+/// The setting is read per call, so it follows configuration reloads. Streamed and Protobuf outputs are left
+/// unwrapped, as are errors.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn response_envelope_enabled() -> bool {
+            connectors::setting("RESPONSE_ENVELOPE").as_deref() == Some("true")
+        }
+
+        fn check_response_envelope_setting() -> Vec<String> {
+            match connectors::setting("RESPONSE_ENVELOPE").as_deref() {
+                None | Some("true" | "false") => Vec::new(),
+                Some(other) => vec![format!("RESPONSE_ENVELOPE must be `true` or `false`, got `{}`", other)],
+            }
+        }
+
+        ::inventory::submit! {
+            crate::connectors::StartupCheck {
+                name: "response_envelope",
+                check: check_response_envelope_setting,
+            }
+        }
+
+        /// What the response envelope reports besides the output.
+        struct ResponseMeta {
+            connector: String,
+            operation: String,
+            version: u32,
+            started: std::time::Instant,
+            ctx: connectors::Context,
+        }
+
+        impl ResponseMeta {
+            fn wrap(self, result: serde_json::Value) -> serde_json::Value {
+                serde_json::json!({
+                    "result": result,
+                    "metadata": {
+                        "connector": resolve_alias(&self.connector),
+                        "operation": self.operation,
+                        "version": self.version,
+                        "durationMs": self.started.elapsed().as_millis() as u64,
+                        "requestId": self.ctx.request_id(),
+                        "warnings": self.ctx.warnings(),
+                    },
+                })
+            }
+        }
+    }
+}
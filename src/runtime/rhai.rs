@@ -0,0 +1,133 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the loader for the Rhai script connectors of `dir`.
+///
+/// Each `<name>.rhai` script becomes the catch-all connector `<name>`, taking every operation of version 1. The
+/// runtime decodes the envelope and runs the script with `input` and `operation` in scope; the script's value is the
+/// output. `throw "..."` fails the call, and `throw #{ message: "...", retriable: true, errorCode: "..." }` controls
+/// retries and BPMN errors like `HandlerError`.
+pub fn expand(dir: &LitStr) -> TokenStream {
+    quote! {
+        struct RhaiScript {
+            name: &'static str,
+            ast: rhai::AST,
+        }
+
+        std::thread_local! {
+            /// The deadline of the call running on this thread, checked as the script progresses.
+            static RHAI_DEADLINE: std::cell::Cell<Option<std::time::Instant>> = const { std::cell::Cell::new(None) };
+        }
+
+        fn rhai_engine() -> &'static rhai::Engine {
+            static ENGINE: std::sync::OnceLock<rhai::Engine> = std::sync::OnceLock::new();
+            ENGINE.get_or_init(|| {
+                let mut engine = rhai::Engine::new();
+                engine.on_progress(|operations| {
+                    if operations % 1024 != 0 {
+                        return None;
+                    }
+                    let late = RHAI_DEADLINE.with(|deadline| deadline.get().is_some_and(|deadline| std::time::Instant::now() >= deadline));
+                    late.then(|| "deadline exceeded".into())
+                });
+                engine
+            })
+        }
+
+        /// Compiles the scripts once, when the dispatch table is built. Scripts that fail to compile are reported
+        /// and left out.
+        fn load_rhai_scripts() -> Vec<&'static connectors::ConnectorRecipe> {
+            let entries = match std::fs::read_dir(#dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::error!(dir = #dir, "cannot read Rhai script directory: {}", e);
+                    return Vec::new();
+                }
+            };
+            let mut paths: Vec<std::path::PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+                .collect();
+            paths.sort();
+            let mut recipes = Vec::new();
+            for path in paths {
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+                let ast = match rhai_engine().compile_file(path.clone()) {
+                    Ok(ast) => ast,
+                    Err(e) => {
+                        tracing::error!(path = %path.display(), "cannot load Rhai script: {}", e);
+                        continue;
+                    }
+                };
+                let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+                let script: &'static RhaiScript = Box::leak(Box::new(RhaiScript { name, ast }));
+                let exec_raw: connectors::ExecRaw = Box::leak(Box::new(move |body, format, ctx| run_rhai_script(script, body, format, ctx)));
+                recipes.push(&*Box::leak(Box::new(connectors::ConnectorRecipe {
+                    name,
+                    operations: &["*"],
+                    version: 1,
+                    aliases: &[],
+                    description: None,
+                    deprecated: None,
+                    sunset: None,
+                    layer: None,
+                    method: "POST",
+                    path: None,
+                    input_type: "serde_json::Value",
+                    output_type: "serde_json::Value",
+                    input_fields: || None,
+                    rename_all: None,
+                    slow_call: None,
+                    exec_raw,
+                })));
+                tracing::info!(connector = name, path = %path.display(), "loaded Rhai script");
+            }
+            recipes
+        }
+
+        fn run_rhai_script(
+            script: &'static RhaiScript,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+        ) -> connectors::DispatcherFuture {
+            Box::pin(async move {
+                let envelope = format
+                    .decode::<serde_json::Value>(&body)
+                    .map_err(|e| connectors::ExecError::Input(format!("Bad {} for `{}`: {}", format.name(), script.name, e)))?;
+                let operation = envelope.pointer("/params/operation").and_then(|op| op.as_str()).unwrap_or_default().to_string();
+                let input = envelope.pointer("/params/input").cloned().unwrap_or_default();
+                let deadline = ctx.remaining().map(|remaining| std::time::Instant::now() + remaining);
+                tokio::task::spawn_blocking(move || {
+                    let input = rhai::serde::to_dynamic(&input)
+                        .map_err(|e| connectors::ExecError::Input(format!("Bad input for `{}`: {}", script.name, e)))?;
+                    let mut scope = rhai::Scope::new();
+                    scope.push_dynamic("input", input);
+                    scope.push("operation", operation.clone());
+                    RHAI_DEADLINE.with(|cell| cell.set(deadline));
+                    let result = rhai_engine().eval_ast_with_scope::<rhai::Dynamic>(&mut scope, &script.ast);
+                    RHAI_DEADLINE.with(|cell| cell.set(None));
+                    match result {
+                        Ok(output) => rhai::serde::from_dynamic::<serde_json::Value>(&output)
+                            .map(connectors::Output::Value)
+                            .map_err(|e| connectors::ExecError::Handler(format!("Bad output from `{}`: {}", script.name, e))),
+                        Err(e) => match *e {
+                            rhai::EvalAltResult::ErrorRuntime(thrown, _) => Err(match rhai::serde::from_dynamic::<PluginError>(&thrown) {
+                                Ok(thrown) => thrown.into(),
+                                Err(_) => connectors::ExecError::Handler(thrown.to_string()),
+                            }),
+                            rhai::EvalAltResult::ErrorTerminated(..) => {
+                                Err(connectors::ExecError::DeadlineExceeded(format!("Deadline exceeded for `{}`/`{}`", script.name, operation)))
+                            }
+                            e => Err(connectors::ExecError::Handler(format!("Rhai script `{}` failed: {}", script.name, e))),
+                        },
+                    }
+                })
+                .await
+                .map_err(|_| connectors::ExecError::Handler(format!("Rhai script `{}` panicked", script.name)))?
+            })
+        }
+    }
+}
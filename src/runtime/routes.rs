@@ -0,0 +1,173 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the routes of operations declared with `method` or `path`, for API gateways and caches that route on
+/// the method and path rather than the envelope's `operation`:
+///
+/// - `GET /csp/{name}/{operation}` for operations declared with `method = "GET"` alone, their input from the query
+///   string, typed after its `ConnectorInput` fields.
+/// - `{method} /csp/{name}/{path}` for operations declared with `path = "..."`. `GET` and `DELETE` take their input
+///   from the query string, other methods from the body, and `{field}` segments of the path fill input fields.
+///
+/// Calls are dispatched like the envelope `POST /csp/{name}` would have been sent. These routes name no version, so
+/// they run the latest version of the operation declaring them.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn get_recipe(name: &str, operation: &str) -> Option<(u32, &'static connectors::ConnectorRecipe)> {
+            let name = resolve_alias(name);
+            dispatch_table()
+                .iter()
+                .filter(|((n, op, _), r)| n == name && op == operation && r.method == "GET" && r.path.is_none())
+                .map(|((_, _, version), r)| (*version, *r))
+                .max_by_key(|(version, _)| *version)
+        }
+
+        /// An input from query or path parameters, typed after its fields, named for the recipe's key case.
+        fn query_input(recipe: &connectors::ConnectorRecipe, query: Vec<(String, String)>) -> Result<serde_json::Value, String> {
+            let fields = (recipe.input_fields)().unwrap_or_default();
+            let mut input = serde_json::Map::new();
+            for (key, value) in query {
+                let Some(field) = fields.iter().find(|field| connectors::wire_name(recipe.rename_all, field.name) == key) else {
+                    return Err(format!("Unknown parameter `{}`", key));
+                };
+                let value = match field.kind {
+                    "Number" => {
+                        let number = value.parse::<i64>().map(serde_json::Number::from).ok()
+                            .or_else(|| value.parse::<u64>().map(serde_json::Number::from).ok())
+                            .or_else(|| value.parse::<f64>().ok().and_then(serde_json::Number::from_f64));
+                        number.map(serde_json::Value::Number).ok_or_else(|| format!("Expected a number for `{}`, got `{}`", key, value))?
+                    }
+                    "Boolean" => match value.as_str() {
+                        "true" => serde_json::Value::Bool(true),
+                        "false" => serde_json::Value::Bool(false),
+                        _ => return Err(format!("Expected `true` or `false` for `{}`, got `{}`", key, value)),
+                    },
+                    // JSON values may be given as JSON, anything else is taken as a string.
+                    "Text" => serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value)),
+                    _ => serde_json::Value::String(value),
+                };
+                if input.insert(key.clone(), value).is_some() {
+                    return Err(format!("Parameter `{}` is repeated", key));
+                }
+            }
+            Ok(input.into())
+        }
+
+        /// Runs `operation` of `name` with `input`, through the envelope `POST /csp/{name}` would have been sent.
+        async fn dispatch_input(
+            name: String,
+            operation: &str,
+            version: u32,
+            input: serde_json::Value,
+            format: connectors::Format,
+            client: axum::Extension<connectors::ClientInfo>,
+            mut headers: axum::http::HeaderMap,
+        ) -> axum::response::Result<axum::response::Response> {
+            let envelope = serde_json::json!({
+                "params": { "operation": operation, "input": input, "version": version },
+            });
+            let body = format.encode(&envelope).map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            headers.insert(axum::http::header::CONTENT_TYPE, axum::http::HeaderValue::from_static(format.content_type()));
+            // The envelope is built here, in version 1, whatever the caller's envelopes are.
+            headers.remove("x-envelope-version");
+            dispatch(axum::extract::Path(name), client, headers, body.into()).await
+        }
+
+        async fn dispatch_get(
+            axum::extract::Path((name, operation)): axum::extract::Path<(String, String)>,
+            axum::extract::Query(query): axum::extract::Query<Vec<(String, String)>>,
+            client: axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+        ) -> axum::response::Result<axum::response::Response> {
+            let Some((version, recipe)) = get_recipe(&name, &operation) else {
+                return Err((axum::http::StatusCode::NOT_FOUND, format!("No GET operation `{}`/`{}`", name, operation)).into());
+            };
+            let input = query_input(recipe, query).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+            // The envelope is JSON whatever the request says, the response is too.
+            dispatch_input(name, &operation, version, input, connectors::Format::Json, client, headers).await
+        }
+
+        /// The operation served on a `path = "..."` route.
+        #[derive(Clone)]
+        struct PathRoute {
+            name: &'static str,
+            operation: String,
+            version: u32,
+            recipe: &'static connectors::ConnectorRecipe,
+        }
+
+        /// The `path = "..."` routes by URL and method, under the connectors' names and aliases.
+        fn path_routes() -> std::collections::BTreeMap<(String, &'static str), PathRoute> {
+            let mut routes = std::collections::BTreeMap::new();
+            for ((_, operation, version), r) in dispatch_table() {
+                let Some(path) = r.path else { continue };
+                // Aliases may be declared on any handler of the connector.
+                let aliases = ::inventory::iter::<crate::connectors::ConnectorRecipe>
+                    .into_iter()
+                    .filter(|other| other.name == r.name)
+                    .flat_map(|other| other.aliases.iter().copied());
+                for name in std::iter::once(r.name).chain(aliases) {
+                    let url = format!("/csp/{}/{}", name, path);
+                    match routes.get(&(url.clone(), r.method)) {
+                        Some(PathRoute { operation: first, .. }) if first != operation => {
+                            tracing::error!(url, method = r.method, first, second = operation.as_str(), "route is declared by two operations, keeping one");
+                        }
+                        Some(PathRoute { version: latest, .. }) if latest > version => {}
+                        _ => {
+                            routes.insert((url, r.method), PathRoute { name: r.name, operation: operation.clone(), version: *version, recipe: r });
+                        }
+                    }
+                }
+            }
+            routes
+        }
+
+        async fn dispatch_path(
+            route: PathRoute,
+            params: Option<axum::extract::Path<Vec<(String, String)>>>,
+            axum::extract::Query(query): axum::extract::Query<Vec<(String, String)>>,
+            client: axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+        ) -> axum::response::Result<axum::response::Response> {
+            let bad_request = |e: String| (axum::http::StatusCode::BAD_REQUEST, e);
+            let (mut input, format) = if matches!(route.recipe.method, "GET" | "DELETE") {
+                (query_input(route.recipe, query).map_err(bad_request)?, connectors::Format::Json)
+            } else {
+                let format = connectors::Format::from_headers(&headers);
+                let input = match body.is_empty() {
+                    true => serde_json::Value::Object(Default::default()),
+                    false => format.decode(&body).map_err(|e| bad_request(format!("Bad {} body: {}", format.name(), e)))?,
+                };
+                (input, format)
+            };
+            let params = params.map(|axum::extract::Path(params)| params).unwrap_or_default();
+            if !params.is_empty() {
+                let serde_json::Value::Object(params) = query_input(route.recipe, params).map_err(bad_request)? else {
+                    unreachable!("parameters make an object");
+                };
+                let Some(input) = input.as_object_mut() else {
+                    return Err(bad_request("The body must be an object to take the path's fields".to_string()).into());
+                };
+                for (key, value) in params {
+                    if input.insert(key.clone(), value).is_some() {
+                        return Err(bad_request(format!("`{}` is given both in the path and the request", key)).into());
+                    }
+                }
+            }
+            dispatch_input(route.name.to_string(), &route.operation, route.version, input, format, client, headers).await
+        }
+
+        /// The handler of a `path = "..."` route, answering its method only.
+        fn path_router(method: &str, route: PathRoute) -> axum::routing::MethodRouter {
+            let filter = match method {
+                "GET" => axum::routing::MethodFilter::GET,
+                "PUT" => axum::routing::MethodFilter::PUT,
+                "PATCH" => axum::routing::MethodFilter::PATCH,
+                "DELETE" => axum::routing::MethodFilter::DELETE,
+                _ => axum::routing::MethodFilter::POST,
+            };
+            axum::routing::on(filter, move |params, query, client, headers, body| dispatch_path(route.clone(), params, query, client, headers, body))
+        }
+    }
+}
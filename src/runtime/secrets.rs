@@ -0,0 +1,357 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, LitStr};
+
+use super::{cloud_secrets, file_secrets, vault};
+
+/// A secrets backend compiled in with `secrets = [...]`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Provider {
+    /// Environment variables, or lines of the configuration file, named after the secret.
+    Env,
+    Vault,
+    /// AWS Secrets Manager, through the AWS SDK's default credentials.
+    Aws,
+    /// GCP Secret Manager, with the service account of the metadata server.
+    Gcp,
+    /// Files of a directory, such as a mounted Kubernetes Secret.
+    Files,
+}
+
+impl Provider {
+    pub fn parse(lit: &LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "env" => Ok(Provider::Env),
+            "vault" => Ok(Provider::Vault),
+            "aws" => Ok(Provider::Aws),
+            "gcp" => Ok(Provider::Gcp),
+            "files" => Ok(Provider::Files),
+            _ => Err(Error::new_spanned(lit, "Expected `env`, `files`, `vault`, `aws` or `gcp`")),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Provider::Env => "env",
+            Provider::Vault => "vault",
+            Provider::Aws => "aws",
+            Provider::Gcp => "gcp",
+            Provider::Files => "files",
+        }
+    }
+
+    /// The settings the provider reads, besides those of every provider.
+    pub fn settings(self) -> &'static [&'static str] {
+        match self {
+            Provider::Env => &[],
+            Provider::Vault => &vault::SETTINGS,
+            Provider::Aws => &cloud_secrets::AWS_SETTINGS,
+            Provider::Gcp => &cloud_secrets::GCP_SETTINGS,
+            Provider::Files => &file_secrets::SETTINGS,
+        }
+    }
+}
+
+/// Generates `connectors::SecretsProvider` and the resolution of `{{secrets.NAME}}` placeholders. The providers in
+/// `providers` are compiled in, and `SECRETS_PROVIDER` picks one at startup, the first by default.
+pub fn expand(providers: &[Provider]) -> TokenStream {
+    let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+    let default = names[0];
+    let expected = names.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ");
+    let builds = providers.iter().map(|provider| {
+        let name = provider.name();
+        let build = match provider {
+            Provider::Env => quote! { Ok(Box::new(EnvSecrets)) },
+            Provider::Vault => quote! { Ok(Box::new(VaultSecrets::from_settings()?)) },
+            Provider::Aws => quote! { Ok(Box::new(AwsSecrets::from_settings()?)) },
+            Provider::Gcp => quote! { Ok(Box::new(GcpSecrets::from_settings()?)) },
+            Provider::Files => quote! { Ok(Box::new(FileSecrets::from_settings()?)) },
+        };
+        quote! { #name => #build, }
+    });
+    let vault = providers.contains(&Provider::Vault).then(vault::expand);
+    let aws = providers.contains(&Provider::Aws).then(cloud_secrets::aws);
+    let gcp = providers.contains(&Provider::Gcp).then(cloud_secrets::gcp);
+    let files = providers.contains(&Provider::Files).then(file_secrets::expand);
+    let cache = (providers.contains(&Provider::Aws) || providers.contains(&Provider::Gcp)).then(cloud_secrets::cache);
+
+    quote! {
+        pub type SecretFuture<'a> = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<String>, String>> + Send + 'a>>;
+
+        /// A backend resolving secrets by name, for `{{secrets.NAME}}` placeholders and `connectors::secret`.
+        pub trait SecretsProvider: Send + Sync {
+            /// The secret `name`, `None` when there is no such secret. Errors are failures to reach the backend.
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a>;
+        }
+
+        /// Secrets from the configuration file or the environment variable named after them.
+        struct EnvSecrets;
+
+        impl SecretsProvider for EnvSecrets {
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a> {
+                Box::pin(async move { Ok(setting(name)) })
+            }
+        }
+
+        #vault
+        #cache
+        #aws
+        #gcp
+        #files
+
+        fn build_secrets_provider() -> Result<Box<dyn SecretsProvider>, String> {
+            let provider = setting("SECRETS_PROVIDER").unwrap_or_else(|| #default.to_string());
+            match provider.as_str() {
+                #(#builds)*
+                _ => Err(format!("SECRETS_PROVIDER must be one of {}, got `{}`", #expected, provider)),
+            }
+        }
+
+        fn secrets_provider() -> &'static dyn SecretsProvider {
+            static PROVIDER: std::sync::OnceLock<Box<dyn SecretsProvider>> = std::sync::OnceLock::new();
+            // Startup validation has built one already, so this can't fail.
+            &**PROVIDER.get_or_init(|| build_secrets_provider().expect("the secrets provider is checked at startup"))
+        }
+
+        fn check_secrets_settings() -> Vec<String> {
+            let mut problems: Vec<String> = build_secrets_provider().err().into_iter().collect();
+            match setting("SECRETS_TRUST_ENVELOPE_TENANT").as_deref() {
+                None | Some("true" | "false") => {}
+                Some(other) => problems.push(format!("SECRETS_TRUST_ENVELOPE_TENANT must be `true` or `false`, got `{}`", other)),
+            }
+            if let Some(tenant) = setting("SECRETS_DEFAULT_TENANT").filter(|tenant| !valid_tenant(tenant)) {
+                problems.push(format!("SECRETS_DEFAULT_TENANT must be made of letters, digits, `_` and `-`, got `{}`", tenant));
+            }
+            problems
+        }
+
+        ::inventory::submit! {
+            StartupCheck {
+                name: "secrets",
+                check: check_secrets_settings,
+            }
+        }
+
+        /// The secret `name` from the configured provider, for the default tenant.
+        pub async fn secret(name: &str) -> Result<String, HandlerError> {
+            tenant_secret(None, name).await
+        }
+
+        /// The secret `name` for the tenant of the call's job, the default tenant for other calls.
+        pub async fn secret_for(ctx: &Context, name: &str) -> Result<String, HandlerError> {
+            tenant_secret(secrets_tenant(ctx), name).await
+        }
+
+        /// The tenant whose secrets a call gets. Zeebe vouches for a job's tenant, but anyone can name one in an
+        /// envelope, so the envelope's only counts with `SECRETS_TRUST_ENVELOPE_TENANT=true`, for runtimes whose
+        /// callers are authenticated in front of them.
+        fn secrets_tenant(ctx: &Context) -> Option<&str> {
+            let job = ctx.job().and_then(|job| job.tenant_id.as_deref()).filter(|tenant| !tenant.is_empty());
+            job.or_else(|| match setting("SECRETS_TRUST_ENVELOPE_TENANT").as_deref() == Some("true") {
+                true => ctx.envelope().and_then(|envelope| envelope.tenant.as_deref()).filter(|tenant| !tenant.is_empty()),
+                false => None,
+            })
+        }
+
+        /// Tenants are substituted into secret names, so they can't carry path separators or other syntax.
+        fn valid_tenant(tenant: &str) -> bool {
+            !tenant.is_empty() && tenant.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        }
+
+        /// `name` under `SECRETS_PREFIX`, whose `{tenant}` is replaced by the tenant, `SECRETS_DEFAULT_TENANT` without
+        /// one: with `tenants/{tenant}/`, tenant `acme`'s `CRM_API_KEY` is `tenants/acme/CRM_API_KEY`.
+        fn namespaced_secret(tenant: Option<&str>, name: &str) -> Result<String, HandlerError> {
+            let Some(prefix) = setting("SECRETS_PREFIX") else { return Ok(name.to_string()) };
+            let default = setting("SECRETS_DEFAULT_TENANT");
+            let tenant = tenant.or(default.as_deref()).unwrap_or("default");
+            if !valid_tenant(tenant) {
+                return Err(HandlerError::fatal(format!("Invalid tenant `{}`, tenants are made of letters, digits, `_` and `-`", tenant)));
+            }
+            Ok(format!("{}{}", prefix.replace("{tenant}", tenant), name))
+        }
+
+        async fn tenant_secret(tenant: Option<&str>, name: &str) -> Result<String, HandlerError> {
+            let name = namespaced_secret(tenant, name)?;
+            match secrets_provider().get(&name).await {
+                Ok(Some(value)) => {
+                    track_secret(&value);
+                    Ok(value)
+                }
+                Ok(None) => Err(HandlerError::fatal(format!("Unknown secret `{}`", name))),
+                Err(e) => Err(HandlerError::retriable(format!("Cannot resolve secret `{}`: {}", name, e))),
+            }
+        }
+
+        /// Every secret value resolved so far, longest first so that one containing another is redacted whole.
+        fn resolved_secrets() -> &'static std::sync::RwLock<Vec<String>> {
+            static RESOLVED: std::sync::OnceLock<std::sync::RwLock<Vec<String>>> = std::sync::OnceLock::new();
+            RESOLVED.get_or_init(Default::default)
+        }
+
+        /// Values this short would redact ordinary words and numbers.
+        const MIN_REDACTED_LEN: usize = 4;
+
+        fn track_secret(value: &str) {
+            if value.len() < MIN_REDACTED_LEN || resolved_secrets().read().unwrap().iter().any(|known| known == value) {
+                return;
+            }
+            let mut resolved = resolved_secrets().write().unwrap();
+            if !resolved.iter().any(|known| known == value) {
+                resolved.push(value.to_string());
+                resolved.sort_by_key(|known| std::cmp::Reverse(known.len()));
+            }
+        }
+
+        /// `text` with every resolved secret replaced by `[REDACTED]`.
+        pub fn redact(text: &str) -> std::borrow::Cow<'_, str> {
+            let resolved = resolved_secrets().read().unwrap();
+            let mut redacted = std::borrow::Cow::Borrowed(text);
+            for known in resolved.iter() {
+                if redacted.contains(known.as_str()) {
+                    redacted = std::borrow::Cow::Owned(redacted.replace(known.as_str(), "[REDACTED]"));
+                }
+            }
+            redacted
+        }
+
+        /// The error with the resolved secrets redacted from its message, before it reaches the caller.
+        pub fn redact_error(e: ExecError) -> ExecError {
+            let scrub = |message: String| redact(&message).into_owned();
+            match e {
+                ExecError::Unsupported(message) => ExecError::Unsupported(scrub(message)),
+                ExecError::Input(message) => ExecError::Input(scrub(message)),
+                ExecError::Handler(message) => ExecError::Handler(scrub(message)),
+                ExecError::CircuitOpen { message, retry_after } => ExecError::CircuitOpen { message: scrub(message), retry_after },
+                ExecError::Overloaded(message) => ExecError::Overloaded(scrub(message)),
+                ExecError::DeadlineExceeded(message) => ExecError::DeadlineExceeded(scrub(message)),
+                ExecError::Disabled(message) => ExecError::Disabled(scrub(message)),
+                ExecError::Failed { message, retries, retry_backoff, error_code } => {
+                    ExecError::Failed { message: scrub(message), retries, retry_backoff, error_code }
+                }
+            }
+        }
+
+        /// Standard output with the resolved secrets redacted from every log line written to it.
+        pub struct RedactingStdout(std::io::Stdout);
+
+        pub fn redacting_stdout() -> RedactingStdout {
+            RedactingStdout(std::io::stdout())
+        }
+
+        impl std::io::Write for RedactingStdout {
+            // The formatter writes each event whole, so a secret never straddles two writes.
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                match std::str::from_utf8(buf) {
+                    Ok(line) => self.0.write_all(redact(line).as_bytes())?,
+                    Err(_) => self.0.write_all(buf)?,
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        /// The names of the `{{secrets.NAME}}` placeholders in `text`, with the range each spans.
+        fn secret_placeholders(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+            let mut placeholders = Vec::new();
+            let mut from = 0;
+            while let Some(start) = text[from..].find("{{").map(|i| from + i) {
+                let Some(end) = text[start..].find("}}").map(|i| start + i + 2) else { break };
+                match text[start + 2..end - 2].trim().strip_prefix("secrets.") {
+                    Some(name) if !name.is_empty() => placeholders.push((start..end, name)),
+                    _ => {}
+                }
+                from = end;
+            }
+            placeholders
+        }
+
+        fn collect_secret_names<'v>(value: &'v serde_json::Value, names: &mut std::collections::BTreeSet<&'v str>) {
+            match value {
+                serde_json::Value::String(text) => names.extend(secret_placeholders(text).into_iter().map(|(_, name)| name)),
+                serde_json::Value::Array(items) => items.iter().for_each(|item| collect_secret_names(item, names)),
+                serde_json::Value::Object(map) => map.values().for_each(|item| collect_secret_names(item, names)),
+                _ => {}
+            }
+        }
+
+        fn replace_secrets(value: &mut serde_json::Value, secrets: &std::collections::HashMap<String, String>) {
+            match value {
+                serde_json::Value::String(text) => {
+                    let mut replaced = String::with_capacity(text.len());
+                    let mut last = 0;
+                    for (range, name) in secret_placeholders(text) {
+                        replaced.push_str(&text[last..range.start]);
+                        replaced.push_str(&secrets[name]);
+                        last = range.end;
+                    }
+                    if last > 0 {
+                        replaced.push_str(&text[last..]);
+                        *text = replaced;
+                    }
+                }
+                serde_json::Value::Array(items) => items.iter_mut().for_each(|item| replace_secrets(item, secrets)),
+                serde_json::Value::Object(map) => map.values_mut().for_each(|item| replace_secrets(item, secrets)),
+                _ => {}
+            }
+        }
+
+        /// Checks that the `secret` fields of an input reference their secrets rather than holding them.
+        pub fn check_secret_fields(fields: &[FieldMeta], rename_all: Option<&str>, input: &serde_json::Value) -> Result<(), String> {
+            for field in fields.iter().filter(|field| field.secret) {
+                let literal = match input.get(field.name) {
+                    Some(serde_json::Value::String(text)) => !text.is_empty() && secret_placeholders(text).is_empty(),
+                    Some(serde_json::Value::Null) | None => false,
+                    Some(_) => true,
+                };
+                if literal {
+                    return Err(format!("`{}` must reference a secret, such as `{{{{secrets.NAME}}}}`", wire_name(rename_all, field.name)));
+                }
+            }
+            Ok(())
+        }
+
+        /// Replaces the `{{secrets.NAME}}` placeholders in the strings of a value, at any depth, each secret being
+        /// fetched once, for the tenant of the call.
+        pub async fn resolve_secrets(ctx: &Context, value: &mut serde_json::Value) -> Result<(), HandlerError> {
+            let mut names = std::collections::BTreeSet::new();
+            collect_secret_names(value, &mut names);
+            if names.is_empty() {
+                return Ok(());
+            }
+            let mut secrets = std::collections::HashMap::new();
+            for name in names {
+                secrets.insert(name.to_string(), secret_for(ctx, name).await?);
+            }
+            replace_secrets(value, &secrets);
+            Ok(())
+        }
+    }
+}
+
+/// The resolution of the placeholders in the envelope's `params.input`, before the handler decodes it.
+pub fn resolve_inputs() -> TokenStream {
+    quote! {
+        let body = match format.decode::<serde_json::Value>(&body) {
+            Ok(mut envelope) => match envelope.pointer_mut("/params/input") {
+                Some(input) => {
+                    let recipe = lookup(&name, &operation, format.peek_version(&body)).ok().map(|(_, r)| r);
+                    let fields = recipe.and_then(|r| (r.input_fields)()).unwrap_or_default();
+                    connectors::check_secret_fields(fields, recipe.and_then(|r| r.rename_all), input).map_err(|e| {
+                        ctx.classify(connectors::ErrorClass::Validation);
+                        connectors::ExecError::Input(e)
+                    })?;
+                    connectors::resolve_secrets(&ctx, input).await.map_err(|e| match e.retriable {
+                        true => connectors::ExecError::Handler(e.message),
+                        false => connectors::ExecError::Input(e.message),
+                    })?;
+                    format.encode(&envelope).map(axum::body::Bytes::from).unwrap_or(body)
+                }
+                None => body,
+            },
+            Err(_) => body,
+        };
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the graceful shutdown: on SIGTERM or Ctrl-C, the listeners stop accepting connections, `/readyz` turns
+/// `503`, and in-flight requests get up to `drain_timeout` to finish. The `#[connector_shutdown]` hooks then run one
+/// after the other, each within its own timeout, so that a stuck hook can't hold up termination.
+pub fn expand(drain_timeout: Duration) -> TokenStream {
+    let drain_ms = drain_timeout.as_millis() as u64;
+    quote! {
+        fn shutdown_sender() -> &'static tokio::sync::watch::Sender<bool> {
+            static SENDER: std::sync::OnceLock<tokio::sync::watch::Sender<bool>> = std::sync::OnceLock::new();
+            SENDER.get_or_init(|| tokio::sync::watch::channel(false).0)
+        }
+
+        fn shutting_down() -> bool {
+            *shutdown_sender().borrow()
+        }
+
+        /// Resolves once the shutdown has started.
+        async fn shutdown_requested() {
+            let mut receiver = shutdown_sender().subscribe();
+            let _ = receiver.wait_for(|down| *down).await;
+        }
+
+        /// Resolves `drain_timeout` after the shutdown has started, when lingering connections are given up on.
+        async fn drain_expired() {
+            shutdown_requested().await;
+            tokio::time::sleep(std::time::Duration::from_millis(#drain_ms)).await;
+        }
+
+        fn watch_shutdown_signals() {
+            tokio::spawn(async {
+                #[cfg(unix)]
+                let terminate = async {
+                    match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                        Ok(mut signal) => {
+                            signal.recv().await;
+                        }
+                        Err(e) => {
+                            tracing::error!("cannot listen for SIGTERM: {}", e);
+                            std::future::pending::<()>().await;
+                        }
+                    }
+                };
+                #[cfg(not(unix))]
+                let terminate = std::future::pending::<()>();
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate => {}
+                }
+                tracing::info!("shutting down, draining connections");
+                shutdown_sender().send_replace(true);
+            });
+        }
+
+        async fn run_shutdown_hooks() {
+            let mut hooks: Vec<&'static connectors::ShutdownRecipe> = ::inventory::iter::<crate::connectors::ShutdownRecipe>.into_iter().collect();
+            hooks.sort_by_key(|r| (r.order, r.name));
+            for r in hooks {
+                let started = std::time::Instant::now();
+                let mut task = tokio::spawn((r.hook)());
+                match tokio::time::timeout(r.timeout, &mut task).await {
+                    Ok(Ok(Ok(()))) => tracing::info!(hook = r.name, duration_ms = started.elapsed().as_millis() as u64, "shutdown hook done"),
+                    Ok(Ok(Err(e))) => tracing::error!(hook = r.name, "shutdown hook failed: {}", e),
+                    Ok(Err(_)) => tracing::error!(hook = r.name, "shutdown hook panicked"),
+                    Err(_) => {
+                        task.abort();
+                        tracing::error!(hook = r.name, "shutdown hook timed out after {}ms, abandoning it", r.timeout.as_millis());
+                    }
+                }
+            }
+        }
+    }
+}
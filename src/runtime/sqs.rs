@@ -0,0 +1,118 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the SQS poller behind `#[camunda_inbound_sqs]`, built on the AWS SDK with the default credential chain.
+///
+/// Each handler long-polls its queue and runs a received batch concurrently. A message is deleted only once its
+/// handler succeeds, and kept invisible for the declared visibility timeout while the handler runs; failed messages
+/// are left to reappear once it elapses, so the queue's redrive policy decides when they are dead-lettered.
+pub fn expand() -> TokenStream {
+    quote! {
+        async fn sqs_client() -> &'static aws_sdk_sqs::Client {
+            static CLIENT: tokio::sync::OnceCell<aws_sdk_sqs::Client> = tokio::sync::OnceCell::const_new();
+            CLIENT
+                .get_or_init(|| async {
+                    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                    aws_sdk_sqs::Client::new(&config)
+                })
+                .await
+        }
+
+        fn start_sqs_poller(r: &'static connectors::InboundRecipe) {
+            let connectors::InboundSource::Sqs { queue_url, queue_url_env, max_messages, wait_time_secs, visibility_timeout_secs } = r.source else {
+                return;
+            };
+            let queue_url = match queue_url_env {
+                Some(name) => match std::env::var(name) {
+                    Ok(url) => url,
+                    Err(_) => {
+                        tracing::error!(handler = r.name, "inbound handler is never called, `{}` is not set", name);
+                        return;
+                    }
+                },
+                None => queue_url.to_string(),
+            };
+            tokio::spawn(async move {
+                let client = sqs_client().await;
+                tracing::info!(handler = r.name, queue_url = queue_url.as_str(), "polling SQS queue");
+                let mut failures: u32 = 0;
+                loop {
+                    let received = client
+                        .receive_message()
+                        .queue_url(&queue_url)
+                        .max_number_of_messages(max_messages)
+                        .wait_time_seconds(wait_time_secs)
+                        .visibility_timeout(visibility_timeout_secs)
+                        .send()
+                        .await;
+                    let messages = match received {
+                        Ok(output) => {
+                            failures = 0;
+                            output.messages.unwrap_or_default()
+                        }
+                        Err(e) => {
+                            tracing::warn!(handler = r.name, queue_url = queue_url.as_str(), "receiving SQS messages failed: {}", aws_sdk_sqs::error::DisplayErrorContext(&e));
+                            let delay = std::time::Duration::from_millis(500).saturating_mul(1 << failures.min(6));
+                            tokio::time::sleep(delay.min(std::time::Duration::from_secs(30))).await;
+                            failures += 1;
+                            continue;
+                        }
+                    };
+                    let mut batch = tokio::task::JoinSet::new();
+                    for message in messages {
+                        batch.spawn(handle_sqs_message(r, client, queue_url.clone(), message, visibility_timeout_secs));
+                    }
+                    while batch.join_next().await.is_some() {}
+                }
+            });
+        }
+
+        async fn handle_sqs_message(
+            r: &'static connectors::InboundRecipe,
+            client: &'static aws_sdk_sqs::Client,
+            queue_url: String,
+            message: aws_sdk_sqs::types::Message,
+            visibility_timeout_secs: i32,
+        ) {
+            let Some(receipt) = message.receipt_handle().map(str::to_string) else { return };
+            let message_id = message.message_id().unwrap_or_default().to_string();
+            let body = axum::body::Bytes::from(message.body().unwrap_or_default().to_string());
+
+            // Extends the message's invisibility halfway through each timeout, for as long as the handler runs.
+            let heartbeat = {
+                let (queue_url, receipt) = (queue_url.clone(), receipt.clone());
+                tokio::spawn(async move {
+                    let period = std::time::Duration::from_secs(visibility_timeout_secs as u64) / 2;
+                    loop {
+                        tokio::time::sleep(period).await;
+                        let extended = client
+                            .change_message_visibility()
+                            .queue_url(&queue_url)
+                            .receipt_handle(&receipt)
+                            .visibility_timeout(visibility_timeout_secs)
+                            .send()
+                            .await;
+                        if let Err(e) = extended {
+                            tracing::warn!("cannot extend the visibility of an SQS message: {}", aws_sdk_sqs::error::DisplayErrorContext(&e));
+                        }
+                    }
+                })
+            };
+            let result = run_inbound(r, body, connectors::Context::default(), Some(message_id.clone()).filter(|id| !id.is_empty())).await;
+            heartbeat.abort();
+
+            match result {
+                Ok(_) => {
+                    let deleted = client.delete_message().queue_url(&queue_url).receipt_handle(&receipt).send().await;
+                    // The message comes back once its visibility timeout elapses and is handled again.
+                    if let Err(e) = deleted {
+                        tracing::warn!(handler = r.name, message_id, "cannot delete SQS message: {}", aws_sdk_sqs::error::DisplayErrorContext(&e));
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(handler = r.name, message_id, retriable = e.retriable, "inbound handler failed, leaving the message in the queue: {}", e);
+                }
+            }
+        }
+    }
+}
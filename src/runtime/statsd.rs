@@ -0,0 +1,132 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings the StatsD exporter reads, from the configuration file or the environment.
+pub const SETTINGS: [&str; 5] = ["STATSD_HOST", "STATSD_PORT", "STATSD_PREFIX", "STATSD_TAGS", "STATSD_FLUSH_INTERVAL"];
+
+/// Generates the push-based exporter of `statsd = true`, sending the metrics `/metrics` serves over UDP to a StatsD
+/// agent, for environments nothing scrapes. Latencies are sent as timers and failures as counters when they happen,
+/// gauges every `STATSD_FLUSH_INTERVAL`. Labels go out as DogStatsD tags, alongside the `STATSD_TAGS`.
+///
+/// The agent's address is resolved once it answers to its name, so that a sidecar starting late only loses the
+/// metrics sent before it did.
+pub fn expand() -> TokenStream {
+    quote! {
+        struct Statsd {
+            socket: std::net::UdpSocket,
+            prefix: String,
+            tags: String,
+        }
+
+        static STATSD: std::sync::OnceLock<Statsd> = std::sync::OnceLock::new();
+
+        /// (host, port, prefix, tags, flush interval)
+        fn statsd_settings() -> Result<(String, u16, String, String, std::time::Duration), String> {
+            let host = connectors::setting("STATSD_HOST").unwrap_or_else(|| "127.0.0.1".to_string());
+            let port = match connectors::setting("STATSD_PORT") {
+                Some(port) => port.parse().map_err(|_| format!("STATSD_PORT must be a port number, got `{}`", port))?,
+                None => 8125,
+            };
+            let prefix = connectors::setting("STATSD_PREFIX").unwrap_or_else(|| "connector".to_string());
+            if prefix.contains([':', '|', '@', '#']) {
+                return Err(format!("STATSD_PREFIX must not contain `:`, `|`, `@` or `#`, got `{}`", prefix));
+            }
+            let tags = connectors::setting("STATSD_TAGS").unwrap_or_default();
+            let tags: Vec<&str> = tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()).collect();
+            if let Some(tag) = tags.iter().find(|tag| tag.contains(['|', '#'])) {
+                return Err(format!("STATSD_TAGS must be comma-separated `key:value` tags, got `{}`", tag));
+            }
+            let interval = connectors::duration_setting("STATSD_FLUSH_INTERVAL", std::time::Duration::from_secs(10))?;
+            if interval.is_zero() {
+                return Err("STATSD_FLUSH_INTERVAL must not be zero".to_string());
+            }
+            Ok((host, port, prefix, tags.join(","), interval))
+        }
+
+        fn check_statsd_settings() -> Vec<String> {
+            statsd_settings().err().into_iter().collect()
+        }
+
+        ::inventory::submit! {
+            crate::connectors::StartupCheck {
+                name: "statsd",
+                check: check_statsd_settings,
+            }
+        }
+
+        fn connect_statsd(host: &str, port: u16, prefix: &str, tags: &str) -> std::io::Result<Statsd> {
+            let address = std::net::ToSocketAddrs::to_socket_addrs(&(host, port))?
+                .next()
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address"))?;
+            let local: std::net::SocketAddr = match address {
+                std::net::SocketAddr::V4(_) => (std::net::Ipv4Addr::UNSPECIFIED, 0).into(),
+                std::net::SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+            };
+            let socket = std::net::UdpSocket::bind(local)?;
+            socket.set_nonblocking(true)?;
+            socket.connect(address)?;
+            Ok(Statsd { socket, prefix: prefix.to_string(), tags: tags.to_string() })
+        }
+
+        fn start_statsd() {
+            let (host, port, prefix, tags, interval) = statsd_settings().expect("checked by validate_startup");
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                loop {
+                    ticks.tick().await;
+                    if STATSD.get().is_none() {
+                        // Name resolution blocks, so it runs off the runtime's workers.
+                        let connected = tokio::task::spawn_blocking({
+                            let (host, prefix, tags) = (host.clone(), prefix.clone(), tags.clone());
+                            move || connect_statsd(&host, port, &prefix, &tags)
+                        })
+                        .await;
+                        match connected {
+                            Ok(Ok(statsd)) => {
+                                tracing::info!(host, port, "sending metrics to StatsD");
+                                let _ = STATSD.set(statsd);
+                            }
+                            Ok(Err(e)) => tracing::warn!(host, port, "cannot reach StatsD, retrying: {}", e),
+                            Err(_) => {}
+                        }
+                    }
+                    flush_statsd_gauges();
+                }
+            });
+        }
+
+        /// Sends one metric, `kind` being `ms`, `c` or `g`; dropped while the agent is unknown or its buffer is full.
+        fn statsd_send(family: &str, value: std::fmt::Arguments<'_>, kind: &str, labels: &[(&str, &str)]) {
+            use std::fmt::Write as _;
+            let Some(statsd) = STATSD.get() else { return };
+            let mut line = format!("{}.{}:{}|{}", statsd.prefix, dotted_metric_name(family), value, kind);
+            let mut separator = "|#";
+            for (key, value) in labels {
+                // Tag values end at `,` and the line at `|`.
+                let _ = write!(line, "{}{}:{}", separator, key, value.replace([',', '|', '#'], "_"));
+                separator = ",";
+            }
+            if !statsd.tags.is_empty() {
+                let _ = write!(line, "{}{}", separator, statsd.tags);
+            }
+            let _ = statsd.socket.send(line.as_bytes());
+        }
+
+        fn statsd_timing(metric: &str, labels: &(String, String), outcome: &str, elapsed: std::time::Duration) {
+            let tags = [("connector", labels.0.as_str()), ("operation", labels.1.as_str()), ("outcome", outcome)];
+            statsd_send(metric, format_args!("{:.3}", elapsed.as_secs_f64() * 1000.0), "ms", &tags);
+        }
+
+        fn statsd_error(labels: &(String, String), class: connectors::ErrorClass) {
+            let tags = [("connector", labels.0.as_str()), ("operation", labels.1.as_str()), ("class", class.label())];
+            statsd_send("connector_errors_total", format_args!("1"), "c", &tags);
+        }
+
+        fn flush_statsd_gauges() {
+            for ((gauge, connector), value) in gauge_table().read().unwrap_or_else(|e| e.into_inner()).iter() {
+                let value = value.load(std::sync::atomic::Ordering::Relaxed);
+                statsd_send(gauge.family(), format_args!("{}", value), "g", &[("connector", connector.as_str())]);
+            }
+        }
+    }
+}
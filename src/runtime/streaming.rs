@@ -0,0 +1,54 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates the response of `stream = true` connectors, written item by item as the handler yields them: as
+/// server-sent events to callers accepting `text/event-stream`, as NDJSON otherwise.
+///
+/// The status is sent with the first bytes, so an error midway through the stream is reported as a last item, an
+/// `error` event or an `{"error": ...}` line, after which the response ends.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn stream_response(headers: &axum::http::HeaderMap, items: connectors::ItemStream) -> axum::response::Response {
+            use futures_util::StreamExt as _;
+            let Some(items) = items.take() else {
+                return axum::response::IntoResponse::into_response((axum::http::StatusCode::INTERNAL_SERVER_ERROR, "The stream was already consumed"));
+            };
+            // Nothing is read past the first error.
+            let items = items.scan(false, |failed, item| {
+                let item = (!*failed).then(|| {
+                    *failed = item.is_err();
+                    item
+                });
+                std::future::ready(item)
+            });
+            let sse = headers
+                .get(axum::http::header::ACCEPT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|accept| accept.contains("text/event-stream"));
+            if sse {
+                let events = items.map(|item| {
+                    let event = match item {
+                        Ok(value) => axum::response::sse::Event::default().json_data(value).unwrap_or_default(),
+                        Err(e) => axum::response::sse::Event::default().event("error").data(e),
+                    };
+                    Ok::<_, std::convert::Infallible>(event)
+                });
+                let sse = axum::response::sse::Sse::new(events).keep_alive(axum::response::sse::KeepAlive::default());
+                return axum::response::IntoResponse::into_response(sse);
+            }
+            let lines = items.map(|item| {
+                let mut line = match item {
+                    Ok(value) => serde_json::to_vec(&value),
+                    Err(e) => serde_json::to_vec(&serde_json::json!({ "error": e })),
+                }
+                .unwrap_or_default();
+                line.push(b'\n');
+                Ok::<_, std::convert::Infallible>(axum::body::Bytes::from(line))
+            });
+            axum::response::IntoResponse::into_response((
+                [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+                axum::body::Body::from_stream(lines),
+            ))
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates `validate_startup`, run before any listener binds: it loads the connector configs and runs every
+/// `connectors::StartupCheck`, such as the `required_settings` being set or the shared HTTP client's settings being
+/// valid. Everything wrong is logged in one report and the process stops, rather than failing on the first call.
+pub fn expand(required_settings: &[LitStr]) -> TokenStream {
+    quote! {
+        fn required_settings() -> Vec<String> {
+            let keys: &[&str] = &[#(#required_settings),*];
+            keys.iter()
+                .filter(|key| connectors::setting(key).is_none())
+                .map(|key| format!("{} is not set", key))
+                .collect()
+        }
+
+        ::inventory::submit! {
+            crate::connectors::StartupCheck {
+                name: "required settings",
+                check: required_settings,
+            }
+        }
+
+        fn validate_startup() {
+            let mut problems = connectors::load_configs();
+            for check in ::inventory::iter::<crate::connectors::StartupCheck> {
+                problems.extend((check.check)().into_iter().map(|problem| format!("{}: {}", check.name, problem)));
+            }
+            if problems.is_empty() {
+                return;
+            }
+            let report: String = problems.iter().map(|problem| format!("\n  - {}", problem)).collect();
+            tracing::error!("startup validation failed with {} problem(s):{}", problems.len(), report);
+            std::process::exit(1);
+        }
+    }
+}
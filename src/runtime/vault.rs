@@ -0,0 +1,206 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// The settings `VaultSecrets` reads.
+pub const SETTINGS: [&str; 11] = [
+    "VAULT_ADDR",
+    "VAULT_NAMESPACE",
+    "VAULT_AUTH",
+    "VAULT_TOKEN",
+    "VAULT_K8S_ROLE",
+    "VAULT_K8S_MOUNT",
+    "VAULT_K8S_TOKEN_FILE",
+    "VAULT_KV_MOUNT",
+    "VAULT_KV_PATH",
+    "VAULT_CACHE_TTL",
+    "VAULT_TIMEOUT",
+];
+
+/// Generates `VaultSecrets`, reading secrets from a KV v2 engine. `{{secrets.NAME}}` is the key `NAME` of the secret
+/// at `VAULT_KV_PATH`, and `{{secrets.crm/API_KEY}}` the key `API_KEY` of the secret at `crm`. Secrets are cached for
+/// `VAULT_CACHE_TTL`, each path with all of its keys.
+///
+/// The client logs in with a token or through the Kubernetes auth method, with the pod's service account token, on
+/// first use. Tokens are renewed once two thirds of their lease have passed, and logged in anew when they can't be.
+pub fn expand() -> TokenStream {
+    quote! {
+        enum VaultAuth {
+            Token(String),
+            Kubernetes { role: String, mount: String, token_file: String },
+        }
+
+        struct VaultToken {
+            token: String,
+            renewable: bool,
+            /// When the token must be renewed, `None` for tokens that don't expire.
+            renew_at: Option<std::time::Instant>,
+        }
+
+        /// A KV v2 secret's keys, as of when they were read.
+        type VaultCache = std::collections::HashMap<String, (std::time::Instant, std::sync::Arc<std::collections::HashMap<String, String>>)>;
+
+        struct VaultSecrets {
+            client: reqwest::Client,
+            addr: String,
+            namespace: Option<String>,
+            auth: VaultAuth,
+            kv_mount: String,
+            kv_path: Option<String>,
+            cache_ttl: std::time::Duration,
+            token: tokio::sync::Mutex<Option<VaultToken>>,
+            cache: std::sync::Mutex<VaultCache>,
+        }
+
+        impl VaultSecrets {
+            fn from_settings() -> Result<Self, String> {
+                let addr = setting("VAULT_ADDR").ok_or("VAULT_ADDR is not set")?;
+                let auth = match setting("VAULT_AUTH").as_deref().unwrap_or("token") {
+                    "token" => VaultAuth::Token(setting("VAULT_TOKEN").ok_or("VAULT_TOKEN is not set")?),
+                    "kubernetes" => VaultAuth::Kubernetes {
+                        role: setting("VAULT_K8S_ROLE").ok_or("VAULT_K8S_ROLE is not set")?,
+                        mount: setting("VAULT_K8S_MOUNT").unwrap_or_else(|| "kubernetes".to_string()),
+                        token_file: setting("VAULT_K8S_TOKEN_FILE").unwrap_or_else(|| "/var/run/secrets/kubernetes.io/serviceaccount/token".to_string()),
+                    },
+                    other => return Err(format!("VAULT_AUTH must be `token` or `kubernetes`, got `{}`", other)),
+                };
+                let client = reqwest::Client::builder()
+                    .timeout(duration_setting("VAULT_TIMEOUT", std::time::Duration::from_secs(10))?)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                Ok(VaultSecrets {
+                    client,
+                    addr: addr.trim_end_matches('/').to_string(),
+                    namespace: setting("VAULT_NAMESPACE"),
+                    auth,
+                    kv_mount: setting("VAULT_KV_MOUNT").unwrap_or_else(|| "secret".to_string()),
+                    kv_path: setting("VAULT_KV_PATH"),
+                    cache_ttl: duration_setting("VAULT_CACHE_TTL", std::time::Duration::from_secs(300))?,
+                    token: tokio::sync::Mutex::new(None),
+                    cache: Default::default(),
+                })
+            }
+
+            fn request(&self, method: reqwest::Method, path: &str, token: Option<&str>) -> reqwest::RequestBuilder {
+                let mut request = self.client.request(method, format!("{}/v1/{}", self.addr, path));
+                if let Some(namespace) = &self.namespace {
+                    request = request.header("X-Vault-Namespace", namespace);
+                }
+                if let Some(token) = token {
+                    request = request.header("X-Vault-Token", token);
+                }
+                request
+            }
+
+            async fn send(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                let status = response.status();
+                let body = response.bytes().await.map_err(|e| e.to_string())?;
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+                if !status.is_success() {
+                    let errors = body["errors"].as_array().map(|errors| errors.iter().filter_map(|e| e.as_str()).collect::<Vec<_>>().join(", "));
+                    return Err(format!("Vault answered {}: {}", status, errors.unwrap_or_default()));
+                }
+                Ok(body)
+            }
+
+            /// The token of a login or renewal response's `auth`, or of a lookup's `data`.
+            fn token_of(token: String, ttl: Option<u64>, renewable: bool) -> VaultToken {
+                let renew_at = ttl.filter(|ttl| *ttl > 0).map(|ttl| std::time::Instant::now() + std::time::Duration::from_secs(ttl * 2 / 3));
+                VaultToken { token, renewable, renew_at }
+            }
+
+            async fn login(&self) -> Result<VaultToken, String> {
+                match &self.auth {
+                    VaultAuth::Token(token) => {
+                        let body = Self::send(self.request(reqwest::Method::GET, "auth/token/lookup-self", Some(token))).await?;
+                        let data = &body["data"];
+                        Ok(Self::token_of(token.clone(), data["ttl"].as_u64(), data["renewable"].as_bool().unwrap_or(false)))
+                    }
+                    VaultAuth::Kubernetes { role, mount, token_file } => {
+                        let jwt = std::fs::read_to_string(token_file).map_err(|e| format!("cannot read {}: {}", token_file, e))?;
+                        let login = serde_json::json!({ "role": role, "jwt": jwt.trim() });
+                        let body = Self::send(self.request(reqwest::Method::POST, &format!("auth/{}/login", mount), None).body(login.to_string())).await?;
+                        let auth = &body["auth"];
+                        let token = auth["client_token"].as_str().ok_or("Vault's login response has no token")?.to_string();
+                        Ok(Self::token_of(token, auth["lease_duration"].as_u64(), auth["renewable"].as_bool().unwrap_or(false)))
+                    }
+                }
+            }
+
+            async fn renew(&self, token: &str) -> Result<VaultToken, String> {
+                let body = Self::send(self.request(reqwest::Method::POST, "auth/token/renew-self", Some(token)).body("{}")).await?;
+                let auth = &body["auth"];
+                Ok(Self::token_of(token.to_string(), auth["lease_duration"].as_u64(), auth["renewable"].as_bool().unwrap_or(false)))
+            }
+
+            async fn token(&self) -> Result<String, String> {
+                let mut current = self.token.lock().await;
+                let due = current.as_ref().map(|t| t.renew_at.is_some_and(|at| at <= std::time::Instant::now()));
+                match due {
+                    Some(false) => {}
+                    Some(true) => {
+                        let token = current.take().expect("a token is due");
+                        let renewed = match token.renewable {
+                            true => self.renew(&token.token).await.inspect_err(|e| tracing::warn!("cannot renew the Vault token, logging in again: {}", e)).ok(),
+                            false => None,
+                        };
+                        *current = Some(match renewed {
+                            Some(renewed) => renewed,
+                            None => self.login().await?,
+                        });
+                    }
+                    None => *current = Some(self.login().await?),
+                }
+                Ok(current.as_ref().expect("a token is set").token.clone())
+            }
+
+            /// The keys of the secret at `path`, from the cache while fresh.
+            async fn read(&self, path: &str) -> Result<std::sync::Arc<std::collections::HashMap<String, String>>, String> {
+                if let Some((read_at, keys)) = self.cache.lock().unwrap().get(path) {
+                    if read_at.elapsed() < self.cache_ttl {
+                        return Ok(keys.clone());
+                    }
+                }
+                let token = self.token().await?;
+                let request = self.request(reqwest::Method::GET, &format!("{}/data/{}", self.kv_mount, path), Some(&token));
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                let keys: std::collections::HashMap<String, String> = match response.status() {
+                    reqwest::StatusCode::NOT_FOUND => Default::default(),
+                    status if status.is_success() => {
+                        let body = response.bytes().await.map_err(|e| e.to_string())?;
+                        let body: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+                        let data = body["data"]["data"].as_object().cloned().unwrap_or_default();
+                        data.into_iter()
+                            .map(|(key, value)| match value {
+                                serde_json::Value::String(value) => (key, value),
+                                value => (key, value.to_string()),
+                            })
+                            .collect()
+                    }
+                    status => {
+                        // A revoked token is replaced on the next read.
+                        if status == reqwest::StatusCode::FORBIDDEN {
+                            *self.token.lock().await = None;
+                        }
+                        return Err(format!("Vault answered {} reading `{}`", status, path));
+                    }
+                };
+                let keys = std::sync::Arc::new(keys);
+                self.cache.lock().unwrap().insert(path.to_string(), (std::time::Instant::now(), keys.clone()));
+                Ok(keys)
+            }
+        }
+
+        impl SecretsProvider for VaultSecrets {
+            fn get<'a>(&'a self, name: &'a str) -> SecretFuture<'a> {
+                Box::pin(async move {
+                    let (path, key) = match name.rsplit_once('/') {
+                        Some((path, key)) => (path, key),
+                        None => (self.kv_path.as_deref().ok_or("VAULT_KV_PATH is not set, secrets must be named `path/key`")?, name),
+                    };
+                    Ok(self.read(path).await?.get(key).cloned())
+                })
+            }
+        }
+    }
+}
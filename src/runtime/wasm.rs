@@ -0,0 +1,133 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Generates the loader for the WASM plugin connectors of `dir`, run with wasmtime.
+///
+/// Each `<name>.wasm` module becomes the catch-all connector `<name>`, taking every operation of version 1. Its ABI is
+/// bytes in, bytes out: the module exports its `memory`, `alloc(len: i32) -> i32` returning where to write the
+/// envelope as JSON, and `execute(ptr: i32, len: i32) -> i64` returning the reply's pointer in the high and length in
+/// the low 32 bits, in the reply format of `plugin::expand`.
+///
+/// Modules import nothing and get a fresh instance per call, so they keep no state between calls. A call is
+/// interrupted once the caller's deadline elapses.
+pub fn expand(dir: &LitStr) -> TokenStream {
+    quote! {
+        struct WasmPlugin {
+            name: &'static str,
+            pre: wasmtime::InstancePre<()>,
+        }
+
+        /// Epochs tick every 10ms on their own thread, bounding calls by their store's epoch deadline.
+        fn wasm_engine() -> &'static wasmtime::Engine {
+            static ENGINE: std::sync::OnceLock<wasmtime::Engine> = std::sync::OnceLock::new();
+            ENGINE.get_or_init(|| {
+                let mut config = wasmtime::Config::new();
+                config.epoch_interruption(true);
+                let engine = wasmtime::Engine::new(&config).expect("cannot create the WASM engine");
+                let ticker = engine.clone();
+                std::thread::Builder::new()
+                    .name("wasm-epoch".to_string())
+                    .spawn(move || loop {
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        ticker.increment_epoch();
+                    })
+                    .expect("cannot start the WASM epoch thread");
+                engine
+            })
+        }
+
+        /// Compiles the plugins once, when the dispatch table is built. Modules that fail to compile are reported and
+        /// left out.
+        fn load_wasm_plugins() -> Vec<&'static connectors::ConnectorRecipe> {
+            let entries = match std::fs::read_dir(#dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::error!(dir = #dir, "cannot read WASM plugin directory: {}", e);
+                    return Vec::new();
+                }
+            };
+            let mut paths: Vec<std::path::PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+                .collect();
+            paths.sort();
+            let mut recipes = Vec::new();
+            for path in paths {
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+                let pre = match wasmtime::Module::from_file(wasm_engine(), &path)
+                    .and_then(|module| wasmtime::Linker::new(wasm_engine()).instantiate_pre(&module))
+                {
+                    Ok(pre) => pre,
+                    Err(e) => {
+                        tracing::error!(path = %path.display(), "cannot load WASM plugin: {:#}", e);
+                        continue;
+                    }
+                };
+                let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+                let plugin: &'static WasmPlugin = Box::leak(Box::new(WasmPlugin { name, pre }));
+                let exec_raw: connectors::ExecRaw = Box::leak(Box::new(move |body, format, ctx| run_wasm_plugin(plugin, body, format, ctx)));
+                recipes.push(&*Box::leak(Box::new(connectors::ConnectorRecipe {
+                    name,
+                    operations: &["*"],
+                    version: 1,
+                    aliases: &[],
+                    description: None,
+                    deprecated: None,
+                    sunset: None,
+                    layer: None,
+                    method: "POST",
+                    path: None,
+                    input_type: "serde_json::Value",
+                    output_type: "serde_json::Value",
+                    input_fields: || None,
+                    rename_all: None,
+                    slow_call: None,
+                    exec_raw,
+                })));
+                tracing::info!(connector = name, path = %path.display(), "loaded WASM plugin");
+            }
+            recipes
+        }
+
+        fn run_wasm_plugin(
+            plugin: &'static WasmPlugin,
+            body: axum::body::Bytes,
+            format: connectors::Format,
+            ctx: connectors::Context,
+        ) -> connectors::DispatcherFuture {
+            Box::pin(async move {
+                let envelope = format
+                    .decode::<serde_json::Value>(&body)
+                    .and_then(|envelope| serde_json::to_vec(&envelope).map_err(|e| e.to_string()))
+                    .map_err(|e| connectors::ExecError::Input(format!("Bad {} for `{}`: {}", format.name(), plugin.name, e)))?;
+                // No deadline still bounds the call, to about 16 months.
+                let ticks = ctx.remaining().map_or(u32::MAX as u64, |remaining| (remaining.as_millis() as u64 / 10).max(1));
+                let reply = tokio::task::spawn_blocking(move || call_wasm_plugin(plugin, &envelope, ticks))
+                    .await
+                    .map_err(|_| connectors::ExecError::Handler(format!("WASM plugin `{}` panicked", plugin.name)))?
+                    .map_err(|e| connectors::ExecError::Handler(format!("WASM plugin `{}` failed: {:#}", plugin.name, e)))?;
+                plugin_reply(plugin.name, &reply)
+            })
+        }
+
+        fn call_wasm_plugin(plugin: &WasmPlugin, envelope: &[u8], ticks: u64) -> wasmtime::Result<Vec<u8>> {
+            let mut store = wasmtime::Store::new(wasm_engine(), ());
+            store.set_epoch_deadline(ticks);
+            let instance = plugin.pre.instantiate(&mut store)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| wasmtime::Error::msg("module exports no `memory`"))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let execute = instance.get_typed_func::<(i32, i32), i64>(&mut store, "execute")?;
+            let len = i32::try_from(envelope.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, envelope)?;
+            let packed = execute.call(&mut store, (ptr, len))? as u64;
+            let mut reply = vec![0; (packed & 0xffff_ffff) as usize];
+            memory.read(&store, (packed >> 32) as usize, &mut reply)?;
+            Ok(reply)
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `/inbound/{path}`, which hands webhook deliveries to the `#[camunda_inbound_webhook]` handler registered
+/// for the path, answering provider verification handshakes on its behalf.
+///
+/// The handler's output is returned as JSON. Retriable errors answer `503` for the provider to deliver again, other
+/// errors `422`.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn webhook_recipe(path: &str) -> Option<(&'static connectors::InboundRecipe, Option<&'static connectors::WebhookVerification>)> {
+            type WebhookTable = std::collections::HashMap<&'static str, (&'static connectors::InboundRecipe, Option<&'static connectors::WebhookVerification>)>;
+            static TABLE: std::sync::OnceLock<WebhookTable> = std::sync::OnceLock::new();
+            let table = TABLE.get_or_init(|| {
+                let mut table = std::collections::HashMap::new();
+                for r in ::inventory::iter::<crate::connectors::InboundRecipe> {
+                    if let connectors::InboundSource::Webhook { path, verification, .. } = &r.source {
+                        if let Some((first, _)) = table.insert(*path, (r, verification.as_ref())) {
+                            tracing::error!(path, first = first.name, second = r.name, "webhook path is declared by two handlers");
+                        }
+                    }
+                }
+                table
+            });
+            table.get(path).copied()
+        }
+
+        /// The response to a verification request, `None` for regular deliveries.
+        fn webhook_handshake(
+            verification: &connectors::WebhookVerification,
+            method: &axum::http::Method,
+            query: &std::collections::HashMap<String, String>,
+            headers: &axum::http::HeaderMap,
+            body: &[u8],
+        ) -> Option<axum::response::Response> {
+            use axum::response::IntoResponse;
+            match verification {
+                connectors::WebhookVerification::Slack => {
+                    let event: serde_json::Value = serde_json::from_slice(body).ok()?;
+                    if event["type"] != "url_verification" {
+                        return None;
+                    }
+                    Some(axum::Json(serde_json::json!({ "challenge": event["challenge"] })).into_response())
+                }
+                connectors::WebhookVerification::MicrosoftGraph => {
+                    let token = query.get("validationToken")?;
+                    Some(([(axum::http::header::CONTENT_TYPE, "text/plain")], token.clone()).into_response())
+                }
+                connectors::WebhookVerification::Meta { verify_token_env } => {
+                    if method != axum::http::Method::GET || query.get("hub.mode").map(String::as_str) != Some("subscribe") {
+                        return None;
+                    }
+                    let expected = std::env::var(verify_token_env).ok().filter(|token| !token.is_empty());
+                    match (expected, query.get("hub.verify_token")) {
+                        (Some(expected), Some(token)) if expected == *token => {
+                            Some(query.get("hub.challenge").cloned().unwrap_or_default().into_response())
+                        }
+                        _ => Some((axum::http::StatusCode::FORBIDDEN, "Verify token mismatch").into_response()),
+                    }
+                }
+                connectors::WebhookVerification::Asana => {
+                    let secret = headers.get("x-hook-secret")?;
+                    Some((axum::http::StatusCode::OK, [("x-hook-secret", secret.clone())]).into_response())
+                }
+            }
+        }
+
+        async fn dispatch_webhook(
+            axum::extract::Path(path): axum::extract::Path<String>,
+            axum::extract::Query(query): axum::extract::Query<std::collections::HashMap<String, String>>,
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            method: axum::http::Method,
+            headers: axum::http::HeaderMap,
+            body: axum::body::Bytes,
+        ) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            let Some((r, verification)) = webhook_recipe(&path) else {
+                return (axum::http::StatusCode::NOT_FOUND, format!("No webhook at `/inbound/{}`", path)).into_response();
+            };
+            if let Some(response) = verification.and_then(|v| webhook_handshake(v, &method, &query, &headers, &body)) {
+                tracing::info!(handler = r.name, path, "answered webhook verification");
+                return response;
+            }
+            if method != axum::http::Method::POST {
+                return axum::http::StatusCode::METHOD_NOT_ALLOWED.into_response();
+            }
+            let ctx = match connectors::Context::from_headers(&headers) {
+                Ok(ctx) => ctx.with_client(client),
+                Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+            };
+            let delivery_id = match r.source {
+                connectors::InboundSource::Webhook { dedup_header: Some(name), .. } => {
+                    headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+                }
+                _ => None,
+            };
+            match run_inbound(r, body, ctx, delivery_id).await {
+                Ok(output) => axum::Json(output).into_response(),
+                Err(e) => {
+                    tracing::warn!(handler = r.name, path, retriable = e.retriable, "inbound handler failed: {}", e);
+                    let status = match e.retriable {
+                        true => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                        false => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                    };
+                    (status, e.message).into_response()
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates `/ws/{path}`, where sources push events over a long-lived WebSocket to the
+/// `#[camunda_inbound_websocket]` handler registered for the path, rather than one HTTP request per event.
+///
+/// Every text or binary frame is a message. Each is answered with an acknowledgement frame carrying its sequence
+/// number on the connection, once its handler is done. At most `max_in_flight` frames of a connection are handled at
+/// once; past that, the connection isn't read until one completes, which pushes back on the sender through TCP.
+pub fn expand() -> TokenStream {
+    quote! {
+        fn websocket_recipe(path: &str) -> Option<&'static connectors::InboundRecipe> {
+            static TABLE: std::sync::OnceLock<std::collections::HashMap<&'static str, &'static connectors::InboundRecipe>> = std::sync::OnceLock::new();
+            let table = TABLE.get_or_init(|| {
+                let mut table = std::collections::HashMap::new();
+                for r in ::inventory::iter::<crate::connectors::InboundRecipe> {
+                    if let connectors::InboundSource::WebSocket { path, .. } = &r.source {
+                        if let Some(first) = table.insert(*path, r) {
+                            tracing::error!(path, first = first.name, second = r.name, "WebSocket path is declared by two handlers");
+                        }
+                    }
+                }
+                table
+            });
+            table.get(path).copied()
+        }
+
+        /// Whether the connection presents the token of the `token_env` variable, as a bearer token or, for browsers
+        /// that can't set headers, a `token` query parameter. An unset or empty variable lets no one in.
+        fn websocket_authorized(
+            token_env: &str,
+            headers: &axum::http::HeaderMap,
+            query: &std::collections::HashMap<String, String>,
+        ) -> bool {
+            let Some(token) = std::env::var(token_env).ok().filter(|token| !token.is_empty()) else {
+                tracing::error!(token_env, "WebSocket token variable is not set, refusing connections");
+                return false;
+            };
+            let presented = headers
+                .get(axum::http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .or(query.get("token").map(String::as_str));
+            // Compared in constant time, without short-circuiting on the first differing byte.
+            presented.is_some_and(|presented| {
+                token.len() == presented.len() && token.bytes().zip(presented.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+            })
+        }
+
+        async fn dispatch_websocket(
+            axum::extract::Path(path): axum::extract::Path<String>,
+            axum::extract::Query(query): axum::extract::Query<std::collections::HashMap<String, String>>,
+            axum::Extension(client): axum::Extension<connectors::ClientInfo>,
+            headers: axum::http::HeaderMap,
+            upgrade: axum::extract::ws::WebSocketUpgrade,
+        ) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            let Some(r) = websocket_recipe(&path) else {
+                return (axum::http::StatusCode::NOT_FOUND, format!("No WebSocket at `/ws/{}`", path)).into_response();
+            };
+            let connectors::InboundSource::WebSocket { token_env, max_in_flight, .. } = r.source else {
+                unreachable!("WebSocket table holds WebSocket handlers only");
+            };
+            if let Some(token_env) = token_env {
+                if !websocket_authorized(token_env, &headers, &query) {
+                    return (
+                        axum::http::StatusCode::UNAUTHORIZED,
+                        [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+                        "Missing or invalid token",
+                    )
+                        .into_response();
+                }
+            }
+            upgrade.on_upgrade(move |socket| handle_websocket(socket, r, client, max_in_flight))
+        }
+
+        /// Reads frames while fewer than `max_in_flight` are being handled, and acknowledges each as it completes:
+        /// `{"seq":1,"ok":true}`, or `{"seq":1,"ok":false,"retriable":true,"error":"..."}`.
+        async fn handle_websocket(
+            mut socket: axum::extract::ws::WebSocket,
+            r: &'static connectors::InboundRecipe,
+            client: connectors::ClientInfo,
+            max_in_flight: usize,
+        ) {
+            use axum::extract::ws::Message;
+            tracing::info!(handler = r.name, client = ?client.ip, "WebSocket connected");
+            let mut running = tokio::task::JoinSet::new();
+            let mut seq: u64 = 0;
+            let mut open = true;
+            while open || !running.is_empty() {
+                tokio::select! {
+                    Some(done) = running.join_next(), if !running.is_empty() => {
+                        let Ok((seq, result)) = done else {
+                            continue;
+                        };
+                        let ack = match result {
+                            Ok(_) => serde_json::json!({ "seq": seq, "ok": true }),
+                            Err(e) => {
+                                tracing::warn!(handler = r.name, seq, retriable = e.retriable, "inbound handler failed: {}", e);
+                                serde_json::json!({ "seq": seq, "ok": false, "retriable": e.retriable, "error": e.message })
+                            }
+                        };
+                        // Once the connection is gone, the remaining frames are still handled, just not acknowledged.
+                        if open && socket.send(Message::Text(ack.to_string().into())).await.is_err() {
+                            open = false;
+                        }
+                    }
+                    frame = socket.recv(), if open && running.len() < max_in_flight => {
+                        let body = match frame {
+                            Some(Ok(Message::Text(text))) => axum::body::Bytes::from(text.as_str().to_owned()),
+                            Some(Ok(Message::Binary(bytes))) => bytes,
+                            // Pings are answered by the socket itself.
+                            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                            Some(Ok(Message::Close(_)) | Err(_)) | None => {
+                                open = false;
+                                continue;
+                            }
+                        };
+                        seq += 1;
+                        let ctx = connectors::Context::default().with_client(client.clone());
+                        running.spawn(async move { (seq, run_inbound(r, body, ctx, None).await) });
+                    }
+                }
+            }
+            tracing::info!(handler = r.name, client = ?client.ip, frames = seq, "WebSocket disconnected");
+        }
+    }
+}
@@ -0,0 +1,753 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub struct WorkerArgs {
+    pub streaming: bool,
+    pub timeout: Duration,
+    pub max_jobs: u32,
+    pub poll_timeout: Duration,
+    pub stream_timeout: Duration,
+    pub reconnect_backoff: Duration,
+}
+
+/// Generates `connectors::zeebe`, a client for the Zeebe gateway's gRPC API spoken over plain HTTP/2 with reqwest and
+/// prost, so no gRPC stack is needed.
+///
+/// The gateway is `ZEEBE_GRPC_ADDRESS` (`http://localhost:26500` by default). With `ZEEBE_CLIENT_ID` and
+/// `ZEEBE_CLIENT_SECRET`, calls carry an OAuth token fetched from `ZEEBE_AUTHORIZATION_SERVER_URL` for the audience
+/// `ZEEBE_TOKEN_AUDIENCE`, as Camunda's own clients do.
+pub fn client() -> TokenStream {
+    quote! {
+        pub mod zeebe {
+            /// A failed gateway call, with its gRPC status code.
+            #[derive(Debug, Clone)]
+            pub struct ZeebeError {
+                pub code: u32,
+                pub message: String,
+            }
+
+            impl ZeebeError {
+                pub const UNKNOWN: u32 = 2;
+                pub const INVALID_ARGUMENT: u32 = 3;
+                pub const ALREADY_EXISTS: u32 = 6;
+                pub const DEADLINE_EXCEEDED: u32 = 4;
+                pub const RESOURCE_EXHAUSTED: u32 = 8;
+                pub const UNIMPLEMENTED: u32 = 12;
+                pub const UNAVAILABLE: u32 = 14;
+                pub const UNAUTHENTICATED: u32 = 16;
+
+                fn new(code: u32, message: impl Into<String>) -> Self {
+                    ZeebeError { code, message: message.into() }
+                }
+            }
+
+            impl std::fmt::Display for ZeebeError {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    write!(f, "Zeebe gateway call failed with status {}: {}", self.code, self.message)
+                }
+            }
+
+            impl std::error::Error for ZeebeError {}
+
+            /// Lets handlers use `?` on gateway calls: an unreachable or overloaded gateway is worth retrying.
+            impl From<ZeebeError> for super::HandlerError {
+                fn from(e: ZeebeError) -> Self {
+                    match e.code {
+                        ZeebeError::DEADLINE_EXCEEDED | ZeebeError::RESOURCE_EXHAUSTED | ZeebeError::UNAVAILABLE => super::HandlerError::retriable(e.to_string()),
+                        _ => super::HandlerError::fatal(e.to_string()),
+                    }
+                }
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct ActivateJobsRequest {
+                #[prost(string, tag = "1")]
+                pub r#type: String,
+                #[prost(string, tag = "2")]
+                pub worker: String,
+                #[prost(int64, tag = "3")]
+                pub timeout: i64,
+                #[prost(int32, tag = "4")]
+                pub max_jobs_to_activate: i32,
+                #[prost(int64, tag = "6")]
+                pub request_timeout: i64,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct ActivateJobsResponse {
+                #[prost(message, repeated, tag = "1")]
+                pub jobs: Vec<ActivatedJob>,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct StreamActivatedJobsRequest {
+                #[prost(string, tag = "1")]
+                pub r#type: String,
+                #[prost(string, tag = "2")]
+                pub worker: String,
+                #[prost(int64, tag = "3")]
+                pub timeout: i64,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct ActivatedJob {
+                #[prost(int64, tag = "1")]
+                pub key: i64,
+                #[prost(string, tag = "2")]
+                pub r#type: String,
+                #[prost(int64, tag = "3")]
+                pub process_instance_key: i64,
+                #[prost(string, tag = "4")]
+                pub bpmn_process_id: String,
+                #[prost(string, tag = "7")]
+                pub element_id: String,
+                /// JSON object of strings.
+                #[prost(string, tag = "9")]
+                pub custom_headers: String,
+                #[prost(int32, tag = "11")]
+                pub retries: i32,
+                /// Unix epoch milliseconds.
+                #[prost(int64, tag = "12")]
+                pub deadline: i64,
+                /// JSON object.
+                #[prost(string, tag = "13")]
+                pub variables: String,
+                #[prost(string, tag = "14")]
+                pub tenant_id: String,
+            }
+
+            impl ActivatedJob {
+                pub fn into_job(self) -> super::JobContext {
+                    super::JobContext {
+                        job_key: self.key.to_string(),
+                        job_type: self.r#type,
+                        process_instance_key: self.process_instance_key.to_string(),
+                        process_definition_id: self.bpmn_process_id,
+                        element_id: self.element_id,
+                        retries: self.retries.max(0) as u32,
+                        deadline: (self.deadline > 0).then_some(self.deadline as u64),
+                        custom_headers: serde_json::from_str(&self.custom_headers).unwrap_or_default(),
+                        variables: serde_json::from_str(&self.variables).unwrap_or_default(),
+                        tenant_id: (!self.tenant_id.is_empty()).then_some(self.tenant_id),
+                    }
+                }
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct CompleteJobRequest {
+                #[prost(int64, tag = "1")]
+                job_key: i64,
+                #[prost(string, tag = "2")]
+                variables: String,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct FailJobRequest {
+                #[prost(int64, tag = "1")]
+                job_key: i64,
+                #[prost(int32, tag = "2")]
+                retries: i32,
+                #[prost(string, tag = "3")]
+                error_message: String,
+                #[prost(int64, tag = "4")]
+                retry_back_off: i64,
+                #[prost(string, tag = "5")]
+                variables: String,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct ThrowErrorRequest {
+                #[prost(int64, tag = "1")]
+                job_key: i64,
+                #[prost(string, tag = "2")]
+                error_code: String,
+                #[prost(string, tag = "3")]
+                error_message: String,
+                #[prost(string, tag = "4")]
+                variables: String,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct PublishMessageRequest {
+                #[prost(string, tag = "1")]
+                name: String,
+                #[prost(string, tag = "2")]
+                correlation_key: String,
+                #[prost(int64, tag = "3")]
+                time_to_live: i64,
+                #[prost(string, tag = "4")]
+                message_id: String,
+                #[prost(string, tag = "5")]
+                variables: String,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct PublishMessageResponse {
+                #[prost(int64, tag = "1")]
+                key: i64,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct CreateProcessInstanceRequest {
+                #[prost(string, tag = "2")]
+                bpmn_process_id: String,
+                /// -1 for the latest version.
+                #[prost(int32, tag = "3")]
+                version: i32,
+                #[prost(string, tag = "4")]
+                variables: String,
+            }
+
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct CreateProcessInstanceWithResultRequest {
+                #[prost(message, optional, tag = "1")]
+                request: Option<CreateProcessInstanceRequest>,
+                #[prost(int64, tag = "2")]
+                request_timeout: i64,
+            }
+
+            /// Both the plain and the with-result response, which share their first fields; tag 5 is only the
+            /// variables in the latter.
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct CreateProcessInstanceResponse {
+                #[prost(int64, tag = "1")]
+                process_definition_key: i64,
+                #[prost(string, tag = "2")]
+                bpmn_process_id: String,
+                #[prost(int32, tag = "3")]
+                version: i32,
+                #[prost(int64, tag = "4")]
+                process_instance_key: i64,
+                #[prost(string, tag = "5")]
+                variables: String,
+            }
+
+            /// A process instance started by `create_process_instance`.
+            #[derive(Debug, Clone, serde::Serialize)]
+            #[serde(rename_all = "camelCase")]
+            pub struct ProcessInstance {
+                pub process_instance_key: String,
+                pub process_definition_key: String,
+                pub bpmn_process_id: String,
+                pub version: i32,
+                /// The instance's variables once it completed, only set by `create_process_instance_with_result`.
+                pub variables: Option<serde_json::Map<String, serde_json::Value>>,
+            }
+
+            impl CreateProcessInstanceResponse {
+                fn into_instance(self, with_result: bool) -> ProcessInstance {
+                    ProcessInstance {
+                        process_instance_key: self.process_instance_key.to_string(),
+                        process_definition_key: self.process_definition_key.to_string(),
+                        bpmn_process_id: self.bpmn_process_id,
+                        version: self.version,
+                        variables: with_result.then(|| serde_json::from_str(&self.variables).unwrap_or_default()),
+                    }
+                }
+            }
+
+            /// The responses of the job commands carry no fields.
+            #[derive(Clone, PartialEq, prost::Message)]
+            struct Empty {}
+
+            pub struct Client {
+                http: reqwest::Client,
+                address: String,
+                credentials: Option<Credentials>,
+                token: tokio::sync::Mutex<Option<(String, std::time::Instant)>>,
+            }
+
+            struct Credentials {
+                url: String,
+                client_id: String,
+                client_secret: String,
+                audience: String,
+            }
+
+            /// Publishes a message, correlated to the process instance waiting for `name` with `correlation_key`, or
+            /// starting one on a message start event. Messages nothing waits for yet are buffered for `ttl`.
+            ///
+            /// Returns the message's key.
+            pub async fn publish_message(
+                name: &str,
+                correlation_key: &str,
+                variables: impl serde::Serialize,
+                ttl: std::time::Duration,
+            ) -> Result<String, ZeebeError> {
+                client().publish_message(name, correlation_key, variables, ttl, None).await
+            }
+
+            /// Starts an instance of the latest version of the process `bpmn_process_id`, e.g. with the values of a
+            /// submitted start form as its variables.
+            pub async fn create_process_instance(bpmn_process_id: &str, variables: impl serde::Serialize) -> Result<ProcessInstance, ZeebeError> {
+                client().create_process_instance(bpmn_process_id, None, variables).await
+            }
+
+            /// Like `create_process_instance`, but waits up to `timeout` for the instance to complete and returns its
+            /// variables.
+            pub async fn create_process_instance_with_result(
+                bpmn_process_id: &str,
+                variables: impl serde::Serialize,
+                timeout: std::time::Duration,
+            ) -> Result<ProcessInstance, ZeebeError> {
+                client().create_process_instance_with_result(bpmn_process_id, None, variables, timeout).await
+            }
+
+            fn variables_json(variables: impl serde::Serialize) -> Result<String, ZeebeError> {
+                match serde_json::to_value(variables) {
+                    Ok(value @ serde_json::Value::Object(_)) => Ok(value.to_string()),
+                    Ok(serde_json::Value::Null) => Ok(String::new()),
+                    Ok(other) => Err(ZeebeError::new(ZeebeError::INVALID_ARGUMENT, format!("variables must be an object, got `{}`", other))),
+                    Err(e) => Err(ZeebeError::new(ZeebeError::INVALID_ARGUMENT, e.to_string())),
+                }
+            }
+
+            /// The client of the gateway configured by the environment, created on first use.
+            pub fn client() -> &'static Client {
+                static CLIENT: std::sync::OnceLock<Client> = std::sync::OnceLock::new();
+                CLIENT.get_or_init(Client::from_env)
+            }
+
+            impl Client {
+                pub fn from_env() -> Self {
+                    let env = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+                    let address = env("ZEEBE_GRPC_ADDRESS").unwrap_or_else(|| "http://localhost:26500".to_string());
+                    let address = address.trim_end_matches('/').to_string();
+                    let credentials = match (env("ZEEBE_CLIENT_ID"), env("ZEEBE_CLIENT_SECRET")) {
+                        (Some(client_id), Some(client_secret)) => Some(Credentials {
+                            url: env("ZEEBE_AUTHORIZATION_SERVER_URL").unwrap_or_else(|| "https://login.cloud.camunda.io/oauth/token".to_string()),
+                            audience: env("ZEEBE_TOKEN_AUDIENCE").unwrap_or_else(|| "zeebe.camunda.io".to_string()),
+                            client_id,
+                            client_secret,
+                        }),
+                        _ => None,
+                    };
+                    let http = reqwest::Client::builder()
+                        .http2_prior_knowledge()
+                        .http2_keep_alive_interval(std::time::Duration::from_secs(30))
+                        .http2_keep_alive_while_idle(true)
+                        .build()
+                        .expect("Failed to build the Zeebe HTTP/2 client");
+                    Client { http, address, credentials, token: tokio::sync::Mutex::new(None) }
+                }
+
+                /// A cached OAuth token, renewed a minute before it expires.
+                async fn token(&self) -> Result<Option<String>, ZeebeError> {
+                    let Some(credentials) = &self.credentials else { return Ok(None) };
+                    let mut token = self.token.lock().await;
+                    if let Some((value, _)) = token.as_ref().filter(|(_, expires)| *expires > std::time::Instant::now()) {
+                        return Ok(Some(value.clone()));
+                    }
+                    let unauthenticated = |e: String| ZeebeError::new(ZeebeError::UNAUTHENTICATED, format!("cannot fetch an OAuth token: {}", e));
+                    let response = reqwest::Client::new()
+                        .post(&credentials.url)
+                        .form(&[
+                            ("grant_type", "client_credentials"),
+                            ("client_id", credentials.client_id.as_str()),
+                            ("client_secret", credentials.client_secret.as_str()),
+                            ("audience", credentials.audience.as_str()),
+                        ])
+                        .send()
+                        .await
+                        .and_then(|res| res.error_for_status())
+                        .map_err(|e| unauthenticated(e.to_string()))?;
+                    let body = response.bytes().await.map_err(|e| unauthenticated(e.to_string()))?;
+                    let body: serde_json::Value = serde_json::from_slice(&body).map_err(|e| unauthenticated(e.to_string()))?;
+                    let value = body["access_token"].as_str().ok_or_else(|| unauthenticated("no `access_token`".to_string()))?.to_string();
+                    let lifetime = std::time::Duration::from_secs(body["expires_in"].as_u64().unwrap_or(300).saturating_sub(60));
+                    *token = Some((value.clone(), std::time::Instant::now() + lifetime));
+                    Ok(Some(value))
+                }
+
+                /// Sends one length-prefixed message to `method` and returns the response, whose messages are read
+                /// with `Messages::next`.
+                async fn send(&self, method: &str, message: &impl prost::Message) -> Result<Messages, ZeebeError> {
+                    let mut body = Vec::with_capacity(5 + message.encoded_len());
+                    body.push(0);
+                    body.extend_from_slice(&(message.encoded_len() as u32).to_be_bytes());
+                    message.encode(&mut body).expect("a Vec grows as needed");
+                    let mut request = self
+                        .http
+                        .post(format!("{}/gateway_protocol.Gateway/{}", self.address, method))
+                        .header("content-type", "application/grpc")
+                        .header("te", "trailers")
+                        .body(body);
+                    if let Some(token) = self.token().await? {
+                        request = request.bearer_auth(token);
+                    }
+                    let response = request.send().await.map_err(|e| ZeebeError::new(ZeebeError::UNAVAILABLE, e.to_string()))?;
+                    // Mapping of HTTP statuses to gRPC codes from the gRPC over HTTP/2 spec.
+                    let code = match response.status().as_u16() {
+                        200 => 0,
+                        401 => ZeebeError::UNAUTHENTICATED,
+                        403 => 7,
+                        404 => ZeebeError::UNIMPLEMENTED,
+                        429 | 502 | 503 | 504 => ZeebeError::UNAVAILABLE,
+                        _ => ZeebeError::UNKNOWN,
+                    };
+                    if code != 0 {
+                        return Err(self.rejected(ZeebeError::new(code, format!("HTTP {}", response.status()))).await);
+                    }
+                    // Errors are usually sent as a response without a body, whose headers carry the status.
+                    let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+                    if let Some(code) = header("grpc-status").and_then(|code| code.parse().ok()).filter(|code| *code != 0) {
+                        let message = header("grpc-message").map(|m| percent_decode(&m)).unwrap_or_default();
+                        return Err(self.rejected(ZeebeError::new(code, message)).await);
+                    }
+                    Ok(Messages { response, buffer: Vec::new() })
+                }
+
+                async fn rejected(&self, e: ZeebeError) -> ZeebeError {
+                    if e.code == ZeebeError::UNAUTHENTICATED {
+                        *self.token.lock().await = None;
+                    }
+                    e
+                }
+
+                async fn call<Response: prost::Message + Default>(&self, method: &str, message: &impl prost::Message) -> Result<Response, ZeebeError> {
+                    self.send(method, message)
+                        .await?
+                        .next()
+                        .await?
+                        .ok_or_else(|| ZeebeError::new(ZeebeError::UNKNOWN, format!("`{}` answered without a message", method)))
+                }
+
+                /// Activates up to `max_jobs` jobs, waiting up to `request_timeout` milliseconds for some to show up
+                /// (the gateway's default for 0, not at all when negative).
+                pub async fn activate_jobs(&self, request: &ActivateJobsRequest) -> Result<Vec<ActivatedJob>, ZeebeError> {
+                    let mut messages = self.send("ActivateJobs", request).await?;
+                    let mut jobs = Vec::new();
+                    while let Some(response) = messages.next::<ActivateJobsResponse>().await? {
+                        jobs.extend(response.jobs);
+                    }
+                    Ok(jobs)
+                }
+
+                /// Asks the gateway for the cluster topology, only to find out it is reachable and accepts the credentials.
+                pub async fn topology(&self) -> Result<(), ZeebeError> {
+                    // The topology itself is skipped while decoding, as unknown fields.
+                    self.call::<Empty>("Topology", &Empty {}).await.map(drop)
+                }
+
+                /// Opens a stream the gateway pushes jobs to as they become activatable.
+                pub async fn stream_jobs(&self, request: &StreamActivatedJobsRequest) -> Result<Messages, ZeebeError> {
+                    self.send("StreamActivatedJobs", request).await
+                }
+
+                pub async fn complete_job(&self, job_key: i64, variables: &serde_json::Value) -> Result<(), ZeebeError> {
+                    let request = CompleteJobRequest { job_key, variables: variables.to_string() };
+                    self.call::<Empty>("CompleteJob", &request).await.map(drop)
+                }
+
+                pub async fn fail_job(
+                    &self,
+                    job_key: i64,
+                    retries: u32,
+                    message: &str,
+                    retry_backoff: Option<std::time::Duration>,
+                    variables: &serde_json::Value,
+                ) -> Result<(), ZeebeError> {
+                    let request = FailJobRequest {
+                        job_key,
+                        retries: retries.min(i32::MAX as u32) as i32,
+                        error_message: message.to_string(),
+                        retry_back_off: retry_backoff.map_or(0, |backoff| backoff.as_millis() as i64),
+                        variables: variables.to_string(),
+                    };
+                    self.call::<Empty>("FailJob", &request).await.map(drop)
+                }
+
+                pub async fn throw_error(&self, job_key: i64, code: &str, message: &str, variables: &serde_json::Value) -> Result<(), ZeebeError> {
+                    let request = ThrowErrorRequest {
+                        job_key,
+                        error_code: code.to_string(),
+                        error_message: message.to_string(),
+                        variables: variables.to_string(),
+                    };
+                    self.call::<Empty>("ThrowError", &request).await.map(drop)
+                }
+
+                /// `publish_message`, with a `message_id` the broker rejects duplicates of while the first is buffered.
+                pub async fn publish_message(
+                    &self,
+                    name: &str,
+                    correlation_key: &str,
+                    variables: impl serde::Serialize,
+                    ttl: std::time::Duration,
+                    message_id: Option<&str>,
+                ) -> Result<String, ZeebeError> {
+                    let request = PublishMessageRequest {
+                        name: name.to_string(),
+                        correlation_key: correlation_key.to_string(),
+                        time_to_live: ttl.as_millis().min(i64::MAX as u128) as i64,
+                        message_id: message_id.unwrap_or_default().to_string(),
+                        variables: variables_json(variables)?,
+                    };
+                    let response: PublishMessageResponse = self.call("PublishMessage", &request).await?;
+                    Ok(response.key.to_string())
+                }
+
+                /// `create_process_instance`, of the given version of the process rather than the latest.
+                pub async fn create_process_instance(
+                    &self,
+                    bpmn_process_id: &str,
+                    version: Option<i32>,
+                    variables: impl serde::Serialize,
+                ) -> Result<ProcessInstance, ZeebeError> {
+                    let request = CreateProcessInstanceRequest {
+                        bpmn_process_id: bpmn_process_id.to_string(),
+                        version: version.unwrap_or(-1),
+                        variables: variables_json(variables)?,
+                    };
+                    let response: CreateProcessInstanceResponse = self.call("CreateProcessInstance", &request).await?;
+                    Ok(response.into_instance(false))
+                }
+
+                /// `create_process_instance_with_result`, of the given version of the process rather than the latest.
+                pub async fn create_process_instance_with_result(
+                    &self,
+                    bpmn_process_id: &str,
+                    version: Option<i32>,
+                    variables: impl serde::Serialize,
+                    timeout: std::time::Duration,
+                ) -> Result<ProcessInstance, ZeebeError> {
+                    let request = CreateProcessInstanceWithResultRequest {
+                        request: Some(CreateProcessInstanceRequest {
+                            bpmn_process_id: bpmn_process_id.to_string(),
+                            version: version.unwrap_or(-1),
+                            variables: variables_json(variables)?,
+                        }),
+                        request_timeout: timeout.as_millis().min(i64::MAX as u128) as i64,
+                    };
+                    let response: CreateProcessInstanceResponse = self.call("CreateProcessInstanceWithResult", &request).await?;
+                    Ok(response.into_instance(true))
+                }
+            }
+
+            /// The messages of a response, as they arrive.
+            pub struct Messages {
+                response: reqwest::Response,
+                buffer: Vec<u8>,
+            }
+
+            impl Messages {
+                /// The next message, `None` once the response ends.
+                pub async fn next<M: prost::Message + Default>(&mut self) -> Result<Option<M>, ZeebeError> {
+                    loop {
+                        if self.buffer.len() >= 5 {
+                            let len = u32::from_be_bytes([self.buffer[1], self.buffer[2], self.buffer[3], self.buffer[4]]) as usize;
+                            if self.buffer[0] != 0 {
+                                return Err(ZeebeError::new(ZeebeError::UNKNOWN, "compressed messages are not supported"));
+                            }
+                            if self.buffer.len() >= 5 + len {
+                                let message = M::decode(&self.buffer[5..5 + len]).map_err(|e| ZeebeError::new(ZeebeError::UNKNOWN, e.to_string()));
+                                self.buffer.drain(..5 + len);
+                                return message.map(Some);
+                            }
+                        }
+                        match self.response.chunk().await {
+                            Ok(Some(chunk)) => self.buffer.extend_from_slice(&chunk),
+                            Ok(None) => return Ok(None),
+                            Err(e) => return Err(ZeebeError::new(ZeebeError::UNAVAILABLE, e.to_string())),
+                        }
+                    }
+                }
+            }
+
+            fn percent_decode(value: &str) -> String {
+                let bytes = value.as_bytes();
+                let mut decoded = Vec::with_capacity(bytes.len());
+                let mut i = 0;
+                while i < bytes.len() {
+                    match (bytes[i], bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())) {
+                        (b'%', Some(byte)) => {
+                            decoded.push(byte);
+                            i += 3;
+                        }
+                        (byte, _) => {
+                            decoded.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+                String::from_utf8_lossy(&decoded).into_owned()
+            }
+        }
+    }
+}
+
+/// Generates the job worker: one loop per job type (each connector name and alias) that takes jobs pushed by
+/// `StreamActivatedJobs`, or long-polls `ActivateJobs` on gateways without job streaming, runs them with
+/// `execute_job` and reports how they ended.
+///
+/// Jobs only reach a stream once it is open, so jobs waiting beforehand are activated with one non-blocking poll
+/// each time the stream (re)connects.
+pub fn worker(args: &WorkerArgs) -> TokenStream {
+    let streaming = args.streaming;
+    let timeout_ms = args.timeout.as_millis() as i64;
+    let max_jobs = args.max_jobs as usize;
+    let poll_timeout_ms = args.poll_timeout.as_millis() as i64;
+    let stream_timeout_ms = args.stream_timeout.as_millis() as u64;
+    let reconnect_ms = args.reconnect_backoff.as_millis() as u64;
+    quote! {
+        fn job_worker_name() -> String {
+            std::env::var("ZEEBE_WORKER_NAME").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string())
+        }
+
+        /// Starts one worker loop per job type, all sharing the `job_worker_max_jobs` budget.
+        fn start_job_worker() {
+            let mut types: Vec<&'static str> = Vec::new();
+            for r in dispatch_table().values() {
+                types.push(r.name);
+                types.extend(r.aliases.iter().copied());
+            }
+            types.sort_unstable();
+            types.dedup();
+            let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(#max_jobs));
+            tracing::info!(types = ?types, streaming = #streaming, "job worker started");
+            for job_type in types {
+                tokio::spawn(job_worker(job_type, permits.clone()));
+            }
+        }
+
+        async fn job_worker(job_type: &'static str, permits: std::sync::Arc<tokio::sync::Semaphore>) {
+            if #streaming {
+                let e = stream_jobs(job_type, &permits).await;
+                tracing::warn!(job_type, "job streaming is unavailable, falling back to long polling: {}", e);
+            }
+            let mut backoff = JobBackoff::default();
+            loop {
+                if let Err(e) = poll_jobs(job_type, &permits, #poll_timeout_ms).await {
+                    tracing::warn!(job_type, "activating jobs failed: {}", e);
+                    backoff.wait().await;
+                } else {
+                    backoff.reset();
+                }
+            }
+        }
+
+        /// Reconnect delay, doubled after each failure up to 30s.
+        #[derive(Default)]
+        struct JobBackoff(u32);
+
+        impl JobBackoff {
+            async fn wait(&mut self) {
+                let delay = std::time::Duration::from_millis(#reconnect_ms).saturating_mul(1 << self.0.min(16));
+                tokio::time::sleep(delay.min(std::time::Duration::from_secs(30))).await;
+                self.0 += 1;
+            }
+
+            fn reset(&mut self) {
+                self.0 = 0;
+            }
+        }
+
+        /// A permit of the job budget, counted as waiting for `job_type` until it is free.
+        async fn job_permit(job_type: &str, permits: &std::sync::Arc<tokio::sync::Semaphore>) -> tokio::sync::OwnedSemaphorePermit {
+            let _waiting = connectors::InFlight::acquire(gauge(Gauge::Waiting, job_type), usize::MAX);
+            permits.clone().acquire_owned().await.expect("the job semaphore is never closed")
+        }
+
+        /// Activates as many jobs as there are free permits, waiting up to `request_timeout` milliseconds for some.
+        ///
+        /// Permits are only taken once jobs arrive, so that idle job types don't hold on to the budget.
+        async fn poll_jobs(
+            job_type: &'static str,
+            permits: &std::sync::Arc<tokio::sync::Semaphore>,
+            request_timeout: i64,
+        ) -> Result<usize, connectors::zeebe::ZeebeError> {
+            drop(job_permit(job_type, permits).await);
+            let request = connectors::zeebe::ActivateJobsRequest {
+                r#type: job_type.to_string(),
+                worker: job_worker_name(),
+                timeout: #timeout_ms,
+                max_jobs_to_activate: permits.available_permits().max(1) as i32,
+                request_timeout,
+            };
+            let jobs = connectors::zeebe::client().activate_jobs(&request).await?;
+            let count = jobs.len();
+            for job in jobs {
+                let permit = job_permit(job_type, permits).await;
+                tokio::spawn(run_job(job, permit));
+            }
+            Ok(count)
+        }
+
+        /// Runs the job stream, reconnecting on errors, until the gateway says it does not support streaming.
+        async fn stream_jobs(job_type: &'static str, permits: &std::sync::Arc<tokio::sync::Semaphore>) -> connectors::zeebe::ZeebeError {
+            let mut backoff = JobBackoff::default();
+            loop {
+                let request = connectors::zeebe::StreamActivatedJobsRequest {
+                    r#type: job_type.to_string(),
+                    worker: job_worker_name(),
+                    timeout: #timeout_ms,
+                };
+                let mut stream = match connectors::zeebe::client().stream_jobs(&request).await {
+                    Ok(stream) => stream,
+                    Err(e) if e.code == connectors::zeebe::ZeebeError::UNIMPLEMENTED => return e,
+                    Err(e) => {
+                        tracing::warn!(job_type, "opening the job stream failed: {}", e);
+                        backoff.wait().await;
+                        continue;
+                    }
+                };
+                backoff.reset();
+                tracing::debug!(job_type, "job stream open");
+                // Drain what was waiting before the stream opened.
+                loop {
+                    match poll_jobs(job_type, permits, -1).await {
+                        Ok(0) => break,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            tracing::warn!(job_type, "activating waiting jobs failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+                let reopen_at = tokio::time::Instant::now() + std::time::Duration::from_millis(#stream_timeout_ms);
+                loop {
+                    match tokio::time::timeout_at(reopen_at, stream.next::<connectors::zeebe::ActivatedJob>()).await {
+                        // Reading no further until there is room for the job holds the rest back in the stream.
+                        Ok(Ok(Some(job))) => {
+                            let permit = job_permit(job_type, permits).await;
+                            tokio::spawn(run_job(job, permit));
+                        }
+                        Ok(Ok(None)) => {
+                            tracing::debug!(job_type, "job stream closed by the gateway, reopening");
+                            break;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::warn!(job_type, "job stream failed, reopening: {}", e);
+                            backoff.wait().await;
+                            break;
+                        }
+                        Err(_) => {
+                            tracing::debug!(job_type, "job stream timeout reached, reopening");
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        async fn run_job(job: connectors::zeebe::ActivatedJob, _permit: tokio::sync::OwnedSemaphorePermit) {
+            let key = job.key;
+            let job_type = job.r#type.clone();
+            let client = connectors::zeebe::client();
+            let reported = match execute_job(job.into_job(), connectors::Context::default()).await {
+                JobOutcome::Complete { variables } => client.complete_job(key, &variables).await,
+                JobOutcome::Fail { message, retries, retry_backoff, variables } => {
+                    tracing::warn!(job_type, job_key = key, retries, "job failed: {}", message);
+                    client.fail_job(key, retries, &message, retry_backoff, &variables).await
+                }
+                JobOutcome::BpmnError { code, message, variables } => client.throw_error(key, &code, &message, &variables).await,
+            };
+            // The job times out and is activated again.
+            if let Err(e) = reported {
+                tracing::error!(job_type, job_key = key, "cannot report the job's outcome: {}", e);
+            }
+        }
+    }
+}
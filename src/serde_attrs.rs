@@ -0,0 +1,87 @@
+use syn::{Error, LitStr};
+
+/// The subset of `#[serde(...)]` that decides the names serde uses for variants and fields.
+#[derive(Default)]
+pub struct SerdeAttrs {
+    pub tag: Option<String>,
+    pub content: Option<String>,
+    pub rename: Option<String>,
+    pub rename_all: Option<String>,
+}
+
+impl SerdeAttrs {
+    pub fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut out = SerdeAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                let slot = if meta.path.is_ident("tag") {
+                    &mut out.tag
+                } else if meta.path.is_ident("content") {
+                    &mut out.content
+                } else if meta.path.is_ident("rename") {
+                    &mut out.rename
+                } else if meta.path.is_ident("rename_all") {
+                    &mut out.rename_all
+                } else {
+                    // Other serde options don't affect names; skip their value, if any.
+                    if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::Expr>()?;
+                    } else if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|_| Ok(()))?;
+                    }
+                    return Ok(());
+                };
+                *slot = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// The name serde gives a variant (PascalCase ident) or a field (snake_case ident) under `rename_all = rule`.
+pub fn apply_rename_all(ident: &str, rule: Option<&str>, span: &syn::Ident) -> syn::Result<String> {
+    let mut words: Vec<String> = Vec::new();
+    for c in ident.chars() {
+        if c == '_' {
+            words.push(String::new());
+            continue;
+        }
+        if c.is_uppercase() || words.is_empty() {
+            words.push(String::new());
+        }
+        words.last_mut().unwrap().extend(c.to_lowercase());
+    }
+    words.retain(|w| !w.is_empty());
+    let capitalize = |w: &String| {
+        let mut chars = w.chars();
+        chars.next().map(|c| c.to_uppercase().collect::<String>() + chars.as_str()).unwrap_or_default()
+    };
+    Ok(match rule {
+        None => ident.to_string(),
+        Some("lowercase") => ident.to_lowercase(),
+        Some("UPPERCASE") => ident.to_uppercase(),
+        Some("PascalCase") => words.iter().map(capitalize).collect(),
+        Some("camelCase") => {
+            let mut out = words.first().cloned().unwrap_or_default();
+            out.extend(words.iter().skip(1).map(capitalize));
+            out
+        }
+        Some("snake_case") => words.join("_"),
+        Some("SCREAMING_SNAKE_CASE") => words.join("_").to_uppercase(),
+        Some("kebab-case") => words.join("-"),
+        Some("SCREAMING-KEBAB-CASE") => words.join("-").to_uppercase(),
+        Some(other) => return Err(Error::new_spanned(span, format!("Unknown serde `rename_all` rule `{}`", other))),
+    })
+}
+
+/// Rejects a `rename_all = "..."` rule serde doesn't know.
+pub fn check_rename_rule(rule: &LitStr) -> syn::Result<()> {
+    let ident = syn::Ident::new("rule", rule.span());
+    apply_rename_all("field", Some(&rule.value()), &ident).map(drop).map_err(|_| {
+        Error::new_spanned(
+            rule,
+            "Expected \"lowercase\", \"UPPERCASE\", \"PascalCase\", \"camelCase\", \"snake_case\", \"SCREAMING_SNAKE_CASE\", \"kebab-case\" or \"SCREAMING-KEBAB-CASE\"",
+        )
+    })
+}
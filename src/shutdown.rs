@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, ItemFn, LitInt, Token};
+
+use crate::args::parse_duration;
+
+/// `#[connector_shutdown(timeout = "5s", order = 10)]`
+pub struct ShutdownArgs {
+    timeout: Duration,
+    order: i32,
+}
+
+impl Parse for ShutdownArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut timeout = Duration::from_secs(10);
+        let mut order = 0;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "timeout" {
+                let lit = input.parse()?;
+                timeout = parse_duration(&lit)?;
+                if timeout.is_zero() {
+                    return Err(Error::new_spanned(lit, "`timeout` must not be zero"));
+                }
+            } else if key == "order" {
+                order = input.parse::<LitInt>()?.base10_parse()?;
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(ShutdownArgs { timeout, order })
+    }
+}
+
+/// Registers `async fn() -> Result<(), String>` to run once the server has drained on shutdown, e.g. to flush
+/// telemetry or close pools. A hook running longer than `timeout` is abandoned.
+pub fn expand(args: ShutdownArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    if input_fn.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Shutdown hooks must be async"));
+    }
+    if !input_fn.sig.inputs.is_empty() {
+        return Err(Error::new_spanned(&input_fn.sig.inputs, "Expected `async fn() -> Result<(), String>`"));
+    }
+    let fn_name = &input_fn.sig.ident;
+    let name = fn_name.to_string();
+    let timeout_ms = args.timeout.as_millis() as u64;
+    let order = args.order;
+
+    Ok(quote! {
+        #input_fn
+
+        const _: () = {
+            fn hook() -> crate::connectors::HookFuture {
+                Box::pin(#fn_name())
+            }
+
+            ::inventory::submit! {
+                crate::connectors::ShutdownRecipe {
+                    name: #name,
+                    order: #order,
+                    timeout: std::time::Duration::from_millis(#timeout_ms),
+                    hook,
+                }
+            }
+        };
+    })
+}
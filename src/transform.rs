@@ -0,0 +1,70 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, ItemFn, LitInt, LitStr, Token};
+
+/// `#[camunda_transform(connector = "crm", operation = "lookup", order = 10)]`
+pub struct TransformArgs {
+    connector: LitStr,
+    operation: Option<LitStr>,
+    order: i32,
+}
+
+impl Parse for TransformArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut connector = None;
+        let mut operation = None;
+        let mut order = 0;
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "connector" {
+                connector = Some(input.parse()?);
+            } else if key == "operation" {
+                operation = Some(input.parse()?);
+            } else if key == "order" {
+                order = input.parse::<LitInt>()?.base10_parse()?;
+            } else {
+                return Err(Error::new_spanned(key, "Unknown attribute key"));
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let connector = connector.ok_or_else(|| input.error("Missing `connector`"))?;
+        Ok(TransformArgs { connector, operation, order })
+    }
+}
+
+/// Registers `fn(&mut serde_json::Value) -> Result<(), String>` to rewrite the envelopes of a connector, or of one of
+/// its operations, before they are deserialized.
+pub fn expand(args: TransformArgs, input_fn: ItemFn) -> syn::Result<TokenStream> {
+    if input_fn.sig.asyncness.is_some() {
+        return Err(Error::new_spanned(input_fn.sig.fn_token, "Transforms cannot be async"));
+    }
+    if input_fn.sig.inputs.len() != 1 {
+        return Err(Error::new_spanned(&input_fn.sig.inputs, "Expected `fn(&mut serde_json::Value) -> Result<(), String>`"));
+    }
+    let fn_name = &input_fn.sig.ident;
+    let name = fn_name.to_string();
+    let connector = &args.connector;
+    let operation = match &args.operation {
+        Some(operation) => quote! { Some(#operation) },
+        None => quote! { None },
+    };
+    let order = args.order;
+
+    Ok(quote! {
+        #input_fn
+
+        ::inventory::submit! {
+            crate::connectors::TransformRecipe {
+                name: #name,
+                connector: #connector,
+                operation: #operation,
+                order: #order,
+                transform: #fn_name,
+            }
+        }
+    })
+}
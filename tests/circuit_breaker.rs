@@ -0,0 +1,92 @@
+use camunda_connector_rs::{camunda_connector, connector_main};
+use serde_json::{Value, json};
+
+connector_main!(port = 8080, circuit_breaker = 2, circuit_breaker_cooldown = "100ms");
+
+const COOLDOWN: std::time::Duration = std::time::Duration::from_millis(100);
+
+#[camunda_connector(name = "ledger", operation = "*")]
+pub async fn ledger(_id: u64, call: connectors::AnyOperation) -> Result<Value, String> {
+    if call.input.get("fail").is_some() {
+        return Err("downstream down".into());
+    }
+    Ok(json!({ "operation": call.operation }))
+}
+
+#[camunda_connector(name = "ledger", operation = "balance")]
+pub async fn balance(_id: u64, _input: Value) -> Result<Value, String> {
+    Ok(json!({ "balance": 0 }))
+}
+
+async fn call(operation: &str, input: Value) -> Result<Value, connectors::ExecError> {
+    let body = json!({ "id": 1, "params": { "operation": operation, "input": input } });
+    execute(
+        "ledger".to_string(),
+        operation.to_string(),
+        serde_json::to_vec(&body).unwrap().into(),
+        connectors::Format::Json,
+        connectors::Context::default(),
+    )
+    .await
+    .map(|connectors::Output::Value(value)| value)
+}
+
+fn is_open(name: &str, operation: &str) -> bool {
+    matches!(circuit_check(name, operation), Err(connectors::ExecError::CircuitOpen { .. }))
+}
+
+#[test]
+fn opens_after_consecutive_failures() {
+    circuit_record("opens", "op", true);
+    assert!(!is_open("opens", "op"));
+    circuit_record("opens", "op", true);
+    match circuit_check("opens", "op") {
+        Err(connectors::ExecError::CircuitOpen { retry_after, .. }) => assert!(retry_after <= COOLDOWN),
+        other => panic!("expected an open circuit, got {:?}", other),
+    }
+    assert!(!is_open("opens", "other"));
+}
+
+#[test]
+fn a_success_resets_the_failure_count_and_drops_the_state() {
+    circuit_record("resets", "op", true);
+    circuit_record("resets", "op", false);
+    assert!(!circuits().lock().unwrap().contains_key(&("resets".to_string(), "op".to_string())));
+    circuit_record("resets", "op", true);
+    assert!(!is_open("resets", "op"));
+}
+
+#[test]
+fn a_single_probe_is_let_through_after_the_cooldown() {
+    circuit_record("probe", "op", true);
+    circuit_record("probe", "op", true);
+    std::thread::sleep(COOLDOWN);
+    assert!(circuit_check("probe", "op").is_ok());
+    assert!(is_open("probe", "op"), "a second call got through while the probe runs");
+    circuit_record("probe", "op", false);
+    assert!(circuit_check("probe", "op").is_ok());
+    assert!(circuit_check("probe", "op").is_ok());
+}
+
+#[test]
+fn a_failed_probe_reopens_the_circuit() {
+    circuit_record("reopens", "op", true);
+    circuit_record("reopens", "op", true);
+    std::thread::sleep(COOLDOWN);
+    assert!(circuit_check("reopens", "op").is_ok());
+    circuit_record("reopens", "op", true);
+    match circuit_check("reopens", "op") {
+        Err(connectors::ExecError::CircuitOpen { retry_after, .. }) => assert!(retry_after > COOLDOWN / 2),
+        other => panic!("expected a reopened circuit, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn catch_all_calls_share_one_breaker() {
+    assert!(call("debit", json!({ "fail": true })).await.is_err());
+    assert!(call("credit", json!({ "fail": true })).await.is_err());
+    let output = call("refund", json!({})).await;
+    assert!(matches!(output, Err(connectors::ExecError::CircuitOpen { .. })), "{:?}", output);
+    // Operations with their own handler have their own breaker.
+    assert_eq!(call("balance", json!({})).await.unwrap(), json!({ "balance": 0 }));
+}
@@ -0,0 +1,61 @@
+use camunda_connector_rs::{ConnectorInput, camunda_connector, connector_main};
+use serde::Deserialize;
+
+connector_main!(port = 8080, element_templates = true);
+
+#[derive(Debug, Deserialize, ConnectorInput)]
+pub struct SendInput {
+    #[connector(label = "Recipient", description = "Address the mail is sent to", group = "Message")]
+    pub to: String,
+    #[connector(label = "Subject", group = "Message")]
+    pub subject: String,
+    #[connector(label = "Body", description = "Plain text, or a FEEL expression", group = "Message", feel = "required")]
+    pub body: String,
+}
+
+#[camunda_connector(name = "mail", operation = "send")]
+pub async fn send(_id: u64, _input: SendInput) -> Result<serde_json::Value, String> {
+    Ok(serde_json::Value::Null)
+}
+
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/element-templates");
+
+#[test]
+fn element_templates_match_golden_files() {
+    connectors::element_templates::assert_golden(GOLDEN);
+}
+
+#[test]
+fn changed_templates_are_reported_with_a_diff() {
+    // Accepting the rendered templates would make the check pass.
+    if std::env::var_os("UPDATE_ELEMENT_TEMPLATES").is_some() {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("element-templates-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let golden = std::fs::read_to_string(format!("{}/mail.send.json", GOLDEN)).unwrap();
+    std::fs::write(dir.join("mail.send.json"), golden.replace("\"label\": \"Subject\"", "\"label\": \"Title\"")).unwrap();
+    std::fs::write(dir.join("mail.archive.json"), "{}\n").unwrap();
+
+    let report = connectors::element_templates::check_golden(&dir).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let expected = format!(
+        concat!(
+            "The element templates don't match the golden files in `{}`, 2 problem(s):\n",
+            "\n",
+            "mail.send.json differs from its golden file (- golden, + rendered):\n",
+            "        \"group\": \"Message\",\n",
+            "        \"id\": \"subject\",\n",
+            "-       \"label\": \"Title\",\n",
+            "+       \"label\": \"Subject\",\n",
+            "        \"type\": \"String\"\n",
+            "      }},\n",
+            "\n",
+            "mail.archive.json is a golden file no connector renders\n",
+            "\n",
+            "Rerun with UPDATE_ELEMENT_TEMPLATES=1 to accept the rendered templates.",
+        ),
+        dir.display(),
+    );
+    assert_eq!(report, expected);
+}
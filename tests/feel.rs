@@ -0,0 +1,105 @@
+use camunda_connector_rs::connector_main;
+use serde_json::{Value, json};
+
+connector_main!(port = 8080);
+
+fn eval(expression: &str) -> Value {
+    eval_in(expression, &json!({}))
+}
+
+fn eval_in(expression: &str, context: &Value) -> Value {
+    connectors::feel::evaluate(expression, context).unwrap_or_else(|e| panic!("`{}` failed: {}", expression, e))
+}
+
+#[test]
+fn and_or_are_three_valued() {
+    assert_eq!(eval("true and true"), json!(true));
+    assert_eq!(eval("true and null"), Value::Null);
+    assert_eq!(eval("false and null"), json!(false));
+    assert_eq!(eval("null and false"), json!(false));
+    assert_eq!(eval("true or null"), json!(true));
+    assert_eq!(eval("null or true"), json!(true));
+    assert_eq!(eval("false or null"), Value::Null);
+    assert_eq!(eval("false or false"), json!(false));
+    // Non-booleans count as null.
+    assert_eq!(eval("true and 1"), Value::Null);
+}
+
+#[test]
+fn indices_are_one_based_and_negative_from_the_end() {
+    let context = json!({ "items": ["a", "b", "c"] });
+    assert_eq!(eval_in("items[1]", &context), json!("a"));
+    assert_eq!(eval_in("items[3]", &context), json!("c"));
+    assert_eq!(eval_in("items[-1]", &context), json!("c"));
+    assert_eq!(eval_in("items[-3]", &context), json!("a"));
+    assert_eq!(eval_in("items[0]", &context), Value::Null);
+    assert_eq!(eval_in("items[4]", &context), Value::Null);
+    assert_eq!(eval_in("items[-4]", &context), Value::Null);
+    assert_eq!(eval_in("missing[1]", &context), Value::Null);
+}
+
+#[test]
+fn filters_keep_the_matching_items() {
+    assert_eq!(eval("[1, 2, 3, 4][item > 2]"), json!([3, 4]));
+    assert_eq!(eval("[1, 2, 3][item > 5]"), json!([]));
+    let context = json!({ "orders": [{ "id": "a", "amount": 5 }, { "id": "b", "amount": 20 }, { "id": "c", "amount": 30 }] });
+    // A filter sees the fields of context items, and a path on the result maps over it.
+    assert_eq!(eval_in("orders[amount > 10].id", &context), json!(["b", "c"]));
+    assert_eq!(eval_in("orders[item.id = \"a\"][1].amount", &context), json!(5));
+}
+
+#[test]
+fn substring_counts_a_negative_start_from_the_end() {
+    assert_eq!(eval("substring(\"foobar\", 3)"), json!("obar"));
+    assert_eq!(eval("substring(\"foobar\", 3, 3)"), json!("oba"));
+    assert_eq!(eval("substring(\"foobar\", -2)"), json!("ar"));
+    assert_eq!(eval("substring(\"foobar\", -2, 1)"), json!("a"));
+    assert_eq!(eval("substring(\"foobar\", -10)"), json!("foobar"));
+    assert_eq!(eval("substring(\"foobar\", 10)"), json!(""));
+}
+
+#[test]
+fn decimal_rounds_half_to_even() {
+    assert_eq!(eval("decimal(1.5, 0)"), json!(2));
+    assert_eq!(eval("decimal(2.5, 0)"), json!(2));
+    assert_eq!(eval("decimal(-2.5, 0)"), json!(-2));
+    assert_eq!(eval("decimal(0.125, 2)"), json!(0.12));
+    assert_eq!(eval("decimal(0.135, 2)"), json!(0.14));
+    assert_eq!(eval("decimal(1 / 3, 2)"), json!(0.33));
+}
+
+#[test]
+fn modulo_takes_the_sign_of_the_divisor() {
+    assert_eq!(eval("modulo(12, 5)"), json!(2));
+    assert_eq!(eval("modulo(-12, 5)"), json!(3));
+    assert_eq!(eval("modulo(12, -5)"), json!(-3));
+    assert_eq!(eval("modulo(-12, -5)"), json!(-2));
+    assert_eq!(eval("modulo(12, 0)"), Value::Null);
+}
+
+#[test]
+fn for_some_and_every_iterate_over_lists() {
+    assert_eq!(eval("for x in [1, 2, 3] return x * 2"), json!([2, 4, 6]));
+    assert_eq!(eval("for x in null return x"), json!([]));
+    assert_eq!(eval("some x in [1, 5] satisfies x > 3"), json!(true));
+    assert_eq!(eval("some x in [1, 2] satisfies x > 3"), json!(false));
+    assert_eq!(eval("every x in [4, 5] satisfies x > 3"), json!(true));
+    assert_eq!(eval("every x in [1, 5] satisfies x > 3"), json!(false));
+    let context = json!({ "lines": [{ "qty": 2, "price": 3 }, { "qty": 1, "price": 10 }] });
+    assert_eq!(eval_in("sum(for line in lines return line.qty * line.price)", &context), json!(16));
+    // The keywords are only keywords when `x in` follows.
+    assert_eq!(eval_in("some + every", &json!({ "some": 1, "every": 2 })), json!(3));
+}
+
+#[test]
+fn deep_or_long_expressions_are_rejected() {
+    assert_eq!(eval(&format!("{}1{}", "(".repeat(30), ")".repeat(30))), json!(1));
+    let nested = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+    assert!(connectors::feel::evaluate(&nested, &json!({})).unwrap_err().contains("nests deeper than 64 levels"));
+    let negated = format!("{}1", "-".repeat(5000));
+    assert!(connectors::feel::evaluate(&negated, &json!({})).unwrap_err().contains("nests deeper than 64 levels"));
+    let chained = vec!["1"; 200].join(" + ");
+    assert!(connectors::feel::evaluate(&chained, &json!({})).unwrap_err().contains("nests deeper than 64 levels"));
+    let long = format!("\"{}\"", "a".repeat(10_000));
+    assert!(connectors::feel::evaluate(&long, &json!({})).unwrap_err().contains("longer than 8192 bytes"));
+}
@@ -0,0 +1,115 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use camunda_connector_rs::{camunda_connector, connector_main};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+connector_main!(port = 8080, idempotency = true);
+
+/// Calls per test key, as the tests run in parallel.
+static CALLS: std::sync::Mutex<std::collections::BTreeMap<String, u32>> = std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+fn calls(key: &str) -> u32 {
+    CALLS.lock().unwrap().get(key).copied().unwrap_or_default()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrderInput {
+    key: String,
+    #[serde(default)]
+    delay_ms: u64,
+    #[serde(default)]
+    fail: bool,
+}
+
+#[camunda_connector(name = "orders", operation = "create")]
+pub async fn create(_id: u64, input: OrderInput) -> Result<Value, String> {
+    let call = {
+        let mut calls = CALLS.lock().unwrap();
+        let n = calls.entry(input.key.clone()).or_default();
+        *n += 1;
+        *n
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(input.delay_ms)).await;
+    if input.fail {
+        return Err(format!("failure #{}", call));
+    }
+    Ok(json!({ "call": call }))
+}
+
+/// The `/csp/{name}` route with the middleware, as `main` serves it.
+fn app() -> axum::Router {
+    axum::Router::new()
+        .route("/csp/{name}", axum::routing::post(dispatch).layer(axum::middleware::from_fn(idempotency)))
+        .layer(axum::Extension(connectors::ClientInfo::default()))
+}
+
+async fn post(idempotency_key: Option<&str>, input: Value) -> axum::response::Response {
+    let body = json!({ "id": 1, "params": { "operation": "create", "input": input } });
+    let mut request = Request::post("/csp/orders").header("content-type", "application/json");
+    if let Some(key) = idempotency_key {
+        request = request.header("idempotency-key", key);
+    }
+    app().oneshot(request.body(Body::from(body.to_string())).unwrap()).await.unwrap()
+}
+
+async fn body(response: axum::response::Response) -> Value {
+    serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn duplicates_replay_the_first_response() {
+    let first = post(Some("replay"), json!({ "key": "replay" })).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first_body = body(first).await;
+
+    let replay = post(Some("replay"), json!({ "key": "replay" })).await;
+    assert_eq!(replay.status(), StatusCode::OK);
+    assert_eq!(replay.headers()["idempotent-replayed"], "true");
+    assert_eq!(replay.headers()["content-type"], "application/json");
+    assert!(replay.headers().contains_key("x-envelope-version"));
+    assert_eq!(body(replay).await, first_body);
+    assert_eq!(calls("replay"), 1);
+}
+
+#[tokio::test]
+async fn a_duplicate_of_a_running_request_gets_a_conflict() {
+    let first = tokio::spawn(post(Some("running"), json!({ "key": "running", "delay_ms": 300 })));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let duplicate = post(Some("running"), json!({ "key": "running" })).await;
+    assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+    assert_eq!(first.await.unwrap().status(), StatusCode::OK);
+    assert_eq!(calls("running"), 1);
+
+    // Once done, the key replays the response again.
+    let replay = post(Some("running"), json!({ "key": "running" })).await;
+    assert_eq!(replay.headers()["idempotent-replayed"], "true");
+}
+
+#[tokio::test]
+async fn failures_are_not_stored() {
+    let failed = post(Some("failing"), json!({ "key": "failing", "fail": true })).await;
+    assert!(!failed.status().is_success());
+    let retried = post(Some("failing"), json!({ "key": "failing" })).await;
+    assert_eq!(retried.status(), StatusCode::OK);
+    assert!(retried.headers().get("idempotent-replayed").is_none());
+    assert_eq!(body(retried).await, json!({ "call": 2 }));
+}
+
+#[tokio::test]
+async fn requests_without_a_key_always_run() {
+    post(None, json!({ "key": "unkeyed" })).await;
+    let second = post(None, json!({ "key": "unkeyed" })).await;
+    assert!(second.headers().get("idempotent-replayed").is_none());
+    assert_eq!(calls("unkeyed"), 2);
+}
+
+#[tokio::test]
+async fn keys_are_not_shared_between_requests() {
+    post(Some("first-key"), json!({ "key": "distinct" })).await;
+    let other = post(Some("second-key"), json!({ "key": "distinct" })).await;
+    assert!(other.headers().get("idempotent-replayed").is_none());
+    assert_eq!(calls("distinct"), 2);
+}
@@ -0,0 +1,49 @@
+use camunda_connector_rs::connector_main;
+
+connector_main!(port = 8080, inbound_dedup = "memory", inbound_dedup_ttl = "100ms");
+
+const TTL: std::time::Duration = std::time::Duration::from_millis(100);
+
+async fn claim(handler: &str, id: &str) -> &'static str {
+    match inbound_dedup_claim(handler, id).await.unwrap() {
+        DedupClaim::New => "new",
+        DedupClaim::Done => "done",
+        DedupClaim::InFlight => "in flight",
+    }
+}
+
+#[tokio::test]
+async fn a_claimed_message_is_in_flight_for_other_deliveries() {
+    assert_eq!(claim("orders", "in-flight").await, "new");
+    assert_eq!(claim("orders", "in-flight").await, "in flight");
+}
+
+#[tokio::test]
+async fn a_completed_message_is_done_until_the_ttl_lapses() {
+    assert_eq!(claim("orders", "completed").await, "new");
+    inbound_dedup_complete("orders", "completed").await.unwrap();
+    assert_eq!(claim("orders", "completed").await, "done");
+    tokio::time::sleep(TTL).await;
+    assert_eq!(claim("orders", "completed").await, "new");
+}
+
+#[tokio::test]
+async fn a_released_message_is_handled_again() {
+    assert_eq!(claim("orders", "released").await, "new");
+    inbound_dedup_release("orders", "released").await.unwrap();
+    assert_eq!(claim("orders", "released").await, "new");
+}
+
+#[tokio::test]
+async fn releasing_keeps_completed_messages() {
+    assert_eq!(claim("orders", "kept").await, "new");
+    inbound_dedup_complete("orders", "kept").await.unwrap();
+    inbound_dedup_release("orders", "kept").await.unwrap();
+    assert_eq!(claim("orders", "kept").await, "done");
+}
+
+#[tokio::test]
+async fn ids_are_scoped_to_their_handler() {
+    assert_eq!(claim("orders", "scoped").await, "new");
+    assert_eq!(claim("invoices", "scoped").await, "new");
+}
@@ -0,0 +1,97 @@
+use camunda_connector_rs::connector_main;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+connector_main!(port = 8080, proxy_protocol = true);
+
+/// Sends `preamble` followed by a request line over a local connection, and reads the PROXY header off the accepted
+/// end, along with whatever it left unread.
+async fn read_header(preamble: &[u8]) -> (std::io::Result<Option<std::net::SocketAddr>>, Vec<u8>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut client = tokio::net::TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    client.write_all(preamble).await.unwrap();
+    client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+    client.shutdown().await.unwrap();
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let header = read_proxy_header(&mut stream).await;
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    (header, rest)
+}
+
+fn v2(command: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.extend([0x20 | command, family]);
+    header.extend((payload.len() as u16).to_be_bytes());
+    header.extend(payload);
+    header
+}
+
+#[tokio::test]
+async fn v1_tcp4_gives_the_source_address() {
+    let (header, rest) = read_header(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n").await;
+    assert_eq!(header.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+    assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+}
+
+#[tokio::test]
+async fn v1_tcp6_gives_the_source_address() {
+    let (header, _) = read_header(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n").await;
+    assert_eq!(header.unwrap(), Some("[2001:db8::1]:56324".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn v1_unknown_keeps_the_peer_address() {
+    let (header, rest) = read_header(b"PROXY UNKNOWN\r\n").await;
+    assert_eq!(header.unwrap(), None);
+    assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+}
+
+#[tokio::test]
+async fn v1_rejects_malformed_headers() {
+    for preamble in [
+        &b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n"[..],
+        b"PROXY TCP4 not-an-ip 198.51.100.1 56324 443\r\n",
+        b"PROXY TCP4 192.0.2.1 198.51.100.1 99999 443\r\n",
+    ] {
+        let (header, _) = read_header(preamble).await;
+        assert_eq!(header.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+    let (header, _) = read_header(format!("PROXY TCP4 {}\r\n", "1".repeat(120)).as_bytes()).await;
+    assert_eq!(header.unwrap_err().to_string(), "PROXY v1 header too long");
+}
+
+#[tokio::test]
+async fn v2_proxy_ipv4_gives_the_source_address() {
+    let mut payload = vec![192, 0, 2, 1, 198, 51, 100, 1];
+    payload.extend(56324u16.to_be_bytes());
+    payload.extend(443u16.to_be_bytes());
+    let (header, rest) = read_header(&v2(0x1, 0x11, &payload)).await;
+    assert_eq!(header.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+    assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+}
+
+#[tokio::test]
+async fn v2_proxy_ipv6_gives_the_source_address() {
+    let source: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let mut payload = source.octets().to_vec();
+    payload.extend([0; 16]);
+    payload.extend(56324u16.to_be_bytes());
+    payload.extend(443u16.to_be_bytes());
+    let (header, _) = read_header(&v2(0x1, 0x21, &payload)).await;
+    assert_eq!(header.unwrap(), Some("[2001:db8::1]:56324".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn v2_local_keeps_the_peer_address() {
+    let (header, rest) = read_header(&v2(0x0, 0x00, &[])).await;
+    assert_eq!(header.unwrap(), None);
+    assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+}
+
+#[tokio::test]
+async fn connections_without_a_header_are_rejected() {
+    let (header, _) = read_header(b"").await;
+    assert_eq!(header.unwrap_err().to_string(), "missing PROXY protocol header");
+    let (header, _) = read_header(&v2(0x2, 0x11, &[])).await;
+    assert_eq!(header.unwrap_err().to_string(), "unknown PROXY v2 command");
+}
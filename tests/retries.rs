@@ -0,0 +1,105 @@
+use camunda_connector_rs::{camunda_connector, connector_main};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+connector_main!(port = 8080);
+
+/// Calls per test key, as the tests run in parallel.
+static CALLS: std::sync::Mutex<std::collections::BTreeMap<String, u32>> = std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+fn record_call(key: &str) -> u32 {
+    let mut calls = CALLS.lock().unwrap();
+    let n = calls.entry(key.to_string()).or_default();
+    *n += 1;
+    *n
+}
+
+fn calls(key: &str) -> u32 {
+    CALLS.lock().unwrap().get(key).copied().unwrap_or_default()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FlakyInput {
+    key: String,
+    /// How many calls fail before one succeeds.
+    failures: u32,
+    fatal: bool,
+}
+
+#[camunda_connector(name = "flaky", operation = "call", max_attempts = 3, backoff = "20ms")]
+pub async fn flaky(_id: u64, input: FlakyInput) -> Result<Value, connectors::HandlerError> {
+    let call = record_call(&input.key);
+    if call <= input.failures {
+        return Err(match input.fatal {
+            true => connectors::HandlerError::fatal(format!("failure #{}", call)),
+            false => connectors::HandlerError::retriable(format!("failure #{}", call)),
+        });
+    }
+    Ok(json!({ "call": call }))
+}
+
+#[camunda_connector(name = "flaky", operation = "once")]
+pub async fn once(_id: u64, input: FlakyInput) -> Result<Value, connectors::HandlerError> {
+    let call = record_call(&input.key);
+    Err(connectors::HandlerError::retriable(format!("failure #{}", call)))
+}
+
+async fn call(operation: &str, input: Value) -> Result<Value, connectors::ExecError> {
+    let body = json!({ "id": 1, "params": { "operation": operation, "input": input } });
+    execute(
+        "flaky".to_string(),
+        operation.to_string(),
+        serde_json::to_vec(&body).unwrap().into(),
+        connectors::Format::Json,
+        connectors::Context::default(),
+    )
+    .await
+    .map(|connectors::Output::Value(value)| value)
+}
+
+#[tokio::test]
+async fn retriable_errors_are_retried_until_the_handler_succeeds() {
+    let output = call("call", json!({ "key": "recovers", "failures": 2, "fatal": false })).await;
+    assert_eq!(output.unwrap(), json!({ "call": 3 }));
+    assert_eq!(calls("recovers"), 3);
+}
+
+#[tokio::test]
+async fn the_last_error_is_returned_after_max_attempts() {
+    let output = call("call", json!({ "key": "exhausted", "failures": 10, "fatal": false })).await;
+    assert!(matches!(output, Err(connectors::ExecError::Handler(ref message)) if message == "failure #3"), "{:?}", output);
+    assert_eq!(calls("exhausted"), 3);
+}
+
+#[tokio::test]
+async fn fatal_errors_are_not_retried() {
+    let output = call("call", json!({ "key": "fatal", "failures": 10, "fatal": true })).await;
+    assert!(matches!(output, Err(connectors::ExecError::Failed { ref message, retries: Some(0), .. }) if message == "failure #1"), "{:?}", output);
+    assert_eq!(calls("fatal"), 1);
+}
+
+#[tokio::test]
+async fn handlers_without_max_attempts_run_once() {
+    let output = call("once", json!({ "key": "once", "failures": 10, "fatal": false })).await;
+    assert!(matches!(output, Err(connectors::ExecError::Handler(_))), "{:?}", output);
+    assert_eq!(calls("once"), 1);
+}
+
+#[tokio::test]
+async fn attempts_back_off_exponentially() {
+    // Waits of at least half of 20ms, then of 40ms.
+    let started = std::time::Instant::now();
+    call("call", json!({ "key": "backoff", "failures": 2, "fatal": false })).await.unwrap();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(30), "{:?}", started.elapsed());
+}
+
+#[test]
+fn backoff_doubles_per_attempt_with_jitter_and_a_cap() {
+    let base = std::time::Duration::from_millis(100);
+    for attempt in 1..=4 {
+        let exp = base * (1 << (attempt - 1));
+        let backoff = connectors::retry_backoff(base, attempt);
+        assert!(backoff >= exp / 2 && backoff <= exp, "attempt {}: {:?}", attempt, backoff);
+    }
+    assert!(connectors::retry_backoff(base, 30) <= std::time::Duration::from_secs(30));
+}